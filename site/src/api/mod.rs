@@ -1 +1,2 @@
 pub mod projects;
+pub mod shaders;
@@ -0,0 +1,637 @@
+use actix_web::{
+    HttpRequest, HttpResponse, get,
+    http::{StatusCode, header::HeaderName},
+    post, web,
+};
+use chrono::{DateTime, Utc};
+use moka::future::Cache;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use sha1::{Digest, Sha1};
+use std::{
+    fs, io,
+    path::PathBuf,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicU32, Ordering},
+    },
+    time::Duration,
+};
+
+/// Per source; the shipped demos are a few KB.
+const MAX_SOURCE: usize = 64 * 1024;
+/// The rest of the lab as JSON; mostly edited modules.
+const MAX_LAB: usize = 128 * 1024;
+const MAX_TITLE: usize = 100;
+/// Hex digits of the hash in a fresh id; 4 more per collision.
+const ID_LEN: usize = 12;
+const ID_STEP: usize = 4;
+/// A full SHA-1, in hex.
+const HASH_LEN: usize = 40;
+
+#[derive(Deserialize)]
+struct NewSnippet {
+    demo: String,
+    vs: String,
+    fs: String,
+    /// Everything else the editor needs to show the shader as it was
+    /// (modules, entry points, textures, ...); only stored and handed back.
+    #[serde(default)]
+    lab: Map<String, Value>,
+    #[serde(default)]
+    title: String,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+struct Snippet {
+    id: String,
+    demo: String,
+    vs: String,
+    fs: String,
+    /// Empty in snippets saved before it was kept.
+    #[serde(default)]
+    lab: Map<String, Value>,
+    title: String,
+    created_at: DateTime<Utc>,
+}
+
+/// What the gallery lists; sources are fetched one snippet at a time.
+#[derive(Serialize, Clone)]
+struct Summary {
+    id: String,
+    demo: String,
+    title: String,
+    created_at: DateTime<Utc>,
+}
+
+impl From<&Snippet> for Summary {
+    fn from(s: &Snippet) -> Self {
+        Self {
+            id: s.id.clone(),
+            demo: s.demo.clone(),
+            title: s.title.clone(),
+            created_at: s.created_at,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct ListQuery {
+    limit: Option<usize>,
+}
+
+/// How much the store takes before it refuses new snippets.
+#[derive(Clone, Copy)]
+pub struct Limits {
+    pub max_snippets: usize,
+    /// Of the snippet files together.
+    pub max_bytes: u64,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self { max_snippets: 10_000, max_bytes: 256 * 1024 * 1024 }
+    }
+}
+
+/// What's on disk, kept in memory so listing never touches it.
+#[derive(Default)]
+struct Index {
+    /// Newest first.
+    summaries: Vec<Summary>,
+    bytes: u64,
+}
+
+enum SaveError {
+    /// Over [`Limits`].
+    Full,
+    Io(io::Error),
+}
+
+impl From<io::Error> for SaveError {
+    fn from(e: io::Error) -> Self {
+        SaveError::Io(e)
+    }
+}
+
+/// Snippets as one JSON file each, named by id. Ids come from the content
+/// (demo, both sources and the lab, not the title), so saving the same
+/// shader twice hands out the same link.
+pub struct ShaderStore {
+    dir: PathBuf,
+    limits: Limits,
+    /// Also held across a save, so two can't race for the same id.
+    index: Mutex<Index>,
+}
+
+impl ShaderStore {
+    pub fn open(dir: impl Into<PathBuf>) -> io::Result<Self> {
+        Self::with_limits(dir, Limits::default())
+    }
+
+    /// Reads every snippet once to build the index; files that don't parse
+    /// are logged and left out.
+    pub fn with_limits(dir: impl Into<PathBuf>, limits: Limits) -> io::Result<Self> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+
+        let mut index = Index::default();
+        for entry in fs::read_dir(&dir)? {
+            let path = entry?.path();
+            if path.extension().is_none_or(|ext| ext != "json") {
+                continue;
+            }
+            let parsed = fs::read(&path).and_then(|bytes| {
+                let snippet: Snippet = serde_json::from_slice(&bytes)?;
+                Ok((snippet, bytes.len() as u64))
+            });
+            match parsed {
+                Ok((snippet, bytes)) => {
+                    index.summaries.push(Summary::from(&snippet));
+                    index.bytes += bytes;
+                }
+                Err(e) => log::warn!("skipping shader snippet {}: {e}", path.display()),
+            }
+        }
+        index.summaries.sort_by_key(|s| std::cmp::Reverse(s.created_at));
+
+        Ok(Self { dir, limits, index: Mutex::new(index) })
+    }
+
+    fn path(&self, id: &str) -> PathBuf {
+        self.dir.join(format!("{id}.json"))
+    }
+
+    fn load(&self, id: &str) -> io::Result<Option<Snippet>> {
+        match fs::read(self.path(id)) {
+            Ok(bytes) => Ok(Some(serde_json::from_slice(&bytes)?)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Stores `new` unless it's already there; returns the stored snippet
+    /// and whether it was created just now.
+    fn save(&self, new: NewSnippet) -> Result<(Snippet, bool), SaveError> {
+        let mut hasher = Sha1::new();
+        for part in [&new.demo, &new.vs, &new.fs] {
+            hasher.update(part.as_bytes());
+            hasher.update([0]);
+        }
+        // left out when empty, so sources saved on their own keep their ids;
+        // keys are sorted, so the same lab always serializes the same
+        if !new.lab.is_empty() {
+            hasher.update(Value::Object(new.lab.clone()).to_string().as_bytes());
+            hasher.update([0]);
+        }
+        let hash: String = hasher.finalize().iter().map(|b| format!("{b:02x}")).collect();
+
+        let mut index = self.index.lock().unwrap_or_else(|e| e.into_inner());
+        // a shorter id taken by other content moves on to a longer one
+        for len in (ID_LEN..=HASH_LEN).step_by(ID_STEP) {
+            let id = &hash[..len];
+            match self.load(id) {
+                Ok(Some(old))
+                    if (&old.demo, &old.vs, &old.fs, &old.lab)
+                        == (&new.demo, &new.vs, &new.fs, &new.lab) =>
+                {
+                    return Ok((old, false));
+                }
+                Ok(Some(_)) => continue,
+                Ok(None) => {}
+                Err(e) => match e.kind() {
+                    // not a snippet (or half of one): taken all the same
+                    io::ErrorKind::InvalidData | io::ErrorKind::UnexpectedEof => continue,
+                    _ => return Err(e.into()),
+                },
+            }
+
+            let snippet = Snippet {
+                id: id.to_owned(),
+                demo: new.demo,
+                vs: new.vs,
+                fs: new.fs,
+                lab: new.lab,
+                title: new.title,
+                created_at: Utc::now(),
+            };
+            let json = serde_json::to_vec(&snippet).map_err(io::Error::from)?;
+            let full = index.summaries.len() >= self.limits.max_snippets
+                || index.bytes + json.len() as u64 > self.limits.max_bytes;
+            if full {
+                return Err(SaveError::Full);
+            }
+
+            // written aside and renamed in, so readers never see half a file
+            let tmp = self.dir.join(format!(".{id}.tmp"));
+            fs::write(&tmp, &json)?;
+            fs::rename(&tmp, self.path(id))?;
+            index.summaries.insert(0, Summary::from(&snippet));
+            index.bytes += json.len() as u64;
+            return Ok((snippet, true));
+        }
+        Err(io::Error::other("hash collision on the full id").into())
+    }
+
+    /// Newest first.
+    fn list(&self, limit: usize) -> Vec<Summary> {
+        let index = self.index.lock().unwrap_or_else(|e| e.into_inner());
+        index.summaries.iter().take(limit).cloned().collect()
+    }
+}
+
+/// Saves per client address in a fixed window; the store's [`Limits`] bound
+/// what gets through all the same.
+pub struct RateLimit {
+    max: u32,
+    hits: Cache<String, Arc<AtomicU32>>,
+    /// Set by the proxy in front to the address it was connected from;
+    /// `X-Forwarded-For` and the like come from the client and aren't used.
+    client_header: Option<HeaderName>,
+}
+
+impl RateLimit {
+    pub fn new(max: u32, window: Duration) -> Self {
+        Self {
+            max,
+            hits: Cache::builder().time_to_live(window).max_capacity(100_000).build(),
+            client_header: None,
+        }
+    }
+
+    /// Count by `header` (e.g. `Fly-Client-IP`) rather than the peer
+    /// address; only for a header the proxy overwrites on every request.
+    pub fn with_client_header(mut self, header: HeaderName) -> Self {
+        self.client_header = Some(header);
+        self
+    }
+
+    /// Who `req` counts against: the trusted header if it's there, else the
+    /// address of the peer without its port.
+    fn client(&self, req: &HttpRequest) -> String {
+        let header = self
+            .client_header
+            .as_ref()
+            .and_then(|name| req.headers().get(name))
+            .and_then(|value| value.to_str().ok());
+        match (header, req.peer_addr()) {
+            (Some(client), _) => client.trim().to_owned(),
+            (None, Some(peer)) => peer.ip().to_string(),
+            (None, None) => "unknown".to_owned(),
+        }
+    }
+
+    async fn allow(&self, client: &str) -> bool {
+        let hits = self
+            .hits
+            .get_with(client.to_owned(), async { Arc::new(AtomicU32::new(0)) })
+            .await;
+        hits.fetch_add(1, Ordering::Relaxed) < self.max
+    }
+}
+
+impl Default for RateLimit {
+    fn default() -> Self {
+        Self::new(10, Duration::from_secs(60))
+    }
+}
+
+fn validate(new: &NewSnippet) -> Result<(), String> {
+    let demo_ok = !new.demo.is_empty()
+        && new.demo.len() <= 32
+        && new.demo.bytes().all(|b| b.is_ascii_lowercase() || b.is_ascii_digit() || b == b'-');
+    if !demo_ok {
+        return Err(format!("bad demo id `{}`", new.demo));
+    }
+    if new.vs.len() > MAX_SOURCE || new.fs.len() > MAX_SOURCE {
+        return Err(format!("sources are limited to {} KB each", MAX_SOURCE / 1024));
+    }
+    if Value::Object(new.lab.clone()).to_string().len() > MAX_LAB {
+        return Err(format!("the lab is limited to {} KB", MAX_LAB / 1024));
+    }
+    if new.title.chars().count() > MAX_TITLE {
+        return Err(format!("titles are limited to {MAX_TITLE} characters"));
+    }
+    Ok(())
+}
+
+/// Ids are lowercase hash prefixes as `save` cuts them; anything else can't
+/// be one (and can't walk out of the store's directory).
+fn valid_id(id: &str) -> bool {
+    (ID_LEN..=HASH_LEN).contains(&id.len())
+        && (id.len() - ID_LEN) % ID_STEP == 0
+        && id.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'))
+}
+
+#[post("/api/shaders")]
+async fn save_shader(
+    req: HttpRequest,
+    store: web::Data<ShaderStore>,
+    rate: web::Data<RateLimit>,
+    body: web::Json<NewSnippet>,
+) -> actix_web::Result<HttpResponse> {
+    if !rate.allow(&rate.client(&req)).await {
+        return Err(actix_web::error::ErrorTooManyRequests("too many saves, try again later"));
+    }
+
+    let mut new = body.into_inner();
+    new.title = new.title.trim().to_owned();
+    validate(&new).map_err(actix_web::error::ErrorBadRequest)?;
+
+    let (snippet, created) = web::block(move || store.save(new)).await?.map_err(|e| match e {
+        SaveError::Full => actix_web::error::InternalError::new(
+            "the shader store is full",
+            StatusCode::INSUFFICIENT_STORAGE,
+        )
+        .into(),
+        SaveError::Io(e) => actix_web::error::ErrorInternalServerError(e),
+    })?;
+
+    let mut response = if created { HttpResponse::Created() } else { HttpResponse::Ok() };
+    Ok(response.json(snippet))
+}
+
+#[get("/api/shaders/{id}")]
+async fn load_shader(
+    store: web::Data<ShaderStore>,
+    id: web::Path<String>,
+) -> actix_web::Result<web::Json<Snippet>> {
+    let id = id.into_inner();
+    if !valid_id(&id) {
+        return Err(actix_web::error::ErrorNotFound("no such shader"));
+    }
+
+    web::block(move || store.load(&id))
+        .await?
+        .map_err(actix_web::error::ErrorInternalServerError)?
+        .map(web::Json)
+        .ok_or_else(|| actix_web::error::ErrorNotFound("no such shader"))
+}
+
+#[get("/api/shaders")]
+async fn list_shaders(
+    store: web::Data<ShaderStore>,
+    query: web::Query<ListQuery>,
+) -> web::Json<Vec<Summary>> {
+    let limit = query.limit.unwrap_or(50).min(200);
+    web::Json(store.list(limit))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{App, test as actix_test};
+    use std::sync::atomic::AtomicUsize;
+
+    /// A fresh directory under the system temp dir, removed on drop.
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new() -> Self {
+            static NEXT: AtomicUsize = AtomicUsize::new(0);
+            let n = NEXT.fetch_add(1, Ordering::Relaxed);
+            let dir = std::env::temp_dir().join(format!("shaders-{}-{n}", std::process::id()));
+            let _ = fs::remove_dir_all(&dir);
+            Self(dir)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn snippet(fs: &str) -> NewSnippet {
+        NewSnippet {
+            demo: "cube".into(),
+            vs: "// vs".into(),
+            fs: fs.into(),
+            lab: Map::new(),
+            title: String::new(),
+        }
+    }
+
+    fn save(store: &ShaderStore, new: NewSnippet) -> (Snippet, bool) {
+        match store.save(new) {
+            Ok(saved) => saved,
+            Err(SaveError::Full) => panic!("store full"),
+            Err(SaveError::Io(e)) => panic!("{e}"),
+        }
+    }
+
+    #[test]
+    fn saves_load_back_and_dedupe() {
+        let dir = TempDir::new();
+        let store = ShaderStore::open(&dir.0).unwrap();
+
+        let (first, created) = save(&store, snippet("// one"));
+        assert!(created);
+        assert_eq!(first.id.len(), ID_LEN);
+        assert!(valid_id(&first.id));
+        assert_eq!(store.load(&first.id).unwrap().unwrap().fs, "// one");
+
+        let (again, created) = save(&store, snippet("// one"));
+        assert!(!created);
+        assert_eq!(again.id, first.id);
+
+        let (second, _) = save(&store, snippet("// two"));
+        let ids: Vec<_> = store.list(10).into_iter().map(|s| s.id).collect();
+        assert_eq!(ids, [second.id.clone(), first.id.clone()]);
+
+        // a reopened store indexes what's on disk
+        let reopened = ShaderStore::open(&dir.0).unwrap();
+        assert_eq!(reopened.list(10).len(), 2);
+        assert!(store.load("0123456789ab").unwrap().is_none());
+    }
+
+    #[test]
+    fn taken_ids_move_on_to_longer_ones() {
+        let dir = TempDir::new();
+        let store = ShaderStore::open(&dir.0).unwrap();
+        let (saved, _) = save(&store, snippet("// one"));
+        fs::remove_file(store.path(&saved.id)).unwrap();
+
+        // other content under the id it would get
+        let squatter = Snippet { fs: "// other".into(), ..saved.clone() };
+        fs::write(store.path(&saved.id), serde_json::to_vec(&squatter).unwrap()).unwrap();
+
+        let (moved, created) = save(&store, snippet("// one"));
+        assert!(created);
+        assert_eq!(moved.id.len(), ID_LEN + ID_STEP);
+        assert!(moved.id.starts_with(&saved.id));
+        assert!(valid_id(&moved.id));
+        assert_eq!(save(&store, snippet("// one")).0.id, moved.id);
+
+        // a file that isn't a snippet takes its id all the same
+        fs::write(store.path(&moved.id), b"{ not json").unwrap();
+        assert_eq!(save(&store, snippet("// one")).0.id.len(), ID_LEN + 2 * ID_STEP);
+    }
+
+    #[test]
+    fn bad_files_are_left_out_of_the_index() {
+        let dir = TempDir::new();
+        let store = ShaderStore::open(&dir.0).unwrap();
+        save(&store, snippet("// one"));
+        fs::write(dir.0.join("0123456789ab.json"), b"{ not json").unwrap();
+        fs::write(dir.0.join("notes.txt"), b"not a snippet").unwrap();
+
+        let reopened = ShaderStore::open(&dir.0).unwrap();
+        assert_eq!(reopened.list(10).len(), 1);
+    }
+
+    #[test]
+    fn full_stores_refuse_new_snippets() {
+        let dir = TempDir::new();
+        let limits = Limits { max_snippets: 2, max_bytes: u64::MAX };
+        let store = ShaderStore::with_limits(&dir.0, limits).unwrap();
+        save(&store, snippet("// one"));
+        save(&store, snippet("// two"));
+        assert!(matches!(store.save(snippet("// three")), Err(SaveError::Full)));
+        // what's there already is still handed back
+        assert!(!save(&store, snippet("// one")).1);
+
+        let dir = TempDir::new();
+        let limits = Limits { max_snippets: usize::MAX, max_bytes: 300 };
+        let store = ShaderStore::with_limits(&dir.0, limits).unwrap();
+        save(&store, snippet("// one"));
+        assert!(matches!(store.save(snippet(&"x".repeat(200))), Err(SaveError::Full)));
+        assert_eq!(store.list(10).len(), 1);
+    }
+
+    #[test]
+    fn ids_match_the_generated_form_only() {
+        for id in ["0123456789ab", "0123456789abcdef", &"f".repeat(HASH_LEN)] {
+            assert!(valid_id(id), "{id}");
+        }
+        for id in [
+            "",
+            "0123456789a",
+            "0123456789abc",
+            "0123456789AB",
+            "0123456789ag",
+            "../../etc/pwd",
+            &"f".repeat(HASH_LEN + ID_STEP),
+        ] {
+            assert!(!valid_id(id), "{id}");
+        }
+    }
+
+    #[actix_web::test]
+    async fn rate_limit_counts_per_client() {
+        let rate = RateLimit::new(2, Duration::from_secs(60));
+        assert!(rate.allow("a").await);
+        assert!(rate.allow("a").await);
+        assert!(!rate.allow("a").await);
+        assert!(rate.allow("b").await);
+    }
+
+    #[actix_web::test]
+    async fn forwarding_headers_from_the_client_share_its_bucket() {
+        let dir = TempDir::new();
+        let app = actix_test::init_service(
+            App::new()
+                .app_data(web::Data::new(ShaderStore::open(&dir.0).unwrap()))
+                .app_data(web::Data::new(RateLimit::new(2, Duration::from_secs(60))))
+                .service(save_shader),
+        )
+        .await;
+
+        let peer = "203.0.113.7:4000".parse().unwrap();
+        for n in 0..3 {
+            let body = serde_json::json!({ "demo": "cube", "vs": "", "fs": format!("// {n}") });
+            let req = actix_test::TestRequest::post()
+                .uri("/api/shaders")
+                .peer_addr(peer)
+                .insert_header(("X-Forwarded-For", format!("198.51.100.{n}")))
+                .insert_header(("Forwarded", format!("for=198.51.100.{n}")))
+                .set_json(&body);
+            let resp = actix_test::call_service(&app, req.to_request()).await;
+            let expected = if n < 2 { StatusCode::CREATED } else { StatusCode::TOO_MANY_REQUESTS };
+            assert_eq!(resp.status(), expected, "save {n}");
+        }
+    }
+
+    #[test]
+    fn clients_are_the_peer_or_the_trusted_header() {
+        let req = |peer: &str| {
+            actix_test::TestRequest::default()
+                .peer_addr(peer.parse().unwrap())
+                .insert_header(("X-Forwarded-For", "198.51.100.1"))
+                .insert_header(("Fly-Client-IP", "198.51.100.2"))
+        };
+        let rate = RateLimit::default();
+        // the port changes per connection, so it isn't part of the key
+        assert_eq!(rate.client(&req("203.0.113.7:4000").to_http_request()), "203.0.113.7");
+        assert_eq!(rate.client(&req("203.0.113.7:4001").to_http_request()), "203.0.113.7");
+
+        let rate = rate.with_client_header(HeaderName::from_static("fly-client-ip"));
+        assert_eq!(rate.client(&req("203.0.113.7:4000").to_http_request()), "198.51.100.2");
+        let direct = actix_test::TestRequest::default()
+            .peer_addr("203.0.113.7:4000".parse().unwrap())
+            .to_http_request();
+        assert_eq!(rate.client(&direct), "203.0.113.7");
+    }
+
+    #[actix_web::test]
+    async fn handlers_save_load_and_reject() {
+        let dir = TempDir::new();
+        let app = actix_test::init_service(
+            App::new()
+                .app_data(web::Data::new(ShaderStore::open(&dir.0).unwrap()))
+                .app_data(web::Data::new(RateLimit::new(2, Duration::from_secs(60))))
+                .service(save_shader)
+                .service(load_shader)
+                .service(list_shaders),
+        )
+        .await;
+
+        let body = serde_json::json!({ "demo": "cube", "vs": "// vs", "fs": "// fs" });
+        let post = || actix_test::TestRequest::post().uri("/api/shaders").set_json(&body);
+        let resp = actix_test::call_service(&app, post().to_request()).await;
+        assert_eq!(resp.status(), StatusCode::CREATED);
+        let saved: Snippet = actix_test::read_body_json(resp).await;
+
+        let resp = actix_test::call_service(&app, post().to_request()).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let resp = actix_test::call_service(&app, post().to_request()).await;
+        assert_eq!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+
+        let get = |id: &str| actix_test::TestRequest::get().uri(&format!("/api/shaders/{id}"));
+        let loaded: Snippet =
+            actix_test::call_and_read_body_json(&app, get(&saved.id).to_request()).await;
+        assert_eq!((loaded.vs, loaded.fs), ("// vs".into(), "// fs".into()));
+
+        let upper = saved.id.to_uppercase();
+        for id in [upper.as_str(), "0123456789ab", "..%2F..%2Fsecret"] {
+            let resp = actix_test::call_service(&app, get(id).to_request()).await;
+            assert_eq!(resp.status(), StatusCode::NOT_FOUND, "{id}");
+        }
+    }
+
+    #[test]
+    fn the_lab_is_kept_and_tells_saves_apart() {
+        let dir = TempDir::new();
+        let store = ShaderStore::open(&dir.0).unwrap();
+        let (bare, _) = save(&store, snippet("// one"));
+        assert_eq!(bare.id, "b728ba4f5b89", "ids of sources alone don't change");
+
+        let lab = serde_json::json!({ "module": "// one module", "passes": { "bloom": false } });
+        let with_lab = || NewSnippet { lab: lab.as_object().unwrap().clone(), ..snippet("// one") };
+        let (saved, created) = save(&store, with_lab());
+        assert!(created);
+        assert_ne!(saved.id, bare.id);
+        assert_eq!(save(&store, with_lab()).0.id, saved.id);
+        assert_eq!(Value::Object(store.load(&saved.id).unwrap().unwrap().lab), lab);
+
+        // snippets from before the lab was kept still load
+        let mut old = serde_json::to_value(&bare).unwrap();
+        old.as_object_mut().unwrap().remove("lab");
+        fs::write(store.path(&bare.id), old.to_string()).unwrap();
+        assert!(store.load(&bare.id).unwrap().unwrap().lab.is_empty());
+
+        let huge = serde_json::json!({ "files": { "big.wgsl": "x".repeat(MAX_LAB) } });
+        let new = NewSnippet { lab: huge.as_object().unwrap().clone(), ..snippet("// one") };
+        assert!(validate(&new).unwrap_err().contains("lab"));
+    }
+}
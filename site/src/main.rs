@@ -1,6 +1,7 @@
 use actix_files::{Files, NamedFile};
 use actix_web::{web, App, HttpServer, middleware::Logger};
 use api::projects::projects;
+use api::shaders::{RateLimit, ShaderStore, list_shaders, load_shader, save_shader};
 use std::env;
 use dotenvy::dotenv;
 
@@ -13,6 +14,15 @@ async fn main() -> std::io::Result<()> {
 
     let projects_pat = env::var("PROJECTS_PAT").expect("PROJECTS_PAT missing");
     let port: u16 = env::var("PORT").unwrap_or_else(|_| "3000".into()).parse().unwrap();
+    let shader_dir = env::var("SHADER_STORE_DIR").unwrap_or_else(|_| "./data/shaders".into());
+    let shader_store = web::Data::new(ShaderStore::open(shader_dir)?);
+    // behind a proxy every peer is the proxy; count by the header it sets
+    let shader_saves = match env::var("CLIENT_IP_HEADER") {
+        Ok(header) => RateLimit::default()
+            .with_client_header(header.parse().expect("CLIENT_IP_HEADER isn't a header name")),
+        Err(_) => RateLimit::default(),
+    };
+    let shader_saves = web::Data::new(shader_saves);
 
     {
         const BANNER: &str = r#"
@@ -39,6 +49,12 @@ async fn main() -> std::io::Result<()> {
             .service(Files::new("/pkg", "./dist"))
             .service(Files::new("/assets", "./dist/assets"))
             .service(projects)
+            .app_data(shader_store.clone())
+            .app_data(shader_saves.clone())
+            .app_data(web::JsonConfig::default().limit(256 * 1024))
+            .service(save_shader)
+            .service(load_shader)
+            .service(list_shaders)
             .service(
                 Files::new("/", "./dist")
                     .index_file("index.html")
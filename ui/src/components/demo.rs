@@ -1,3 +1,5 @@
+use std::collections::BTreeMap;
+
 use glam::Vec2;
 use leptos::prelude::ClassAttribute;
 use leptos::prelude::ElementChild;
@@ -11,10 +13,13 @@ use wasm_bindgen::JsCast;
 use web_sys::{HtmlCanvasElement, HtmlElement, PointerEvent};
 
 use crate::pages::classic::classic::PassFlags;
+use crate::render::renderer::gpu::textures::channel_name;
+
+use super::shader_editor::Lab;
 
 use super::demos::{animals::main::Animals, frag_intro::main::FragIntro, planet::main::CubePlanet};
 
-#[derive(Copy, Clone, PartialEq, Eq)]
+#[derive(Copy, Clone, PartialEq, Eq, Hash)]
 pub enum Demo {
     CubePlanet,
     Animals,
@@ -30,7 +35,51 @@ const FRAG_FS: &str = include_str!("../render/renderer/shaders/frag_intro.frag.w
 const FISH_VS: &str = include_str!("../render/renderer/shaders/fish.vert.wgsl");
 const FISH_FS: &str = include_str!("../render/renderer/shaders/fish.frag.wgsl");
 
+/// The pair as one module: the stages declare nothing twice, and what they
+/// both include is pasted in once.
+macro_rules! module {
+    ($name:literal) => {
+        concat!(
+            "// ", $name, ".vert.wgsl and ", $name, ".frag.wgsl as one module\n",
+            include_str!(concat!("../render/renderer/shaders/", $name, ".vert.wgsl")),
+            "\n",
+            include_str!(concat!("../render/renderer/shaders/", $name, ".frag.wgsl")),
+        )
+    };
+}
+
+const CUBE_MODULE: &str = module!("cube");
+const FRAG_MODULE: &str = module!("frag_intro");
+const FISH_MODULE: &str = module!("fish");
+
+/// Served from `ui/assets`.
+const PLANET_TEXTURE: &str = "/assets/images/texture.png";
+
 impl Demo {
+    pub const ALL: [Demo; 3] = [Demo::Animals, Demo::CubePlanet, Demo::FragIntro];
+
+    /// Short name for commands (`:demo frag-intro`) and links.
+    pub fn slug(&self) -> &'static str {
+        match self {
+            Demo::Animals => "animals",
+            Demo::CubePlanet => "cube-planet",
+            Demo::FragIntro => "frag-intro",
+        }
+    }
+
+    /// Exact slug, or a prefix only one demo has (`frag`).
+    pub fn from_slug(name: &str) -> Option<Demo> {
+        let name = name.to_ascii_lowercase();
+        if let Some(demo) = Self::ALL.into_iter().find(|d| d.slug() == name) {
+            return Some(demo);
+        }
+        let mut matches = Self::ALL.into_iter().filter(|d| d.slug().starts_with(&name));
+        match (matches.next(), matches.next()) {
+            (Some(demo), None) => Some(demo),
+            _ => None,
+        }
+    }
+
     pub fn label(&self) -> &'static str {
         match self {
             Demo::Animals => "Procedurally Animated Animals",
@@ -46,6 +95,49 @@ impl Demo {
         }
     }
 
+    /// Both of [`shaders`](Self::shaders) in one module.
+    pub fn module(&self) -> &'static str {
+        match self {
+            Demo::Animals => FISH_MODULE,
+            Demo::CubePlanet => CUBE_MODULE,
+            Demo::FragIntro => FRAG_MODULE,
+        }
+    }
+
+    /// The passes drawing with the editor's sources, by label, with the
+    /// (vertex, fragment) entry points they run unless others are picked.
+    pub fn entry_points(&self) -> &'static [(&'static str, (&'static str, &'static str))] {
+        match self {
+            Demo::Animals => &[
+                ("skin pass", ("bones_vs", "bones_fs")),
+                ("circle pass", ("joints_vs", "joints_fs")),
+            ],
+            Demo::CubePlanet | Demo::FragIntro => {
+                &[("Default Render Pass", ("vs_main", "fs_main"))]
+            }
+        }
+    }
+
+    /// Whether the demo's passes bind the texturing group (2), on top of
+    /// the common and spatial groups every pass has.
+    pub fn textured(&self) -> bool {
+        !matches!(self, Demo::Animals)
+    }
+
+    /// Whether a model can be loaded in place of the demo's mesh.
+    pub fn models(&self) -> bool {
+        matches!(self, Demo::CubePlanet)
+    }
+
+    /// What the demo's texture channels load, by channel name, unless
+    /// others are picked.
+    pub fn textures(&self) -> BTreeMap<String, String> {
+        match self {
+            Demo::CubePlanet => [(channel_name(0), PLANET_TEXTURE.to_owned())].into(),
+            Demo::Animals | Demo::FragIntro => BTreeMap::new(),
+        }
+    }
+
     pub fn description(&self) -> impl IntoView + use<> {
         match self {
             Demo::Animals => view! {
@@ -101,16 +193,11 @@ impl Demo {
     }
 
     /// Returns a View that mounts the proper canvas component
-    pub fn canvas(
-        self,
-        vs_src: RwSignal<String>,
-        fs_src: RwSignal<String>,
-        pass_flags: PassFlags,
-    ) -> AnyView {
+    pub fn canvas(self, lab: Lab, pass_flags: PassFlags) -> AnyView {
         match self {
-            Demo::Animals => view! { <Animals vs_src fs_src pass_flags/> }.into_any(),
-            Demo::CubePlanet => view! { <CubePlanet vs_src fs_src/> }.into_any(),
-            Demo::FragIntro => view! { <FragIntro vs_src fs_src/> }.into_any(),
+            Demo::Animals => view! { <Animals shaders=lab.shaders pass_flags/> }.into_any(),
+            Demo::CubePlanet => view! { <CubePlanet lab/> }.into_any(),
+            Demo::FragIntro => view! { <FragIntro lab/> }.into_any(),
         }
     }
 }
@@ -165,3 +252,39 @@ pub fn to_clip_space(e: &PointerEvent, canvas: &HtmlCanvasElement) -> Vec2 {
 
     p
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::render::renderer::gpu::diagnostics::{Severity, Stage, check_wgsl, entry_points};
+    use crate::render::renderer::gpu::preprocess::expand_shipped;
+
+    fn errors(src: &str, stage: Stage) -> Vec<String> {
+        let expanded = expand_shipped(src, stage).unwrap();
+        check_wgsl(&expanded.code, stage)
+            .into_iter()
+            .filter(|d| d.severity == Severity::Error)
+            .map(|d| d.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn shipped_shaders_and_their_modules_validate() {
+        for demo in Demo::ALL {
+            let (vs, fs) = demo.shaders();
+            assert_eq!(errors(vs, Stage::Vertex), Vec::<String>::new(), "{}", demo.slug());
+            assert_eq!(errors(fs, Stage::Fragment), Vec::<String>::new(), "{}", demo.slug());
+
+            let module = demo.module();
+            assert!(module.contains(vs) && module.contains(fs));
+            for stage in [Stage::Vertex, Stage::Fragment] {
+                assert_eq!(errors(module, stage), Vec::<String>::new(), "{}", demo.slug());
+            }
+            let found = entry_points(&expand_shipped(module, Stage::Vertex).unwrap().code);
+            for (_, (vs_entry, fs_entry)) in demo.entry_points() {
+                assert!(found.contains(&(vs_entry.to_string(), Stage::Vertex)), "{found:?}");
+                assert!(found.contains(&(fs_entry.to_string(), Stage::Fragment)), "{found:?}");
+            }
+        }
+    }
+}
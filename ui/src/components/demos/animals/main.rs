@@ -1,9 +1,10 @@
 use leptos::{
     IntoView, component,
-    prelude::{Effect, Get, RwSignal},
+    prelude::RwSignal,
     view,
 };
 
+use crate::components::demo::Demo;
 use crate::pages::classic::classic::PassFlags;
 
 use glam::Vec2;
@@ -14,7 +15,11 @@ use crate::{
         make_points_rpass, start_rendering
     },
     meshes,
-    render::renderer::{camera_input::CameraInput, gpu::GpuState},
+    render::renderer::{
+        camera_input::CameraInput,
+        gpu::GpuState,
+        graph::{LiveShaders, RenderGraph},
+    },
 };
 
 use super::utils::{drag_head_to_cursor, make_skin_rpass, make_spine_rpass, solve_chain};
@@ -168,7 +173,7 @@ impl Animal {
         a
     }
 
-    pub fn recompute_joints(&mut self, pts: &Vec<Vec2>) {
+    pub fn recompute_joints(&mut self, pts: &[Vec2]) {
         for (i, p) in pts.iter().enumerate() {
             let center = *p;
 
@@ -224,23 +229,9 @@ impl Animal {
     }
 }
 
-#[component]
-pub fn Animals(
-    vs_src: RwSignal<String>,
-    fs_src: RwSignal<String>,
-    pass_flags: PassFlags,
-) -> impl IntoView {
-    let state_rc: Rc<RefCell<Option<GpuState>>> = Rc::new(RefCell::new(None));
-
-    let points_rc: Rc<RefCell<Vec<Vec2>>> =
-        Rc::new(RefCell::new(meshes::animals::FISH_SPINE.to_vec()));
-
-    let camera_rc: Rc<RefCell<Option<CameraInput>>> = Rc::new(RefCell::new(None));
-
-    let gpu_support = RwSignal::new(true);
-    let show_hint = RwSignal::new(true);
-
-    let sizes = vec![
+/// Fish whose spine runs through `pts` (head first), skin already computed.
+pub fn fish(pts: &[Vec2]) -> Animal {
+    let sizes = [
         Vec2::new(0.1, 0.10),
         Vec2::new(0.1, 0.15),
         Vec2::new(0.1, 0.25),
@@ -251,78 +242,71 @@ pub fn Animals(
         Vec2::new(0.1, 0.025),
     ];
 
-    let snake = {
-        let pts = points_rc.borrow();
+    let mut joints = Vec::with_capacity(pts.len());
+    for (i, (p, s)) in pts.iter().zip(sizes).enumerate() {
+        let center = *p;
 
-        let mut joints = Vec::with_capacity(pts.len());
-        for (i, (p, s)) in pts.iter().zip(sizes.clone()).enumerate() {
-            let center = *p;
+        let dir = {
+            if i == pts.len() - 1 {
+                -(p - pts[i - 1]).normalize()
+            } else {
+                (p - pts[i + 1]).normalize()
+            }
+        };
 
-            let dir = {
-                if i == pts.len() - 1 {
-                    -(p - pts[i - 1]).normalize()
-                } else {
-                    (p - pts[i + 1]).normalize()
-                }
-            };
+        joints.push(Joint::new(center, s, dir));
+    }
 
-            joints.push(Joint::new(center, Vec2::new(s[0], s[1]), dir));
-        }
+    Animal::new(joints)
+}
 
-        Animal::new(joints.clone())
-    };
+#[component]
+pub fn Animals(shaders: LiveShaders, pass_flags: PassFlags) -> impl IntoView {
+    let state_rc: Rc<RefCell<Option<GpuState>>> = Rc::new(RefCell::new(None));
 
-    let snake_rc = Rc::new(RefCell::new(snake));
+    let points_rc: Rc<RefCell<Vec<Vec2>>> =
+        Rc::new(RefCell::new(meshes::animals::FISH_SPINE.to_vec()));
 
-    let (spine_pass, spine_pipe) = make_spine_rpass(
+    let camera_rc: Rc<RefCell<Option<CameraInput>>> = Rc::new(RefCell::new(None));
+
+    let gpu_support = RwSignal::new(true);
+    let show_hint = RwSignal::new(true);
+
+    let snake_rc = Rc::new(RefCell::new(fish(&points_rc.borrow())));
+
+    let spine_pass = make_spine_rpass(
         snake_rc.clone(),
-        vs_src,
-        fs_src,
-        pass_flags.init_pass("Spine pass", true),
+        shaders,
+        pass_flags.init_pass(Demo::Animals, "Spine pass", true),
     );
 
-    let (skin_pass, skin_pipe) = make_skin_rpass(
+    let skin_pass = make_skin_rpass(
         snake_rc.clone(),
         0.015,
-        vs_src,
-        fs_src,
-        pass_flags.init_pass("Skin pass", true),
+        shaders,
+        pass_flags.init_pass(Demo::Animals, "Skin pass", true),
     );
 
-    {
-        let vs_src = vs_src.clone();
-        let fs_src = fs_src.clone();
-        let pipes = [skin_pipe.clone(), spine_pipe.clone()];
-
-        Effect::new(move |_| {
-            vs_src.get();
-            fs_src.get();
-            for p in &pipes {
-                *p.borrow_mut() = None;
-            }
-        });
-    }
-
-    start_rendering(
+    let playback = start_rendering(
         state_rc,
         camera_rc,
         show_hint,
         gpu_support,
         CANVAS_ID,
-        vec![
+        RenderGraph::with_nodes([
             skin_pass,
             spine_pass,
             make_points_rpass(
                 points_rc.clone(),
                 [1., 0., 0., 0.],
-                pass_flags.init_pass("Spine debug points pass", true),
+                pass_flags.init_pass(Demo::Animals, "Spine debug points pass", true),
             ),
             make_points_rpass(
                 snake_rc.clone().borrow().skin.clone(),
                 [0., 1., 0., 0.],
-                pass_flags.init_pass("Skin debug points pass", true),
+                pass_flags.init_pass(Demo::Animals, "Skin debug points pass", true),
             ),
-        ],
+        ]),
         drag_head_to_cursor(points_rc.clone()),
         move || {
             solve_chain(points_rc.clone(), 0.15, 9)();
@@ -336,6 +320,6 @@ pub fn Animals(
     );
 
     view! {
-        { super::view::canvas(gpu_support, show_hint) }
+        { super::view::canvas(gpu_support, show_hint, playback) }
     }
 }
@@ -1,92 +1,28 @@
 use std::{cell::RefCell, rc::Rc};
 
 use glam::{Mat4, Vec2, Vec4};
-use leptos::prelude::{GetUntracked, RwSignal};
+use leptos::prelude::RwSignal;
 use web_sys::{HtmlCanvasElement, PointerEvent};
 
 use crate::{
-    components::{
-        demo::to_clip_space,
-        demos::utils::{add_listener, InstanceCtx, RenderPass},
-    }, meshes::{
+    components::{demo::to_clip_space, demos::utils::add_listener},
+    meshes::{
         quad::{QUAD_INDICES, QUAD_VERTS},
         utils::stroke_polyline,
-    }, render::renderer::{
-        camera_input::CameraInput,
-        gpu::{
-            gpu_state::{create_idx_buff_init, create_vert_buff_init, FrameCtx, Projection}, vertex_ctx::VertexCtx, GpuState
+    },
+    render::renderer::{
+        gpu::gpu_state::Projection,
+        graph::{
+            ColorAttachment, Geometry, LiveShaders, MaterialGroup, MeshPass, MeshPassDesc, NodeRef,
+            Shaders, Target,
         },
         instance::InstanceRaw,
-        vertex::Vertex,
-    }
+        mesh::CpuMesh,
+    },
 };
 
 use super::main::{Animal, Joint};
 
-fn make_joint_pipe(st: &GpuState, vs_src: &str, fs_src: &str) -> wgpu::RenderPipeline {
-    let color_target = Some(wgpu::ColorTargetState {
-        format: st.surface_context.config.format, // same as the swap-chain
-        blend: Some(wgpu::BlendState::PREMULTIPLIED_ALPHA_BLENDING),
-        write_mask: wgpu::ColorWrites::ALL,
-    });
-
-    let vs = st
-        .surface_context
-        .device
-        .create_shader_module(wgpu::ShaderModuleDescriptor {
-            label: Some("vs shader with custom topology"),
-            source: wgpu::ShaderSource::Wgsl(vs_src.into()),
-        });
-
-    let fs = st
-        .surface_context
-        .device
-        .create_shader_module(wgpu::ShaderModuleDescriptor {
-            label: Some("fs shader with custom topology"),
-            source: wgpu::ShaderSource::Wgsl(fs_src.into()),
-        });
-
-    let layout =
-        st.surface_context
-            .device
-            .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-                label: Some("spine layout"),
-                bind_group_layouts: &[
-                    &st.resource_context.common_bind_group.layout,
-                    &st.resource_context.spatial_bind_group.layout,
-                ],
-                push_constant_ranges: &[],
-            });
-
-    st.surface_context
-        .device
-        .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            label: Some("joints pipe"),
-            layout: Some(&layout),
-            cache: None,
-            vertex: wgpu::VertexState {
-                module: &vs,
-                entry_point: Some("joints_vs"),
-                buffers: &[Vertex::desc(), InstanceRaw::desc()],
-                compilation_options: Default::default(),
-            },
-            fragment: Some(wgpu::FragmentState {
-                module: &fs,
-                entry_point: Some("joints_fs"),
-                targets: &[color_target],
-                compilation_options: Default::default(),
-            }),
-            primitive: wgpu::PrimitiveState {
-                topology: wgpu::PrimitiveTopology::TriangleList,
-                strip_index_format: None,
-                ..Default::default()
-            },
-            depth_stencil: None,
-            multisample: Default::default(),
-            multiview: None,
-        })
-}
-
 /// Build one column-major model matrix per joint, ready for instancing.
 /// (scale → rotate → translate in a single shot, no trigonometry)
 fn build_joint_instances(joints: &[Joint]) -> Vec<InstanceRaw> {
@@ -113,234 +49,68 @@ fn build_joint_instances(joints: &[Joint]) -> Vec<InstanceRaw> {
         .collect()
 }
 
-pub(crate) fn make_spine_rpass(
+pub fn make_spine_rpass(
     snake: Rc<RefCell<Animal>>,
-
-    vs_src: RwSignal<String>,
-    fs_src: RwSignal<String>,
+    shaders: LiveShaders,
 
     enabled: RwSignal<bool>,
-) -> (RenderPass, Rc<RefCell<Option<wgpu::RenderPipeline>>>) {
-    let pipeline = Rc::new(RefCell::new(None));
-    let pipe_handle = pipeline.clone();
-
-    let vs_handle = vs_src.clone();
-    let fs_handle = fs_src.clone();
-
-    let vbuf_handle: Rc<RefCell<Option<wgpu::Buffer>>> = Rc::new(RefCell::new(None));
-    let ibuf_handle: Rc<RefCell<Option<wgpu::Buffer>>> = Rc::new(RefCell::new(None));
-    let inst_handle: Rc<RefCell<Option<InstanceCtx>>> = Rc::new(RefCell::new(None));
-
-    let pass = Rc::new(RefCell::new(
-        move |st: &mut GpuState, cam: &CameraInput, ctx: &mut FrameCtx| {
-            if !enabled.get_untracked() {
-                return;
-            }
-
-            st.populate_common_buffers(&Projection::FlatQuad, cam);
-
-            if pipe_handle.borrow().is_none() {
-                *pipe_handle.borrow_mut() = Some(make_joint_pipe(
-                    st,
-                    &vs_handle.get_untracked(),
-                    &fs_handle.get_untracked(),
-                ));
-            }
-
-            if vbuf_handle.borrow().is_none() {
-                *vbuf_handle.borrow_mut() =
-                    Some(create_vert_buff_init(&st.surface_context, QUAD_VERTS));
-                *ibuf_handle.borrow_mut() =
-                    Some(create_idx_buff_init(&st.surface_context, QUAD_INDICES));
-            }
-
-            if inst_handle.borrow().is_none() {
-                *inst_handle.borrow_mut() = Some(InstanceCtx::new(&st.surface_context, 256));
-            }
-
-            {
-                let mut binding = inst_handle.borrow_mut();
-                let inst = binding.as_mut().unwrap();
-
-                let s = snake.borrow();
-
-                inst.sync_instances(&st.surface_context, || build_joint_instances(&s.spine));
-            }
-
-            // 3) bind + draw
-            let mut rp = ctx.encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                label: Some("circle pass"),
-                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view: &ctx.color_view,
-                    resolve_target: None,
-                    ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Load,
-                        store: wgpu::StoreOp::Store,
-                    },
-                })],
-                depth_stencil_attachment: None,
-                ..Default::default()
-            });
-
-            rp.set_pipeline(pipe_handle.borrow().as_ref().unwrap());
-
-            let binding = vbuf_handle.borrow();
-            let vbuf = binding.as_ref().unwrap();
-            rp.set_vertex_buffer(0, vbuf.slice(..));
-
-            let binding = inst_handle.borrow();
-            let inst = binding.as_ref().unwrap();
-            rp.set_vertex_buffer(1, inst.buff.slice(..));
-
-            let binding = ibuf_handle.borrow();
-            let ibuf = binding.as_ref().unwrap();
-            rp.set_index_buffer(ibuf.slice(..), wgpu::IndexFormat::Uint16);
-
-            rp.set_bind_group(0, &st.resource_context.common_bind_group.group, &[]);
-            rp.set_bind_group(1, &st.resource_context.spatial_bind_group.group, &[]);
-
-            let idx_count = QUAD_INDICES.len() as u32;
-            let inst_count = inst_handle.borrow().as_ref().unwrap().count;
-            rp.draw_indexed(0..idx_count, 0, 0..inst_count);
+) -> NodeRef {
+    let quad = CpuMesh::new(QUAD_VERTS.to_vec(), QUAD_INDICES.to_vec());
+
+    MeshPass::new(MeshPassDesc {
+        label: "circle pass",
+        color: ColorAttachment {
+            target: Target::Surface,
+            load: wgpu::LoadOp::Load,
         },
-    ));
-
-    (pass, pipeline)
-}
-
-fn make_skin_pipe(st: &GpuState, vs_src: &str, fs_src: &str) -> wgpu::RenderPipeline {
-    let vs = st
-        .surface_context
-        .device
-        .create_shader_module(wgpu::ShaderModuleDescriptor {
-            label: Some("animals vs"),
-            source: wgpu::ShaderSource::Wgsl(vs_src.into()),
-        });
+        depth: None,
 
-    let fs = st
-        .surface_context
-        .device
-        .create_shader_module(wgpu::ShaderModuleDescriptor {
-            label: Some("animals fs"),
-            source: wgpu::ShaderSource::Wgsl(fs_src.into()),
-        });
+        shaders: Shaders::Live(shaders),
+        entry_points: ("joints_vs", "joints_fs"),
+        topology: wgpu::PrimitiveTopology::TriangleList,
+        blend: Some(wgpu::BlendState::PREMULTIPLIED_ALPHA_BLENDING),
+        material: MaterialGroup::None,
 
-    let layout =
-        st.surface_context
-            .device
-            .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-                label: Some("skin layout"),
-                bind_group_layouts: &[
-                    &st.resource_context.common_bind_group.layout,
-                    &st.resource_context.spatial_bind_group.layout,
-                ],
-                push_constant_ranges: &[],
-            });
+        geometry: Geometry::Mesh(Rc::new(RefCell::new(quad))),
+        instances: Some(Box::new(move || build_joint_instances(&snake.borrow().spine))),
+        projection: Rc::new(RefCell::new(Projection::FlatQuad)),
 
-    st.surface_context
-        .device
-        .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            label: Some("skin pipe"),
-            layout: Some(&layout),
-            cache: None,
-            vertex: wgpu::VertexState {
-                module: &vs,
-                entry_point: Some("bones_vs"),
-                buffers: &[Vertex::desc()],
-                compilation_options: Default::default(),
-            },
-            fragment: Some(wgpu::FragmentState {
-                module: &fs,
-                entry_point: Some("bones_fs"),
-                targets: &[Some(wgpu::ColorTargetState {
-                    format: st.surface_context.config.format,
-                    blend: None,
-                    write_mask: wgpu::ColorWrites::ALL,
-                })],
-                compilation_options: Default::default(),
-            }),
-            primitive: wgpu::PrimitiveState {
-                topology: wgpu::PrimitiveTopology::TriangleStrip,
-                ..Default::default()
-            },
-            depth_stencil: None,
-            multisample: Default::default(),
-            multiview: None,
-        })
+        enabled: Some(enabled),
+    })
+    .into_node()
 }
 
-pub(crate) fn make_skin_rpass(
+pub fn make_skin_rpass(
     snake: Rc<RefCell<Animal>>,
     width: f32,
-
-    vs_src: RwSignal<String>,
-    fs_src: RwSignal<String>,
+    shaders: LiveShaders,
 
     enabled: RwSignal<bool>,
-) -> (RenderPass, Rc<RefCell<Option<wgpu::RenderPipeline>>>) {
-    let pipeline = Rc::new(RefCell::new(None));
-    let pipe_handle = pipeline.clone();
-
-    let skin_ctx = Rc::new(RefCell::new(None::<VertexCtx<Vertex>>));
-
-    let pass = Rc::new(RefCell::new(
-        move |st: &mut GpuState, cam: &CameraInput, ctx: &mut FrameCtx| {
-            if !enabled.get_untracked() {
-                return;
-            }
-
-            st.populate_common_buffers(&Projection::FlatQuad, cam);
-
-            // (re-)compile pipeline if needed
-            if pipe_handle.borrow().is_none() {
-                let pipe = make_skin_pipe(st, &vs_src.get_untracked(), &fs_src.get_untracked());
-                *pipe_handle.borrow_mut() = Some(pipe);
-            }
-
-            // lazily create helper ctx
-            if skin_ctx.borrow().is_none() {
-                *skin_ctx.borrow_mut() = Some(VertexCtx::<Vertex>::new(&st.surface_context, 1024));
-            }
-
-            {
-                let mut ctx_v = skin_ctx.borrow_mut();
-                let vc = ctx_v.as_mut().unwrap();
-
-                let verts = {
-                    let s = snake.borrow();
-                    let skin = &s.skin.borrow();
-                    stroke_polyline(skin, width) // Vec<Vertex>
-                };
-
-                vc.sync(&st.surface_context, |v| v.extend(verts));
-            }
-
-            let mut rp = ctx.encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                label: Some("skin pass"),
-                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view: &ctx.color_view,
-                    resolve_target: None,
-                    ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Load,
-                        store: wgpu::StoreOp::Store,
-                    },
-                })],
-                depth_stencil_attachment: None,
-                ..Default::default()
-            });
-
-            let vc = skin_ctx.borrow();
-            let vc = vc.as_ref().unwrap();
-
-            rp.set_pipeline(pipe_handle.borrow().as_ref().unwrap());
-            rp.set_vertex_buffer(0, vc.buf.slice(..));
-            rp.set_bind_group(0, &st.resource_context.common_bind_group.group, &[]);
-            rp.set_bind_group(1, &st.resource_context.spatial_bind_group.group, &[]);
-            rp.draw(0..vc.count as u32, 0..1);
+) -> NodeRef {
+    MeshPass::new(MeshPassDesc {
+        label: "skin pass",
+        color: ColorAttachment {
+            target: Target::Surface,
+            load: wgpu::LoadOp::Load,
         },
-    ));
-
-    (pass, pipeline)
+        depth: None,
+
+        shaders: Shaders::Live(shaders),
+        entry_points: ("bones_vs", "bones_fs"),
+        topology: wgpu::PrimitiveTopology::TriangleStrip,
+        blend: None,
+        material: MaterialGroup::None,
+
+        geometry: Geometry::Dynamic(Box::new(move |verts| {
+            let s = snake.borrow();
+            verts.extend(stroke_polyline(&s.skin.borrow(), width));
+        })),
+        instances: None,
+        projection: Rc::new(RefCell::new(Projection::FlatQuad)),
+
+        enabled: Some(enabled),
+    })
+    .into_node()
 }
 
 pub(crate) fn solve_chain(
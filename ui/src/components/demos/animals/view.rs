@@ -4,22 +4,25 @@ use leptos::{
     view,
 };
 
+use crate::components::demos::playback::{Playback, PlaybackBar};
 use crate::components::demos::utils::WebGPUNotSupportedMsg;
 use super::main::CANVAS_ID;
 
-pub(crate) fn canvas(gpu_support: RwSignal<bool>, show_hint: RwSignal<bool>) -> impl IntoView {
+pub(crate) fn canvas(
+    gpu_support: RwSignal<bool>,
+    show_hint: RwSignal<bool>,
+    playback: Playback,
+) -> impl IntoView {
     view! {
         <div class="relative w-full group">
           <Show
-            when=move || matches!(gpu_support.get(), true)
+            when=move || gpu_support.get()
             fallback=move || view! { <WebGPUNotSupportedMsg/> }
           >
 
           <canvas
             id=CANVAS_ID
-            width="864"
-            height="1024"
-            class="w-full h-full object-cover touch-none select-none"
+            class="block w-full aspect-[27/32] touch-none select-none"
           ></canvas>
 
           <Show when=move || show_hint.get()>
@@ -34,6 +37,8 @@ pub(crate) fn canvas(gpu_support: RwSignal<bool>, show_hint: RwSignal<bool>) ->
               </div>
           </Show>
 
+          <PlaybackBar playback />
+
           </Show>
         </div>
     }
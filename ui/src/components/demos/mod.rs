@@ -3,3 +3,4 @@ pub mod animals;
 pub mod frag_intro;
 pub mod mandelbrot;
 pub mod planet;
+pub mod playback;
@@ -1,24 +1,27 @@
 use leptos::prelude::{
-    ClassAttribute, Effect, ElementChild, Get, GlobalAttributes, RwSignal, Show,
+    ClassAttribute, ElementChild, Get, GlobalAttributes, RwSignal, Show,
 };
 use std::cell::RefCell;
 use std::rc::Rc;
 
 use leptos::view;
 
+use crate::components::demos::playback::PlaybackBar;
 use crate::components::demos::utils::start_rendering;
 use crate::components::demos::utils::WebGPUNotSupportedMsg;
+use crate::components::shader_editor::Lab;
 use crate::meshes;
 use crate::render::renderer::camera_input::CameraInput;
 use crate::render::renderer::gpu::GpuState;
 use crate::render::renderer::gpu::gpu_state::Projection;
 use crate::render::renderer::gpu::gpu_state::make_default_rpass;
+use crate::render::renderer::graph::RenderGraph;
 use crate::render::renderer::mesh::CpuMesh;
 use leptos::IntoView;
 use leptos::component;
 
 #[component]
-pub fn CubePlanet(vs_src: RwSignal<String>, fs_src: RwSignal<String>) -> impl IntoView {
+pub fn CubePlanet(lab: Lab) -> impl IntoView {
     let canvas_id = "cube-demo-canvas";
 
     let state_rc: Rc<RefCell<Option<GpuState>>> = Rc::new(RefCell::new(None));
@@ -35,30 +38,21 @@ pub fn CubePlanet(vs_src: RwSignal<String>, fs_src: RwSignal<String>) -> impl In
     let mesh = Rc::new(RefCell::new(mesh));
     let proj = Rc::new(RefCell::new(Projection::Fulcrum));
 
-    let (default_rpass, default_pipe) = make_default_rpass(mesh.clone(), proj, vs_src, fs_src);
-    {
-        let vs_src = vs_src.clone();
-        let fs_src = fs_src.clone();
-        let pipes = [
-            default_pipe.clone(),
-        ];
-
-        Effect::new(move |_| {
-            vs_src.get();
-            fs_src.get();
-            for p in &pipes {
-                *p.borrow_mut() = None;
-            }
-        });
-    }
+    let default_rpass = make_default_rpass(
+        mesh.clone(),
+        proj,
+        lab.shaders,
+        lab.textures,
+        Some(lab.model.mesh()),
+    );
 
-    start_rendering(
+    let playback = start_rendering(
         state_rc,
         camera_rc,
         show_hint,
         gpu_support,
         canvas_id,
-        vec![default_rpass],
+        RenderGraph::with_nodes([default_rpass]),
         |_| {},
         || {},
     );
@@ -67,15 +61,13 @@ pub fn CubePlanet(vs_src: RwSignal<String>, fs_src: RwSignal<String>) -> impl In
     view! {
         <div class="relative w-full group">
           <Show
-            when=move || matches!(gpu_support.get(), true)
+            when=move || gpu_support.get()
             fallback=move || view! { <WebGPUNotSupportedMsg/> }
           >
 
           <canvas
             id=canvas_id
-            width="864"
-            height="1024"
-            class="w-full h-full object-cover touch-none select-none"
+            class="block w-full aspect-[27/32] touch-none select-none"
           ></canvas>
 
           <Show when=move || show_hint.get()>
@@ -91,6 +83,8 @@ pub fn CubePlanet(vs_src: RwSignal<String>, fs_src: RwSignal<String>) -> impl In
               </div>
           </Show>
 
+          <PlaybackBar playback />
+
           </Show>
         </div>
     }
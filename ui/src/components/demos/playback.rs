@@ -0,0 +1,109 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use leptos::prelude::{
+    ClassAttribute, ElementChild, Get, GlobalAttributes, LocalStorage, OnAttribute,
+    PropAttribute, RwSignal, Set, StoredValue, WithValue, event_target_value,
+};
+use leptos::{IntoView, component, view};
+
+use crate::render::renderer::clock::PlaybackClock;
+
+/// Speeds offered by [`PlaybackBar`].
+const SPEEDS: [f64; 5] = [0.25, 0.5, 1.0, 2.0, 4.0];
+
+/// A demo's [`PlaybackClock`], shared between its `GpuState` (which ticks
+/// it) and the controls; the signals mirror the clock for the view.
+#[derive(Copy, Clone)]
+pub struct Playback {
+    clock: StoredValue<Rc<RefCell<PlaybackClock>>, LocalStorage>,
+    paused: RwSignal<bool>,
+    speed: RwSignal<f64>,
+}
+
+impl Default for Playback {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Playback {
+    pub fn new() -> Self {
+        Self {
+            clock: StoredValue::new_local(Rc::new(RefCell::new(PlaybackClock::new()))),
+            paused: RwSignal::new(false),
+            speed: RwSignal::new(1.0),
+        }
+    }
+
+    /// Handle for `GpuState::set_clock`.
+    pub fn clock(&self) -> Rc<RefCell<PlaybackClock>> {
+        self.clock.with_value(Rc::clone)
+    }
+
+    pub fn toggle(&self) {
+        let paused = self.clock.with_value(|c| {
+            let mut clock = c.borrow_mut();
+            clock.toggle();
+            clock.is_paused()
+        });
+        self.paused.set(paused);
+    }
+
+    /// One frame on; pauses first if playing.
+    pub fn step(&self) {
+        self.clock.with_value(|c| {
+            let mut clock = c.borrow_mut();
+            clock.pause();
+            clock.step(1);
+        });
+        self.paused.set(true);
+    }
+
+    pub fn set_speed(&self, speed: f64) {
+        self.clock.with_value(|c| c.borrow_mut().speed = speed);
+        self.speed.set(speed);
+    }
+
+    /// Back to time 0, playing or not as before.
+    pub fn restart(&self) {
+        self.clock.with_value(|c| c.borrow_mut().seek(0.0));
+    }
+}
+
+/// Pause, step, speed and restart for a demo's time.
+#[component]
+pub fn PlaybackBar(playback: Playback) -> impl IntoView {
+    let button = "px-2 py-0.5 rounded bg-black/50 text-text hover:text-accent";
+
+    view! {
+        <div class="absolute bottom-2 left-2 flex items-center gap-1 text-xs \
+                    opacity-0 group-hover:opacity-100 focus-within:opacity-100 transition">
+            <button
+                class=button
+                title=move || if playback.paused.get() { "Play" } else { "Pause" }
+                on:click=move |_| playback.toggle()
+            >
+                { move || if playback.paused.get() { "▶" } else { "❚❚" } }
+            </button>
+            <button class=button title="Next frame" on:click=move |_| playback.step()>
+                "▶|"
+            </button>
+            <button class=button title="Back to the start" on:click=move |_| playback.restart()>
+                "⟲"
+            </button>
+            <select
+                class="px-1 py-0.5 rounded bg-black/50 text-text"
+                title="Speed"
+                prop:value=move || playback.speed.get().to_string()
+                on:change=move |ev| {
+                    if let Ok(speed) = event_target_value(&ev).parse() {
+                        playback.set_speed(speed);
+                    }
+                }
+            >
+                { SPEEDS.map(|s| view! { <option value=s.to_string()>{ format!("{s}×") }</option> }) }
+            </select>
+        </div>
+    }
+}
@@ -2,23 +2,26 @@ use leptos_use::{UseRafFnOptions, use_raf_fn_with_options};
 use leptos_use::utils::Pausable;
 use std::cell::RefCell;
 use std::rc::Rc;
-use wgpu::BindGroup;
 use wgpu::Device;
 use wgpu::util::DeviceExt;
 
+use crate::components::demos::playback::Playback;
 use crate::meshes::quad::QUAD_INDICES;
 use crate::meshes::quad::QUAD_VERTS;
 use crate::render::renderer::camera_input::CameraInput;
 use crate::render::renderer::gpu::GpuState;
-use crate::render::renderer::gpu::gpu_state::FrameCtx;
+use crate::render::renderer::gpu::gpu_state::FrameError;
 use crate::render::renderer::gpu::gpu_state::Projection;
-use crate::render::renderer::gpu::gpu_state::create_idx_buff_init;
 use crate::render::renderer::gpu::gpu_state::create_instance_buff;
-use crate::render::renderer::gpu::gpu_state::create_vert_buff_init;
+use crate::render::renderer::gpu::resource_context::Group;
 use crate::render::renderer::gpu::surface_context::SurfaceContext;
+use crate::render::renderer::graph::{
+    ColorAttachment, Geometry, MaterialGroup, MeshPass, MeshPassDesc, NodeRef, RenderGraph,
+    Shaders, Target,
+};
 use crate::render::renderer::instance::InstanceRaw;
-use crate::render::renderer::vertex::Vertex;
-use crate::render::web_gpu::init_wgpu;
+use crate::render::renderer::mesh::CpuMesh;
+use crate::render::web_gpu::{init_wgpu, reinit_wgpu};
 use anyhow::Result;
 use anyhow::anyhow;
 use glam::Mat4;
@@ -31,10 +34,15 @@ use leptos::prelude::ElementChild;
 use leptos::prelude::GetUntracked;
 use leptos::prelude::RwSignal;
 use leptos::prelude::Set;
+use leptos::prelude::StoredValue;
+use leptos::prelude::UpdateValue;
+use leptos::prelude::SetValue;
+use leptos::prelude::on_cleanup;
 use leptos::reactive::spawn_local;
 use leptos::{IntoView, component, view};
 use wasm_bindgen::{JsCast, convert::FromWasmAbi, prelude::Closure};
 use web_sys::HtmlCanvasElement;
+use web_sys::js_sys;
 
 /// true on PCs with a mouse/track-pad, false on touch devices
 pub fn is_desktop() -> bool {
@@ -95,6 +103,93 @@ where
     cb.forget();
 }
 
+/// Canvas size in physical pixels: its CSS box times `devicePixelRatio`.
+pub fn display_size(canvas: &HtmlCanvasElement) -> (u32, u32) {
+    let dpr = web_sys::window().unwrap().device_pixel_ratio();
+    let rect = canvas.get_bounding_client_rect();
+    physical_size(rect.width(), rect.height(), dpr)
+}
+
+/// A CSS size in device pixels, to the nearest one.
+fn physical_size(css_width: f64, css_height: f64, dpr: f64) -> (u32, u32) {
+    ((css_width * dpr).round() as u32, (css_height * dpr).round() as u32)
+}
+
+/// What [`add_resize_observer`] set up; the callback holds the GPU state,
+/// so both have to go when the demo does.
+pub struct ResizeHandle {
+    observer: web_sys::ResizeObserver,
+    _callback: Closure<dyn FnMut(js_sys::Array)>,
+}
+
+impl ResizeHandle {
+    /// Stop observing and drop the callback, and the state with it.
+    pub fn disconnect(self) {
+        self.observer.disconnect();
+    }
+}
+
+/// Keep the canvas backing store (and the GPU state) at the element's
+/// on-screen size, so output is crisp on hi-DPI and never stretched.
+pub fn add_resize_observer(
+    state: &Rc<RefCell<Option<GpuState>>>,
+    canvas: &HtmlCanvasElement,
+) -> ResizeHandle {
+    // Safari has no `device-pixel-content-box` and throws on the option
+    let device_px_box = web_sys::window()
+        .and_then(|w| js_sys::Reflect::get(&w, &"ResizeObserverEntry".into()).ok())
+        .and_then(|ctor| js_sys::Reflect::get(&ctor, &"prototype".into()).ok())
+        .is_some_and(|proto| {
+            js_sys::Reflect::has(&proto, &"devicePixelContentBoxSize".into()).unwrap_or(false)
+        });
+
+    let st = state.clone();
+    let cv = canvas.clone();
+    let cb = Closure::wrap(Box::new(move |entries: js_sys::Array| {
+        let Some(entry) = entries.get(0).dyn_into::<web_sys::ResizeObserverEntry>().ok() else {
+            return;
+        };
+
+        // exact device pixels where supported (Chromium), CSS px * dpr otherwise
+        let exact = device_px_box
+            .then(|| entry.device_pixel_content_box_size().get(0))
+            .and_then(|size| size.dyn_into::<web_sys::ResizeObserverSize>().ok());
+
+        let (w, h) = match exact {
+            Some(size) => (size.inline_size() as u32, size.block_size() as u32),
+            None => display_size(&cv),
+        };
+
+        let Ok(mut guard) = st.try_borrow_mut() else {
+            return;
+        };
+        let Some(state) = guard.as_mut() else {
+            return;
+        };
+
+        state.resize(w, h);
+
+        // the canvas must match the (possibly clamped) surface size
+        let (w, h) = (state.surface_context.config.width, state.surface_context.config.height);
+        if cv.width() != w || cv.height() != h {
+            cv.set_width(w);
+            cv.set_height(h);
+        }
+    }) as Box<dyn FnMut(js_sys::Array)>);
+
+    let observer = web_sys::ResizeObserver::new(cb.as_ref().unchecked_ref()).unwrap();
+
+    if device_px_box {
+        let opts = web_sys::ResizeObserverOptions::new();
+        opts.set_box(web_sys::ResizeObserverBoxOptions::DevicePixelContentBox);
+        observer.observe_with_options(canvas, &opts);
+    } else {
+        observer.observe(canvas);
+    }
+
+    ResizeHandle { observer, _callback: cb }
+}
+
 pub fn add_camera_orbit(
     camera_input: &Rc<RefCell<Option<CameraInput>>>,
     canvas: &HtmlCanvasElement,
@@ -111,7 +206,7 @@ pub fn add_camera_orbit(
 
     let cv = canvas.clone();
     let ci = camera_input.clone();
-    add_listener(&canvas, "pointerdown", move |e: web_sys::PointerEvent| {
+    add_listener(canvas, "pointerdown", move |e: web_sys::PointerEvent| {
         if e.button() != 0 {
             return;
         }
@@ -142,7 +237,7 @@ pub fn add_camera_orbit(
     // ─── MOUSEMOVE ───
     let ci = camera_input.clone();
     let cv = canvas.clone();
-    add_listener(&canvas, "pointermove", move |e: web_sys::PointerEvent| {
+    add_listener(canvas, "pointermove", move |e: web_sys::PointerEvent| {
         if let Ok(mut guard) = ci.try_borrow_mut() {
             let ci = guard.as_mut().unwrap();
 
@@ -177,7 +272,7 @@ pub fn add_camera_orbit(
     // ─── MOUSEUP / MOUSELEAVE ───
     let ci = camera_input.clone();
     let cv = canvas.clone();
-    add_listener(&canvas, "pointerup", move |e: web_sys::PointerEvent| {
+    add_listener(canvas, "pointerup", move |e: web_sys::PointerEvent| {
         let _ = cv.release_pointer_capture(e.pointer_id());
 
         if let Ok(mut guard) = ci.try_borrow_mut() {
@@ -187,7 +282,7 @@ pub fn add_camera_orbit(
     });
 
     let ci = camera_input.clone();
-    add_listener(&canvas, "pointerleave", move |_: web_sys::PointerEvent| {
+    add_listener(canvas, "pointerleave", move |_: web_sys::PointerEvent| {
         if let Ok(mut guard) = ci.try_borrow_mut() {
             let ci = guard.as_mut().unwrap();
             ci.dragging = false;
@@ -211,7 +306,7 @@ pub fn add_mousewheel_zoom(
     ); // now I can safely unwrap past this point
 
     let ci = camera_input.clone();
-    add_listener(&canvas, "wheel", move |e: web_sys::WheelEvent| {
+    add_listener(canvas, "wheel", move |e: web_sys::WheelEvent| {
         if let Ok(mut guard) = ci.try_borrow_mut() {
             let ci = guard.as_mut().unwrap();
             let delta = e.delta_y() as f32 * 0.01;
@@ -244,8 +339,6 @@ fn add_input_handlers(
     }
 }
 
-pub type RenderPass = Rc<RefCell<dyn FnMut(&mut GpuState, &CameraInput, &mut FrameCtx)>>;
-
 /// Which GPU sub-passes should run this frame?
 #[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
 pub enum PassKind {
@@ -303,6 +396,10 @@ impl InstanceCtx {
     }
 }
 
+type ResumeFn = Box<dyn Fn()>;
+
+/// Returns the demo's clock, for a [`PlaybackBar`](super::playback::PlaybackBar).
+#[allow(clippy::too_many_arguments)]
 pub fn start_rendering<OnReady, OnFrame>(
     state_rc: Rc<RefCell<Option<GpuState>>>,
     camera_rc: Rc<RefCell<Option<CameraInput>>>,
@@ -312,37 +409,48 @@ pub fn start_rendering<OnReady, OnFrame>(
 
     canvas_id: &str,
 
-    rpasses: Vec<RenderPass>,
+    graph: RenderGraph,
 
     on_canvas_ready: OnReady, // extra closure after canvas is ready hook
     on_frame_ready: OnFrame,  // extra closure to run every frame
-) where
+) -> Playback
+where
     OnReady: 'static + Fn(&HtmlCanvasElement) + Clone,
     OnFrame: 'static + FnMut() + Clone,
 {
+    let playback = Playback::new();
     let state_handle = state_rc.clone();
     let camera_handle = camera_rc.clone();
-    let rpasses_handle = rpasses.clone();
+    let graph_handle = RefCell::new(graph);
+    let canvas_id = canvas_id.to_owned();
 
     let on_frame_rc = Rc::new(RefCell::new(on_frame_ready));
-    let raf_handle: Rc<RefCell<Option<Box<dyn Fn()>>>> = Rc::new(RefCell::new(None));
+    let raf_handle: Rc<RefCell<Option<ResumeFn>>> = Rc::new(RefCell::new(None));
 
     let Pausable { resume, .. } = use_raf_fn_with_options(
         {
             let on_frame_handle = on_frame_rc.clone();
+            let canvas_id = canvas_id.clone();
             move |_args| {
                 on_frame_handle.borrow_mut()();
 
-                if let (Some(state), Ok(cam_ref)) = (
-                    state_handle.borrow_mut().as_mut(),
-                    camera_handle.try_borrow(),
-                ) {
+                let mut guard = state_handle.borrow_mut();
+                if let (Some(state), Ok(cam_ref)) = (guard.as_mut(), camera_handle.try_borrow()) {
                     let cam = cam_ref.as_ref().expect("CameraInput is None");
-                    let mut ctx = state.begin_frame();
-                    for pass in &rpasses_handle {
-                        (pass.borrow_mut())(state, cam, &mut ctx);
+                    match state.begin_frame() {
+                        Ok(mut ctx) => {
+                            if let Err(err) = graph_handle.borrow_mut().execute(state, cam, &mut ctx) {
+                                web_sys::console::error_1(&format!("render graph: {err:?}").into());
+                            }
+                            state.end_frame(ctx);
+                        }
+                        Err(FrameError::Skipped) => {}
+                        Err(FrameError::DeviceLost) => {
+                            // frames stop (state is None) until the new device is up
+                            let old = guard.take().unwrap();
+                            recover_device(state_handle.clone(), old, &canvas_id, gpu_support);
+                        }
                     }
-                    state.end_frame(ctx);
                 }
             }
         },
@@ -354,17 +462,21 @@ pub fn start_rendering<OnReady, OnFrame>(
     );
 
     *raf_handle.borrow_mut() = Some(Box::new(resume));
-
-    let canvas_id = canvas_id.to_owned();
     let on_canvas_ready = Rc::new(on_canvas_ready);
 
+    // set once the device is up; a demo switch would otherwise keep the old
+    // one alive, resizing a surface nothing draws to
+    let resize = StoredValue::new_local(None::<ResizeHandle>);
+    on_cleanup(move || {
+        if let Some(Some(observer)) = resize.try_update_value(Option::take) {
+            observer.disconnect();
+        }
+    });
+
     Effect::new(move |_| {
         let state_rc_init = state_rc.clone();
         let camera_rc_init = camera_rc.clone();
 
-        let show_hint = show_hint.clone();
-        let gpu_support = gpu_support.clone();
-
         let canvas_id = canvas_id.clone();
 
         let on_ready = on_canvas_ready.clone();
@@ -383,10 +495,15 @@ pub fn start_rendering<OnReady, OnFrame>(
                 }
             };
 
+            // size the backing store before the surface is configured from it
+            let (w, h) = display_size(&canvas);
+            canvas.set_width(w.max(1));
+            canvas.set_height(h.max(1));
+
             // run user hook
             (on_ready)(&canvas);
 
-            let state = match init_wgpu(&canvas).await {
+            let mut state = match init_wgpu(&canvas).await {
                 Ok(s) => s,
                 Err(err) => {
                     gpu_support.set(false);
@@ -395,16 +512,55 @@ pub fn start_rendering<OnReady, OnFrame>(
                 }
             };
 
+            // a device loss carries the clock over (see `reinit_wgpu`)
+            state.set_clock(playback.clock());
             *state_rc_init.borrow_mut() = Some(state);
             *camera_rc_init.borrow_mut() = Some(CameraInput::default());
 
             add_input_handlers(&camera_rc_init, &canvas, show_hint);
+            let observer = add_resize_observer(&state_rc_init, &canvas);
+            // unmounted while the device was coming up
+            if let Some(Some(observer)) = resize.try_set_value(Some(observer)) {
+                observer.disconnect();
+            }
 
             if let Some(resume) = raf_handle.clone().borrow().as_ref() {
                 resume();
             }
         });
     });
+
+    playback
+}
+
+/// Re-create the GPU state after a device loss and put it back in `state_rc`.
+fn recover_device(
+    state_rc: Rc<RefCell<Option<GpuState>>>,
+    old: GpuState,
+    canvas_id: &str,
+    gpu_support: RwSignal<bool>,
+) {
+    web_sys::console::warn_1(&"GPU device lost, re-initialising".into());
+
+    let canvas_id = canvas_id.to_owned();
+    spawn_local(async move {
+        // the demo unmounted meanwhile, or its canvas was swapped out
+        let Some(canvas) = get_canvas(&canvas_id) else {
+            gpu_support.set(false);
+            web_sys::console::error_1(
+                &format!("WGPU re-init failed: no canvas `#{canvas_id}`").into(),
+            );
+            return;
+        };
+
+        match reinit_wgpu(&canvas, old).await {
+            Ok(state) => *state_rc.borrow_mut() = Some(state),
+            Err(err) => {
+                gpu_support.set(false);
+                web_sys::console::error_1(&format!("WGPU re-init failed: {err:?}").into());
+            }
+        }
+    });
 }
 
 #[repr(C)]
@@ -440,7 +596,7 @@ fn color_bgl(dev: &Device) -> wgpu::BindGroupLayout {
 fn init_color_bg(buf: wgpu::Buffer, bgl: &wgpu::BindGroupLayout, dev: &Device) -> wgpu::BindGroup {
     dev.create_bind_group(&wgpu::BindGroupDescriptor {
         label: Some("debug-colour BG"),
-        layout: &bgl,
+        layout: bgl,
         entries: &[wgpu::BindGroupEntry {
             binding: 0,
             resource: buf.as_entire_binding(),
@@ -448,78 +604,11 @@ fn init_color_bg(buf: wgpu::Buffer, bgl: &wgpu::BindGroupLayout, dev: &Device) -
     })
 }
 
-fn make_debug_points_pipe(
-    st: &GpuState,
-    color_bgl: &wgpu::BindGroupLayout,
-) -> wgpu::RenderPipeline {
-    let pipe_layout =
-        st.surface_context
-            .device
-            .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-                label: Some("debug points pipeline layout"),
-                bind_group_layouts: &[
-                    &st.resource_context.common_bind_group.layout,
-                    &st.resource_context.spatial_bind_group.layout,
-                    color_bgl,
-                ],
-                push_constant_ranges: &[],
-            });
-
-    st.surface_context
-        .device
-        .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            label: Some("points for debugging"),
-            layout: Some(&pipe_layout),
-            cache: None,
-            vertex: wgpu::VertexState {
-                module: &st.surface_context.device.create_shader_module(
-                    wgpu::ShaderModuleDescriptor {
-                        label: Some("debug fs shader that just draws in points"),
-                        source: wgpu::ShaderSource::Wgsl(
-                            include_str!("../../render/renderer/shaders/debug_points.vert.wgsl")
-                                .into(),
-                        ),
-                    },
-                ),
-                entry_point: Some("vs_main"),
-                buffers: &[Vertex::desc(), InstanceRaw::desc()],
-                compilation_options: Default::default(),
-            },
-            fragment: Some(wgpu::FragmentState {
-                module: &st.surface_context.device.create_shader_module(
-                    wgpu::ShaderModuleDescriptor {
-                        label: Some("debug fs shader that just draws in points"),
-                        source: wgpu::ShaderSource::Wgsl(
-                            include_str!("../../render/renderer/shaders/debug_points.frag.wgsl")
-                                .into(),
-                        ),
-                    },
-                ),
-                entry_point: Some("fs_main"),
-                targets: &[Some(wgpu::ColorTargetState {
-                    format: st.surface_context.config.format,
-                    blend: Some(wgpu::BlendState::PREMULTIPLIED_ALPHA_BLENDING),
-                    write_mask: wgpu::ColorWrites::ALL,
-                })],
-                compilation_options: Default::default(),
-            }),
-            primitive: wgpu::PrimitiveState {
-                topology: wgpu::PrimitiveTopology::TriangleList,
-                strip_index_format: None,
-                ..Default::default()
-            },
-            depth_stencil: None,
-            multisample: Default::default(),
-            multiview: None,
-        })
-}
-
 fn map_quads_to_points(r: f32, pts_handle: &Rc<RefCell<Vec<Vec2>>>) -> Vec<InstanceRaw> {
     pts_handle
         .borrow()
         .iter()
-        .enumerate()
-        .map(|(_, p)| {
+        .map(|p| {
             let model = Mat4::from_scale_rotation_translation(
                 Vec3::new(r, r, 1.0),
                 glam::Quat::IDENTITY,
@@ -530,97 +619,56 @@ fn map_quads_to_points(r: f32, pts_handle: &Rc<RefCell<Vec<Vec2>>>) -> Vec<Insta
         .collect()
 }
 
-pub(crate) fn make_points_rpass(
+pub fn make_points_rpass(
     points: Rc<RefCell<Vec<Vec2>>>,
     color: [f32; 4],
 
     enabled: RwSignal<bool>,
-) -> RenderPass {
-    let pipeline = Rc::new(RefCell::new(None));
-
-    let pipe_handle = pipeline.clone();
-    let pts_handle = points.clone();
-
-    let vbuf_handle: Rc<RefCell<Option<wgpu::Buffer>>> = Rc::new(RefCell::new(None));
-    let ibuf_handle: Rc<RefCell<Option<wgpu::Buffer>>> = Rc::new(RefCell::new(None));
-    let inst_handle: Rc<RefCell<Option<InstanceCtx>>> = Rc::new(RefCell::new(None));
-
-    let color_bg_handle: Rc<RefCell<Option<BindGroup>>> = Rc::new(RefCell::new(None));
-
-    Rc::new(RefCell::new(
-        move |st: &mut GpuState, cam: &CameraInput, ctx: &mut FrameCtx| {
-            if !enabled.get_untracked() {
-                return;
-            }
-
-            st.populate_common_buffers(&Projection::FlatQuad, cam);
-
-            if pipe_handle.borrow().is_none() {
-                let buf = color_buffer_init(&st.surface_context.device, color);
-                let cbgl = color_bgl(&st.surface_context.device);
-
-                let color_bg = init_color_bg(buf, &cbgl, &st.surface_context.device);
-
-                *color_bg_handle.borrow_mut() = Some(color_bg);
-                *pipe_handle.borrow_mut() = Some(make_debug_points_pipe(st, &cbgl));
-            }
-
-            if vbuf_handle.borrow().is_none() {
-                *vbuf_handle.borrow_mut() =
-                    Some(create_vert_buff_init(&st.surface_context, QUAD_VERTS));
-                *ibuf_handle.borrow_mut() =
-                    Some(create_idx_buff_init(&st.surface_context, QUAD_INDICES));
-            }
-
-            if inst_handle.borrow().is_none() {
-                *inst_handle.borrow_mut() = Some(InstanceCtx::new(&st.surface_context, 256));
-            }
-
-            {
-                let mut binding = inst_handle.borrow_mut();
-                let inst = binding.as_mut().unwrap();
-
-                inst.sync_instances(&st.surface_context, || {
-                    map_quads_to_points(0.02, &pts_handle)
-                });
-            }
+) -> NodeRef {
+    let quad = CpuMesh::new(QUAD_VERTS.to_vec(), QUAD_INDICES.to_vec());
+
+    MeshPass::new(MeshPassDesc {
+        label: "debug points pass",
+        color: ColorAttachment {
+            target: Target::Surface,
+            load: wgpu::LoadOp::Load,
+        },
+        depth: None,
 
-            // 3) bind + draw
-            let mut rp = ctx.encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                label: Some("debug points pass"),
-                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view: &ctx.color_view,
-                    resolve_target: None,
-                    ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Load,
-                        store: wgpu::StoreOp::Store,
-                    },
-                })],
-                depth_stencil_attachment: None,
-                ..Default::default()
-            });
-
-            rp.set_pipeline(pipe_handle.borrow().as_ref().unwrap());
-
-            let binding = vbuf_handle.borrow();
-            let vbuf = binding.as_ref().unwrap();
-            rp.set_vertex_buffer(0, vbuf.slice(..));
-
-            let binding = inst_handle.borrow();
-            let inst = binding.as_ref().unwrap();
-            rp.set_vertex_buffer(1, inst.buff.slice(..));
-
-            let binding = ibuf_handle.borrow();
-            let ibuf = binding.as_ref().unwrap();
-            rp.set_index_buffer(ibuf.slice(..), wgpu::IndexFormat::Uint16);
-
-            rp.set_bind_group(0, &st.resource_context.common_bind_group.group, &[]);
-            rp.set_bind_group(1, &st.resource_context.spatial_bind_group.group, &[]);
-            rp.set_bind_group(2, color_bg_handle.borrow().as_ref().unwrap(), &[]);
-
-            let idx_count = QUAD_INDICES.len() as u32;
-            let inst_count = inst_handle.borrow().as_ref().unwrap().count;
-            rp.draw_indexed(0..idx_count, 0, 0..inst_count);
+        shaders: Shaders::Static {
+            vs: include_str!("../../render/renderer/shaders/debug_points.vert.wgsl"),
+            fs: include_str!("../../render/renderer/shaders/debug_points.frag.wgsl"),
         },
-    ))
+        entry_points: ("vs_main", "fs_main"),
+        topology: wgpu::PrimitiveTopology::TriangleList,
+        blend: Some(wgpu::BlendState::PREMULTIPLIED_ALPHA_BLENDING),
+        material: MaterialGroup::Custom(Box::new(move |dev: &Device| {
+            let layout = color_bgl(dev);
+            let group = init_color_bg(color_buffer_init(dev, color), &layout, dev);
+            Group { group, layout }
+        })),
+
+        geometry: Geometry::Mesh(Rc::new(RefCell::new(quad))),
+        instances: Some(Box::new(move || map_quads_to_points(0.02, &points))),
+        projection: Rc::new(RefCell::new(Projection::FlatQuad)),
+
+        enabled: Some(enabled),
+    })
+    .into_node()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn css_sizes_scale_by_the_pixel_ratio_to_the_nearest_pixel() {
+        assert_eq!(physical_size(300.0, 150.0, 1.0), (300, 150));
+        assert_eq!(physical_size(300.0, 150.0, 2.0), (600, 300));
+        // fractional ratios (browser zoom) and boxes round, not truncate
+        assert_eq!(physical_size(333.3, 100.4, 1.5), (500, 151));
+        assert_eq!(physical_size(99.6, 0.2, 1.0), (100, 0));
+        // a hidden canvas has no box at all
+        assert_eq!(physical_size(0.0, 0.0, 2.0), (0, 0));
+    }
 }
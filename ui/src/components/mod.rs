@@ -1,4 +1,7 @@
 pub mod demos_menu;
 pub mod demos;
 pub mod demo;
+pub mod persist;
+pub mod share;
 pub mod shader_editor;
+pub mod snippets;
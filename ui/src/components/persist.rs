@@ -0,0 +1,118 @@
+//! Edits kept in localStorage, per demo, so they survive switching demos
+//! and reloading. Only what differs from the shipped demo is stored; a key
+//! going away is how things get reset.
+
+use std::collections::BTreeMap;
+
+use crate::components::demo::Demo;
+use crate::render::renderer::gpu::samplers::SamplerDesc;
+
+fn storage() -> Option<web_sys::Storage> {
+    web_sys::window()?.local_storage().ok()?
+}
+
+fn key(demo: Demo, item: &str) -> String {
+    format!("shader-lab/{}/{item}", demo.slug())
+}
+
+pub fn load(demo: Demo, item: &str) -> Option<String> {
+    storage()?.get_item(&key(demo, item)).ok()?
+}
+
+/// Save `value`, or forget the item when it's `None`. Quota errors and
+/// private browsing just mean nothing is kept.
+pub fn store(demo: Demo, item: &str, value: Option<&str>) {
+    let Some(storage) = storage() else { return };
+    let key = key(demo, item);
+    match value {
+        Some(value) => storage.set_item(&key, value).ok(),
+        None => storage.remove_item(&key).ok(),
+    };
+}
+
+/// The demo's sources as last edited, or as shipped.
+pub fn shaders(demo: Demo) -> (String, String) {
+    let (vs, fs) = demo.shaders();
+    (
+        load(demo, "vs").unwrap_or_else(|| vs.to_owned()),
+        load(demo, "fs").unwrap_or_else(|| fs.to_owned()),
+    )
+}
+
+/// The demo's one-module source as last edited, or as shipped.
+pub fn module(demo: Demo) -> String {
+    load(demo, "module").unwrap_or_else(|| demo.module().to_owned())
+}
+
+/// The demo's entry point picks, by `pass/vs` or `pass/fs`.
+pub fn entry_points(demo: Demo) -> BTreeMap<String, String> {
+    load(demo, "entry-points")
+        .and_then(|json| serde_json::from_str(&json).ok())
+        .unwrap_or_default()
+}
+
+/// Keep `picks` as the demo's entry point picks; none left forgets them.
+pub fn store_entry_points(demo: Demo, picks: &BTreeMap<String, String>) {
+    let json = serde_json::to_string(picks).ok().filter(|_| !picks.is_empty());
+    store(demo, "entry-points", json.as_deref());
+}
+
+/// The demo's texture URLs by channel, as last picked or as shipped.
+pub fn textures(demo: Demo) -> BTreeMap<String, String> {
+    load(demo, "textures")
+        .and_then(|json| serde_json::from_str(&json).ok())
+        .unwrap_or_else(|| demo.textures())
+}
+
+/// Keep `urls` as the demo's texture URLs, unless they're the shipped ones.
+pub fn store_textures(demo: Demo, urls: &BTreeMap<String, String>) {
+    let json = serde_json::to_string(urls).ok().filter(|_| *urls != demo.textures());
+    store(demo, "textures", json.as_deref());
+}
+
+/// The demo's sampler picks by channel; channels not in it use the default.
+pub fn samplers(demo: Demo) -> BTreeMap<String, SamplerDesc> {
+    load(demo, "samplers")
+        .and_then(|json| serde_json::from_str(&json).ok())
+        .unwrap_or_default()
+}
+
+/// Keep `picks` as the demo's sampler picks; none left forgets them.
+pub fn store_samplers(demo: Demo, picks: &BTreeMap<String, SamplerDesc>) {
+    let json = serde_json::to_string(picks).ok().filter(|_| !picks.is_empty());
+    store(demo, "samplers", json.as_deref());
+}
+
+/// The built-in model the demo draws instead of its mesh, if one was picked.
+pub fn model(demo: Demo) -> Option<String> {
+    load(demo, "model")
+}
+
+pub fn store_model(demo: Demo, asset: Option<&str>) {
+    store(demo, "model", asset);
+}
+
+/// Keep `src` as the demo's `vs`, `fs` or `module`, unless it's the
+/// shipped one.
+pub fn store_shader(demo: Demo, item: &str, src: &str, shipped: &str) {
+    store(demo, item, (src != shipped).then_some(src));
+}
+
+const MODULES: &str = "shader-lab/modules";
+
+/// Module edits (name to source), shared by every demo.
+pub fn modules() -> BTreeMap<String, String> {
+    storage()
+        .and_then(|s| s.get_item(MODULES).ok()?)
+        .and_then(|json| serde_json::from_str(&json).ok())
+        .unwrap_or_default()
+}
+
+/// Keep `edits` as the module edits; none left forgets them.
+pub fn store_modules(edits: &BTreeMap<String, String>) {
+    let Some(storage) = storage() else { return };
+    match serde_json::to_string(edits) {
+        Ok(json) if !edits.is_empty() => storage.set_item(MODULES, &json).ok(),
+        _ => storage.remove_item(MODULES).ok(),
+    };
+}
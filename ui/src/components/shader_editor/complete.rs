@@ -0,0 +1,573 @@
+//! Completion for what the editor knows without compiling: the resources
+//! the pipeline layout binds (`ResourceContext::pipeline_layout`), the
+//! vertex inputs (`Vertex` and `InstanceRaw` attributes), WGSL's built-in
+//! functions, and a `bindings` snippet that declares all of it for the
+//! selected demo.
+
+use std::ops::Range;
+
+use crate::components::demo::Demo;
+use crate::render::renderer::gpu::diagnostics::Stage;
+
+use super::vim::INDENT;
+use super::wgsl::{self, BUILTINS, TokenKind};
+
+/// More than this and the list is no help; keep typing.
+const MAX_ITEMS: usize = 10;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) enum Kind {
+    Resource,
+    Field,
+    Input,
+    Snippet,
+    Function,
+}
+
+impl Kind {
+    pub fn tag(self) -> &'static str {
+        match self {
+            Kind::Resource => "var",
+            Kind::Field => "field",
+            Kind::Input => "in",
+            Kind::Snippet => "snippet",
+            Kind::Function => "fn",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct Suggestion {
+    pub label: String,
+    pub kind: Kind,
+    pub detail: String,
+    /// Replaces the word being typed.
+    pub insert: String,
+    /// Where the cursor ends up, in bytes into `insert`.
+    pub cursor: usize,
+}
+
+/// Where completion happens: what the demo's passes bind depends on the
+/// demo, what a shader can see of it on the stage. Modules can end up in
+/// either stage, so they have none.
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub(crate) struct Scope {
+    pub demo: Demo,
+    pub stage: Option<Stage>,
+}
+
+/// An open suggestion list.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct Menu {
+    /// What a pick replaces.
+    pub word: Range<usize>,
+    pub items: Vec<Suggestion>,
+    pub selected: usize,
+}
+
+impl Menu {
+    /// Move the selection, wrapping around.
+    pub fn step(&mut self, by: isize) {
+        let len = self.items.len() as isize;
+        self.selected = (self.selected as isize + by).rem_euclid(len) as usize;
+    }
+
+    /// `text` with the selected suggestion in, and the cursor after it.
+    pub fn pick(&self, text: &str) -> (String, usize) {
+        let item = &self.items[self.selected];
+        let mut new = text.to_owned();
+        new.replace_range(self.word.clone(), &item.insert);
+        (new, self.word.start + item.cursor)
+    }
+}
+
+struct Resource {
+    name: &'static str,
+    group: u32,
+    binding: u32,
+    /// The one stage the layout shows it to.
+    stage: Stage,
+    /// A struct when it has `fields`, else a plain or handle type.
+    ty: &'static str,
+    uniform: bool,
+    fields: &'static [(&'static str, &'static str)],
+    what: &'static str,
+}
+
+/// Groups 0 to 2 as `utils::{common, spatial, texturing}_bind_group` lay
+/// them out, channels where `textures::channel_bindings` puts them. Names
+/// and structs are the ones the shipped shaders use.
+const RESOURCES: &[Resource] = &[
+    Resource {
+        name: "g_time",
+        group: 0,
+        binding: 0,
+        stage: Stage::Fragment,
+        ty: "TimeUBO",
+        uniform: true,
+        fields: &[
+            ("millis", "u32"),
+            ("secs", "u32"),
+            ("dt_millis", "u32"),
+            ("frame_id", "u32"),
+            ("elapsed", "f32"),
+        ],
+        what: "frame clock since start; `elapsed` is in seconds",
+    },
+    Resource {
+        name: "screen",
+        group: 0,
+        binding: 1,
+        stage: Stage::Fragment,
+        ty: "Screen",
+        uniform: true,
+        fields: &[("resolution", "vec2<f32>"), ("_pad", "vec2<f32>")],
+        what: "canvas size in pixels",
+    },
+    Resource {
+        name: "camera",
+        group: 1,
+        binding: 0,
+        stage: Stage::Vertex,
+        ty: "Camera",
+        uniform: true,
+        fields: &[("view_proj", "mat4x4<f32>")],
+        what: "projection times view",
+    },
+    Resource {
+        name: "model",
+        group: 1,
+        binding: 1,
+        stage: Stage::Vertex,
+        ty: "mat4x4<f32>",
+        uniform: true,
+        fields: &[],
+        what: "model matrix",
+    },
+    Resource {
+        name: "light",
+        group: 1,
+        binding: 2,
+        stage: Stage::Fragment,
+        ty: "Light",
+        uniform: true,
+        fields: &[("dir", "vec3<f32>"), ("color", "vec3<f32>")],
+        what: "directional light",
+    },
+    Resource {
+        name: "texture_data",
+        group: 2,
+        binding: 0,
+        stage: Stage::Fragment,
+        ty: "texture_2d<f32>",
+        uniform: false,
+        fields: &[],
+        what: "iChannel0: the demo's texture, or what the UI tab loads",
+    },
+    Resource {
+        name: "texture_sampler",
+        group: 2,
+        binding: 1,
+        stage: Stage::Fragment,
+        ty: "sampler",
+        uniform: false,
+        fields: &[],
+        what: "filtering sampler",
+    },
+    Resource {
+        name: "material",
+        group: 2,
+        binding: 2,
+        stage: Stage::Fragment,
+        ty: "Material",
+        uniform: true,
+        fields: &[("base_colour", "vec4<f32>")],
+        what: "material constants",
+    },
+    Resource {
+        name: "iChannel1",
+        group: 2,
+        binding: 3,
+        stage: Stage::Fragment,
+        ty: "texture_2d<f32>",
+        uniform: false,
+        fields: &[],
+        what: "texture channel 1, loaded in the UI tab",
+    },
+    Resource {
+        name: "iChannel1_sampler",
+        group: 2,
+        binding: 4,
+        stage: Stage::Fragment,
+        ty: "sampler",
+        uniform: false,
+        fields: &[],
+        what: "sampler for iChannel1",
+    },
+    Resource {
+        name: "iChannel2",
+        group: 2,
+        binding: 5,
+        stage: Stage::Fragment,
+        ty: "texture_2d<f32>",
+        uniform: false,
+        fields: &[],
+        what: "texture channel 2, loaded in the UI tab",
+    },
+    Resource {
+        name: "iChannel2_sampler",
+        group: 2,
+        binding: 6,
+        stage: Stage::Fragment,
+        ty: "sampler",
+        uniform: false,
+        fields: &[],
+        what: "sampler for iChannel2",
+    },
+    Resource {
+        name: "iChannel3",
+        group: 2,
+        binding: 7,
+        stage: Stage::Fragment,
+        ty: "texture_2d<f32>",
+        uniform: false,
+        fields: &[],
+        what: "texture channel 3, loaded in the UI tab",
+    },
+    Resource {
+        name: "iChannel3_sampler",
+        group: 2,
+        binding: 8,
+        stage: Stage::Fragment,
+        ty: "sampler",
+        uniform: false,
+        fields: &[],
+        what: "sampler for iChannel3",
+    },
+];
+
+/// `Vertex::ATTRIBS` (0-2), then `InstanceRaw::ATTRIBS`, the model matrix
+/// a column at a time (3-6).
+const INPUTS: &[(u32, &str, &str, &str)] = &[
+    (0, "position", "vec3<f32>", "Vertex::position"),
+    (1, "normal", "vec3<f32>", "Vertex::normal"),
+    (2, "uv", "vec2<f32>", "Vertex::uv"),
+    (3, "m0", "vec4<f32>", "instance model matrix, column 0"),
+    (4, "m1", "vec4<f32>", "instance model matrix, column 1"),
+    (5, "m2", "vec4<f32>", "instance model matrix, column 2"),
+    (6, "m3", "vec4<f32>", "instance model matrix, column 3"),
+];
+
+/// Signatures of the built-ins people reach for; the rest go without.
+const SIGNATURES: &[(&str, &str)] = &[
+    ("abs", "abs(e: T) -> T"),
+    ("atan2", "atan2(y: T, x: T) -> T"),
+    ("ceil", "ceil(e: T) -> T"),
+    ("clamp", "clamp(e: T, low: T, high: T) -> T"),
+    ("cos", "cos(e: T) -> T"),
+    ("cross", "cross(a: vec3<T>, b: vec3<T>) -> vec3<T>"),
+    ("distance", "distance(a: T, b: T) -> f32"),
+    ("dot", "dot(a: vecN<T>, b: vecN<T>) -> T"),
+    ("dpdx", "dpdx(e: T) -> T"),
+    ("dpdy", "dpdy(e: T) -> T"),
+    ("exp", "exp(e: T) -> T"),
+    ("floor", "floor(e: T) -> T"),
+    ("fract", "fract(e: T) -> T"),
+    ("fwidth", "fwidth(e: T) -> T"),
+    ("length", "length(e: T) -> f32"),
+    ("log", "log(e: T) -> T"),
+    ("max", "max(a: T, b: T) -> T"),
+    ("min", "min(a: T, b: T) -> T"),
+    ("mix", "mix(a: T, b: T, t: T) -> T"),
+    ("normalize", "normalize(e: vecN<T>) -> vecN<T>"),
+    ("pow", "pow(e: T, p: T) -> T"),
+    ("reflect", "reflect(e: T, normal: T) -> T"),
+    ("round", "round(e: T) -> T"),
+    ("saturate", "saturate(e: T) -> T"),
+    ("select", "select(f: T, t: T, cond: bool) -> T"),
+    ("sign", "sign(e: T) -> T"),
+    ("sin", "sin(e: T) -> T"),
+    ("smoothstep", "smoothstep(low: T, high: T, x: T) -> T"),
+    ("sqrt", "sqrt(e: T) -> T"),
+    ("step", "step(edge: T, x: T) -> T"),
+    ("tan", "tan(e: T) -> T"),
+    ("textureDimensions", "textureDimensions(t) -> vec2<u32>"),
+    ("textureLoad", "textureLoad(t, coords: vec2<i32>, level: i32) -> vec4<f32>"),
+    ("textureSample", "textureSample(t, s: sampler, coords: vec2<f32>) -> vec4<f32>"),
+    ("textureSampleLevel", "textureSampleLevel(t, s, coords, level: f32) -> vec4<f32>"),
+    ("transpose", "transpose(e: matRxC<T>) -> matCxR<T>"),
+];
+
+/// The texturing group is only there for demos that have a material.
+fn available(scope: Scope) -> impl Iterator<Item = &'static Resource> {
+    RESOURCES
+        .iter()
+        .filter(move |r| scope.stage.is_none_or(|s| r.stage == s))
+        .filter(move |r| r.group != 2 || scope.demo.textured())
+}
+
+fn declaration(r: &Resource) -> String {
+    let var = if r.uniform { "var<uniform>" } else { "var" };
+    format!("@group({}) @binding({}) {var} {}: {};", r.group, r.binding, r.name, r.ty)
+}
+
+/// What `text` already declares at the top level: struct names and
+/// `var`s, whatever they're bound to.
+fn declared(text: &str) -> Vec<&str> {
+    let tokens: Vec<_> = wgsl::tokenize(text)
+        .into_iter()
+        .filter(|t| !matches!(t.kind, TokenKind::Whitespace | TokenKind::Comment))
+        .collect();
+    let word = |i: usize| tokens.get(i).map(|t| &text[t.span.clone()]);
+
+    let mut names = Vec::new();
+    for i in 0..tokens.len() {
+        let mut name = match word(i) {
+            Some("struct" | "var") => i + 1,
+            _ => continue,
+        };
+        // past `<uniform>`
+        if word(name) == Some("<") {
+            while word(name).is_some_and(|w| w != ">") {
+                name += 1;
+            }
+            name += 1;
+        }
+        // `texture_data` highlights as a type
+        let named = |t: &&wgsl::Token| matches!(t.kind, TokenKind::Ident | TokenKind::Type);
+        if let Some(t) = tokens.get(name).filter(named) {
+            names.push(&text[t.span.clone()]);
+        }
+    }
+    names
+}
+
+/// Declarations for everything `scope` binds that `text` doesn't declare
+/// yet; in the vertex shader also a struct with every vertex input.
+pub(crate) fn boilerplate(scope: Scope, text: &str) -> String {
+    let have = declared(text);
+    let mut blocks = Vec::new();
+
+    for r in available(scope).filter(|r| !have.contains(&r.name)) {
+        let mut block = String::new();
+        if !r.fields.is_empty() && !have.contains(&r.ty) {
+            block.push_str(&format!("struct {} {{\n", r.ty));
+            for (field, ty) in r.fields {
+                block.push_str(&format!("{INDENT}{field}: {ty},\n"));
+            }
+            block.push_str("};\n");
+        }
+        block.push_str(&declaration(r));
+        blocks.push(block);
+    }
+
+    if scope.stage == Some(Stage::Vertex) && !have.contains(&"VSIn") {
+        let mut block = String::from("struct VSIn {\n");
+        for (location, name, ty, _) in INPUTS {
+            block.push_str(&format!("{INDENT}@location({location}) {name}: {ty},\n"));
+        }
+        block.push_str("};");
+        blocks.push(block);
+    }
+    blocks.join("\n\n")
+}
+
+fn suggestion(label: &str, kind: Kind, detail: String, insert: String) -> Suggestion {
+    let cursor = insert.len();
+    Suggestion { label: label.to_owned(), kind, detail, insert, cursor }
+}
+
+/// Everything that can be typed as a word of its own; `included` is what
+/// the `#include`s in `text` pull in.
+fn everything(scope: Scope, text: &str, included: &str) -> Vec<Suggestion> {
+    let mut all = Vec::new();
+    for r in available(scope) {
+        let detail = format!("{}: @group({}) @binding({}), {}", r.ty, r.group, r.binding, r.what);
+        all.push(suggestion(r.name, Kind::Resource, detail, r.name.to_owned()));
+    }
+    if scope.stage == Some(Stage::Vertex) {
+        for (location, name, ty, what) in INPUTS {
+            let decl = format!("@location({location}) {name}: {ty},");
+            all.push(suggestion(name, Kind::Input, format!("{decl} {what}"), decl));
+        }
+    }
+    let stage = match scope.stage {
+        Some(Stage::Vertex) => "vertex shader",
+        Some(_) => "fragment shader",
+        None => "either stage",
+    };
+    let missing = boilerplate(scope, &format!("{included}\n{text}"));
+    if !missing.is_empty() {
+        let detail = format!("declare what {} binds for the {stage}", scope.demo.slug());
+        all.push(suggestion("bindings", Kind::Snippet, detail, missing));
+    }
+    for name in BUILTINS {
+        let detail = SIGNATURES
+            .iter()
+            .find(|(n, _)| n == name)
+            .map_or("built-in function", |(_, sig)| sig);
+        let mut item = suggestion(name, Kind::Function, detail.to_owned(), format!("{name}()"));
+        item.cursor -= 1;
+        all.push(item);
+    }
+    all
+}
+
+/// Suggestions for the word ending at `at`, if there are any worth showing.
+/// `included` is the source of the modules `text` includes. `forced`
+/// (Ctrl-Space) also lists them before anything is typed.
+pub(crate) fn menu(
+    text: &str,
+    at: usize,
+    scope: Scope,
+    included: &str,
+    forced: bool,
+) -> Option<Menu> {
+    let in_comment = wgsl::tokenize(text)
+        .iter()
+        .any(|t| t.kind == TokenKind::Comment && t.span.start < at && at <= t.span.end);
+    if in_comment {
+        return None;
+    }
+
+    let ident = |c: char| c == '_' || c.is_alphanumeric();
+    let start = text[..at].trim_end_matches(ident).len();
+    let word = &text[start..at];
+    if word.starts_with(|c: char| c.is_ascii_digit()) {
+        return None;
+    }
+
+    // `g_time.` lists the fields of the struct it's bound to
+    let candidates = match text[..start].strip_suffix('.') {
+        Some(before) => {
+            let receiver = &before[before.trim_end_matches(ident).len()..];
+            let r = available(scope).find(|r| r.name == receiver)?;
+            r.fields
+                .iter()
+                .map(|(field, ty)| {
+                    let detail = format!("{}.{field}: {ty}", r.ty);
+                    suggestion(field, Kind::Field, detail, field.to_string())
+                })
+                .collect()
+        }
+        None if word.is_empty() && !forced => return None,
+        None => everything(scope, text, included),
+    };
+
+    let lower = word.to_lowercase();
+    let mut items: Vec<Suggestion> = candidates
+        .into_iter()
+        .filter(|s| s.label.to_lowercase().starts_with(&lower))
+        .collect();
+    // a finished word needs no list
+    if !forced && items.iter().all(|s| s.label == word) {
+        return None;
+    }
+    items.sort_by(|a, b| {
+        // ones matching the case as typed first
+        let other_case = |s: &Suggestion| !s.label.starts_with(word);
+        (other_case(a), a.kind, &a.label).cmp(&(other_case(b), b.kind, &b.label))
+    });
+    items.truncate(MAX_ITEMS);
+
+    (!items.is_empty()).then_some(Menu { word: start..at, items, selected: 0 })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FRAGMENT: Scope = Scope { demo: Demo::CubePlanet, stage: Some(Stage::Fragment) };
+    const VERTEX: Scope = Scope { demo: Demo::CubePlanet, stage: Some(Stage::Vertex) };
+
+    /// Labels offered at the end of `text`.
+    fn labels(text: &str, scope: Scope, forced: bool) -> Vec<String> {
+        menu(text, text.len(), scope, "", forced)
+            .map(|m| m.items.into_iter().map(|s| s.label).collect())
+            .unwrap_or_default()
+    }
+
+    #[test]
+    fn resources_rank_before_inputs_before_functions() {
+        assert_eq!(
+            labels("let x = m", VERTEX, false),
+            ["model", "m0", "m1", "m2", "m3", "max", "min", "mix", "modf"],
+        );
+        // and alphabetically within a kind
+        assert_eq!(
+            labels("tex", FRAGMENT, false)[..4],
+            ["texture_data", "texture_sampler", "textureDimensions", "textureGather"],
+        );
+    }
+
+    #[test]
+    fn matching_ignores_case_and_caps_the_list() {
+        assert_eq!(labels("ich", FRAGMENT, false)[0], "iChannel1");
+        let many = labels("s", FRAGMENT, false);
+        assert_eq!(many.len(), MAX_ITEMS);
+        assert_eq!(many[0], "screen");
+    }
+
+    #[test]
+    fn scope_decides_what_is_bound() {
+        assert!(labels("li", VERTEX, false).is_empty());
+        assert_eq!(labels("li", FRAGMENT, false), ["light"]);
+        let untextured = Scope { demo: Demo::Animals, stage: Some(Stage::Fragment) };
+        assert!(!labels("tex", untextured, false).contains(&"texture_data".to_owned()));
+        assert!(labels("pos", FRAGMENT, false).is_empty());
+        assert_eq!(labels("pos", VERTEX, false), ["position"]);
+    }
+
+    #[test]
+    fn no_menu_when_there_is_nothing_to_help_with() {
+        assert!(labels("let x = ", FRAGMENT, false).is_empty());
+        assert!(!labels("let x = ", FRAGMENT, true).is_empty());
+        assert!(labels("// sc", FRAGMENT, false).is_empty());
+        assert!(labels("1e", FRAGMENT, false).is_empty());
+        // a finished word, unless asked
+        assert!(labels("light", FRAGMENT, false).is_empty());
+        assert_eq!(labels("light", FRAGMENT, true), ["light"]);
+    }
+
+    #[test]
+    fn fields_after_a_dot() {
+        assert_eq!(labels("g_time.", FRAGMENT, true).len(), 5);
+        assert_eq!(labels("g_time.e", FRAGMENT, false), ["elapsed"]);
+        assert!(labels("nothing.e", FRAGMENT, false).is_empty());
+    }
+
+    #[test]
+    fn picks_replace_the_word() {
+        let text = "let s = smo";
+        let menu = menu(text, text.len(), FRAGMENT, "", false).unwrap();
+        assert_eq!(menu.items[0].label, "smoothstep");
+        assert_eq!(menu.pick(text), ("let s = smoothstep()".to_owned(), text.len() + 8));
+
+        let mut menu = menu;
+        menu.step(-1);
+        assert_eq!(menu.selected, menu.items.len() - 1);
+        menu.step(1);
+        assert_eq!(menu.selected, 0);
+    }
+
+    #[test]
+    fn bindings_declare_only_what_is_missing() {
+        let missing = labels("bind", FRAGMENT, false);
+        assert_eq!(missing, ["bindings"]);
+
+        let all = boilerplate(FRAGMENT, "");
+        assert!(all.contains("struct TimeUBO {"));
+        assert!(all.contains("@group(0) @binding(0) var<uniform> g_time: TimeUBO;"));
+
+        let have = "struct TimeUBO { x: f32 };\n\
+                    @group(0) @binding(0) var<uniform> g_time: TimeUBO;";
+        let rest = boilerplate(FRAGMENT, have);
+        assert!(!rest.contains("TimeUBO"));
+        assert!(rest.contains("var<uniform> screen: Screen;"));
+        // included modules count as declared
+        let text = "bind";
+        let included = boilerplate(FRAGMENT, "");
+        assert!(menu(text, text.len(), FRAGMENT, &included, false).is_none());
+        assert!(boilerplate(VERTEX, "").contains("struct VSIn {"));
+    }
+}
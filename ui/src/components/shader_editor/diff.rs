@@ -0,0 +1,278 @@
+//! Line diff between the shipped shader and the edited one, for the diff
+//! view. A plain LCS table: after trimming the common ends, what's left of
+//! a few hundred lines costs nothing next to a compile.
+
+use std::ops::Range;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(crate) enum Op {
+    Same,
+    Removed,
+    Added,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct Line {
+    pub op: Op,
+    pub text: String,
+    /// 1-based, in the shipped source; `None` for added lines.
+    pub old: Option<usize>,
+    /// 1-based, in the edited source; `None` for removed lines.
+    pub new: Option<usize>,
+}
+
+/// A run of changed lines: 0-based line ranges, the shipped `old` lines
+/// stand where the edit has `new`. Either may be empty.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct Hunk {
+    pub old: Range<usize>,
+    pub new: Range<usize>,
+}
+
+/// What the view draws, top to bottom.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) enum Row {
+    Line(Line),
+    /// Starts hunk `n` (index into [`hunks`]).
+    Hunk(usize),
+    /// Unchanged lines left out.
+    Skipped(usize),
+}
+
+pub(crate) fn diff(old: &str, new: &str) -> Vec<Line> {
+    let a: Vec<&str> = old.split('\n').collect();
+    let b: Vec<&str> = new.split('\n').collect();
+
+    let prefix = a.iter().zip(&b).take_while(|(x, y)| x == y).count();
+    let suffix = a[prefix..]
+        .iter()
+        .rev()
+        .zip(b[prefix..].iter().rev())
+        .take_while(|(x, y)| x == y)
+        .count();
+    let (mid_a, mid_b) = (&a[prefix..a.len() - suffix], &b[prefix..b.len() - suffix]);
+
+    // lcs[i][j]: longest common run of mid_a[i..] and mid_b[j..]
+    let (n, m) = (mid_a.len(), mid_b.len());
+    let mut lcs = vec![vec![0u32; m + 1]; n + 1];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            lcs[i][j] = if mid_a[i] == mid_b[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let line = |op, text: &str, old, new| Line { op, text: text.to_owned(), old, new };
+    let same = |i: usize, j: usize| line(Op::Same, a[i], Some(i + 1), Some(j + 1));
+
+    let mut out: Vec<Line> = (0..prefix).map(|i| same(i, i)).collect();
+
+    let (mut i, mut j) = (0, 0);
+    while i < n || j < m {
+        let (ai, bj) = (prefix + i, prefix + j);
+        if i < n && j < m && mid_a[i] == mid_b[j] {
+            out.push(same(ai, bj));
+            i += 1;
+            j += 1;
+        } else if j < m && (i == n || lcs[i][j + 1] >= lcs[i + 1][j]) {
+            out.push(line(Op::Added, b[bj], None, Some(bj + 1)));
+            j += 1;
+        } else {
+            out.push(line(Op::Removed, a[ai], Some(ai + 1), None));
+            i += 1;
+        }
+    }
+
+    out.extend((0..suffix).map(|k| same(a.len() - suffix + k, b.len() - suffix + k)));
+
+    // removals before additions within a run, so it reads as "this became that"
+    for run in out.chunk_by_mut(|x, y| (x.op == Op::Same) == (y.op == Op::Same)) {
+        if run[0].op != Op::Same {
+            run.sort_by_key(|l| l.op == Op::Added);
+        }
+    }
+    out
+}
+
+/// Runs of changed lines in `lines`, in order. Removals within a run are
+/// listed before additions, so a run is always one contiguous replacement.
+pub(crate) fn hunks(lines: &[Line]) -> Vec<Hunk> {
+    let mut hunks: Vec<Hunk> = Vec::new();
+    // 0-based position in each source before the current line
+    let (mut old, mut new) = (0, 0);
+    let mut in_hunk = false;
+
+    for line in lines {
+        match line.op {
+            Op::Same => {
+                in_hunk = false;
+                old += 1;
+                new += 1;
+            }
+            op => {
+                if !in_hunk {
+                    hunks.push(Hunk { old: old..old, new: new..new });
+                    in_hunk = true;
+                }
+                let hunk = hunks.last_mut().unwrap();
+                if op == Op::Removed {
+                    old += 1;
+                    hunk.old.end = old;
+                } else {
+                    new += 1;
+                    hunk.new.end = new;
+                }
+            }
+        }
+    }
+    hunks
+}
+
+/// `lines` with only `context` unchanged lines around each hunk.
+pub(crate) fn rows(lines: Vec<Line>, context: usize) -> Vec<Row> {
+    let changed: Vec<usize> = (0..lines.len()).filter(|&i| lines[i].op != Op::Same).collect();
+    let near_change = |i: usize| {
+        changed
+            .iter()
+            .any(|&c| c.saturating_sub(context) <= i && i <= c + context)
+    };
+
+    let mut rows = Vec::new();
+    let mut skipped = 0;
+    let mut hunk = 0;
+    let mut prev = Op::Same;
+    for (i, line) in lines.into_iter().enumerate() {
+        if line.op == Op::Same && !near_change(i) {
+            skipped += 1;
+            prev = line.op;
+            continue;
+        }
+        if skipped > 0 {
+            rows.push(Row::Skipped(skipped));
+            skipped = 0;
+        }
+        if line.op != Op::Same && prev == Op::Same {
+            rows.push(Row::Hunk(hunk));
+            hunk += 1;
+        }
+        prev = line.op;
+        rows.push(Row::Line(line));
+    }
+    if skipped > 0 {
+        rows.push(Row::Skipped(skipped));
+    }
+    rows
+}
+
+/// `new` with `hunk` put back the way `old` has it.
+pub(crate) fn revert(old: &str, new: &str, hunk: &Hunk) -> String {
+    let old: Vec<&str> = old.split('\n').collect();
+    let new: Vec<&str> = new.split('\n').collect();
+
+    let mut out = Vec::with_capacity(new.len());
+    out.extend_from_slice(&new[..hunk.new.start]);
+    out.extend_from_slice(&old[hunk.old.clone()]);
+    out.extend_from_slice(&new[hunk.new.end..]);
+    out.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OLD: &str = "a\nb\nc\nd\ne\nf\ng";
+
+    /// Each line as `op` and text, e.g. `-c`, `+x`, ` a`.
+    fn marked(old: &str, new: &str) -> Vec<String> {
+        diff(old, new)
+            .into_iter()
+            .map(|l| {
+                let mark = match l.op {
+                    Op::Same => ' ',
+                    Op::Removed => '-',
+                    Op::Added => '+',
+                };
+                format!("{mark}{}", l.text)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn unchanged_sources_have_no_hunks() {
+        let lines = diff(OLD, OLD);
+        assert!(lines.iter().all(|l| l.op == Op::Same));
+        assert_eq!(lines[6].old, Some(7));
+        assert!(hunks(&lines).is_empty());
+        assert_eq!(rows(lines, 3), [Row::Skipped(7)]);
+    }
+
+    #[test]
+    fn lines_are_removed_before_added() {
+        let new = "a\nB\nC\nd\ne\nf\ng";
+        assert_eq!(marked(OLD, new), [" a", "-b", "-c", "+B", "+C", " d", " e", " f", " g"]);
+
+        let lines = diff(OLD, new);
+        assert_eq!((lines[1].old, lines[1].new), (Some(2), None));
+        assert_eq!((lines[3].old, lines[3].new), (None, Some(2)));
+        assert_eq!((lines[5].old, lines[5].new), (Some(4), Some(4)));
+        assert_eq!(hunks(&lines), [Hunk { old: 1..3, new: 1..3 }]);
+    }
+
+    #[test]
+    fn hunks_cover_insertions_and_deletions() {
+        // x inserted after a, d deleted, g changed
+        let new = "a\nx\nb\nc\ne\nf\nG";
+        let lines = diff(OLD, new);
+        assert_eq!(
+            hunks(&lines),
+            [
+                Hunk { old: 1..1, new: 1..2 },
+                Hunk { old: 3..4, new: 4..4 },
+                Hunk { old: 6..7, new: 6..7 },
+            ],
+        );
+    }
+
+    #[test]
+    fn each_hunk_reverts_on_its_own() {
+        let new = "a\nx\nb\nc\ne\nf\nG";
+        let hunks = hunks(&diff(OLD, new));
+        assert_eq!(revert(OLD, new, &hunks[0]), "a\nb\nc\ne\nf\nG");
+        assert_eq!(revert(OLD, new, &hunks[1]), "a\nx\nb\nc\nd\ne\nf\nG");
+        assert_eq!(revert(OLD, new, &hunks[2]), "a\nx\nb\nc\ne\nf\ng");
+
+        // one at a time, as the view does, gets back to the shipped source
+        let mut text = new.to_owned();
+        while let Some(hunk) = super::hunks(&diff(OLD, &text)).first().cloned() {
+            text = revert(OLD, &text, &hunk);
+        }
+        assert_eq!(text, OLD);
+    }
+
+    #[test]
+    fn rows_keep_context_around_hunks() {
+        let old = (1..=20).map(|i| i.to_string()).collect::<Vec<_>>().join("\n");
+        let new = old.replace("\n5\n", "\nfive\n").replace("\n15\n", "\n");
+        let rows = rows(diff(&old, &new), 2);
+
+        let shape: Vec<String> = rows
+            .iter()
+            .map(|row| match row {
+                Row::Skipped(n) => format!("...{n}"),
+                Row::Hunk(n) => format!("@{n}"),
+                Row::Line(l) if l.op == Op::Same => l.text.clone(),
+                Row::Line(l) => format!("{:?} {}", l.op, l.text),
+            })
+            .collect();
+        assert_eq!(
+            shape,
+            [
+                "...2", "3", "4", "@0", "Removed 5", "Added five", "6", "7", "...5", "13", "14",
+                "@1", "Removed 15", "16", "17", "...3",
+            ],
+        );
+    }
+}
@@ -0,0 +1,111 @@
+//! The Diff tab: [`super::diff`]'s rows drawn as a unified diff, with a
+//! revert button per hunk.
+
+use leptos::prelude::{
+    ClassAttribute, CollectView, ElementChild, GetUntracked, GlobalAttributes, IntoAny,
+    OnAttribute, RwSignal, Set, StoredValue, UpdateValue, With,
+};
+use leptos::{IntoView, component, view};
+
+use super::diff::{self, Op, Row};
+use super::history::History;
+
+/// The edited source against the shipped one, with a few lines of context
+/// around each change. Every hunk can be put back on its own, as an
+/// undoable edit.
+#[component]
+pub(super) fn DiffView(
+    src: RwSignal<String>,
+    shipped: &'static str,
+    history: StoredValue<History>,
+) -> impl IntoView {
+    let revert = move |n: usize| {
+        let current = src.get_untracked();
+        let Some(hunk) = diff::hunks(&diff::diff(shipped, &current)).get(n).cloned() else {
+            return;
+        };
+        let reverted = diff::revert(shipped, &current, &hunk);
+        history.update_value(|h| {
+            h.record(&current, &reverted);
+            h.close();
+        });
+        src.set(reverted);
+    };
+
+    let rows = move || {
+        let lines = src.with(|src| diff::diff(shipped, src));
+        if lines.iter().all(|l| l.op == Op::Same) {
+            return view! {
+                <p class="p-4 text-text-muted">"No changes from the shipped shader."</p>
+            }
+            .into_any();
+        }
+
+        let hunks = diff::hunks(&lines);
+        let number = |n: Option<usize>| n.map(|n| n.to_string()).unwrap_or_default();
+        diff::rows(lines, 3)
+            .into_iter()
+            .map(|row| match row {
+                Row::Line(line) => {
+                    let (sign, colour) = match line.op {
+                        Op::Same => (" ", "text-text"),
+                        Op::Removed => ("-", "bg-red-900/40 text-red-200"),
+                        Op::Added => ("+", "bg-green-900/40 text-green-200"),
+                    };
+                    view! {
+                        <div class=format!("flex whitespace-pre {colour}")>
+                            <span class="w-10 shrink-0 pr-2 text-right text-text-muted select-none">
+                                { number(line.old) }
+                            </span>
+                            <span class="w-10 shrink-0 pr-2 text-right text-text-muted select-none">
+                                { number(line.new) }
+                            </span>
+                            <span class="w-4 shrink-0 select-none">{ sign }</span>
+                            <span>{ line.text }</span>
+                        </div>
+                    }
+                    .into_any()
+                }
+                Row::Hunk(n) => {
+                    let h = &hunks[n];
+                    let header = format!(
+                        "@@ -{},{} +{},{} @@",
+                        h.old.start + 1,
+                        h.old.len(),
+                        h.new.start + 1,
+                        h.new.len(),
+                    );
+                    view! {
+                        <div class="flex items-center gap-3 mt-2 px-2 text-text-muted">
+                            <span>{ header }</span>
+                            <button
+                                class="px-2 border rounded hover:text-accent"
+                                title="Put these lines back the way the demo ships them"
+                                on:click=move |_| revert(n)
+                            >
+                                "Revert"
+                            </button>
+                        </div>
+                    }
+                    .into_any()
+                }
+                Row::Skipped(count) => {
+                    let plural = if count == 1 { "" } else { "s" };
+                    view! {
+                        <div class="px-2 text-text-muted select-none">
+                            { format!("⋯ {count} unchanged line{plural}") }
+                        </div>
+                    }
+                    .into_any()
+                }
+            })
+            .collect_view()
+            .into_any()
+    };
+
+    view! {
+        <div class="flex-1 min-h-0 rounded-xl bg-surface overflow-auto py-2 text-xs font-mono">
+            { rows }
+        </div>
+    }
+}
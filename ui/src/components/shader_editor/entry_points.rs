@@ -0,0 +1,85 @@
+//! The UI tab's entry-point pickers. Every pass a demo builds is listed
+//! with the entry points it was built with; picking another is what lets
+//! one module hold several versions of a stage.
+
+use leptos::prelude::{
+    ClassAttribute, CollectView, ElementChild, Get, Memo, OnAttribute, RwSignal, event_target_value,
+};
+use leptos::{IntoView, component, view};
+
+use crate::components::demo::Demo;
+use crate::render::renderer::gpu::diagnostics::Stage;
+use crate::render::renderer::gpu::setup::ShaderSetup;
+
+use super::utils::stage_label;
+
+/// Which entry points each of the demo's passes runs, out of the ones the
+/// sources declare.
+#[component]
+pub(super) fn EntryPointsPanel(
+    selected_demo: RwSignal<Demo>,
+    setup: ShaderSetup,
+    /// `@vertex` and `@fragment` functions found in the sources.
+    found: Memo<(Vec<String>, Vec<String>)>,
+) -> impl IntoView {
+    let picker = move |pass: &'static str, stage: Stage, default: &'static str| {
+        let on_change = move |ev| setup.pick(pass, stage, event_target_value(&ev), default);
+        let options = move || {
+            let current = setup.entry(pass, stage, default);
+            let (vs, fs) = found.get();
+            let mut names = if stage == Stage::Vertex { vs } else { fs };
+            let missing = !names.contains(&current);
+            if missing {
+                names.insert(0, current.clone());
+            }
+            names
+                .into_iter()
+                .map(|name| {
+                    let selected = name == current;
+                    let label = if missing && selected {
+                        format!("{name} (not declared)")
+                    } else {
+                        name.clone()
+                    };
+                    view! { <option value=name selected=selected>{ label }</option> }
+                })
+                .collect::<Vec<_>>()
+        };
+
+        view! {
+            <label class="flex items-center gap-2">
+                <span class="text-text-muted w-16">{ stage_label(stage) }</span>
+                <select
+                    class="flex-1 px-2 py-1 text-text bg-surface border rounded font-mono text-xs"
+                    on:change=on_change
+                >
+                    { options }
+                </select>
+            </label>
+        }
+    };
+
+    let passes = move || {
+        selected_demo
+            .get()
+            .entry_points()
+            .iter()
+            .map(|&(pass, (vs, fs))| {
+                view! {
+                    <div class="flex flex-col gap-1">
+                        <span class="text-text">{ pass }</span>
+                        { picker(pass, Stage::Vertex, vs) }
+                        { picker(pass, Stage::Fragment, fs) }
+                    </div>
+                }
+            })
+            .collect_view()
+    };
+
+    view! {
+        <div class="flex flex-col gap-3">
+            <p class="text-text-muted text-sm">"Entry points"</p>
+            { passes }
+        </div>
+    }
+}
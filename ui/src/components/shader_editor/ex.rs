@@ -0,0 +1,410 @@
+//! The `:` command line. Parsing and the text-only commands live here; the
+//! ones that reach outside the buffer (switching demos, compiling) are
+//! carried out by the editor.
+//!
+//! Patterns in `:s` are plain text, not regexes.
+
+use crate::components::demo::Demo;
+
+use super::vim::{first_non_blank, nth_line};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(crate) enum Switch {
+    On,
+    Off,
+    Toggle,
+}
+
+#[derive(Clone, PartialEq, Eq)]
+pub(crate) enum Command {
+    /// `:w`: compile and apply now.
+    Write,
+    /// `:e default`: back to the demo's built-in source.
+    ResetToDefault,
+    Substitute(Substitute),
+    /// `:set number`, `:set nonu`, `:set nu!`
+    Number(Switch),
+    /// `:12`, `:$`
+    Goto(Line),
+    /// `:demo animals`; `None` lists them.
+    Demo(Option<Demo>),
+}
+
+/// A line address: 1-based, the cursor's line, or the last one.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(crate) enum Line {
+    Number(usize),
+    Current,
+    Last,
+}
+
+/// First and last line, both included.
+type Range = (Line, Line);
+
+#[derive(Clone, PartialEq, Eq)]
+pub(crate) struct Substitute {
+    /// `None` is the cursor's line.
+    range: Option<Range>,
+    pattern: String,
+    replacement: String,
+    /// `g`: every match on a line, not just the first.
+    global: bool,
+    /// `i`: ASCII case-insensitive.
+    ignore_case: bool,
+}
+
+/// `Ok(None)` for an empty line, which does nothing.
+pub(crate) fn parse(line: &str) -> Result<Option<Command>, String> {
+    let line = line.trim();
+    let (range, rest) = parse_range(line)?;
+
+    // a bare address is a jump
+    if rest.is_empty() {
+        return Ok(range.map(|(_, to)| Command::Goto(to)));
+    }
+
+    let name_len = rest
+        .find(|c: char| !c.is_ascii_alphabetic())
+        .unwrap_or(rest.len());
+    let (name, args) = rest.split_at(name_len);
+    let args = args.trim();
+
+    // `:s` takes its delimiter straight after the name
+    if matches!(name, "s" | "substitute") {
+        return parse_substitute(range, args).map(|s| Some(Command::Substitute(s)));
+    }
+    if range.is_some() {
+        return Err("E481: No range allowed".to_owned());
+    }
+
+    let command = match (name, args) {
+        ("w" | "write", "") => Command::Write,
+        ("e" | "edit", "default") => Command::ResetToDefault,
+        ("e" | "edit", _) => return Err("E32: Only `:e default` is supported".to_owned()),
+        ("set" | "se", opt) => parse_set(opt)?,
+        ("demo", "") => Command::Demo(None),
+        ("demo", name) => match Demo::from_slug(name) {
+            Some(demo) => Command::Demo(Some(demo)),
+            None => return Err(format!("Unknown demo: {name} (try {})", demo_names())),
+        },
+        _ => return Err(format!("E492: Not an editor command: {line}")),
+    };
+    Ok(Some(command))
+}
+
+pub(crate) fn demo_names() -> String {
+    Demo::ALL.map(|d| d.slug()).join(", ")
+}
+
+fn parse_set(opt: &str) -> Result<Command, String> {
+    let switch = match opt {
+        "number" | "nu" => Switch::On,
+        "nonumber" | "nonu" => Switch::Off,
+        "number!" | "nu!" | "invnumber" | "invnu" => Switch::Toggle,
+        "" => return Err("E471: Argument required".to_owned()),
+        _ => return Err(format!("E518: Unknown option: {opt}")),
+    };
+    Ok(Command::Number(switch))
+}
+
+/// `%`, `N`, `N,M`, `.`, `$`, or nothing.
+fn parse_range(line: &str) -> Result<(Option<Range>, &str), String> {
+    if let Some(rest) = line.strip_prefix('%') {
+        return Ok((Some((Line::Number(1), Line::Last)), rest.trim_start()));
+    }
+
+    let Some((from, rest)) = parse_line(line)? else {
+        return Ok((None, line));
+    };
+    match rest.strip_prefix(',') {
+        Some(rest) => match parse_line(rest.trim_start())? {
+            Some((to, rest)) => Ok((Some((from, to)), rest.trim_start())),
+            None => Err("E14: Invalid address".to_owned()),
+        },
+        None => Ok((Some((from, from)), rest.trim_start())),
+    }
+}
+
+fn parse_line(s: &str) -> Result<Option<(Line, &str)>, String> {
+    if let Some(rest) = s.strip_prefix('.') {
+        return Ok(Some((Line::Current, rest)));
+    }
+    if let Some(rest) = s.strip_prefix('$') {
+        return Ok(Some((Line::Last, rest)));
+    }
+
+    let digits = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    if digits == 0 {
+        return Ok(None);
+    }
+    let n = s[..digits]
+        .parse()
+        .map_err(|_| "E14: Invalid address".to_owned())?;
+    Ok(Some((Line::Number(n), &s[digits..])))
+}
+
+/// `/pat/rep/flags`, any punctuation as the delimiter.
+fn parse_substitute(range: Option<Range>, args: &str) -> Result<Substitute, String> {
+    let mut chars = args.chars();
+    let delim = chars
+        .next()
+        .filter(|c| !c.is_alphanumeric() && !c.is_whitespace() && *c != '\\')
+        .ok_or("E146: Regular expressions can't be delimited by letters")?;
+
+    let mut parts = vec![String::new()];
+    let mut escaped = false;
+    for c in chars {
+        if !escaped && c == delim && parts.len() < 3 {
+            parts.push(String::new());
+            continue;
+        }
+        let part = parts.last_mut().unwrap();
+        match c {
+            _ if escaped => {
+                // `\/` is the delimiter itself; keep other escapes for the
+                // replacement (`\n`, `\&`)
+                if c != delim {
+                    part.push('\\');
+                }
+                part.push(c);
+                escaped = false;
+            }
+            '\\' => escaped = true,
+            _ => part.push(c),
+        }
+    }
+
+    let mut parts = parts.into_iter();
+    let pattern = parts.next().unwrap_or_default();
+    let replacement = parts.next().unwrap_or_default();
+    let flags = parts.next().unwrap_or_default();
+
+    if pattern.is_empty() {
+        return Err("E35: No previous regular expression".to_owned());
+    }
+    if let Some(bad) = flags.chars().find(|c| !matches!(c, 'g' | 'i')) {
+        return Err(format!("E488: Trailing characters: {bad}"));
+    }
+
+    Ok(Substitute {
+        range,
+        pattern: unescape(&pattern, None),
+        replacement,
+        global: flags.contains('g'),
+        ignore_case: flags.contains('i'),
+    })
+}
+
+/// `\n` and `\t`, and in replacements `&` for the match (`\&` for a
+/// literal one).
+fn unescape(s: &str, matched: Option<&str>) -> String {
+    let mut out = String::new();
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some('n') => out.push('\n'),
+                Some('t') => out.push('\t'),
+                Some(c) => out.push(c),
+                None => out.push('\\'),
+            },
+            '&' => match matched {
+                Some(m) => out.push_str(m),
+                None => out.push('&'),
+            },
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// 1-based line of byte offset `at`.
+pub(crate) fn line_of(text: &str, at: usize) -> usize {
+    text[..at].matches('\n').count() + 1
+}
+
+fn resolve(line: Line, text: &str, cursor: usize) -> usize {
+    match line {
+        Line::Number(n) => n,
+        Line::Current => line_of(text, cursor),
+        Line::Last => line_of(text, text.len()),
+    }
+}
+
+/// Where `:N` puts the cursor: the line's first non-blank, clamped to the
+/// buffer.
+pub(crate) fn goto(text: &str, cursor: usize, line: Line) -> usize {
+    first_non_blank(text, nth_line(text, resolve(line, text, cursor).max(1)))
+}
+
+impl Substitute {
+    /// The new text, the cursor (start of the last changed line) and a
+    /// report for the message line.
+    pub fn apply(&self, text: &str, cursor: usize) -> Result<(String, usize, String), String> {
+        let total = line_of(text, text.len());
+        let (from, to) = match self.range {
+            Some((a, b)) => (resolve(a, text, cursor), resolve(b, text, cursor)),
+            None => {
+                let here = line_of(text, cursor);
+                (here, here)
+            }
+        };
+        let (from, to) = (from.min(to), from.max(to));
+        if from == 0 || to > total {
+            return Err("E16: Invalid range".to_owned());
+        }
+
+        let mut out = String::with_capacity(text.len());
+        let (mut count, mut lines, mut last) = (0, 0, 0);
+        for (i, line) in text.split('\n').enumerate() {
+            if i > 0 {
+                out.push('\n');
+            }
+            let n = i + 1;
+            let start = out.len();
+            let replaced = if (from..=to).contains(&n) { self.line(line, &mut out) } else { 0 };
+            if replaced == 0 {
+                out.truncate(start);
+                out.push_str(line);
+            } else {
+                count += replaced;
+                lines += 1;
+                last = start;
+            }
+        }
+
+        if count == 0 {
+            return Err(format!("E486: Pattern not found: {}", self.pattern));
+        }
+        let report = match (count, lines) {
+            (1, _) => "1 substitution".to_owned(),
+            (c, 1) => format!("{c} substitutions on 1 line"),
+            (c, l) => format!("{c} substitutions on {l} lines"),
+        };
+        let cursor = first_non_blank(&out, last);
+        Ok((out, cursor, report))
+    }
+
+    /// Substituted `line` appended to `out`; returns how many matches.
+    fn line(&self, line: &str, out: &mut String) -> usize {
+        let find = |from: usize| -> Option<usize> {
+            if self.ignore_case {
+                let pat = self.pattern.as_bytes();
+                line.as_bytes()[from..]
+                    .windows(pat.len())
+                    .position(|w| w.eq_ignore_ascii_case(pat))
+                    .map(|i| from + i)
+            } else {
+                line[from..].find(&self.pattern).map(|i| from + i)
+            }
+        };
+
+        let mut count = 0;
+        let mut at = 0;
+        while let Some(i) = find(at) {
+            let end = i + self.pattern.len();
+            out.push_str(&line[at..i]);
+            out.push_str(&unescape(&self.replacement, Some(&line[i..end])));
+            at = end;
+            count += 1;
+            if !self.global {
+                break;
+            }
+        }
+        out.push_str(&line[at..]);
+        count
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEXT: &str = "let a = a;\n    a += 1;\nlet b = a * a;\n";
+
+    /// `:{cmd}` run on `text` with the cursor on line `on`.
+    fn sub(cmd: &str, text: &str, on: usize) -> Result<(String, usize, String), String> {
+        let Some(Command::Substitute(s)) = parse(cmd)? else {
+            panic!("`{cmd}` isn't a substitution");
+        };
+        s.apply(text, nth_line(text, on))
+    }
+
+    fn error(cmd: &str) -> String {
+        parse(cmd).err().unwrap_or_else(|| panic!("`{cmd}` parsed"))
+    }
+
+    fn substituted(cmd: &str, on: usize) -> String {
+        sub(cmd, TEXT, on).unwrap_or_else(|e| panic!("`{cmd}`: {e}")).0
+    }
+
+    #[test]
+    fn ranges_pick_the_lines() {
+        assert_eq!(substituted("s/a/x/", 1), "let x = a;\n    a += 1;\nlet b = a * a;\n");
+        assert_eq!(substituted("s/a/x/", 2), "let a = a;\n    x += 1;\nlet b = a * a;\n");
+        assert_eq!(substituted("2,3s/a/x/", 1), "let a = a;\n    x += 1;\nlet b = x * a;\n");
+        assert_eq!(substituted("3,2s/a/x/", 1), substituted("2,3s/a/x/", 1));
+        assert_eq!(substituted(".,$s/a/x/", 2), substituted("2,3s/a/x/", 1));
+        assert_eq!(substituted("%s/a/x/", 3), "let x = a;\n    x += 1;\nlet b = x * a;\n");
+        assert_eq!(substituted("3s/a/x/", 1), "let a = a;\n    a += 1;\nlet b = x * a;\n");
+    }
+
+    #[test]
+    fn flags() {
+        assert_eq!(substituted("%s/a/x/g", 1), "let x = x;\n    x += 1;\nlet b = x * x;\n");
+        assert_eq!(sub("s/LET/var/i", TEXT, 1).unwrap().0, TEXT.replacen("let", "var", 1));
+        assert_eq!(sub("s/LET/var/", TEXT, 1).unwrap_err(), "E486: Pattern not found: LET");
+        assert_eq!(sub("s/a/x/gi", "A a", 1).unwrap().0, "x x");
+        assert_eq!(sub("s/a/x/q", TEXT, 1).unwrap_err(), "E488: Trailing characters: q");
+    }
+
+    #[test]
+    fn replacements_and_delimiters() {
+        assert_eq!(sub("s/a/[&]/g", "a b a", 1).unwrap().0, "[a] b [a]");
+        assert_eq!(sub(r"s/a/\&/", "a", 1).unwrap().0, "&");
+        assert_eq!(sub(r"s/, /,\n/g", "f(a, b)", 1).unwrap().0, "f(a,\nb)");
+        assert_eq!(sub(r"s/\//÷/", "a / b", 1).unwrap().0, "a ÷ b");
+        assert_eq!(sub("s#/#÷#", "a / b", 1).unwrap().0, "a ÷ b");
+        // a missing last delimiter is fine, an empty replacement deletes
+        assert_eq!(sub("s/ b", "a b", 1).unwrap().0, "a");
+    }
+
+    #[test]
+    fn reports_and_cursor() {
+        let (text, cursor, report) = sub("%s/a/x/g", TEXT, 1).unwrap();
+        assert_eq!(report, "5 substitutions on 3 lines");
+        // the last changed line's first non-blank
+        assert_eq!(cursor, nth_line(&text, 3));
+
+        let (_, cursor, report) = sub("1,2s/a/x/", TEXT, 1).unwrap();
+        assert_eq!(report, "2 substitutions on 2 lines");
+        assert_eq!(cursor, nth_line(TEXT, 2) + 4);
+        assert_eq!(sub("s/a/x/g", TEXT, 1).unwrap().2, "2 substitutions on 1 line");
+        assert_eq!(sub("s/a/x/", TEXT, 1).unwrap().2, "1 substitution");
+    }
+
+    #[test]
+    fn bad_substitutions() {
+        assert_eq!(sub("0s/a/x/", TEXT, 1).unwrap_err(), "E16: Invalid range");
+        assert_eq!(sub("1,9s/a/x/", TEXT, 1).unwrap_err(), "E16: Invalid range");
+        assert_eq!(sub("2s/let/x/", TEXT, 1).unwrap_err(), "E486: Pattern not found: let");
+        assert!(error("s//x/").starts_with("E35"));
+        assert!(error("s a/b/").starts_with("E146"));
+        assert!(error("1,s/a/b/").starts_with("E14"));
+    }
+
+    #[test]
+    fn other_commands() {
+        assert!(matches!(parse("  "), Ok(None)));
+        assert!(matches!(parse("w"), Ok(Some(Command::Write))));
+        assert!(matches!(parse("12"), Ok(Some(Command::Goto(Line::Number(12))))));
+        assert!(matches!(parse("$"), Ok(Some(Command::Goto(Line::Last)))));
+        assert!(matches!(parse("set nu!"), Ok(Some(Command::Number(Switch::Toggle)))));
+        assert!(matches!(parse("e default"), Ok(Some(Command::ResetToDefault))));
+        assert!(matches!(parse("demo"), Ok(Some(Command::Demo(None)))));
+        assert!(error("5w").starts_with("E481"));
+        assert!(error("frobnicate").starts_with("E492"));
+        assert_eq!(goto(TEXT, 0, Line::Number(2)), nth_line(TEXT, 2) + 4);
+        assert_eq!(goto(TEXT, 0, Line::Number(99)), TEXT.len());
+    }
+}
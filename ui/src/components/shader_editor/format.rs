@@ -0,0 +1,369 @@
+//! WGSL formatting on top of the highlighting tokenizer, so comments stay
+//! where they are. It re-indents and evens out spacing within lines
+//! (binary and assignment operators get a space each side), but never joins
+//! or splits them: a range of lines (`=`, `gq`) formats the same as it
+//! would as part of the whole file.
+//!
+//! Also the small edits insert mode makes on its own: indenting new lines
+//! and pairing brackets.
+
+use std::ops::{Range, RangeInclusive};
+
+use super::vim::{INDENT, indentation};
+use super::wgsl::{self, TokenKind};
+
+/// All of `src`.
+pub(crate) fn format(src: &str) -> String {
+    format_lines(src, 1..=usize::MAX)
+}
+
+/// Lines `lines` (1-based) of `src`; the others are kept as they are, but
+/// still count for the indentation.
+pub(crate) fn format_lines(src: &str, lines: RangeInclusive<usize>) -> String {
+    let tokens = wgsl::tokenize(src);
+    // indentation of what's inside each open bracket
+    let mut open: Vec<usize> = Vec::new();
+    let mut out = Vec::new();
+
+    let all = wgsl::lines(src, &tokens);
+    for (i, (segments, text)) in all.iter().zip(src.split('\n')).enumerate() {
+        let pieces = operators(src, segments);
+
+        // leading closers sit at the level of the line that opened them;
+        // they all come before anything this line opens
+        let mut depth = open.last().copied().unwrap_or(0);
+        let mut leading = true;
+        for piece in &pieces {
+            match (piece.kind, piece.text) {
+                (TokenKind::Punct, "(" | "[" | "{") => {
+                    open.push(depth + 1);
+                    leading = false;
+                }
+                (TokenKind::Punct, ")" | "]" | "}") => {
+                    let inner = open.pop().unwrap_or(1);
+                    if leading {
+                        depth = inner - 1;
+                    }
+                }
+                _ => leading = false,
+            }
+        }
+
+        let continued_comment = pieces.first().is_some_and(|p| {
+            p.kind == TokenKind::Comment && !p.text.starts_with("//") && !p.text.starts_with("/*")
+        });
+        if !lines.contains(&(i + 1)) || continued_comment {
+            out.push(text.to_owned());
+        } else if pieces.is_empty() {
+            out.push(String::new());
+        } else {
+            out.push(format!("{}{}", INDENT.repeat(depth), join(&pieces)));
+        }
+    }
+    out.join("\n")
+}
+
+/// Operators the tokenizer hands over one character at a time, longest
+/// first.
+const COMPOUND: &[&str] = &[
+    "<<=", ">>=", "==", "!=", "<=", ">=", "&&", "||", "<<", ">>", "+=", "-=", "*=", "/=",
+    "%=", "&=", "|=", "^=", "->", "++", "--",
+];
+
+/// What a piece of punctuation is doing, for spacing.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Role {
+    /// Not an operator: brackets, commas, words, ...
+    Other,
+    /// Between two operands, assignments and `->` included.
+    Binary,
+    /// In front of its operand: `-x`, `!a`, `*ptr`, `&v`.
+    Unary,
+    /// `i++`, `i--`.
+    Postfix,
+    /// The `<` and `>` around template arguments (`vec3<f32>`).
+    Template,
+}
+
+struct Piece<'a> {
+    text: &'a str,
+    kind: TokenKind,
+    /// Whether whitespace came before it in the source.
+    spaced: bool,
+    role: Role,
+}
+
+impl Piece<'_> {
+    /// Whether an operator after it would have an operand on its left.
+    fn ends_operand(&self) -> bool {
+        match self.kind {
+            TokenKind::Ident | TokenKind::Number | TokenKind::Type | TokenKind::Builtin => true,
+            TokenKind::Keyword => matches!(self.text, "true" | "false"),
+            TokenKind::Punct => {
+                matches!(self.text, ")" | "]") || (self.text == ">" && self.role == Role::Template)
+            }
+            _ => false,
+        }
+    }
+
+    /// Whether a `<` after it opens template arguments.
+    fn takes_template(&self) -> bool {
+        match self.kind {
+            TokenKind::Type => true,
+            TokenKind::Keyword => self.text == "var",
+            TokenKind::Builtin => self.text == "bitcast",
+            _ => false,
+        }
+    }
+}
+
+/// One line's tokens without the whitespace, compound operators joined up
+/// and every operator told apart by what's around it.
+fn operators<'a>(src: &'a str, segments: &[(Range<usize>, TokenKind)]) -> Vec<Piece<'a>> {
+    let mut pieces: Vec<Piece> = Vec::new();
+    // template arguments still open on this line
+    let mut templates = 0;
+    let mut j = 0;
+
+    while j < segments.len() {
+        let (range, kind) = (&segments[j].0, segments[j].1);
+        let spaced = j > 0 && segments[j - 1].1 == TokenKind::Whitespace;
+        if kind == TokenKind::Whitespace {
+            j += 1;
+            continue;
+        }
+
+        // adjacent punctuation that spells a compound operator; a `>` that
+        // closes template arguments stays on its own (`array<vec2<f32>>`)
+        let mut end = j + 1;
+        if kind == TokenKind::Punct && !(templates > 0 && &src[range.clone()] == ">") {
+            let run = segments[j..]
+                .iter()
+                .take(3)
+                .take_while(|(_, kind)| *kind == TokenKind::Punct)
+                .count();
+            let joined = |n: usize| &src[range.start..segments[j + n - 1].0.end];
+            if let Some(n) = (2..=run).rev().find(|&n| COMPOUND.contains(&joined(n))) {
+                end = j + n;
+            }
+        }
+        let text = &src[range.start..segments[end - 1].0.end];
+        j = end;
+
+        let prev = pieces.iter().rev().find(|p| p.kind != TokenKind::Comment);
+        let operand = prev.is_some_and(Piece::ends_operand);
+        let role = match (kind, text) {
+            (TokenKind::Punct, "<") if prev.is_some_and(Piece::takes_template) => {
+                templates += 1;
+                Role::Template
+            }
+            (TokenKind::Punct, ">") if templates > 0 => {
+                templates -= 1;
+                Role::Template
+            }
+            (TokenKind::Punct, "++" | "--") if operand => Role::Postfix,
+            (TokenKind::Punct, "-" | "*" | "&") if !operand => Role::Unary,
+            (TokenKind::Punct, "!" | "~") => Role::Unary,
+            (TokenKind::Punct, "+" | "-" | "*" | "/" | "%" | "&" | "|" | "^" | "<" | ">" | "=") => {
+                Role::Binary
+            }
+            (TokenKind::Punct, op) if COMPOUND.contains(&op) => Role::Binary,
+            _ => Role::Other,
+        };
+        pieces.push(Piece { text, kind, spaced, role });
+    }
+    pieces
+}
+
+/// The pieces of one line, with spaces where they belong.
+fn join(pieces: &[Piece]) -> String {
+    let mut line = String::new();
+    let mut prev: Option<&Piece> = None;
+    for piece in pieces {
+        if prev.is_some_and(|prev| space_between(prev, piece)) {
+            line.push(' ');
+        }
+        line.push_str(piece.text);
+        prev = Some(piece);
+    }
+    line
+}
+
+/// Binary operators get a space each side, unary ones and template
+/// brackets hug what they go with. Commas and colons get a space after and
+/// none before, brackets hug what they hold, calls and indexing hug their
+/// callee. Anything else keeps whether it had a space.
+fn space_between(prev: &Piece, cur: &Piece) -> bool {
+    use TokenKind::*;
+
+    match ((prev.text, prev.kind, prev.role), (cur.text, cur.kind, cur.role)) {
+        (_, (_, Comment, _)) | ((_, Comment, _), _) => true,
+        ((_, _, Role::Binary), _) | (_, (_, _, Role::Binary)) => true,
+        ((_, _, Role::Unary), _) | (("<", _, Role::Template), _) => false,
+        (_, (_, _, Role::Postfix | Role::Template)) => false,
+        (_, ("," | ";" | ":" | ")" | "]" | ".", Punct, _)) => false,
+        (("(" | "[" | ".", Punct, _), _) => false,
+        (("," | ";" | ":", Punct, _), _) => true,
+        ((_, Ident | Builtin | Type | Attribute, _), ("(", Punct, _)) => false,
+        ((_, Ident, _), ("[", Punct, _)) | ((")" | "]", Punct, _), ("[", Punct, _)) => false,
+        _ => cur.spaced,
+    }
+}
+
+/// Insert-mode help for `key` with bytes `start..end` selected: keeps the
+/// indentation on a new line (one more after an opening bracket), pairs
+/// brackets, types over a closing bracket that's already there and
+/// outdents a `}` typed on an empty line. `None` leaves the key to the
+/// textarea.
+pub(crate) fn assist(key: &str, text: &str, start: usize, end: usize) -> Option<(String, usize)> {
+    if start != end {
+        return None;
+    }
+    let at = start;
+    let before = text[..at].chars().next_back();
+    let after = text[at..].chars().next();
+    let closer = |open: char| match open {
+        '(' => Some(')'),
+        '[' => Some(']'),
+        '{' => Some('}'),
+        _ => None,
+    };
+    let edit = |insert: &str, cursor: usize| {
+        let mut new = text.to_owned();
+        new.insert_str(at, insert);
+        Some((new, at + cursor))
+    };
+
+    match key {
+        "Enter" => {
+            let indent = indentation(text, at);
+            let opened = text[..at].trim_end_matches([' ', '\t']).chars().next_back();
+            match opened.and_then(closer) {
+                // `{|}` opens up into three lines
+                Some(close) if after == Some(close) => {
+                    let inner = format!("\n{indent}{INDENT}");
+                    edit(&format!("{inner}\n{indent}"), inner.len())
+                }
+                Some(_) => edit(&format!("\n{indent}{INDENT}"), 1 + indent.len() + INDENT.len()),
+                None => edit(&format!("\n{indent}"), 1 + indent.len()),
+            }
+        }
+        "(" | "[" | "{" => {
+            let open = key.chars().next()?;
+            // only where a pair can't split a word: before space, the end, or
+            // another closer
+            let free = after
+                .is_none_or(|c| c.is_whitespace() || matches!(c, ')' | ']' | '}' | ',' | ';'));
+            free.then(|| edit(&format!("{open}{}", closer(open)?), 1))?
+        }
+        ")" | "]" | "}" => {
+            let close = key.chars().next()?;
+            if after == Some(close) {
+                return Some((text.to_owned(), at + 1));
+            }
+            let line_start = text[..at].rfind('\n').map_or(0, |i| i + 1);
+            let typed = &text[line_start..at];
+            if close == '}' && !typed.is_empty() && typed.trim().is_empty() {
+                let keep = typed.len().saturating_sub(INDENT.len());
+                let mut new = text.to_owned();
+                new.replace_range(line_start + keep..at, "}");
+                return Some((new, line_start + keep + 1));
+            }
+            None
+        }
+        "Backspace" => {
+            let open = before?;
+            (closer(open)? == after?).then(|| {
+                let mut new = text.to_owned();
+                new.replace_range(at - 1..at + 1, "");
+                (new, at - 1)
+            })
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::components::demo::Demo;
+
+    #[test]
+    fn binary_and_assignment_operators_get_spaces() {
+        for (src, formatted) in [
+            ("x=a+b*c;", "x = a + b * c;"),
+            ("x  +=  1;", "x += 1;"),
+            ("if a<=b&&c!=d||!e {", "if a <= b && c != d || !e {"),
+            ("let m = a<<2u|b>>1u;", "let m = a << 2u | b >> 1u;"),
+            ("fn f(p: vec2f)->f32 {", "fn f(p: vec2f) -> f32 {"),
+            ("let t = f(x)%2.0;", "let t = f(x) % 2.0;"),
+            ("let b = true==false;", "let b = true == false;"),
+            ("i <<= 1u;", "i <<= 1u;"),
+        ] {
+            assert_eq!(format(src), formatted, "{src}");
+        }
+    }
+
+    #[test]
+    fn unary_operators_hug_their_operand() {
+        for (src, formatted) in [
+            ("let x = - y;", "let x = -y;"),
+            ("return - 1.0;", "return -1.0;"),
+            ("f( -a, - b)", "f(-a, -b)"),
+            ("x = a * - b;", "x = a * -b;"),
+            ("* p = 1;", "*p = 1;"),
+            ("let v = & w;", "let v = &w;"),
+            ("if ! ok {", "if !ok {"),
+            ("x = a[i]-1;", "x = a[i] - 1;"),
+            ("i ++;", "i++;"),
+        ] {
+            assert_eq!(format(src), formatted, "{src}");
+        }
+    }
+
+    #[test]
+    fn template_brackets_are_not_comparisons() {
+        for (src, formatted) in [
+            ("var < uniform > u: Camera;", "var<uniform> u: Camera;"),
+            ("let v = vec3 < f32 >(1.0);", "let v = vec3<f32>(1.0);"),
+            ("var a: array<vec2<f32>,4>=b;", "var a: array<vec2<f32>, 4> = b;"),
+            ("fn f() -> vec4<f32> {", "fn f() -> vec4<f32> {"),
+            ("let u = bitcast<u32>(x);", "let u = bitcast<u32>(x);"),
+            ("let c = a<b;", "let c = a < b;"),
+            ("let c = f(a)>b;", "let c = f(a) > b;"),
+        ] {
+            assert_eq!(format(src), formatted, "{src}");
+        }
+    }
+
+    #[test]
+    fn indentation_follows_brackets() {
+        let src = "fn f() {\nif x {\nreturn vec3f(\n1.0,\n);\n}\n  }";
+        let formatted =
+            "fn f() {\n    if x {\n        return vec3f(\n            1.0,\n        );\n    }\n}";
+        assert_eq!(format(src), formatted);
+        // a range formats as it would in the whole file, the rest is left
+        assert_eq!(
+            format_lines(src, 2..=2),
+            "fn f() {\n    if x {\nreturn vec3f(\n1.0,\n);\n}\n  }",
+        );
+    }
+
+    #[test]
+    fn comments_stay_as_written() {
+        let src = "x=1;// a=b\n/* a=b\n   c */y=2;";
+        // a line that carries on a block comment is left alone entirely
+        assert_eq!(format(src), "x = 1; // a=b\n/* a=b\n   c */y=2;");
+    }
+
+    #[test]
+    fn formatting_is_idempotent() {
+        let odd = "fn  f ( a:f32,b :f32 )->f32{\nlet x=-a*(b+ 1.0)  ;// note\n\
+                   var<private> y:array<vec2<f32>,2>;\nif(x>=0.0&&!z){ i++; }\nreturn x;}";
+        let shipped = Demo::ALL.map(|d| d.module());
+        for src in shipped.iter().copied().chain([odd]) {
+            let once = format(src);
+            assert_eq!(format(&once), once);
+        }
+    }
+}
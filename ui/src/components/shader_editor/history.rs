@@ -0,0 +1,261 @@
+//! Editor-owned undo tree, one per tab. The textarea's value is rewritten
+//! from the source signals, which leaves the browser's own undo stack
+//! useless, so every edit is recorded here instead.
+//!
+//! Nodes are whole snapshots (shaders are a few KB). Undoing and then
+//! editing starts a new branch rather than dropping the old one; `g-`/`g+`
+//! walk every state in the order it was made, branches included.
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Step {
+    /// `u`, Ctrl-z: back to the parent.
+    Undo,
+    /// Ctrl-r, Ctrl-y: forward along the branch last undone.
+    Redo,
+    /// `g-`: the state made before this one.
+    Earlier,
+    /// `g+`: the state made after this one.
+    Later,
+}
+
+struct Node {
+    parent: Option<usize>,
+    /// Child redo goes to; the most recently made or visited one.
+    redo: Option<usize>,
+    text: String,
+    /// Where the edit that made this state starts; the cursor goes here
+    /// whether it's undone or redone.
+    at: usize,
+}
+
+pub struct History {
+    // in the order they were made, so `g-`/`g+` are index steps
+    nodes: Vec<Node>,
+    current: usize,
+    /// Whether the next edit extends the current node (typing in one insert
+    /// session) instead of making a new one.
+    open: bool,
+    /// End of the last edit, for telling continued typing from a jump.
+    last_end: usize,
+}
+
+impl History {
+    pub fn new(text: &str) -> Self {
+        Self {
+            nodes: vec![Node {
+                parent: None,
+                redo: None,
+                text: text.to_owned(),
+                at: 0,
+            }],
+            current: 0,
+            open: false,
+            last_end: 0,
+        }
+    }
+
+    /// Make sure we're at `text`. If not, the source was replaced from
+    /// outside (another demo, a reset) and the history starts over from it.
+    pub fn sync(&mut self, text: &str) {
+        if self.nodes[self.current].text != text {
+            *self = Self::new(text);
+        }
+    }
+
+    /// Record that the text went from `old` to `new`.
+    pub fn record(&mut self, old: &str, new: &str) {
+        if old == new {
+            return;
+        }
+        self.sync(old);
+
+        let (at, old_end, new_end) = changed(old, new);
+        // typing (or backspacing) on from where the last edit ended extends
+        // it; typing elsewhere is a new step, even within one insert session
+        let continues = at <= self.last_end && self.last_end <= old_end;
+        if self.open && continues && self.current != 0 {
+            self.nodes[self.current].text = new.to_owned();
+            self.nodes[self.current].at = self.nodes[self.current].at.min(at);
+        } else {
+            self.nodes.push(Node {
+                parent: Some(self.current),
+                redo: None,
+                text: new.to_owned(),
+                at,
+            });
+            let child = self.nodes.len() - 1;
+            self.nodes[self.current].redo = Some(child);
+            self.current = child;
+        }
+        self.last_end = new_end;
+    }
+
+    /// Let following edits join the current step (insert mode, typing).
+    pub fn open(&mut self) {
+        self.open = true;
+    }
+
+    /// End the current step; the next edit starts a new one.
+    pub fn close(&mut self) {
+        self.open = false;
+    }
+
+    /// Move `count` steps; returns the text to show and where to put the
+    /// cursor, or `None` when there's nowhere to go.
+    pub fn step(&mut self, step: Step, count: usize) -> Option<(String, usize)> {
+        self.close();
+        let start = self.current;
+        let mut at = None;
+
+        for _ in 0..count.max(1) {
+            let cur = self.current;
+            let next = match step {
+                Step::Undo => {
+                    let parent = self.nodes[cur].parent;
+                    if let Some(p) = parent {
+                        self.nodes[p].redo = Some(cur);
+                    }
+                    at = Some(self.nodes[cur].at);
+                    parent
+                }
+                Step::Redo => self.nodes[cur].redo,
+                Step::Earlier => cur.checked_sub(1),
+                Step::Later => (cur + 1 < self.nodes.len()).then_some(cur + 1),
+            };
+            let Some(next) = next else { break };
+            if step != Step::Undo {
+                at = Some(self.nodes[next].at);
+            }
+            self.current = next;
+        }
+
+        if self.current == start {
+            return None;
+        }
+        // redo from any ancestor should lead back here
+        let mut node = self.current;
+        while let Some(parent) = self.nodes[node].parent {
+            self.nodes[parent].redo = Some(node);
+            node = parent;
+        }
+
+        let text = self.nodes[self.current].text.clone();
+        let at = at.unwrap_or(0).min(text.len());
+        Some((text, at))
+    }
+}
+
+/// Where `old` and `new` differ, trimming the common prefix and suffix:
+/// `(start, end in old, end in new)`, in bytes.
+pub(crate) fn changed(old: &str, new: &str) -> (usize, usize, usize) {
+    let prefix = old
+        .char_indices()
+        .zip(new.chars())
+        .find(|&((_, a), b)| a != b)
+        .map_or(old.len().min(new.len()), |((i, _), _)| i);
+
+    let suffix = old[prefix..]
+        .chars()
+        .rev()
+        .zip(new[prefix..].chars().rev())
+        .take_while(|(a, b)| a == b)
+        .map(|(a, _)| a.len_utf8())
+        .sum::<usize>();
+
+    (prefix, old.len() - suffix, new.len() - suffix)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `edits` recorded one after another, from `start`.
+    fn history(start: &str, edits: &[&str]) -> History {
+        let mut history = History::new(start);
+        let mut old = start;
+        for new in edits {
+            history.record(old, new);
+            old = new;
+        }
+        history
+    }
+
+    fn text(step: Option<(String, usize)>) -> Option<String> {
+        step.map(|(text, _)| text)
+    }
+
+    #[test]
+    fn typing_on_in_one_session_is_one_step() {
+        let mut h = History::new("fn");
+        h.open();
+        h.record("fn", "fn a");
+        h.record("fn a", "fn ab");
+        // backspacing over what was just typed too
+        h.record("fn ab", "fn a");
+        h.record("fn a", "fn ac");
+        h.close();
+        h.record("fn ac", "fn ac;");
+
+        assert_eq!(text(h.step(Step::Undo, 1)).as_deref(), Some("fn ac"));
+        assert_eq!(h.step(Step::Undo, 1), Some(("fn".to_owned(), 2)));
+        assert_eq!(h.step(Step::Undo, 1), None);
+    }
+
+    #[test]
+    fn typing_elsewhere_starts_a_new_step() {
+        let mut h = History::new("a b");
+        h.open();
+        h.record("a b", "ax b");
+        h.record("ax b", "ax by");
+        assert_eq!(text(h.step(Step::Undo, 1)).as_deref(), Some("ax b"));
+        assert_eq!(text(h.step(Step::Undo, 1)).as_deref(), Some("a b"));
+    }
+
+    #[test]
+    fn closed_edits_are_steps_of_their_own() {
+        let mut h = history("", &["a", "ab", "abc"]);
+        assert_eq!(text(h.step(Step::Undo, 2)).as_deref(), Some("a"));
+        assert_eq!(h.step(Step::Redo, 1), Some(("ab".to_owned(), 1)));
+        // as far as it goes
+        assert_eq!(text(h.step(Step::Redo, 5)).as_deref(), Some("abc"));
+        assert_eq!(h.step(Step::Redo, 1), None);
+    }
+
+    #[test]
+    fn undone_branches_are_kept() {
+        let mut h = history("a", &["ab"]);
+        h.step(Step::Undo, 1);
+        h.record("a", "ac");
+
+        // redo follows the newer branch; g-/g+ visit both in the order made
+        assert_eq!(text(h.step(Step::Undo, 1)).as_deref(), Some("a"));
+        assert_eq!(text(h.step(Step::Redo, 1)).as_deref(), Some("ac"));
+        assert_eq!(text(h.step(Step::Earlier, 1)).as_deref(), Some("ab"));
+        assert_eq!(text(h.step(Step::Earlier, 1)).as_deref(), Some("a"));
+        assert_eq!(h.step(Step::Earlier, 1), None);
+        assert_eq!(text(h.step(Step::Later, 2)).as_deref(), Some("ac"));
+
+        // and redo goes back down the branch last visited
+        h.step(Step::Earlier, 1);
+        h.step(Step::Undo, 1);
+        assert_eq!(text(h.step(Step::Redo, 1)).as_deref(), Some("ab"));
+    }
+
+    #[test]
+    fn outside_changes_start_over() {
+        let mut h = history("a", &["ab"]);
+        h.sync("something else");
+        assert_eq!(h.step(Step::Undo, 1), None);
+        h.record("x", "xy");
+        assert_eq!(text(h.step(Step::Undo, 1)).as_deref(), Some("x"));
+    }
+
+    #[test]
+    fn changes_are_trimmed_to_whole_chars() {
+        assert_eq!(changed("abc", "abc"), (3, 3, 3));
+        assert_eq!(changed("abc", "aXc"), (1, 2, 2));
+        assert_eq!(changed("ab", "aab"), (1, 1, 2));
+        assert_eq!(changed("éa", "ëa"), (0, 2, 2));
+        assert_eq!(changed("aé", "a"), (1, 3, 1));
+    }
+}
@@ -0,0 +1,16 @@
+//! The state the Shader Lab hands between the editor and the demo canvas.
+
+use crate::render::renderer::gpu::textures::TextureSlots;
+use crate::render::renderer::graph::LiveShaders;
+use super::model::ModelSlot;
+
+/// Everything the Shader Lab edits that the canvas draws with; the editor
+/// and whichever demo is mounted share one.
+#[derive(Copy, Clone)]
+pub struct Lab {
+    pub shaders: LiveShaders,
+    /// Channels for the demos that sample textures.
+    pub textures: TextureSlots,
+    /// Swapped in for the demo's mesh, where it has one.
+    pub model: ModelSlot,
+}
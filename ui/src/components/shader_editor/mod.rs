@@ -1,4 +1,19 @@
 pub mod view;
+pub mod complete;
+pub mod diff;
+pub mod diff_view;
+pub mod entry_points;
+pub mod ex;
+pub mod format;
+pub mod history;
+pub mod lab;
+pub mod model;
+pub mod params;
+pub mod sharing;
+pub mod textures;
 pub mod utils;
+pub mod vim;
+pub mod wgsl;
 
+pub use lab::Lab;
 pub use view::ShaderEditor;
@@ -0,0 +1,277 @@
+//! Models the Shader Lab draws in place of a demo's own mesh: loading them
+//! into a [`ModelSlot`], and the UI tab's picker for them.
+
+use std::sync::Arc;
+
+use leptos::prelude::{
+    ClassAttribute, CollectView, ElementChild, Get, GlobalAttributes, OnAttribute, PropAttribute,
+    RwSignal, Update, With, WithUntracked, event_target_value,
+};
+use leptos::task::spawn_local;
+use leptos::{IntoView, component, view};
+use wasm_bindgen::JsCast;
+
+use crate::components::demo::Demo;
+use crate::meshes::import;
+use crate::render::renderer::gpu::textures::fetch;
+use crate::render::renderer::mesh::{CpuMesh, MeshSlot};
+
+/// Where the model in a [`ModelSlot`] came from.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ModelSource {
+    /// One of [`import::ASSETS`], by file name.
+    Asset(String),
+    /// A dropped or picked file, by name; gone after a reload.
+    File(String),
+}
+
+impl ModelSource {
+    pub fn label(&self) -> &str {
+        match self {
+            ModelSource::Asset(name) | ModelSource::File(name) => name,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum ModelState {
+    /// Drawing the demo's own mesh.
+    #[default]
+    Empty,
+    Loading,
+    Ready { vertices: usize, triangles: usize },
+    Failed(String),
+}
+
+#[derive(Clone, Default)]
+struct Model {
+    source: Option<ModelSource>,
+    state: ModelState,
+    /// Bumped per load, so a slow one can't land after a later one.
+    request: u64,
+}
+
+/// A model the Shader Lab swaps in for a demo's mesh, through the
+/// [`MeshSlot`] its pass draws from. Keeps the old one until a new one has
+/// loaded.
+#[derive(Copy, Clone)]
+pub struct ModelSlot {
+    model: RwSignal<Model>,
+    mesh: MeshSlot,
+}
+
+impl Default for ModelSlot {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ModelSlot {
+    pub fn new() -> Self {
+        Self {
+            model: RwSignal::new(Model::default()),
+            mesh: MeshSlot::new(),
+        }
+    }
+
+    /// What the demo's pass draws in place of its own mesh.
+    pub fn mesh(&self) -> MeshSlot {
+        self.mesh
+    }
+
+    pub fn source(&self) -> Option<ModelSource> {
+        self.model.with(|m| m.source.clone())
+    }
+
+    pub fn state(&self) -> ModelState {
+        self.model.with(|m| m.state.clone())
+    }
+
+    /// The compiled-in model loaded, if that's what the slot holds; what's
+    /// worth keeping or sharing.
+    pub fn asset(&self) -> Option<String> {
+        self.model.with(|m| match &m.source {
+            Some(ModelSource::Asset(name)) => Some(name.clone()),
+            _ => None,
+        })
+    }
+
+    /// Load `asset`, or go back to the demo's mesh without one; for what
+    /// [`asset`](Self::asset) kept.
+    pub fn set_asset(&self, asset: Option<&str>) {
+        match asset {
+            Some(name) => self.load_asset(name),
+            None => self.clear(),
+        }
+    }
+
+    /// Load one of [`import::ASSETS`].
+    pub fn load_asset(&self, name: &str) {
+        let request = self.begin(ModelSource::Asset(name.to_owned()));
+        let mesh = import::asset(name)
+            .ok_or_else(|| format!("no built-in model `{name}`"))
+            .and_then(|bytes| import::load(name, bytes));
+        self.finish(request, mesh);
+    }
+
+    /// Parse a file's contents, read through the object URL made for it
+    /// (revoked once read). The format goes by `name`'s extension.
+    pub fn load_file(&self, name: String, object_url: String) {
+        let request = self.begin(ModelSource::File(name.clone()));
+        let slot = *self;
+        spawn_local(async move {
+            let mesh = fetch(&object_url)
+                .await
+                .and_then(|bytes| import::load(&name, &bytes));
+            web_sys::Url::revoke_object_url(&object_url).ok();
+            slot.finish(request, mesh);
+        });
+    }
+
+    /// Parse `bytes` right away, as [`load_file`](Self::load_file) does.
+    pub fn load_bytes(&self, name: String, bytes: &[u8]) {
+        let request = self.begin(ModelSource::File(name.clone()));
+        self.finish(request, import::load(&name, bytes));
+    }
+
+    /// Back to the demo's own mesh.
+    pub fn clear(&self) {
+        self.model.update(|m| {
+            *m = Model {
+                request: m.request + 1,
+                ..Model::default()
+            };
+        });
+        self.mesh.set(None);
+    }
+
+    fn begin(&self, source: ModelSource) -> u64 {
+        let mut request = 0;
+        self.model.update(|m| {
+            m.source = Some(source);
+            m.state = ModelState::Loading;
+            m.request += 1;
+            request = m.request;
+        });
+        request
+    }
+
+    fn finish(&self, request: u64, mesh: Result<CpuMesh, String>) {
+        if self.model.with_untracked(|m| m.request != request) {
+            return;
+        }
+        match mesh {
+            Ok(mesh) => {
+                self.model.update(|m| {
+                    m.state = ModelState::Ready {
+                        vertices: mesh.vertices.len(),
+                        triangles: mesh.triangle_count(),
+                    }
+                });
+                self.mesh.set(Some(Arc::new(mesh)));
+            }
+            Err(e) => self.model.update(|m| m.state = ModelState::Failed(e)),
+        }
+    }
+}
+
+/// What the demo draws: its own mesh, a built-in model, or an OBJ or glTF
+/// file picked or dropped on the panel; see [`crate::meshes::import`].
+#[component]
+pub(super) fn ModelPanel(selected_demo: RwSignal<Demo>, model: ModelSlot) -> impl IntoView {
+    let status = move || {
+        let from = model.source().map(|s| s.label().to_owned()).unwrap_or_default();
+        match model.state() {
+            ModelState::Empty => "the demo's own mesh".to_owned(),
+            ModelState::Loading => format!("loading {from}…"),
+            ModelState::Ready { vertices, triangles } => {
+                format!("{from} ({vertices} vertices, {triangles} triangles)")
+            }
+            ModelState::Failed(e) => format!("couldn't load {from}: {e}"),
+        }
+    };
+    let picked = move || match model.source() {
+        Some(ModelSource::Asset(name)) => name,
+        _ => String::new(),
+    };
+    let assets = move || {
+        import::ASSETS
+            .iter()
+            .map(|&(name, _)| {
+                let selected = move || picked() == name;
+                view! { <option value=name prop:selected=selected>{ name }</option> }
+            })
+            .collect_view()
+    };
+    let on_asset = move |ev| match event_target_value(&ev).as_str() {
+        "" => model.clear(),
+        name => model.load_asset(name),
+    };
+
+    let load_file = move |file: web_sys::File| {
+        if let Ok(url) = web_sys::Url::create_object_url_with_blob(&file) {
+            model.load_file(file.name(), url);
+        }
+    };
+    let on_pick = move |ev: web_sys::Event| {
+        let file = ev
+            .target()
+            .and_then(|t| t.dyn_into::<web_sys::HtmlInputElement>().ok())
+            .and_then(|input| input.files())
+            .and_then(|files| files.get(0));
+        if let Some(file) = file {
+            load_file(file);
+        }
+    };
+    let on_drop = move |ev: web_sys::DragEvent| {
+        ev.prevent_default();
+        let file = ev.data_transfer().and_then(|dt| dt.files()).and_then(|f| f.get(0));
+        if let Some(file) = file {
+            load_file(file);
+        }
+    };
+    let accept: Vec<_> = import::EXTENSIONS.iter().map(|ext| format!(".{ext}")).collect();
+    let accept = accept.join(",");
+
+    move || {
+        let accept = accept.clone();
+        selected_demo.get().models().then(move || {
+            view! {
+                <div
+                    class="flex flex-col gap-1 p-2 border border-dashed rounded"
+                    on:dragover=|ev: web_sys::DragEvent| ev.prevent_default()
+                    on:drop=on_drop
+                >
+                    <div class="flex items-center gap-2">
+                        <span class="text-text-muted text-sm">"Model"</span>
+                        <button
+                            class="ml-auto px-2 py-0.5 border rounded text-text hover:text-accent"
+                            on:click=move |_| model.clear()
+                        >
+                            "Clear"
+                        </button>
+                    </div>
+                    <span class="text-text-muted text-xs truncate" title=status>{ status }</span>
+                    <div class="flex items-center gap-2 text-xs">
+                        <select
+                            class="flex-1 px-2 py-1 text-text bg-surface border rounded"
+                            title="Built-in models; or drop an OBJ or glTF file here"
+                            on:change=on_asset
+                        >
+                            <option value="" prop:selected=move || picked().is_empty()>
+                                "demo mesh"
+                            </option>
+                            { assets() }
+                        </select>
+                        <label class=
+                            "px-2 py-1 border rounded text-text hover:text-accent cursor-pointer"
+                        >
+                            "File…"
+                            <input type="file" accept=accept class="hidden" on:change=on_pick />
+                        </label>
+                    </div>
+                </div>
+            }
+        })
+    }
+}
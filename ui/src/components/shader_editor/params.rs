@@ -0,0 +1,153 @@
+//! The UI tab's controls for the uniforms the sources declare, as
+//! [`ShaderParams`] reflects them.
+
+use leptos::prelude::{
+    AnyView, ClassAttribute, CollectView, ElementChild, IntoAny, OnAttribute, PropAttribute,
+    StoredValue, WithValue, event_target_checked, event_target_value,
+};
+use leptos::{IntoView, component, view};
+
+use crate::render::renderer::gpu::params::{
+    Control, PARAMS_BINDING, PARAMS_GROUP, ParamField, Scalar, ShaderParams,
+};
+
+/// A control per field of the uniforms the shaders declare; see
+/// [`crate::render::renderer::gpu::params`] for the annotations.
+#[component]
+pub(super) fn ParamsPanel(params: ShaderParams) -> impl IntoView {
+    move || {
+        let layout = params.layout();
+        if layout.is_empty() {
+            return ().into_any();
+        }
+        let unsupported = (!layout.unsupported.is_empty()).then(|| {
+            view! {
+                <p class="text-text-muted text-sm">
+                    "No control for " { layout.unsupported.join(", ") } "; left at zero."
+                </p>
+            }
+        });
+
+        view! {
+            <div class="flex flex-col gap-3">
+                <p class="text-text-muted text-sm">
+                    { format!("@group({PARAMS_GROUP}) @binding({PARAMS_BINDING})") }
+                </p>
+                {
+                    layout.fields
+                        .into_iter()
+                        .map(|field| view! { <ParamControl field params /> })
+                        .collect_view()
+                }
+                { unsupported }
+            </div>
+        }
+        .into_any()
+    }
+}
+
+#[component]
+fn ParamControl(field: ParamField, params: ShaderParams) -> impl IntoView {
+    let name = StoredValue::new(field.name.clone());
+    let value = move || name.with_value(|n| params.get(n));
+
+    let input = match field.control {
+        Control::Slider { min, max, step } => {
+            let decimals = match field.scalar {
+                Scalar::F32 if step < 1.0 => (-step.log10()).ceil().min(4.0) as usize,
+                _ => 0,
+            };
+            let axis = |i| (field.components > 1).then(|| ["x", "y", "z", "w"][i]);
+            (0..field.components)
+                .map(|i| param_slider(name, params, i, axis(i), (min, max, step), decimals))
+                .collect_view()
+                .into_any()
+        }
+        Control::Color => {
+            let on_input = move |ev| {
+                let Some(rgb) = from_hex(&event_target_value(&ev)) else { return };
+                name.with_value(|n| params.update(n, |v| v[..3].copy_from_slice(&rgb)));
+            };
+            let alpha = (field.components == 4)
+                .then(|| param_slider(name, params, 3, Some("a"), (0.0, 1.0, 0.01), 2));
+            view! {
+                <input
+                    type="color"
+                    class="w-16 h-8 bg-transparent"
+                    prop:value=move || to_hex(value())
+                    on:input=on_input
+                />
+                { alpha }
+            }
+            .into_any()
+        }
+        Control::Toggle => view! {
+            <input
+                type="checkbox"
+                class="self-start"
+                prop:checked=move || value()[0] != 0.0
+                on:change=move |ev| {
+                    let on = if event_target_checked(&ev) { 1.0 } else { 0.0 };
+                    name.with_value(|n| params.update(n, |v| v[0] = on));
+                }
+            />
+        }
+        .into_any(),
+    };
+
+    view! {
+        <div class="flex flex-col gap-1">
+            <span class="text-text">{ field.name }</span>
+            { input }
+        </div>
+    }
+}
+
+/// One component of a field; `(min, max, step)`.
+fn param_slider(
+    name: StoredValue<String>,
+    params: ShaderParams,
+    i: usize,
+    axis: Option<&'static str>,
+    (min, max, step): (f32, f32, f32),
+    decimals: usize,
+) -> AnyView {
+    let value = move || name.with_value(|n| params.get(n))[i];
+    let on_input = move |ev| {
+        let Ok(v) = event_target_value(&ev).parse::<f32>() else { return };
+        name.with_value(|n| params.update(n, |old| old[i] = v));
+    };
+
+    view! {
+        <div class="flex items-center gap-2">
+            { axis.map(|a| view! { <span class="text-text-muted w-3">{ a }</span> }) }
+            <input
+                type="range"
+                class="flex-1"
+                min=min.to_string()
+                max=max.to_string()
+                step=step.to_string()
+                prop:value=move || value().to_string()
+                on:input=on_input
+            />
+            <span class="text-text w-16 text-right tabular-nums">
+                { move || format!("{:.*}", decimals, value()) }
+            </span>
+        </div>
+    }
+    .into_any()
+}
+
+fn to_hex(v: [f32; 4]) -> String {
+    let byte = |c: f32| (c.clamp(0.0, 1.0) * 255.0).round() as u8;
+    format!("#{:02x}{:02x}{:02x}", byte(v[0]), byte(v[1]), byte(v[2]))
+}
+
+fn from_hex(hex: &str) -> Option<[f32; 3]> {
+    let hex = hex.strip_prefix('#')?;
+    let channel = |i: usize| {
+        let c = u8::from_str_radix(hex.get(i..i + 2)?, 16).ok()?;
+        Some(c as f32 / 255.0)
+    };
+    Some([channel(0)?, channel(2)?, channel(4)?])
+}
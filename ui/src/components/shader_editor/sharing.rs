@@ -0,0 +1,109 @@
+//! The buttons that hand the lab on: a link that carries it whole, or a
+//! short one to the sources saved on the server.
+
+use gloo_timers::callback::Timeout;
+use leptos::prelude::{
+    ClassAttribute, ElementChild, Get, GetUntracked, GlobalAttributes, OnAttribute, RwSignal, Set,
+};
+use leptos::task::spawn_local;
+use leptos::{IntoView, component, view};
+
+use crate::components::demo::Demo;
+use crate::components::share::{self, Shared};
+use crate::components::snippets;
+use crate::pages::classic::classic::PassFlags;
+use crate::render::renderer::graph::LiveShaders;
+
+use super::lab::Lab;
+
+/// The lab as it is now, as both buttons hand it on.
+fn shared(demo: Demo, lab: Lab, pass_flags: &PassFlags) -> Shared {
+    let Lab { shaders, textures, model } = lab;
+    let LiveShaders { vs: vs_src, fs: fs_src, files, setup, .. } = shaders;
+    Shared {
+        demo,
+        vs: vs_src.get_untracked(),
+        fs: fs_src.get_untracked(),
+        passes: pass_flags.states(demo),
+        files: files.edits(),
+        module: setup.single.get_untracked().then(|| setup.module.get_untracked()),
+        entry_points: setup.picks(),
+        textures: Some(textures.urls()).filter(|urls| *urls != demo.textures()),
+        samplers: textures.samplers(),
+        model: model.asset(),
+    }
+}
+
+/// Copies a link to the lab as it is now (see `share`).
+#[component]
+pub(super) fn ShareButton(
+    selected_demo: RwSignal<Demo>,
+    lab: Lab,
+    pass_flags: PassFlags,
+) -> impl IntoView {
+    let copied = RwSignal::new(false);
+
+    let on_click = move |_| {
+        let shared = shared(selected_demo.get_untracked(), lab, &pass_flags);
+        let Some(link) = share::link(&shared) else { return };
+        spawn_local(async move {
+            if share::copy(&link).await {
+                copied.set(true);
+                Timeout::new(2000, move || copied.set(false)).forget();
+            }
+        });
+    };
+
+    view! {
+        <button
+            class="px-3 py-1 text-text border rounded hover:text-accent"
+            title="Copy a link to these shaders"
+            on:click=on_click
+        >
+            { move || if copied.get() { "Copied!" } else { "Share" } }
+        </button>
+    }
+}
+
+/// Saves what a share link would carry on the server and copies the short
+/// link it hands back (see `snippets`).
+#[component]
+pub(super) fn SaveButton(
+    selected_demo: RwSignal<Demo>,
+    lab: Lab,
+    pass_flags: PassFlags,
+) -> impl IntoView {
+    let status = RwSignal::new(None::<Result<(), String>>);
+
+    let on_click = move |_| {
+        let shared = shared(selected_demo.get_untracked(), lab, &pass_flags);
+        spawn_local(async move {
+            match snippets::save(&shared).await {
+                Ok(link) => {
+                    share::copy(&link).await;
+                    status.set(Some(Ok(())));
+                    Timeout::new(2000, move || status.set(None)).forget();
+                }
+                // kept until the next try, so the reason can be read
+                Err(e) => status.set(Some(Err(e))),
+            }
+        });
+    };
+
+    view! {
+        <button
+            class="px-3 py-1 text-text border rounded hover:text-accent"
+            title=move || match status.get() {
+                Some(Err(e)) => format!("Couldn't save: {e}"),
+                _ => "Save these shaders and copy a short link".to_owned(),
+            }
+            on:click=on_click
+        >
+            { move || match status.get() {
+                None => "Save",
+                Some(Ok(())) => "Saved!",
+                Some(Err(_)) => "Not saved",
+            }}
+        </button>
+    }
+}
@@ -0,0 +1,154 @@
+//! The UI tab's texture channels, for the demos that sample them: where
+//! each image comes from, how far it got, and the sampler it's read with.
+
+use leptos::prelude::{
+    ClassAttribute, CollectView, ElementChild, Get, GlobalAttributes, OnAttribute, PropAttribute,
+    RwSignal, event_target_value,
+};
+use leptos::{IntoView, component, view};
+use wasm_bindgen::JsCast;
+
+use crate::components::demo::Demo;
+use crate::render::renderer::gpu::samplers::{Filter, SamplerDesc, Wrap};
+use crate::render::renderer::gpu::textures::{
+    CHANNELS, SlotState, Source, TextureSlots, channel_bindings, channel_name,
+};
+
+/// What each texture channel holds: an image from a URL, or a file picked
+/// or dropped on its row; see [`crate::render::renderer::gpu::textures`].
+#[component]
+pub(super) fn TexturesPanel(selected_demo: RwSignal<Demo>, textures: TextureSlots) -> impl IntoView {
+    let channel = move |c: usize| {
+        let (texture_at, sampler_at) = channel_bindings(c);
+        let status = move || {
+            let slot = textures.slot(c);
+            let from = slot.source.as_ref().map(|s| s.label().to_owned()).unwrap_or_default();
+            match slot.state {
+                SlotState::Empty => "empty, samples black".to_owned(),
+                SlotState::Loading => format!("loading {from}…"),
+                SlotState::Ready { width, height } => format!("{from} ({width}×{height})"),
+                SlotState::Failed(e) => format!("couldn't load {from}: {e}"),
+            }
+        };
+        let url = move || match textures.slot(c).source {
+            Some(Source::Url(url)) => url,
+            _ => String::new(),
+        };
+        let sampler = move || textures.slot(c).sampler;
+        let filters = Filter::ALL.map(|f| {
+            let selected = move || sampler().filter == f;
+            view! { <option value=f.label() prop:selected=selected>{ f.label() }</option> }
+        });
+        let wraps = Wrap::ALL.map(|w| {
+            let selected = move || sampler().wrap == w;
+            view! { <option value=w.label() prop:selected=selected>{ w.label() }</option> }
+        });
+        let on_filter = move |ev| {
+            let picked = event_target_value(&ev);
+            if let Some(filter) = Filter::ALL.into_iter().find(|f| f.label() == picked) {
+                textures.set_sampler(c, SamplerDesc { filter, ..sampler() });
+            }
+        };
+        let on_wrap = move |ev| {
+            let picked = event_target_value(&ev);
+            if let Some(wrap) = Wrap::ALL.into_iter().find(|w| w.label() == picked) {
+                textures.set_sampler(c, SamplerDesc { wrap, ..sampler() });
+            }
+        };
+
+        let load_file = move |file: web_sys::File| {
+            if let Ok(url) = web_sys::Url::create_object_url_with_blob(&file) {
+                textures.load_file(c, file.name(), url);
+            }
+        };
+        let on_url = move |ev| {
+            let url = event_target_value(&ev);
+            match url.trim() {
+                "" => textures.clear(c),
+                url => textures.load_url(c, url.to_owned()),
+            }
+        };
+        let on_pick = move |ev: web_sys::Event| {
+            let file = ev
+                .target()
+                .and_then(|t| t.dyn_into::<web_sys::HtmlInputElement>().ok())
+                .and_then(|input| input.files())
+                .and_then(|files| files.get(0));
+            if let Some(file) = file {
+                load_file(file);
+            }
+        };
+        let on_drop = move |ev: web_sys::DragEvent| {
+            ev.prevent_default();
+            let file = ev.data_transfer().and_then(|dt| dt.files()).and_then(|f| f.get(0));
+            if let Some(file) = file {
+                load_file(file);
+            }
+        };
+
+        view! {
+            <div
+                class="flex flex-col gap-1 p-2 border border-dashed rounded"
+                on:dragover=|ev: web_sys::DragEvent| ev.prevent_default()
+                on:drop=on_drop
+            >
+                <div class="flex items-center gap-2">
+                    <span class="text-text">{ channel_name(c) }</span>
+                    <span class="text-text-muted text-xs">
+                        { format!("@binding({texture_at}), sampler @binding({sampler_at})") }
+                    </span>
+                    <button
+                        class="ml-auto px-2 py-0.5 border rounded text-text hover:text-accent"
+                        on:click=move |_| textures.clear(c)
+                    >
+                        "Clear"
+                    </button>
+                </div>
+                <span class="text-text-muted text-xs truncate" title=status>{ status }</span>
+                <div class="flex items-center gap-2">
+                    <input
+                        type="url"
+                        class="flex-1 px-2 py-1 text-text bg-surface border rounded text-xs"
+                        placeholder="Image URL, or drop a file here"
+                        prop:value=url
+                        on:change=on_url
+                    />
+                    <label class=
+                        "px-2 py-1 border rounded text-text hover:text-accent cursor-pointer"
+                    >
+                        "File…"
+                        <input type="file" accept="image/*" class="hidden" on:change=on_pick />
+                    </label>
+                </div>
+                <div class="flex items-center gap-2 text-xs">
+                    <span class="text-text-muted">"sampler"</span>
+                    <select
+                        class="px-2 py-1 text-text bg-surface border rounded"
+                        title="Filtering, between texels and mip levels"
+                        on:change=on_filter
+                    >
+                        { filters }
+                    </select>
+                    <select
+                        class="px-2 py-1 text-text bg-surface border rounded"
+                        title="What's sampled outside 0-1"
+                        on:change=on_wrap
+                    >
+                        { wraps }
+                    </select>
+                </div>
+            </div>
+        }
+    };
+
+    move || {
+        selected_demo.get().textured().then(|| {
+            view! {
+                <div class="flex flex-col gap-3">
+                    <p class="text-text-muted text-sm">"Textures (group 2)"</p>
+                    { (0..CHANNELS).map(channel).collect_view() }
+                </div>
+            }
+        })
+    }
+}
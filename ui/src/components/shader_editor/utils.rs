@@ -1,133 +1,418 @@
 use leptos::html::Textarea;
 use leptos::prelude::NodeRef;
 use leptos::prelude::Get;
+use leptos::prelude::GetUntracked;
+use leptos::prelude::Memo;
 use leptos::prelude::Set;
 use leptos::prelude::RwSignal;
+use leptos::prelude::StoredValue;
+use leptos::prelude::Update;
+use leptos::prelude::UpdateValue;
+use leptos::prelude::WithUntracked;
+use leptos::prelude::WithValue;
+use web_sys::HtmlTextAreaElement;
 
-use super::view::Mode;
+use crate::render::renderer::gpu::diagnostics::Stage;
 
+use super::complete::{Menu, Scope, menu};
+use super::format::assist;
+use super::history::{History, Step};
+use super::vim::{Mode, Outcome, Vim, clamp_normal};
+
+/// Line under the editor: a command's result, until the next key.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) enum Message {
+    Info(String),
+    Error(String),
+}
+
+/// What the vim glue keeps between keys. Only handles, so it's `Copy`.
+#[derive(Copy, Clone)]
+pub(crate) struct VimState {
+    /// Outlives the key handlers, which are rebuilt with the textarea.
+    pub engine: StoredValue<Vim>,
+    pub mode: RwSignal<Mode>,
+    /// Keys of an unfinished command (vim's `showcmd`).
+    pub showcmd: RwSignal<String>,
+    /// What's typed after `:`, while the command line is open.
+    pub cmdline: RwSignal<Option<String>>,
+    pub message: RwSignal<Option<Message>>,
+}
+
+impl VimState {
+    pub fn new() -> Self {
+        Self {
+            engine: StoredValue::new(Vim::new()),
+            mode: RwSignal::new(Mode::Normal),
+            showcmd: RwSignal::new(String::new()),
+            cmdline: RwSignal::new(None),
+            message: RwSignal::new(None),
+        }
+    }
+}
+
+/// Suggestions at the cursor; they open while typing, or on Ctrl-Space.
+#[derive(Copy, Clone)]
+pub(crate) struct Completer {
+    pub menu: RwSignal<Option<Menu>>,
+    pub scope: Memo<Scope>,
+    /// The modules the source being edited includes, one after the other.
+    pub included: Memo<String>,
+}
+
+impl Completer {
+    pub fn new(scope: Memo<Scope>, included: Memo<String>) -> Self {
+        Self { menu: RwSignal::new(None), scope, included }
+    }
+
+    /// Open, update or close the list for where the cursor is now.
+    pub fn refresh(&self, t: &HtmlTextAreaElement, forced: bool) {
+        let scope = self.scope.get_untracked();
+        let found = self
+            .included
+            .with_untracked(|included| menu(&t.value(), cursor_of(t), scope, included, forced));
+        if found.is_some() || self.menu.with_untracked(Option::is_some) {
+            self.menu.set(found);
+        }
+    }
+
+    pub fn close(&self) {
+        if self.menu.with_untracked(Option::is_some) {
+            self.menu.set(None);
+        }
+    }
+}
+
+/// Put the selected suggestion in, as if it was typed.
+pub(crate) fn pick_completion(
+    t: &HtmlTextAreaElement,
+    completer: Completer,
+    history: StoredValue<History>,
+    vim_enabled: bool,
+    set_text: &impl Fn(String),
+) {
+    let Some(menu) = completer.menu.get_untracked() else { return };
+    completer.menu.set(None);
+    let old = t.value();
+    // the list is refreshed on every edit, but don't trust it blindly
+    if old.get(menu.word.clone()).is_none() {
+        return;
+    }
+
+    let (text, at) = menu.pick(&old);
+    t.set_value(&text);
+    set_text(text.clone());
+    history.update_value(|h| record_input(h, &old, &text, vim_enabled));
+    place_cursor(t, &text, at, Mode::Insert);
+}
+
+/// Runs keys through the vim engine and writes the result back to the
+/// textarea. `set_text` gets the new source after an edit (the textarea is
+/// updated first, so the selection can be placed right away); `on_command`
+/// runs a `:` line and returns what to show for it. Undo and redo go
+/// through the active tab's `history` whether vim is on or not, and the
+/// `completer`'s list takes the keys that move through it while it's open.
 pub(crate) fn keydown(
     vim_enabled: bool,
-    mode: RwSignal<Mode>,
+    vim: VimState,
+    completer: Completer,
+    history: impl Fn() -> StoredValue<History> + 'static,
     textarea_ref: NodeRef<Textarea>,
+    set_text: impl Fn(String) + 'static,
+    on_command: impl Fn(String) -> Result<Option<String>, String> + 'static,
 ) -> impl Fn(web_sys::KeyboardEvent) + 'static {
     move |ev: web_sys::KeyboardEvent| {
-        if !vim_enabled {
+        let Some(t) = textarea_ref.get() else { return };
+        let history = history();
+
+        if vim_enabled {
+            if vim.message.get_untracked().is_some() {
+                vim.message.set(None);
+            }
+            if let Some(line) = vim.cmdline.get_untracked() {
+                ev.prevent_default();
+                command_line_key(&ev.key(), line, vim, &on_command);
+                return;
+            }
+        }
+
+        let ctrl = ev.ctrl_key() || ev.meta_key();
+        let undo = match ev.key().as_str() {
+            "z" if ctrl && !ev.shift_key() => Some(Step::Undo),
+            "z" | "Z" | "y" if ctrl => Some(Step::Redo),
+            _ => None,
+        };
+        if let Some(step) = undo {
+            ev.prevent_default();
+            let shown = if vim_enabled { vim.mode.get_untracked() } else { Mode::Insert };
+            completer.close();
+            undo_step(&t, &history, step, 1, shown, &set_text);
             return;
         }
 
-        match mode.get() {
-            Mode::Insert => {
-                if ev.key() == "Escape" || ev.key() == "Esc" {
-                    mode.set(Mode::Normal);
-                    update_block_cursor(&textarea_ref, Mode::Normal);
+        let typing = !vim_enabled || vim.mode.get_untracked() == Mode::Insert;
+        if !typing {
+            completer.close();
+        } else if completer.menu.with_untracked(Option::is_some) {
+            let step = match (ev.key().as_str(), ev.ctrl_key()) {
+                ("ArrowDown", _) | ("n", true) => Some(1),
+                ("ArrowUp", _) | ("p", true) => Some(-1),
+                _ => None,
+            };
+            if let Some(by) = step {
+                ev.prevent_default();
+                completer.menu.update(|m| m.iter_mut().for_each(|m| m.step(by)));
+                return;
+            }
+            match ev.key().as_str() {
+                "Enter" | "Tab" => {
                     ev.prevent_default();
+                    pick_completion(&t, completer, history, vim_enabled, &set_text);
+                    return;
+                }
+                // typing goes on as usual, `on_input` refreshes the list
+                "Escape" | "Esc" | "ArrowLeft" | "ArrowRight" | "Home" | "End" => {
+                    completer.close()
                 }
+                _ => {}
             }
-            Mode::Normal => {
-                match ev.key().as_str() {
-                    "i" => {
-                        mode.set(Mode::Insert);
-                        update_block_cursor(&textarea_ref, Mode::Insert);
-                        ev.prevent_default();
-                    }
-                    "h" | "j" | "k" | "l" | "w" | "b" | "0" | "$" => {
-                        vim_motion(&textarea_ref, &ev.key());
-                        update_block_cursor(&textarea_ref, Mode::Normal);
-                        ev.prevent_default();
-                    }
-                    _ => ev.prevent_default(),
+        }
+        if typing && ev.ctrl_key() && ev.key() == " " {
+            ev.prevent_default();
+            completer.refresh(&t, true);
+            return;
+        }
+
+        // brackets and indentation while typing
+        if typing && !ctrl && !ev.alt_key() {
+            let old = t.value();
+            let (start, end) = selection_of(&t);
+            if let Some((text, at)) = assist(&ev.key(), &old, start, end) {
+                ev.prevent_default();
+                completer.close();
+                if text != old {
+                    t.set_value(&text);
+                    set_text(text.clone());
+                    history.update_value(|h| record_input(h, &old, &text, vim_enabled));
                 }
+                place_cursor(&t, &text, at, Mode::Insert);
+                return;
             }
         }
-    }
-}
 
-pub(crate) fn update_block_cursor(textarea: &NodeRef<Textarea>, mode: Mode) {
-    let Some(t) = textarea.get() else { return };
+        if !vim_enabled {
+            return;
+        }
+        let key = match ev.key().as_str() {
+            "r" if ev.ctrl_key() && vim.mode.get_untracked() != Mode::Insert => "<C-r>".to_owned(),
+            // browser shortcuts (copy, paste, switching tabs) stay the browser's
+            _ if ctrl || ev.alt_key() => return,
+            "Esc" => "Escape".to_owned(),
+            k => k.to_owned(),
+        };
 
-    let pos = t.selection_start().unwrap().unwrap();
+        let old = t.value();
+        let mut text = old.clone();
+        let mut cursor = vim
+            .engine
+            .with_value(|v| v.visual_cursor(&text))
+            .unwrap_or_else(|| cursor_of(&t));
 
-    match mode {
-        Mode::Insert => {
-            // collapse selection
-            t.set_selection_end(Some(pos)).ok();
+        let Some((outcome, new_mode, pending)) = vim.engine.try_update_value(|v| {
+            let outcome = v.key(&key, &mut text, &mut cursor);
+            (outcome, v.mode, v.pending())
+        }) else {
+            return;
+        };
+        match outcome {
+            Outcome::PassThrough => return,
+            Outcome::Handled => ev.prevent_default(),
+            Outcome::History(step, count) => {
+                ev.prevent_default();
+                undo_step(&t, &history, step, count, new_mode, &set_text);
+                return;
+            }
+            Outcome::CommandLine(prefill) => {
+                ev.prevent_default();
+                vim.cmdline.set(Some(prefill));
+            }
         }
-        Mode::Normal => {
-            // select the character under (after) the cursor
-            t.set_selection_end(Some(pos + 1)).ok();
+
+        let edited = text != old;
+        if edited {
+            t.set_value(&text);
+            set_text(text.clone());
         }
+        history.update_value(|h| {
+            h.record(&old, &text);
+            // `cw`, `o`, ...: what gets typed next is part of the same change
+            if edited && new_mode == Mode::Insert {
+                h.open();
+            } else {
+                h.close();
+            }
+        });
+
+        if vim.mode.get_untracked() != new_mode {
+            vim.mode.set(new_mode);
+        }
+        if vim.showcmd.get_untracked() != pending {
+            vim.showcmd.set(pending);
+        }
+        match vim.engine.with_value(|v| v.selection(&text, cursor)) {
+            Some((start, end)) => select(&t, &text, start, end),
+            None => place_cursor(&t, &text, cursor, new_mode),
+        }
+    }
+}
+
+/// Editing the `:` line; `Enter` runs it.
+fn command_line_key(
+    key: &str,
+    mut line: String,
+    vim: VimState,
+    on_command: &impl Fn(String) -> Result<Option<String>, String>,
+) {
+    match key {
+        "Escape" | "Esc" => vim.cmdline.set(None),
+        // like vim, backspacing past the `:` closes the line
+        "Backspace" => vim.cmdline.set(line.pop().map(|_| line)),
+        "Enter" => {
+            vim.cmdline.set(None);
+            vim.message.set(match on_command(line) {
+                Ok(info) => info.map(Message::Info),
+                Err(e) => Some(Message::Error(e)),
+            });
+        }
+        k if k.chars().count() == 1 => {
+            line.push_str(k);
+            vim.cmdline.set(Some(line));
+        }
+        _ => {}
     }
 }
 
-fn vim_motion(textarea: &NodeRef<Textarea>, key: &str) {
-    let Some(t) = textarea.get() else {
+/// Text typed straight into the textarea (insert mode, or vim off). Joins
+/// the step being typed; without vim a new line starts the next one.
+pub(crate) fn record_input(history: &mut History, old: &str, new: &str, vim_enabled: bool) {
+    history.record(old, new);
+    if !vim_enabled && old.matches('\n').count() != new.matches('\n').count() {
+        history.close();
+    } else {
+        history.open();
+    }
+}
+
+/// Swap in a whole new text from outside the engine (`:s`, `:e default`),
+/// as one undo step.
+pub(crate) fn replace_text(
+    t: &HtmlTextAreaElement,
+    history: StoredValue<History>,
+    text: String,
+    cursor: usize,
+    mode: Mode,
+    set_text: &impl Fn(String),
+) {
+    let old = t.value();
+    history.update_value(|h| {
+        h.record(&old, &text);
+        h.close();
+    });
+    show_text(t, text, cursor, mode, set_text);
+}
+
+fn undo_step(
+    t: &HtmlTextAreaElement,
+    history: &StoredValue<History>,
+    step: Step,
+    count: usize,
+    mode: Mode,
+    set_text: &impl Fn(String),
+) {
+    let current = t.value();
+    let Some(Some((text, at))) = history.try_update_value(|h| {
+        h.sync(&current);
+        h.step(step, count)
+    }) else {
         return;
     };
+    show_text(t, text, at, mode, set_text);
+}
 
-    let start = t.selection_start().unwrap().unwrap();
+fn show_text(
+    t: &HtmlTextAreaElement,
+    text: String,
+    cursor: usize,
+    mode: Mode,
+    set_text: &impl Fn(String),
+) {
+    t.set_value(&text);
+    move_cursor(t, &text, cursor, mode);
+    set_text(text);
+}
 
-    // TODO: add basic selections and line edits and shit
-    let _end = t.selection_end().unwrap().unwrap();
+/// Re-show the cursor after the browser moved it (click, focus).
+pub(crate) fn update_block_cursor(textarea: &NodeRef<Textarea>, mode: Mode) {
+    let Some(t) = textarea.get() else { return };
 
-    let mut pos = start; // treat both ends the same
+    let text = t.value();
+    place_cursor(&t, &text, cursor_of(&t), mode);
+}
 
-    let value = t.value();
-    let len = value.len() as u32;
+/// The cursor as a byte offset into the textarea's value.
+pub(crate) fn cursor_of(t: &HtmlTextAreaElement) -> usize {
+    to_byte(&t.value(), t.selection_start().ok().flatten().unwrap_or(0))
+}
 
-    match key {
-        "h" => { if pos > 0 { pos -= 1; } }
-        "l" => { if pos < len { pos += 1; } }
-        "0" => pos = 0, "$" => pos = len,
-        "w" => { // next word
-            if let Some(off) = value[pos as usize..].find(|c: char| c.is_alphanumeric()) {
-                pos += off as u32;
-                if let Some(off2) = value[pos as usize..].find(|c: char| !c.is_alphanumeric()) {
-                    pos += off2 as u32;
-                } else {
-                    pos = len;
-                }
-            }
-        }
-        "b" => { // previous word
-            if pos > 0 {
-                let rev = value[..pos as usize].chars().rev();
-                let mut seen_word = false;
-                for (i, c) in rev.enumerate() {
-                    if c.is_alphanumeric() {
-                        seen_word = true;
-                    } else if seen_word {
-                        pos -= i as u32;
-                        break;
-                    }
-                }
-            }
-        }
-        "j" | "k" => {
-            // line-wise: need split on '\n'
-            let lines: Vec<&str> = value.split('\n').collect();
-            let mut cur = 0u32;
-            let mut row = 0usize;
-            for (i, line) in lines.iter().enumerate() {
-                if cur + line.len() as u32 + 1 > pos {
-                    row = i;
-                    break;
-                }
-                cur += line.len() as u32 + 1; // +1 for '\n'
-            }
-            let col = pos - cur;
-            if key == "j" && row + 1 < lines.len() {
-                pos = cur + lines[row].len() as u32 + 1 + col.min(lines[row + 1].len() as u32);
-            }
-            if key == "k" && row > 0 {
-                let prev_len = lines[row - 1].len() as u32;
-                let prev_start = cur - (prev_len + 1);
-                pos = prev_start + col.min(prev_len);
-            }
+/// Selected bytes; empty at the cursor.
+fn selection_of(t: &HtmlTextAreaElement) -> (usize, usize) {
+    let text = t.value();
+    let start = t.selection_start().ok().flatten().unwrap_or(0);
+    let end = t.selection_end().ok().flatten().unwrap_or(start);
+    (to_byte(&text, start), to_byte(&text, end))
+}
+
+/// Put the cursor at byte `at`, kept on a character outside insert mode.
+pub(crate) fn move_cursor(t: &HtmlTextAreaElement, text: &str, at: usize, mode: Mode) {
+    let at = if mode == Mode::Insert { at } else { clamp_normal(text, at) };
+    place_cursor(t, text, at, mode);
+}
+
+/// Insert mode gets a caret; the others select the character under the
+/// cursor, which is what the block cursor is drawn from.
+fn place_cursor(t: &HtmlTextAreaElement, text: &str, pos: usize, mode: Mode) {
+    let end = match mode {
+        Mode::Insert => pos,
+        _ => pos + text[pos..].chars().next().map_or(0, char::len_utf8),
+    };
+    select(t, text, pos, end);
+}
+
+/// Select bytes `start..end`.
+fn select(t: &HtmlTextAreaElement, text: &str, start: usize, end: usize) {
+    t.set_selection_range(to_utf16(text, start), to_utf16(text, end)).ok();
+}
+
+/// Textarea offsets count UTF-16 code units; the engine works in bytes.
+fn to_byte(text: &str, utf16: u32) -> usize {
+    let mut units = 0;
+    for (i, c) in text.char_indices() {
+        if units >= utf16 {
+            return i;
         }
-        _ => {}
+        units += c.len_utf16() as u32;
     }
-    t.set_selection_start(Some(pos)).ok();
-    t.set_selection_end(Some(pos)).ok();
+    text.len()
+}
+
+fn to_utf16(text: &str, byte: usize) -> u32 {
+    text[..byte].chars().map(|c| c.len_utf16() as u32).sum()
 }
 
+pub(crate) fn stage_label(stage: Stage) -> &'static str {
+    match stage {
+        Stage::Vertex => "VS",
+        Stage::Fragment => "FS",
+        Stage::Pipeline => "pipeline",
+    }
+}
@@ -2,17 +2,36 @@ use crate::components::demo::Demo;
 use crate::components::shader_editor::utils::update_block_cursor;
 use crate::components::demos::utils::is_desktop;
 use crate::pages::classic::classic::PassFlags;
+use crate::render::renderer::gpu::diagnostics::{
+    Diagnostic, Severity, Stage, check_wgsl, entry_points,
+};
+use crate::render::renderer::gpu::preprocess::ShaderFiles;
+use crate::render::renderer::graph::LiveShaders;
+use std::collections::{BTreeMap, HashMap};
+use std::ops::Range;
+use wasm_bindgen::JsCast;
+use leptos::prelude::Children;
 use leptos::prelude::AnyView;
+use leptos::prelude::AriaAttributes;
+use leptos::prelude::GlobalAttributes;
 use leptos::prelude::For;
 use leptos::prelude::GetUntracked;
 use leptos::prelude::IntoAny;
+use leptos::prelude::Effect;
 use leptos::prelude::Memo;
+use leptos::prelude::StoredValue;
+use leptos::prelude::StyleAttribute;
+use leptos::prelude::CustomAttribute;
+use leptos::prelude::UpdateValue;
+use leptos::prelude::WithValue;
+use leptos::prelude::WithUntracked;
 use leptos::prelude::Update;
+use leptos::prelude::With;
 use leptos::prelude::event_target_checked;
 use leptos::{
     IntoView, component,
     control_flow::Show,
-    html::Textarea,
+    html::{Pre, Textarea},
     prelude::{
         ClassAttribute, ElementChild, Get, NodeRef, NodeRefAttribute, OnAttribute, PropAttribute,
         RwSignal, Set, event_target_value,
@@ -20,26 +39,41 @@ use leptos::{
     view,
 };
 
-#[derive(Copy, Clone, PartialEq, Eq)]
-pub(crate) enum Mode {
-    Insert,
-    Normal,
-}
-
 #[derive(Copy, Clone, PartialEq, Eq)]
 pub(crate) enum Tab {
     Vs,
     Fs,
     Ui,
+    /// The one module both stages come from, in its place while
+    /// `ShaderSetup::single` is on.
+    Wgsl,
+    /// An `#include`able module, by index into the `ShaderFiles`.
+    Module(usize),
 }
 
 type Handler<E> = Box<dyn FnMut(E)>;
 
-use super::utils::keydown;
+use super::complete::Scope;
+use super::diff_view::DiffView;
+use super::entry_points::EntryPointsPanel;
+use super::ex::{self, Command, Switch, line_of};
+use super::format;
+use super::history::History;
+use super::lab::Lab;
+use super::sharing::{SaveButton, ShareButton};
+use super::model::ModelPanel;
+use super::params::ParamsPanel;
+use super::textures::TexturesPanel;
+use super::utils::{
+    Completer, Message, VimState, cursor_of, keydown, move_cursor, pick_completion, record_input,
+    replace_text, stage_label,
+};
+use super::vim::{Mode, Vim, first_non_blank, nth_line};
+use super::wgsl::{self, TokenKind};
 
 #[component]
-fn OptionsPanel(pass_flags: PassFlags) -> impl IntoView {
-    let items = move || pass_flags.iter();
+fn OptionsPanel(selected_demo: RwSignal<Demo>, pass_flags: PassFlags) -> impl IntoView {
+    let items = move || pass_flags.iter(selected_demo.get());
 
     view! {
         <div class="flex flex-col gap-2">
@@ -66,13 +100,29 @@ fn OptionsPanel(pass_flags: PassFlags) -> impl IntoView {
 #[component]
 fn TabBar(
     active_tab: RwSignal<Tab>,
-    ui_enabled: Memo<bool>,
     vim_enabled: RwSignal<bool>,
+    /// WGSL in place of VS and FS.
+    single: RwSignal<bool>,
+
+    /// Per tab (VS, FS, UI, WGSL): differs from what the demo ships with.
+    modified: [Memo<bool>; 4],
+    on_reset: Handler<web_sys::MouseEvent>,
+
+    files: ShaderFiles,
+    /// Modules with a tab, in the order they were opened.
+    open_modules: RwSignal<Vec<usize>>,
+
+    /// More buttons, before reset.
+    children: Children,
 ) -> impl IntoView {
     let mk_btn = move |label: &'static str, tab: Tab| -> AnyView {
-        let active_tab = active_tab.clone();
-
         let on_click: Handler<web_sys::MouseEvent> = Box::new(move |_| active_tab.set(tab));
+        let modified = match tab {
+            Tab::Vs => modified[0],
+            Tab::Fs => modified[1],
+            Tab::Wgsl => modified[3],
+            _ => modified[2],
+        };
 
         view! {
             <button
@@ -85,12 +135,94 @@ fn TabBar(
                     }
                 )
                 on:click=on_click
+                title=move || modified.get().then_some("edited; saved in this browser")
             >
                 { label }
+                { move || modified.get().then_some(view! { <span class="text-accent">" •"</span> }) }
             </button>
         }
         .into_any()
     };
+    let any_modified = move || modified.iter().any(|m| m.get());
+
+    let module_tabs = move || {
+        open_modules
+            .get()
+            .into_iter()
+            .map(|i| {
+                let tab = Tab::Module(i);
+                let close = move |ev: web_sys::MouseEvent| {
+                    ev.stop_propagation();
+                    open_modules.update(|open| open.retain(|&o| o != i));
+                    if active_tab.get_untracked() == tab {
+                        active_tab.set(Tab::Vs);
+                    }
+                };
+                view! {
+                    <button
+                        class=move || format!(
+                            "px-3 py-1 border-b-2 transition-colors {}",
+                            if active_tab.get() == tab {
+                                "border-white text-white"
+                            } else {
+                                "border-transparent text-gray-400 hover:text-gray-200"
+                            }
+                        )
+                        on:click=move |_| active_tab.set(tab)
+                        title=move || files.modified(i).then_some("edited; saved in this browser")
+                    >
+                        { move || files.name(i) }
+                        {
+                            move || files.modified(i)
+                                .then_some(view! { <span class="text-accent">" •"</span> })
+                        }
+                        <span
+                            class="ml-2 text-text-muted hover:text-accent"
+                            title="Close"
+                            on:click=close
+                        >
+                            "×"
+                        </span>
+                    </button>
+                }
+            })
+            .collect::<Vec<_>>()
+    };
+
+    // opens a module's tab, or makes a new module first
+    let on_module = move |ev: web_sys::Event| {
+        let choice = event_target_value(&ev);
+        let Some(select) = ev
+            .target()
+            .and_then(|t| t.dyn_into::<web_sys::HtmlSelectElement>().ok())
+        else {
+            return;
+        };
+        select.set_value("");
+
+        let index = if choice == "new" {
+            let Some(window) = web_sys::window() else { return };
+            let Ok(Some(name)) = window.prompt_with_message("Name of the new module:") else {
+                return;
+            };
+            match files.create(&name) {
+                Ok(i) => i,
+                Err(e) => {
+                    window.alert_with_message(&e).ok();
+                    return;
+                }
+            }
+        } else {
+            let Ok(i) = choice.parse() else { return };
+            i
+        };
+        open_modules.update(|open| {
+            if !open.contains(&index) {
+                open.push(index);
+            }
+        });
+        active_tab.set(Tab::Module(index));
+    };
 
     view! {
         <Show when=move || is_desktop() && vim_enabled.get()>
@@ -100,20 +232,50 @@ fn TabBar(
         </Show>
 
         <div class="flex justify-between items-center w-full">
-            { mk_btn("VS", Tab::Vs) }
-            { mk_btn("FS", Tab::Fs) }
             {
                 move || {
-                    if ui_enabled.get() {
-                        mk_btn("UI", Tab::Ui)
+                    if single.get() {
+                        mk_btn("WGSL", Tab::Wgsl)
                     } else {
-                        view! {}.into_any()
+                        view! { { mk_btn("VS", Tab::Vs) } { mk_btn("FS", Tab::Fs) } }.into_any()
                     }
                 }
             }
+            { mk_btn("UI", Tab::Ui) }
+            { module_tabs }
 
-            <Show when=move || is_desktop()>
-                <div class="ml-auto">
+            <div class="ml-auto flex gap-2">
+                <select
+                    class="px-2 py-1 text-text bg-surface border rounded hover:text-accent"
+                    title="Modules shaders can #include"
+                    on:change=on_module
+                >
+                    <option value="" selected>"Modules…"</option>
+                    {
+                        move || files
+                            .names()
+                            .into_iter()
+                            .enumerate()
+                            .map(|(i, name)| {
+                                view! { <option value=i.to_string()>{ name }</option> }
+                            })
+                            .collect::<Vec<_>>()
+                    }
+                    <option value="new">"New module…"</option>
+                </select>
+                { children() }
+                <button
+                    class=move || format!(
+                        "px-3 py-1 text-text border rounded hover:text-accent {}",
+                        if any_modified() { "" } else { "invisible" }
+                    )
+                    title="Restore the demo's shaders and passes"
+                    on:click=on_reset
+                >
+                    "Reset"
+                </button>
+
+                <Show when=move || is_desktop()>
                     <button
                         class="px-3 py-1 text-text border rounded hover:text-accent"
                         on:click=move |_| vim_enabled.update(|b| *b = !*b)
@@ -126,103 +288,417 @@ fn TabBar(
                             }
                         }}
                     </button>
-                </div>
-            </Show>
+                </Show>
+            </div>
         </div>
     }
 }
 
+/// Mode and diagnostics; in vim mode also the `:` line, and the result of
+/// the last command until the next key.
 #[component]
-fn StatusBar(status: impl Fn() -> String + Send + 'static) -> impl IntoView {
+fn StatusBar(
+    status: impl Fn() -> String + Send + 'static,
+    vim: VimState,
+    vim_enabled: RwSignal<bool>,
+) -> impl IntoView {
+    let line = move || match (vim_enabled.get(), vim.cmdline.get(), vim.message.get()) {
+        (true, Some(cmd), _) => view! {
+            <span class="font-mono whitespace-pre">
+                ":" { cmd } <span class="animate-pulse">"█"</span>
+            </span>
+        }
+        .into_any(),
+        (true, None, Some(Message::Error(e))) => view! { <span class="text-red-300">{ e }</span> }.into_any(),
+        (true, None, Some(Message::Info(m))) => m.into_any(),
+        _ => status().into_any(),
+    };
+
     view! {
         <div class="h-6 px-3 text-xs flex items-center bg-neutral-dark text-text \
                     border-t border-gray-700 select-none">
-            { status }
+            { line }
         </div>
     }
 }
 
+/// Where `d` is, with a leading space: the line and column, after the
+/// module when it's in one. Empty for errors that aren't anywhere.
+fn location(d: &Diagnostic) -> String {
+    let Some(s) = d.span else { return String::new() };
+    match &d.file {
+        Some(file) => format!(" {file}:{}:{}", s.line, s.column),
+        None => format!(" {}:{}", s.line, s.column),
+    }
+}
+
+/// Errors and warnings for both sources; hidden when there are none.
+#[component]
+fn DiagnosticsPanel(diagnostics: Memo<Vec<Diagnostic>>) -> impl IntoView {
+    let rows = move || {
+        diagnostics
+            .get()
+            .into_iter()
+            .map(|d| {
+                let colour = match d.severity {
+                    Severity::Error => "text-red-300",
+                    Severity::Warning => "text-yellow-300",
+                };
+                let at = location(&d);
+                view! {
+                    <li class=format!("whitespace-pre-wrap break-words {colour}")>
+                        <span class="font-semibold">
+                            { format!("{}{at} {}", stage_label(d.stage), d.severity) }
+                        </span>
+                        " "
+                        { d.message }
+                    </li>
+                }
+            })
+            .collect::<Vec<_>>()
+    };
+
+    view! {
+        <Show when=move || !diagnostics.get().is_empty()>
+            <ul class="max-h-32 overflow-y-auto mt-2 px-3 py-2 rounded-xl text-xs font-mono \
+                       bg-neutral-dark border border-gray-700 space-y-1">
+                { rows }
+            </ul>
+        </Show>
+    }
+}
+
+fn token_class(kind: TokenKind) -> &'static str {
+    match kind {
+        TokenKind::Keyword => "text-fuchsia-400",
+        TokenKind::Type => "text-sky-300",
+        TokenKind::Attribute => "text-amber-300",
+        TokenKind::Builtin => "text-teal-300",
+        TokenKind::Number => "text-orange-300",
+        TokenKind::Comment => "text-text-muted italic",
+        TokenKind::Ident => "text-text",
+        TokenKind::Punct => "text-text/70",
+        TokenKind::Whitespace => "",
+    }
+}
+
+/// One line of the backdrop behind the (transparent) textarea: coloured
+/// tokens, tinted when something on it is wrong, with the offending span
+/// underlined.
+fn code_line(
+    src: &str,
+    line: Range<usize>,
+    segments: &[(Range<usize>, TokenKind)],
+    markers: &[&Diagnostic],
+) -> AnyView {
+    let tint = match markers.iter().map(|d| d.severity).min() {
+        Some(Severity::Error) => "bg-red-500/15",
+        Some(Severity::Warning) => "bg-yellow-400/15",
+        None => "",
+    };
+
+    // first marker's span, clamped to this line
+    let squiggle = markers.first().and_then(|d| {
+        let span = d.span?;
+        let class = match d.severity {
+            Severity::Error => "underline decoration-wavy decoration-red-400",
+            Severity::Warning => "underline decoration-wavy decoration-yellow-400",
+        };
+        let start = span.start.clamp(line.start, line.end);
+        let mut end = span.end.clamp(start, line.end);
+        if end == start && start < line.end {
+            end += src[start..].chars().next().map_or(0, char::len_utf8);
+        }
+        Some((start..end, class))
+    });
+
+    let mut pieces = Vec::new();
+    for (range, kind) in segments {
+        let mut cuts = vec![range.start, range.end];
+        if let Some((sq, _)) = &squiggle {
+            cuts.extend([sq.start, sq.end].into_iter().filter(|&c| range.contains(&c)));
+        }
+        cuts.sort_unstable();
+        cuts.dedup();
+
+        for w in cuts.windows(2) {
+            let marked = squiggle
+                .as_ref()
+                .filter(|(sq, _)| sq.start <= w[0] && w[1] <= sq.end);
+            let class = match marked {
+                Some((_, sq)) => format!("{} {sq}", token_class(*kind)),
+                None => token_class(*kind).to_owned(),
+            };
+            pieces.push(view! { <span class=class>{ src[w[0]..w[1]].to_owned() }</span> }.into_any());
+        }
+    }
+
+    // zero-width spans ("expected `;`") get one character underlined, the
+    // trailing space when at the end of the line; it also keeps empty lines
+    // one line tall
+    let tail = match &squiggle {
+        Some((sq, class)) if sq.is_empty() => *class,
+        _ => "",
+    };
+    pieces.push(view! { <span class=tail>" "</span> }.into_any());
+
+    view! { <div class=tint>{ pieces }</div> }.into_any()
+}
+
 #[component]
 fn CodeArea(
     tab: RwSignal<Tab>,
     vs_src: RwSignal<String>,
     fs_src: RwSignal<String>,
+    module: RwSignal<String>,
+    files: ShaderFiles,
+
+    /// Diagnostics for the source in `tab`.
+    markers: Memo<Vec<Diagnostic>>,
 
     on_input: Handler<web_sys::Event>,
     on_keydown: Handler<web_sys::KeyboardEvent>,
 
+    /// Suggestions shown at the cursor.
+    completer: Completer,
+    /// Mouse down on a suggestion; its `data-index` says which.
+    on_pick: Handler<web_sys::MouseEvent>,
+
     vim_enabled: RwSignal<bool>,
-    mode: RwSignal<Mode>,
+    vim: VimState,
+    /// `:set number`
+    line_numbers: RwSignal<bool>,
 
     /// NodeRef so the parent can call `.focus()` etc.
     textarea_ref: NodeRef<Textarea>,
 ) -> impl IntoView {
+    let backdrop_ref = NodeRef::<Pre>::new();
+    let gutter_ref = NodeRef::<Pre>::new();
+    let mode = vim.mode;
+
+    let source = move || match tab.get() {
+        Tab::Vs => vs_src.get(),
+        Tab::Fs => fs_src.get(),
+        Tab::Wgsl => module.get(),
+        Tab::Module(i) => files.src(i).map(|src| src.get()).unwrap_or_default(),
+        Tab::Ui => String::new(),
+    };
+
+    let backdrop = move || {
+        let src = source();
+        let markers = markers.get();
+        let tokens = wgsl::tokenize(&src);
+
+        let mut line_start = 0;
+        wgsl::lines(&src, &tokens)
+            .iter()
+            .zip(src.split('\n'))
+            .enumerate()
+            .map(|(i, (segments, text))| {
+                let here: Vec<&Diagnostic> = markers
+                    .iter()
+                    .filter(|d| d.span.is_some_and(|s| s.line as usize == i + 1))
+                    .collect();
+                let line = line_start..line_start + text.len();
+                line_start = line.end + 1;
+                code_line(&src, line, segments, &here)
+            })
+            .collect::<Vec<_>>()
+    };
+
+    let numbers = move || {
+        let count = source().matches('\n').count() + 1;
+        (1..=count).map(|n| n.to_string()).collect::<Vec<_>>().join("\n")
+    };
+
+    // the textarea's scroll, which the suggestions move with
+    let scroll = RwSignal::new((0, 0));
+
+    // under the word being completed: the textarea has `p-4` and a border,
+    // and `text-xs` lines are 1rem high
+    let menu_position = move || {
+        let Some(word) = completer.menu.with(|m| m.as_ref().map(|m| m.word.clone())) else {
+            return String::new();
+        };
+        let src = source();
+        let before = src.get(..word.start).unwrap_or_default();
+        let line = before.matches('\n').count();
+        let column = before[before.rfind('\n').map_or(0, |i| i + 1)..].chars().count();
+        let (top, left) = scroll.get();
+        format!(
+            "top: calc({}rem + 1px - {top}px); left: calc(1rem + 1px + {column}ch - {left}px);",
+            line + 2,
+        )
+    };
+
+    let suggestions = move || {
+        let Some(menu) = completer.menu.get() else { return Vec::new() };
+        menu.items
+            .into_iter()
+            .enumerate()
+            .map(|(i, item)| {
+                let selected = i == menu.selected;
+                view! {
+                    <li
+                        role="option"
+                        data-index=i.to_string()
+                        aria-selected=selected.to_string()
+                        class=format!(
+                            "flex gap-3 px-2 py-0.5 rounded cursor-pointer {}",
+                            if selected { "bg-primary/30" } else { "hover:bg-neutral-dark" },
+                        )
+                    >
+                        <span class="text-text">{ item.label }</span>
+                        <span class="text-text-muted truncate">{ item.detail }</span>
+                        <span class="ml-auto text-accent">{ item.kind.tag() }</span>
+                    </li>
+                }
+            })
+            .collect::<Vec<_>>()
+    };
+
+    // the backdrop and gutter can't scroll by themselves, follow the textarea
+    let sync_scroll = move |_| {
+        let Some(t) = textarea_ref.get() else { return };
+        scroll.set((t.scroll_top(), t.scroll_left()));
+        if let Some(b) = backdrop_ref.get() {
+            b.set_scroll_top(t.scroll_top());
+            b.set_scroll_left(t.scroll_left());
+        }
+        if let Some(g) = gutter_ref.get() {
+            g.set_scroll_top(t.scroll_top());
+        }
+    };
+
     view! {
-        <textarea 
-            class=move || {
-                let base = "flex-1 bg-surface text-text text-xs p-4 font-mono \
-                            rounded-xl resize-none border border-transparent \
-                            focus:border-gray-300 focus:outline-none \
-                            focus:ring-1 focus:ring-gray-400 focus:ring-opacity-50 \
-                            selection:bg-text selection:text-surface";
-                let caret = if !vim_enabled.get() || mode.get() == Mode::Insert {
-                    "caret-visible"
+        <div class="flex flex-1 min-h-0 rounded-xl bg-surface overflow-hidden">
+            // always rendered, so it's already scrolled along when shown
+            <pre
+                aria-hidden="true"
+                node_ref=gutter_ref
+                class=move || if line_numbers.get() {
+                    "shrink-0 m-0 py-4 pl-3 pr-2 text-xs font-mono text-right \
+                     text-text-muted overflow-hidden select-none border-y border-transparent"
                 } else {
-                    "caret-transparent"
-                };
+                    "hidden"
+                }
+            >
+                { numbers }
+            </pre>
 
-                format!("{base} {caret}")
-            }
-            prop:value = move || match tab.get() {
-                Tab::Vs => vs_src.get(),
-                Tab::Fs => fs_src.get(),
-                Tab::Ui => String::new(),
-            }
+            <div class="relative flex-1 min-w-0">
+                <pre
+                    aria-hidden="true"
+                    node_ref=backdrop_ref
+                    class="absolute inset-0 m-0 p-4 text-xs font-mono whitespace-pre \
+                           overflow-hidden pointer-events-none text-text \
+                           border border-transparent"
+                >
+                    { backdrop }
+                </pre>
+
+                <textarea
+                    wrap="off"
+                    spellcheck="false"
+                    class=move || {
+                        // text comes from the backdrop; the textarea only
+                        // contributes caret, selection and input
+                        let base = "absolute inset-0 w-full h-full bg-transparent text-transparent text-xs p-4 \
+                                    font-mono whitespace-pre overflow-auto \
+                                    rounded-xl resize-none border border-transparent \
+                                    focus:border-gray-300 focus:outline-none \
+                                    focus:ring-1 focus:ring-gray-400 focus:ring-opacity-50";
+                        let (caret, selection) = match mode.get() {
+                            _ if !vim_enabled.get() => ("caret-visible", "selection:bg-primary/30"),
+                            Mode::Insert => ("caret-visible", "selection:bg-primary/30"),
+                            // the block cursor is a one-character selection
+                            Mode::Normal => ("caret-transparent", "selection:bg-text selection:text-surface"),
+                            Mode::Visual | Mode::VisualLine => ("caret-transparent", "selection:bg-primary/30"),
+                        };
+
+                        format!("{base} {caret} {selection}")
+                    }
+                    prop:value = source
 
-            on:input=on_input
-            on:keydown=on_keydown
-            node_ref=textarea_ref
+                    on:input=on_input
+                    on:keydown=on_keydown
+                    on:scroll=sync_scroll
+                    node_ref=textarea_ref
 
-            on:focus={
-                let vim_enabled  = vim_enabled.clone();
-                let mode         = mode.clone();
-                let textarea_ref = textarea_ref.clone();
+                    on:blur=move |_| completer.close()
 
-                move |_| {
-                    if vim_enabled.get_untracked() && mode.get_untracked() == Mode::Normal {
-                        update_block_cursor(&textarea_ref, Mode::Normal);
+                    on:focus=move |_| {
+                        if vim_enabled.get_untracked() && mode.get_untracked() == Mode::Normal {
+                            update_block_cursor(&textarea_ref, Mode::Normal);
+                        }
                     }
-                }
-            }
 
-            on:click={
-                let vim_enabled  = vim_enabled.clone();
-                let mode         = mode.clone();
-                let textarea_ref = textarea_ref.clone();
+                    on:click=move |_| {
+                        completer.close();
+                        if !vim_enabled.get_untracked() {
+                            return;
+                        }
+                        // clicking away ends a visual selection
+                        if matches!(mode.get_untracked(), Mode::Visual | Mode::VisualLine) {
+                            vim.engine.update_value(Vim::leave_visual);
+                            mode.set(Mode::Normal);
+                        }
+                        if mode.get_untracked() == Mode::Normal {
+                            update_block_cursor(&textarea_ref, Mode::Normal);
+                        }
+                    }
+                />
 
-                move |_| {
-                    if vim_enabled.get_untracked() && mode.get_untracked() == Mode::Normal {
-                        update_block_cursor(&textarea_ref, Mode::Normal);
+                // always rendered, so the handler is attached once
+                <ul
+                    role="listbox"
+                    class=move || if completer.menu.with(Option::is_some) {
+                        "absolute z-10 m-0 p-1 list-none max-w-[40rem] rounded border \
+                         border-neutral-dark bg-surface text-xs font-mono shadow-lg"
+                    } else {
+                        "hidden"
                     }
-                }
-            }
-        />
+                    style=menu_position
+                    on:mousedown=on_pick
+                >
+                    { suggestions }
+                </ul>
+            </div>
+        </div>
     }
 }
 
 #[component]
 pub fn ShaderEditor(
-    vs_src: RwSignal<String>,
-    fs_src: RwSignal<String>,
-
+    lab: Lab,
     pass_flags: PassFlags,
     selected_demo: RwSignal<Demo>,
 ) -> impl IntoView {
+    let Lab { shaders, textures, model } = lab;
+    let LiveShaders { vs: vs_src, fs: fs_src, diagnostics, params, files, setup } = shaders;
     let vim_enabled = RwSignal::new(false);
 
     let active_tab = RwSignal::new(Tab::Vs);
+    let open_modules = RwSignal::new(Vec::new());
 
-    let ui_enabled = Memo::new(move |_| selected_demo.get() == Demo::Animals);
+    // the VS/FS tabs and the WGSL one take each other's place
+    Effect::new(move |_| {
+        let single = setup.single.get();
+        match active_tab.get_untracked() {
+            Tab::Vs | Tab::Fs if single => active_tab.set(Tab::Wgsl),
+            Tab::Wgsl if !single => active_tab.set(Tab::Vs),
+            _ => {}
+        }
+    });
+
+    // what `tab` edits; the UI tab edits no source
+    let source_of = move |tab| match tab {
+        Tab::Vs => Some(vs_src),
+        Tab::Fs => Some(fs_src),
+        Tab::Wgsl => Some(setup.module),
+        Tab::Module(i) => files.src(i),
+        Tab::Ui => None,
+    };
 
     let textarea_ref = NodeRef::<Textarea>::new();
 
@@ -232,65 +708,479 @@ pub fn ShaderEditor(
         }
     };
 
-    let mode = RwSignal::new(Mode::Normal);
+    let vim = VimState::new();
+    let completer = Completer::new(
+        Memo::new(move |_| Scope {
+            demo: selected_demo.get(),
+            stage: match active_tab.get() {
+                Tab::Fs => Some(Stage::Fragment),
+                Tab::Wgsl | Tab::Module(_) => None,
+                _ => Some(Stage::Vertex),
+            },
+        }),
+        // so the bindings snippet leaves out what an include declares
+        Memo::new(move |_| {
+            let tab = active_tab.get();
+            let stage = if tab == Tab::Fs { Stage::Fragment } else { Stage::Vertex };
+            let Some(src) = source_of(tab) else { return String::new() };
+            src.with(|src| files.expand(src, stage))
+                .map(|expanded| expanded.included().collect::<Vec<_>>().join("\n"))
+                .unwrap_or_default()
+        }),
+    );
+    let line_numbers = RwSignal::new(false);
+    // the active tab against what the demo ships with
+    let diff_mode = RwSignal::new(false);
+
+    // undo per tab; works with vim off too
+    let vs_history = StoredValue::new(History::new(&vs_src.get_untracked()));
+    let fs_history = StoredValue::new(History::new(&fs_src.get_untracked()));
+    let wgsl_history = StoredValue::new(History::new(&setup.module.get_untracked()));
+    // a module's starts when it's first edited
+    let module_histories = StoredValue::new(HashMap::<usize, StoredValue<History>>::new());
+    let history = move || match active_tab.get_untracked() {
+        Tab::Fs => fs_history,
+        Tab::Wgsl => wgsl_history,
+        Tab::Module(i) => {
+            let started = module_histories.with_value(|h| h.get(&i).copied());
+            started.unwrap_or_else(|| {
+                let src = files.src(i).map(|src| src.get_untracked()).unwrap_or_default();
+                let history = StoredValue::new(History::new(&src));
+                module_histories.update_value(|h| {
+                    h.insert(i, history);
+                });
+                history
+            })
+        }
+        _ => vs_history,
+    };
+
+    // checked on every edit with the includes pasted in, no device needed;
+    // errors sort before warnings. Modules declare more than any one shader
+    // uses, so what they leave unused isn't worth a warning
+    let check = move |src: RwSignal<String>, stage| {
+        Memo::new(move |_| {
+            let mut found: Vec<Diagnostic> = match src.with(|src| files.expand(src, stage)) {
+                Ok(expanded) => check_wgsl(&expanded.code, stage)
+                    .into_iter()
+                    .map(|d| expanded.locate(d))
+                    .filter(|d| d.file.is_none() || d.severity == Severity::Error)
+                    .collect(),
+                Err(e) => vec![e],
+            };
+            found.sort_by_key(|d| d.severity);
+            found
+        })
+    };
+    let vs_checked = check(vs_src, Stage::Vertex);
+    let fs_checked = check(fs_src, Stage::Fragment);
+    // the one module goes through both stages; what's wrong with it either
+    // way is listed once
+    let module_vs = check(setup.module, Stage::Vertex);
+    let module_fs = check(setup.module, Stage::Fragment);
+    let module_checked = Memo::new(move |_| {
+        let mut found = module_vs.get();
+        for d in module_fs.get() {
+            let seen = found
+                .iter()
+                .any(|f| (&f.message, f.span, &f.file) == (&d.message, d.span, &d.file));
+            if !seen {
+                found.push(d);
+            }
+        }
+        found.sort_by_key(|d| d.severity);
+        found
+    });
+
+    // what the sources declare, for picking entry points
+    let found = Memo::new(move |_| {
+        let declared = |src: RwSignal<String>, stage| {
+            src.with(|src| files.expand(src, stage))
+                .map(|expanded| entry_points(&expanded.code))
+                .unwrap_or_default()
+                .into_iter()
+                .filter(|(_, s)| *s == stage)
+                .map(|(name, _)| name)
+                .collect::<Vec<_>>()
+        };
+        let (vs, fs) = if setup.single.get() {
+            (setup.module, setup.module)
+        } else {
+            (vs_src, fs_src)
+        };
+        (declared(vs, Stage::Vertex), declared(fs, Stage::Fragment))
+    });
+
+    // plus what only the device can tell (pipeline layout, entry points)
+    let all_diagnostics = Memo::new(move |_| {
+        let mut all = if setup.single.get() {
+            module_checked.get()
+        } else {
+            let mut all = vs_checked.get();
+            all.extend(fs_checked.get());
+            all
+        };
+        for d in diagnostics.errors() {
+            if !all.contains(&d) {
+                all.push(d);
+            }
+        }
+        all
+    });
+
+    // the tab's own; a module's come from the shaders that include it
+    let markers = Memo::new(move |_| {
+        let (found, file) = match active_tab.get() {
+            Tab::Vs => (vs_checked.get(), None),
+            Tab::Fs => (fs_checked.get(), None),
+            Tab::Wgsl => (module_checked.get(), None),
+            Tab::Module(i) => match files.name(i) {
+                Some(name) => (all_diagnostics.get(), Some(name)),
+                None => return Vec::new(),
+            },
+            Tab::Ui => return Vec::new(),
+        };
+        found.into_iter().filter(|d| d.file == file).collect::<Vec<_>>()
+    });
 
     let status = move || {
         let tab_lbl = match active_tab.get() {
-            Tab::Vs => "VS",
-            Tab::Fs => "FS",
-            Tab::Ui => "UI",
+            Tab::Vs => "VS".to_owned(),
+            Tab::Fs => "FS".to_owned(),
+            Tab::Ui => "UI".to_owned(),
+            Tab::Wgsl => "WGSL".to_owned(),
+            Tab::Module(i) => files.name(i).unwrap_or_default(),
         };
-        match mode.get() {
-            Mode::Insert => format!("-- INSERT -- [{tab_lbl}]"),
-            Mode::Normal => format!("-- NORMAL -- [{tab_lbl}]"),
+        let mut status = if vim_enabled.get() {
+            match vim.mode.get() {
+                Mode::Insert => format!("-- INSERT -- [{tab_lbl}]"),
+                Mode::Normal => format!("-- NORMAL -- [{tab_lbl}]"),
+                Mode::Visual => format!("-- VISUAL -- [{tab_lbl}]"),
+                Mode::VisualLine => format!("-- VISUAL LINE -- [{tab_lbl}]"),
+            }
+        } else {
+            format!("[{tab_lbl}]")
+        };
+
+        // keys of a half-typed command, like vim's showcmd
+        let pending = vim.showcmd.get();
+        if vim_enabled.get() && !pending.is_empty() {
+            status.push_str(&format!("  {pending}"));
+        }
+
+        if let Some(d) = markers.get().first() {
+            if let Some(s) = d.span {
+                status.push_str(&format!("  {}:{}", s.line, s.column));
+            }
+            status.push_str(&format!("  {}: {}", d.severity, d.message));
+        }
+        status
+    };
+
+    // edits are saved per demo; these mark what differs from the shipped one
+    let shipped = Memo::new(move |_| selected_demo.get().shaders());
+    let modified = [
+        Memo::new(move |_| vs_src.with(|src| src != shipped.get().0)),
+        Memo::new(move |_| fs_src.with(|src| src != shipped.get().1)),
+        {
+            let pass_flags = pass_flags.clone();
+            Memo::new(move |_| {
+                pass_flags.modified(selected_demo.get())
+                    || params.modified()
+                    || !setup.picks().is_empty()
+                    || textures.urls() != selected_demo.get().textures()
+                    || !textures.samplers().is_empty()
+                    || model.source().is_some()
+            })
+        },
+        Memo::new(move |_| setup.module.with(|src| src != selected_demo.get().module())),
+    ];
+
+    // back to the shipped demo; undoable like any other edit
+    let reset: Handler<web_sys::MouseEvent> = {
+        let pass_flags = pass_flags.clone();
+        Box::new(move |_| {
+            let demo = selected_demo.get_untracked();
+            let (vs, fs) = demo.shaders();
+            let module = demo.module();
+            for (src, history, shipped) in [
+                (vs_src, vs_history, vs),
+                (fs_src, fs_history, fs),
+                (setup.module, wgsl_history, module),
+            ] {
+                src.with_untracked(|old| {
+                    history.update_value(|h| {
+                        h.record(old, shipped);
+                        h.close();
+                    })
+                });
+                src.set(shipped.to_owned());
+            }
+            pass_flags.reset(demo);
+            params.reset();
+            setup.set_picks(BTreeMap::new());
+            textures.load_urls(&demo.textures());
+            textures.set_samplers(&BTreeMap::new());
+            model.clear();
+        })
+    };
+
+    let share_flags = pass_flags.clone();
+    let save_flags = pass_flags.clone();
+
+    let set_text = move |val| {
+        if let Some(src) = source_of(active_tab.get_untracked()) {
+            src.set(val);
+        }
+    };
+
+    // the whole tab through the formatter, one undo step; the cursor stays
+    // on its line
+    let format_src = move |_| {
+        let Some(src) = source_of(active_tab.get_untracked()) else { return };
+        let old = src.get_untracked();
+        let new = format::format(&old);
+        if new == old {
+            return;
+        }
+        match textarea_ref.get_untracked().filter(|_| !diff_mode.get_untracked()) {
+            Some(t) => {
+                let at = first_non_blank(&new, nth_line(&new, line_of(&old, cursor_of(&t))));
+                let mode = if vim_enabled.get_untracked() {
+                    vim.mode.get_untracked()
+                } else {
+                    Mode::Insert
+                };
+                replace_text(&t, history(), new, at, mode, &set_text);
+            }
+            None => {
+                history().update_value(|h| {
+                    h.record(&old, &new);
+                    h.close();
+                });
+                src.set(new);
+            }
+        }
+    };
+
+    // `:` commands; what they return goes on the message line
+    let run_command = move |line: String| -> Result<Option<String>, String> {
+        let Some(command) = ex::parse(&line)? else { return Ok(None) };
+        let Some(t) = textarea_ref.get() else { return Ok(None) };
+        let text = t.value();
+        let cursor = cursor_of(&t);
+        let mode = vim.mode.get_untracked();
+
+        match command {
+            // edits are compiled as they're typed; report how that went
+            Command::Write => {
+                let all = all_diagnostics.get_untracked();
+                let mut errors = all.iter().filter(|d| d.severity == Severity::Error);
+                let Some(first) = errors.next() else {
+                    return Ok(Some("compiled".to_owned()));
+                };
+                let at = location(first);
+                Err(format!(
+                    "{} error(s), still running the last good shaders; {}{at}: {}",
+                    errors.count() + 1,
+                    stage_label(first.stage),
+                    first.message,
+                ))
+            }
+            Command::ResetToDefault => {
+                let demo = selected_demo.get_untracked();
+                let (vs, fs) = demo.shaders();
+                let (src, report) = match active_tab.get_untracked() {
+                    Tab::Fs => (fs, format!("reset to the {} default", demo.slug())),
+                    Tab::Wgsl => (demo.module(), format!("reset to the {} default", demo.slug())),
+                    Tab::Module(i) => {
+                        let name = files.name(i).unwrap_or_default();
+                        let Some(shipped) = files.shipped(i) else {
+                            return Err(format!("{name} is a new module, it has no default"));
+                        };
+                        (shipped, format!("reset {name} to the shipped one"))
+                    }
+                    _ => (vs, format!("reset to the {} default", demo.slug())),
+                };
+                replace_text(&t, history(), src.to_owned(), 0, mode, &set_text);
+                Ok(Some(report))
+            }
+            Command::Substitute(sub) => {
+                let (new, at, report) = sub.apply(&text, cursor)?;
+                replace_text(&t, history(), new, at, mode, &set_text);
+                Ok(Some(report))
+            }
+            Command::Number(switch) => {
+                line_numbers.update(|on| {
+                    *on = match switch {
+                        Switch::On => true,
+                        Switch::Off => false,
+                        Switch::Toggle => !*on,
+                    }
+                });
+                Ok(None)
+            }
+            Command::Goto(to) => {
+                move_cursor(&t, &text, ex::goto(&text, cursor, to), mode);
+                Ok(None)
+            }
+            Command::Demo(None) => Ok(Some(format!("demos: {}", ex::demo_names()))),
+            Command::Demo(Some(demo)) => {
+                selected_demo.set(demo);
+                Ok(None)
+            }
         }
     };
 
     view! {
         <div class="w-full h-[40rem] flex flex-col" on:click=focus_textarea>
-            <TabBar active_tab ui_enabled vim_enabled />
+            <TabBar
+                active_tab
+                single=setup.single
+                vim_enabled
+                modified
+                on_reset=reset
+                files
+                open_modules
+            >
+                <button
+                    class=move || format!(
+                        "px-3 py-1 border rounded hover:text-accent {}",
+                        if diff_mode.get() { "text-accent" } else { "text-text" },
+                    )
+                    title="Compare with the shipped shader"
+                    aria-pressed=move || diff_mode.get().to_string()
+                    on:click=move |_| diff_mode.update(|on| *on = !*on)
+                >
+                    "Diff"
+                </button>
+                <button
+                    class=move || format!(
+                        "px-3 py-1 border rounded hover:text-accent {}",
+                        if setup.single.get() { "text-accent" } else { "text-text" },
+                    )
+                    title="Write both stages in one module and pick their entry points"
+                    aria-pressed=move || setup.single.get().to_string()
+                    on:click=move |_| setup.single.update(|on| *on = !*on)
+                >
+                    "One module"
+                </button>
+                <button
+                    class="px-3 py-1 border rounded text-text hover:text-accent"
+                    title="Re-indent and tidy the spacing of this shader"
+                    on:click=format_src
+                >
+                    "Format"
+                </button>
+                <ShareButton selected_demo lab pass_flags=share_flags />
+                <SaveButton selected_demo lab pass_flags=save_flags />
+            </TabBar>
+
+            <Show when=move || active_tab.get() != Tab::Ui && diff_mode.get()>
+                {
+                    move || {
+                        let (vs, fs) = shipped.get();
+                        let tab = active_tab.get();
+                        let Some(src) = source_of(tab) else { return ().into_any() };
+                        let shipped = match tab {
+                            Tab::Fs => fs,
+                            Tab::Wgsl => selected_demo.get().module(),
+                            // a new module is all additions
+                            Tab::Module(i) => files.shipped(i).unwrap_or_default(),
+                            _ => vs,
+                        };
+                        let history = history();
+                        view! { <DiffView src shipped history /> }.into_any()
+                    }
+                }
+            </Show>
 
-            <Show when=move || active_tab.get() != Tab::Ui>
+            <Show when=move || active_tab.get() != Tab::Ui && !diff_mode.get()>
                 {   // these closures must be Fn, so build fresh handlers every call
-                    let key_handler: Handler<web_sys::KeyboardEvent> =
-                        Box::new(keydown(vim_enabled.get(), mode, textarea_ref.clone()));
+                    let key_handler: Handler<web_sys::KeyboardEvent> = Box::new(keydown(
+                        vim_enabled.get(),
+                        vim,
+                        completer,
+                        history,
+                        textarea_ref,
+                        set_text,
+                        run_command,
+                    ));
 
                     let on_input: Handler<web_sys::Event> = Box::new(move |ev| {
                         let val = event_target_value(&ev);
-                        match active_tab.get() {
-                            Tab::Vs => vs_src.set(val),
-                            Tab::Fs => fs_src.set(val),
-                            Tab::Ui => (),
+                        let Some(src) = source_of(active_tab.get()) else { return };
+                        let vim_on = vim_enabled.get_untracked();
+                        src.with_untracked(|old| {
+                            history().update_value(|h| record_input(h, old, &val, vim_on))
+                        });
+                        src.set(val);
+                        if !vim_on || vim.mode.get_untracked() == Mode::Insert {
+                            if let Some(t) = textarea_ref.get_untracked() {
+                                completer.refresh(&t, false);
+                            }
                         }
                     });
 
+                    let on_pick: Handler<web_sys::MouseEvent> = Box::new(move |ev| {
+                        // the textarea keeps the focus, and with it the caret
+                        ev.prevent_default();
+                        let index = ev
+                            .target()
+                            .and_then(|t| t.dyn_into::<web_sys::Element>().ok())
+                            .and_then(|e| e.closest("[data-index]").ok().flatten())
+                            .and_then(|e| e.get_attribute("data-index"))
+                            .and_then(|i| i.parse().ok());
+                        let (Some(index), Some(t)) = (index, textarea_ref.get_untracked()) else {
+                            return;
+                        };
+                        completer.menu.update(|m| m.iter_mut().for_each(|m| m.selected = index));
+                        let vim_on = vim_enabled.get_untracked();
+                        pick_completion(&t, completer, history(), vim_on, &set_text);
+                    });
+
                     view! {
                         <CodeArea
                             vs_src = vs_src
                             fs_src = fs_src
+                            module = setup.module
+                            files
                             tab = active_tab
-                            textarea_ref = textarea_ref.clone()
-                            mode
+                            markers
+                            textarea_ref = textarea_ref
+                            vim
                             vim_enabled
+                            line_numbers
 
                             on_input
                             on_keydown = key_handler
+                            completer
+                            on_pick
                         />
                     }.into_any()
                 }
             </Show>
 
-            <Show
-                when=move || (active_tab.get() == Tab::Ui) && (ui_enabled.get())
-            >
+            <DiagnosticsPanel diagnostics=all_diagnostics />
+
+            <Show when=move || active_tab.get() == Tab::Ui>
                 {
                     let flags_handle = pass_flags.clone();
-                    view! { <OptionsPanel pass_flags=flags_handle /> }.into_any()
+                    view! {
+                        <div class="flex flex-col gap-4 overflow-y-auto">
+                            <OptionsPanel selected_demo pass_flags=flags_handle />
+                            <EntryPointsPanel selected_demo setup found />
+                            <ModelPanel selected_demo model />
+                            <TexturesPanel selected_demo textures />
+                            <ParamsPanel params />
+                        </div>
+                    }
+                    .into_any()
                 }
             </Show>
 
-            <Show when=move || vim_enabled.get()>
-                { view! { <StatusBar status/> }.into_any() }
+            <Show when=move || vim_enabled.get() || !markers.get().is_empty()>
+                { view! { <StatusBar status vim vim_enabled /> }.into_any() }
             </Show>
         </div>
     }
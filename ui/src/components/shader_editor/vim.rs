@@ -0,0 +1,1465 @@
+//! The vim engine behind the editor. It knows nothing about the DOM: keys
+//! go in (as `KeyboardEvent.key` strings), and it edits a `String` and a
+//! byte-offset cursor. `utils::keydown` does the textarea plumbing.
+//!
+//! Grammar: `[count] operator [count] (motion | text object)`, doubled
+//! operators for whole lines (`dd`, `>>`, `gqq`), and `[count] command`. Keys are
+//! collected until they parse; anything that can't become valid is dropped.
+//! In visual modes motions and text objects move the selection and
+//! operators act on it. Undo isn't the engine's business: `u`, `Ctrl-r`,
+//! `g-` and `g+` come back out as [`Outcome::History`].
+
+use std::iter::Peekable;
+
+use super::format::format_lines;
+use super::history::{Step, changed};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(crate) enum Mode {
+    Insert,
+    Normal,
+    /// `v`: characters from the anchor to the cursor, both included.
+    Visual,
+    /// `V`: whole lines from the anchor's to the cursor's.
+    VisualLine,
+}
+
+/// What the caller should do with the key event.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) enum Outcome {
+    /// Consumed; suppress the browser's default.
+    Handled,
+    /// Let the textarea have it (typing in insert mode).
+    PassThrough,
+    /// Consumed; move through the undo history `count` times.
+    History(Step, usize),
+    /// `:`: open the command line, starting with this text (the line range
+    /// of a visual selection).
+    CommandLine(String),
+}
+
+pub(crate) const INDENT: &str = "    ";
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Motion {
+    Left,
+    Right,
+    Down,
+    Up,
+    WordStart { big: bool },
+    WordBack { big: bool },
+    WordEnd { big: bool },
+    LineStart,
+    FirstNonBlank,
+    LineEnd,
+    /// `gg`, or line `count`
+    FirstLine,
+    /// `G`, or line `count`
+    LastLine,
+    /// `f`/`t` (forward), `F`/`T` (backward); `till` stops one short.
+    Find { ch: char, backward: bool, till: bool },
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Object {
+    Word,
+    /// Opening and closing bracket.
+    Block(char, char),
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Target {
+    Motion(Motion),
+    Object { object: Object, inner: bool },
+    /// Doubled operator: `count` whole lines.
+    Lines,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Op {
+    Delete,
+    Change,
+    Yank,
+    Indent,
+    Dedent,
+    /// `=` / `gq`: always whole lines.
+    Format,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum InsertAt {
+    /// `i`
+    Cursor,
+    /// `a`
+    After,
+    /// `A`
+    LineEnd,
+    /// `I`
+    FirstNonBlank,
+    /// `o`
+    Below,
+    /// `O`
+    Above,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Cmd {
+    Move(Motion),
+    Operate(Op, Target),
+    /// `x`
+    DeleteChar,
+    Insert(InsertAt),
+    /// `p` / `P`
+    Put { before: bool },
+    /// `.`
+    Repeat,
+    /// `v` / `V`
+    Visual { line: bool },
+    /// `u`, `<C-r>`, `g-`, `g+`
+    History(Step),
+    /// `:`
+    CommandLine,
+}
+
+impl Cmd {
+    /// Whether `.` should repeat it.
+    fn is_change(self) -> bool {
+        !matches!(
+            self,
+            Cmd::Move(_)
+                | Cmd::Repeat
+                | Cmd::Visual { .. }
+                | Cmd::History(_)
+                | Cmd::CommandLine
+                | Cmd::Operate(Op::Yank, _)
+        )
+    }
+}
+
+/// Commands in the visual modes.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum VisualCmd {
+    Move(Motion),
+    /// `iw`, `a(`, ...: select the object.
+    Select { object: Object, inner: bool },
+    Operate(Op),
+    /// Linewise variants (`D`, `X`, `Y`, `C`, `S`, `R`).
+    OperateLines(Op),
+    /// `p` / `P`: replace the selection with the register.
+    Put,
+    /// `o`: jump to the other end.
+    SwapEnds,
+    /// `v` / `V`: switch kind, or leave when it's the current one.
+    Switch { line: bool },
+    /// `:`: the command line, for the selected lines.
+    CommandLine,
+}
+
+enum Parse<C = Cmd> {
+    Done(Option<usize>, C),
+    More,
+    Invalid,
+}
+
+/// What `.` replays.
+#[derive(Clone, Debug)]
+struct Change {
+    cmd: Cmd,
+    count: Option<usize>,
+    /// Text typed in insert mode, for commands that end up there.
+    inserted: Option<String>,
+}
+
+/// An insert-mode session started by a command, to be completed on `Esc`.
+struct InsertSession {
+    /// What `.` replays; `None` for changes it can't (multi-line visual).
+    cmd: Option<Cmd>,
+    count: Option<usize>,
+    // the buffer when insert mode began; diffed on Esc to get what was typed
+    before: String,
+}
+
+#[derive(Clone, Default)]
+struct Register {
+    text: String,
+    linewise: bool,
+}
+
+pub(crate) struct Vim {
+    pub mode: Mode,
+    /// Other end of the selection in the visual modes.
+    anchor: usize,
+    /// The cursor, while in a visual mode; the textarea only shows the
+    /// selection, which doesn't say where in it the cursor is.
+    visual_cursor: usize,
+    pending: Vec<String>,
+    register: Register,
+    last_change: Option<Change>,
+    insert: Option<InsertSession>,
+}
+
+impl Default for Vim {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Vim {
+    pub fn new() -> Self {
+        Self {
+            mode: Mode::Normal,
+            anchor: 0,
+            visual_cursor: 0,
+            pending: Vec::new(),
+            register: Register::default(),
+            last_change: None,
+            insert: None,
+        }
+    }
+
+    /// Keys typed so far of an unfinished command (vim's `showcmd`).
+    pub fn pending(&self) -> String {
+        self.pending.concat()
+    }
+
+    /// Drop a visual selection, e.g. when the textarea was clicked.
+    pub fn leave_visual(&mut self) {
+        if matches!(self.mode, Mode::Visual | Mode::VisualLine) {
+            self.mode = Mode::Normal;
+            self.pending.clear();
+        }
+    }
+
+    /// Where the cursor is in a visual mode, clamped to `text`.
+    pub fn visual_cursor(&self, text: &str) -> Option<usize> {
+        matches!(self.mode, Mode::Visual | Mode::VisualLine)
+            .then(|| floor_char(text, self.visual_cursor))
+    }
+
+    /// Byte range to highlight in the visual modes; linewise selections
+    /// include the final line break.
+    pub fn selection(&self, text: &str, cursor: usize) -> Option<(usize, usize)> {
+        match self.mode {
+            Mode::Visual | Mode::VisualLine => {
+                let range = self.visual_range(text, cursor);
+                let end = if range.linewise { next_char(text, range.end) } else { range.end };
+                Some((range.start, end))
+            }
+            _ => None,
+        }
+    }
+
+    /// Feed one key. `cursor` is a byte offset into `text`; both are updated
+    /// in place. Keys are `KeyboardEvent.key` values, plus `<C-r>`.
+    pub fn key(&mut self, key: &str, text: &mut String, cursor: &mut usize) -> Outcome {
+        if self.mode == Mode::Insert {
+            if key != "Escape" {
+                return Outcome::PassThrough;
+            }
+            self.finish_insert(text, cursor);
+            return Outcome::Handled;
+        }
+
+        if key == "Escape" {
+            self.pending.clear();
+            self.mode = Mode::Normal;
+            *cursor = clamp_normal(text, *cursor);
+            return Outcome::Handled;
+        }
+        // Shift, Enter, arrows, ...: swallowed, but not part of a command
+        if key.chars().count() != 1 && key != "<C-r>" {
+            return Outcome::Handled;
+        }
+        self.pending.push(key.to_owned());
+
+        if self.mode == Mode::Normal {
+            match parse(&self.pending) {
+                Parse::More => {}
+                Parse::Invalid => self.pending.clear(),
+                Parse::Done(count, Cmd::History(step)) => {
+                    self.pending.clear();
+                    return Outcome::History(step, count.unwrap_or(1));
+                }
+                Parse::Done(_, Cmd::CommandLine) => {
+                    self.pending.clear();
+                    return Outcome::CommandLine(String::new());
+                }
+                Parse::Done(count, cmd) => {
+                    self.pending.clear();
+                    self.run(cmd, count, text, cursor);
+                }
+            }
+        } else {
+            match parse_visual(&self.pending) {
+                Parse::More => {}
+                Parse::Invalid => self.pending.clear(),
+                Parse::Done(_, VisualCmd::CommandLine) => {
+                    self.pending.clear();
+                    let range = self.visual_range(text, *cursor);
+                    let line = |at: usize| text[..at].matches('\n').count() + 1;
+                    self.mode = Mode::Normal;
+                    *cursor = clamp_normal(text, *cursor);
+                    let last = prev_char(text, range.end).max(range.start);
+                    return Outcome::CommandLine(format!("{},{}", line(range.start), line(last)));
+                }
+                Parse::Done(count, cmd) => {
+                    self.pending.clear();
+                    self.visual(cmd, count, text, cursor);
+                }
+            }
+        }
+        self.visual_cursor = *cursor;
+        Outcome::Handled
+    }
+
+    fn run(&mut self, cmd: Cmd, count: Option<usize>, text: &mut String, cursor: &mut usize) {
+        if cmd == Cmd::Repeat {
+            self.repeat(count, text, cursor);
+            return;
+        }
+
+        let n = count.unwrap_or(1);
+        match cmd {
+            Cmd::Move(motion) => {
+                if let Some((pos, _)) = eval_motion(motion, count, text, *cursor) {
+                    *cursor = pos;
+                }
+            }
+            Cmd::Operate(op, target) => {
+                if let Some(range) = resolve(op, target, n, count, text, *cursor) {
+                    self.operate(op, range, text, cursor);
+                }
+            }
+            Cmd::DeleteChar => {
+                let end = (0..n).fold(*cursor, |at, _| {
+                    if at < line_end(text, at) { next_char(text, at) } else { at }
+                });
+                if end > *cursor {
+                    self.operate(Op::Delete, Range::chars(*cursor, end), text, cursor);
+                }
+            }
+            Cmd::Insert(at) => self.start_insert(at, text, cursor),
+            Cmd::Put { before } => self.put(before, n, text, cursor),
+            Cmd::Visual { line } => {
+                self.mode = if line { Mode::VisualLine } else { Mode::Visual };
+                self.anchor = *cursor;
+            }
+            Cmd::Repeat | Cmd::History(_) | Cmd::CommandLine => unreachable!(),
+        }
+
+        self.finish(cmd.is_change().then_some(cmd), count, text, cursor);
+    }
+
+    /// After a change: start tracking the insert session it opened, or
+    /// remember it for `.`.
+    fn finish(&mut self, cmd: Option<Cmd>, count: Option<usize>, text: &str, cursor: &mut usize) {
+        if self.mode == Mode::Insert {
+            self.insert = Some(InsertSession {
+                cmd,
+                count,
+                before: text.to_owned(),
+            });
+            return;
+        }
+        if let Some(cmd) = cmd {
+            self.last_change = Some(Change {
+                cmd,
+                count,
+                inserted: None,
+            });
+        }
+        *cursor = clamp_normal(text, *cursor);
+    }
+
+    fn visual(&mut self, cmd: VisualCmd, count: Option<usize>, text: &mut String, cursor: &mut usize) {
+        match cmd {
+            VisualCmd::Move(motion) => {
+                if let Some((pos, _)) = eval_motion(motion, count, text, *cursor) {
+                    *cursor = pos;
+                }
+            }
+            VisualCmd::Select { object, inner } => {
+                let range = text_object(object, inner, text, *cursor);
+                if let Some(range) = range.filter(|r| r.end > r.start) {
+                    self.anchor = range.start;
+                    *cursor = prev_char(text, range.end);
+                    if range.linewise {
+                        self.mode = Mode::VisualLine;
+                    }
+                }
+            }
+            VisualCmd::SwapEnds => {
+                let anchor = floor_char(text, self.anchor);
+                self.anchor = *cursor;
+                *cursor = anchor;
+            }
+            VisualCmd::Switch { line } => {
+                let kind = if line { Mode::VisualLine } else { Mode::Visual };
+                self.mode = if self.mode == kind { Mode::Normal } else { kind };
+            }
+            VisualCmd::CommandLine => unreachable!(),
+            VisualCmd::Operate(op) | VisualCmd::OperateLines(op) => {
+                if matches!(cmd, VisualCmd::OperateLines(_)) {
+                    self.mode = Mode::VisualLine;
+                }
+                let range = self.visual_range(text, *cursor);
+                let repeat = self.visual_repeat(op, range, text);
+
+                self.mode = Mode::Normal;
+                *cursor = range.start;
+                self.operate(op, range, text, cursor);
+
+                let (cmd, count) = repeat.unzip();
+                self.finish(cmd.filter(|c| c.is_change()), count, text, cursor);
+            }
+            VisualCmd::Put => {
+                let range = self.visual_range(text, *cursor);
+                let Register { text: pasted, linewise } = self.register.clone();
+                // the replaced text takes the register's place, as in vim
+                self.yank(text, range);
+
+                let pasted = match (range.linewise, linewise) {
+                    (false, false) => pasted,
+                    (false, true) => format!("\n{pasted}"),
+                    (true, _) => pasted.strip_suffix('\n').unwrap_or(&pasted).to_owned(),
+                };
+                text.replace_range(range.start..range.end, &pasted);
+
+                self.mode = Mode::Normal;
+                *cursor = if range.linewise {
+                    first_non_blank(text, range.start)
+                } else {
+                    prev_char(text, range.start + pasted.len())
+                };
+                *cursor = clamp_normal(text, *cursor);
+            }
+        }
+    }
+
+    fn visual_range(&self, text: &str, cursor: usize) -> Range {
+        let anchor = floor_char(text, self.anchor);
+        if self.mode == Mode::VisualLine {
+            Range::lines(text, anchor, cursor)
+        } else {
+            let end = next_char(text, anchor.max(cursor));
+            Range::chars(anchor.min(cursor), end)
+        }
+    }
+
+    /// The normal-mode command `.` repeats a visual operator as: the same
+    /// number of lines, or of characters within one line.
+    fn visual_repeat(&self, op: Op, range: Range, text: &str) -> Option<(Cmd, usize)> {
+        let selected = &text[range.start..range.end];
+        if range.linewise {
+            Some((Cmd::Operate(op, Target::Lines), selected.lines().count().max(1)))
+        } else if !selected.contains('\n') {
+            let chars = selected.chars().count();
+            Some((Cmd::Operate(op, Target::Motion(Motion::Right)), chars))
+        } else {
+            None
+        }
+    }
+
+    fn repeat(&mut self, count: Option<usize>, text: &mut String, cursor: &mut usize) {
+        let Some(change) = self.last_change.clone() else {
+            return;
+        };
+        let count = count.or(change.count);
+
+        self.run(change.cmd, count, text, cursor);
+        if self.mode == Mode::Insert {
+            let typed = change.inserted.unwrap_or_default();
+            text.insert_str(*cursor, &typed);
+            *cursor += typed.len();
+            self.finish_insert(text, cursor);
+        }
+    }
+
+    /// `Esc` from insert mode: work out what was typed, apply the count
+    /// (`3ifoo<Esc>`), remember it all for `.`.
+    fn finish_insert(&mut self, text: &mut String, cursor: &mut usize) {
+        self.mode = Mode::Normal;
+
+        if let Some(session) = self.insert.take() {
+            // one contiguous edit, which is all insert mode does without leaving it
+            let (start, _, end) = changed(&session.before, text);
+            let typed = text[start..end].to_owned();
+
+            let extra = session.count.unwrap_or(1).saturating_sub(1);
+            // counts repeat plain inserts, not `cw` and friends
+            if extra > 0 && matches!(session.cmd, Some(Cmd::Insert(_))) && !typed.is_empty() {
+                let more = typed.repeat(extra);
+                text.insert_str(*cursor, &more);
+                *cursor += more.len();
+            }
+
+            if let Some(cmd) = session.cmd {
+                self.last_change = Some(Change {
+                    cmd,
+                    count: session.count,
+                    inserted: Some(typed),
+                });
+            }
+        }
+
+        // like vim, leaving insert mode steps back onto the last typed char
+        if *cursor > line_start(text, *cursor) {
+            *cursor = prev_char(text, *cursor);
+        }
+        *cursor = clamp_normal(text, *cursor);
+    }
+
+    fn start_insert(&mut self, at: InsertAt, text: &mut String, cursor: &mut usize) {
+        let pos = *cursor;
+        *cursor = match at {
+            InsertAt::Cursor => pos,
+            InsertAt::After => {
+                if pos < line_end(text, pos) { next_char(text, pos) } else { pos }
+            }
+            InsertAt::LineEnd => line_end(text, pos),
+            InsertAt::FirstNonBlank => first_non_blank(text, pos),
+            InsertAt::Below => {
+                let indent = indentation(text, pos).to_owned();
+                let end = line_end(text, pos);
+                text.insert_str(end, &format!("\n{indent}"));
+                end + 1 + indent.len()
+            }
+            InsertAt::Above => {
+                let indent = indentation(text, pos).to_owned();
+                let start = line_start(text, pos);
+                text.insert_str(start, &format!("{indent}\n"));
+                start + indent.len()
+            }
+        };
+        self.mode = Mode::Insert;
+    }
+
+    fn operate(&mut self, op: Op, range: Range, text: &mut String, cursor: &mut usize) {
+        let Range { start, end, linewise } = range;
+
+        match op {
+            Op::Yank => {
+                self.yank(text, range);
+                *cursor = (*cursor).min(start);
+            }
+            Op::Delete => {
+                self.yank(text, range);
+                if linewise {
+                    // take a line break with the lines, from before them if
+                    // they run to the end of the buffer
+                    let (from, to) = if end < text.len() {
+                        (start, end + 1)
+                    } else {
+                        (start.saturating_sub(1), end)
+                    };
+                    text.replace_range(from..to, "");
+                    *cursor = first_non_blank(text, from.min(text.len()));
+                } else {
+                    text.replace_range(start..end, "");
+                    *cursor = start;
+                }
+            }
+            Op::Change => {
+                self.yank(text, range);
+                if linewise {
+                    // keep the first line's indentation, like autoindent
+                    let indent = indentation(text, start).to_owned();
+                    text.replace_range(start..end, &indent);
+                    *cursor = start + indent.len();
+                } else {
+                    text.replace_range(start..end, "");
+                    *cursor = start;
+                }
+                self.mode = Mode::Insert;
+            }
+            Op::Indent | Op::Dedent => {
+                let lines: Vec<String> = text[start..end]
+                    .split('\n')
+                    .map(|line| match op {
+                        _ if line.trim().is_empty() => line.to_owned(),
+                        Op::Indent => format!("{INDENT}{line}"),
+                        _ => {
+                            let strip = line
+                                .bytes()
+                                .take(INDENT.len())
+                                .take_while(|&b| b == b' ')
+                                .count();
+                            let strip =
+                                if strip == 0 && line.starts_with('\t') { 1 } else { strip };
+                            line[strip..].to_owned()
+                        }
+                    })
+                    .collect();
+                text.replace_range(start..end, &lines.join("\n"));
+                *cursor = first_non_blank(text, start);
+            }
+            Op::Format => {
+                let line = |at: usize| text[..at].matches('\n').count() + 1;
+                let last = if linewise { end } else { prev_char(text, end).max(start) };
+                let (first, last) = (line(start), line(last));
+                *text = format_lines(text, first..=last);
+                *cursor = first_non_blank(text, nth_line(text, first));
+            }
+        }
+    }
+
+    fn yank(&mut self, text: &str, range: Range) {
+        let mut copied = text[range.start..range.end].to_owned();
+        if range.linewise {
+            copied.push('\n');
+        }
+        self.register = Register {
+            text: copied,
+            linewise: range.linewise,
+        };
+    }
+
+    fn put(&mut self, before: bool, n: usize, text: &mut String, cursor: &mut usize) {
+        if self.register.text.is_empty() {
+            return;
+        }
+        let pasted = self.register.text.repeat(n);
+
+        if self.register.linewise {
+            let at = if before {
+                line_start(text, *cursor)
+            } else {
+                let end = line_end(text, *cursor);
+                if end == text.len() {
+                    // last line has no break to paste after
+                    text.push('\n');
+                }
+                end + 1
+            };
+
+            let pasted = if at == text.len() {
+                pasted.trim_end_matches('\n')
+            } else {
+                &pasted
+            };
+            text.insert_str(at, pasted);
+            *cursor = first_non_blank(text, at);
+        } else {
+            let at = if before || *cursor == line_end(text, *cursor) {
+                *cursor
+            } else {
+                next_char(text, *cursor)
+            };
+            text.insert_str(at, &pasted);
+            *cursor = prev_char(text, at + pasted.len());
+        }
+    }
+}
+
+/// Byte range an operator works on; linewise ranges cover whole lines,
+/// without the final line break.
+#[derive(Copy, Clone, Debug)]
+struct Range {
+    start: usize,
+    end: usize,
+    linewise: bool,
+}
+
+impl Range {
+    fn chars(a: usize, b: usize) -> Self {
+        Self {
+            start: a.min(b),
+            end: a.max(b),
+            linewise: false,
+        }
+    }
+
+    fn lines(text: &str, a: usize, b: usize) -> Self {
+        Self {
+            start: line_start(text, a.min(b)),
+            end: line_end(text, a.max(b)),
+            linewise: true,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Kind {
+    Exclusive,
+    Inclusive,
+    Linewise,
+}
+
+fn parse(keys: &[String]) -> Parse {
+    let mut keys = keys.iter().map(String::as_str).peekable();
+
+    let count1 = take_count(&mut keys);
+
+    let Some(first) = keys.next() else {
+        return Parse::More;
+    };
+
+    let op = match first {
+        "d" => Some(Op::Delete),
+        "c" => Some(Op::Change),
+        "y" => Some(Op::Yank),
+        ">" => Some(Op::Indent),
+        "<" => Some(Op::Dedent),
+        "=" => Some(Op::Format),
+        "g" if keys.next_if_eq(&"q").is_some() => Some(Op::Format),
+        _ => None,
+    };
+
+    let Some(op) = op else {
+        let cmd = match first {
+            "x" => Cmd::DeleteChar,
+            "i" => Cmd::Insert(InsertAt::Cursor),
+            "a" => Cmd::Insert(InsertAt::After),
+            "A" => Cmd::Insert(InsertAt::LineEnd),
+            "I" => Cmd::Insert(InsertAt::FirstNonBlank),
+            "o" => Cmd::Insert(InsertAt::Below),
+            "O" => Cmd::Insert(InsertAt::Above),
+            "p" => Cmd::Put { before: false },
+            "P" => Cmd::Put { before: true },
+            "." => Cmd::Repeat,
+            "D" => Cmd::Operate(Op::Delete, Target::Motion(Motion::LineEnd)),
+            "C" => Cmd::Operate(Op::Change, Target::Motion(Motion::LineEnd)),
+            "s" => Cmd::Operate(Op::Change, Target::Motion(Motion::Right)),
+            "v" => Cmd::Visual { line: false },
+            "V" => Cmd::Visual { line: true },
+            "u" => Cmd::History(Step::Undo),
+            "<C-r>" => Cmd::History(Step::Redo),
+            ":" => Cmd::CommandLine,
+            "g" if keys.peek().is_some_and(|k| matches!(*k, "-" | "+")) => {
+                let later = keys.next() == Some("+");
+                Cmd::History(if later { Step::Later } else { Step::Earlier })
+            }
+            _ => {
+                return match parse_motion(first, &mut keys) {
+                    Parse::Done(_, Cmd::Move(m)) => Parse::Done(count1, Cmd::Move(m)),
+                    other => other,
+                };
+            }
+        };
+        return if keys.next().is_some() { Parse::Invalid } else { Parse::Done(count1, cmd) };
+    };
+
+    // operator [count]
+    let count2 = take_count(&mut keys);
+    let count = match (count1, count2) {
+        (None, None) => None,
+        (a, b) => Some(a.unwrap_or(1) * b.unwrap_or(1)),
+    };
+
+    let Some(next) = keys.next() else {
+        return Parse::More;
+    };
+
+    // `gqq` and `gqgq` like `dd`; `gqg` could still become `gqgg`
+    let doubled = match first {
+        "g" => next == "q" || (next == "g" && keys.next_if_eq(&"q").is_some()),
+        _ => next == first,
+    };
+    if doubled {
+        return Parse::Done(count, Cmd::Operate(op, Target::Lines));
+    }
+
+    if next == "i" || next == "a" {
+        let Some(obj) = keys.next() else {
+            return Parse::More;
+        };
+        let Some(object) = parse_object(obj) else {
+            return Parse::Invalid;
+        };
+        return Parse::Done(
+            count,
+            Cmd::Operate(op, Target::Object { object, inner: next == "i" }),
+        );
+    }
+
+    match parse_motion(next, &mut keys) {
+        Parse::Done(_, Cmd::Move(m)) => Parse::Done(count, Cmd::Operate(op, Target::Motion(m))),
+        other => other,
+    }
+}
+
+fn parse_visual(keys: &[String]) -> Parse<VisualCmd> {
+    let mut keys = keys.iter().map(String::as_str).peekable();
+
+    let count = take_count(&mut keys);
+    let Some(first) = keys.next() else {
+        return Parse::More;
+    };
+
+    let cmd = match first {
+        "d" | "x" => VisualCmd::Operate(Op::Delete),
+        "c" | "s" => VisualCmd::Operate(Op::Change),
+        "y" => VisualCmd::Operate(Op::Yank),
+        ">" => VisualCmd::Operate(Op::Indent),
+        "<" => VisualCmd::Operate(Op::Dedent),
+        "=" => VisualCmd::OperateLines(Op::Format),
+        "g" if keys.next_if_eq(&"q").is_some() => VisualCmd::OperateLines(Op::Format),
+        "D" | "X" => VisualCmd::OperateLines(Op::Delete),
+        "C" | "S" | "R" => VisualCmd::OperateLines(Op::Change),
+        "Y" => VisualCmd::OperateLines(Op::Yank),
+        "p" | "P" => VisualCmd::Put,
+        "o" | "O" => VisualCmd::SwapEnds,
+        "v" => VisualCmd::Switch { line: false },
+        "V" => VisualCmd::Switch { line: true },
+        ":" => VisualCmd::CommandLine,
+        "i" | "a" => {
+            let Some(obj) = keys.next() else {
+                return Parse::More;
+            };
+            let Some(object) = parse_object(obj) else {
+                return Parse::Invalid;
+            };
+            VisualCmd::Select { object, inner: first == "i" }
+        }
+        _ => {
+            return match parse_motion(first, &mut keys) {
+                Parse::Done(_, Cmd::Move(m)) => Parse::Done(count, VisualCmd::Move(m)),
+                Parse::More => Parse::More,
+                _ => Parse::Invalid,
+            };
+        }
+    };
+    Parse::Done(count, cmd)
+}
+
+fn parse_object(key: &str) -> Option<Object> {
+    Some(match key {
+        "w" => Object::Word,
+        "(" | ")" | "b" => Object::Block('(', ')'),
+        "{" | "}" | "B" => Object::Block('{', '}'),
+        "[" | "]" => Object::Block('[', ']'),
+        _ => return None,
+    })
+}
+
+/// Leading digits as a count; a lone `0` is the motion, not a count.
+fn take_count<'a>(keys: &mut Peekable<impl Iterator<Item = &'a str>>) -> Option<usize> {
+    let mut digits = String::new();
+    while let Some(k) =
+        keys.next_if(|k| k.chars().all(|c| c.is_ascii_digit()) && !(digits.is_empty() && *k == "0"))
+    {
+        digits.push_str(k);
+    }
+    digits.parse().ok()
+}
+
+fn parse_motion<'a>(first: &str, keys: &mut impl Iterator<Item = &'a str>) -> Parse {
+    let motion = match first {
+        "h" => Motion::Left,
+        "l" | " " => Motion::Right,
+        "j" => Motion::Down,
+        "k" => Motion::Up,
+        "w" => Motion::WordStart { big: false },
+        "W" => Motion::WordStart { big: true },
+        "b" => Motion::WordBack { big: false },
+        "B" => Motion::WordBack { big: true },
+        "e" => Motion::WordEnd { big: false },
+        "E" => Motion::WordEnd { big: true },
+        "0" => Motion::LineStart,
+        "^" => Motion::FirstNonBlank,
+        "$" => Motion::LineEnd,
+        "G" => Motion::LastLine,
+        "g" => match keys.next() {
+            None => return Parse::More,
+            Some("g") => Motion::FirstLine,
+            Some(_) => return Parse::Invalid,
+        },
+        "f" | "t" | "F" | "T" => {
+            let Some(ch) = keys.next() else {
+                return Parse::More;
+            };
+            let Some(ch) = ch.chars().next() else {
+                return Parse::Invalid;
+            };
+            Motion::Find {
+                ch,
+                backward: first == "F" || first == "T",
+                till: first == "t" || first == "T",
+            }
+        }
+        _ => return Parse::Invalid,
+    };
+    Parse::Done(None, Cmd::Move(motion))
+}
+
+/// Range for `op` over `target`, or `None` when the target doesn't exist
+/// (no enclosing block, `f` that finds nothing, ...).
+fn resolve(
+    op: Op,
+    target: Target,
+    n: usize,
+    count: Option<usize>,
+    text: &str,
+    cursor: usize,
+) -> Option<Range> {
+    let range = match target {
+        Target::Lines => {
+            let last = (1..n).fold(cursor, |at, _| {
+                let end = line_end(text, at);
+                if end < text.len() { end + 1 } else { at }
+            });
+            Range::lines(text, cursor, last)
+        }
+        Target::Object { object, inner } => text_object(object, inner, text, cursor)?,
+        Target::Motion(motion) => {
+            // `cw` changes to the end of the word, like `ce`, counting the
+            // word under the cursor even from its last character (where `e`
+            // would already be off to the next one)
+            let (motion, count) = match motion {
+                Motion::WordStart { big }
+                    if op == Op::Change
+                        && !char_at(text, cursor).is_some_and(char::is_whitespace) =>
+                {
+                    let on_last =
+                        class_at(text, next_char(text, cursor), big) != class_at(text, cursor, big);
+                    (Motion::WordEnd { big }, if on_last { Some(n - 1) } else { count })
+                }
+                m => (m, count),
+            };
+
+            let (mut target, kind) = eval_motion(motion, count, text, cursor)?;
+
+            // `dw` on the last word of a line stops at the line's end
+            if matches!(motion, Motion::WordStart { .. }) && target > line_end(text, cursor) {
+                target = line_end(text, cursor);
+            }
+
+            match kind {
+                Kind::Linewise => Range::lines(text, cursor, target),
+                Kind::Exclusive => Range::chars(cursor, target),
+                Kind::Inclusive => {
+                    let (a, b) = (cursor.min(target), cursor.max(target));
+                    Range::chars(a, if b < text.len() { next_char(text, b) } else { b })
+                }
+            }
+        }
+    };
+
+    // shifting is always by whole lines
+    Some(match op {
+        Op::Indent | Op::Dedent if !range.linewise => Range::lines(text, range.start, range.end),
+        _ => range,
+    })
+}
+
+/// Where `motion` (repeated `count` times) goes from `cursor`.
+fn eval_motion(
+    motion: Motion,
+    count: Option<usize>,
+    text: &str,
+    cursor: usize,
+) -> Option<(usize, Kind)> {
+    let n = count.unwrap_or(1);
+    let repeat = |step: &dyn Fn(usize) -> usize| (0..n).fold(cursor, |at, _| step(at));
+
+    Some(match motion {
+        Motion::Left => (
+            repeat(&|at| if at > line_start(text, at) { prev_char(text, at) } else { at }),
+            Kind::Exclusive,
+        ),
+        Motion::Right => (
+            repeat(&|at| if at < line_end(text, at) { next_char(text, at) } else { at }),
+            Kind::Exclusive,
+        ),
+        Motion::Down | Motion::Up => {
+            let col = text[line_start(text, cursor)..cursor].chars().count();
+            let line = repeat(&|at| {
+                if motion == Motion::Down {
+                    let end = line_end(text, at);
+                    if end < text.len() { end + 1 } else { at }
+                } else {
+                    let start = line_start(text, at);
+                    if start > 0 { line_start(text, start - 1) } else { at }
+                }
+            });
+            (column(text, line_start(text, line), col), Kind::Linewise)
+        }
+        Motion::WordStart { big } => (repeat(&|at| word_start(text, at, big)), Kind::Exclusive),
+        Motion::WordBack { big } => (repeat(&|at| word_back(text, at, big)), Kind::Exclusive),
+        Motion::WordEnd { big } => (repeat(&|at| word_end(text, at, big)), Kind::Inclusive),
+        Motion::LineStart => (line_start(text, cursor), Kind::Exclusive),
+        Motion::FirstNonBlank => (first_non_blank(text, cursor), Kind::Exclusive),
+        Motion::LineEnd => {
+            // `2$` is the end of the next line
+            let line = (1..n).fold(cursor, |at, _| {
+                let end = line_end(text, at);
+                if end < text.len() { end + 1 } else { at }
+            });
+            let end = line_end(text, line);
+            let last = if end > line_start(text, line) { prev_char(text, end) } else { end };
+            (last, Kind::Inclusive)
+        }
+        Motion::FirstLine | Motion::LastLine => {
+            let line = match (motion, count) {
+                (_, Some(n)) => nth_line(text, n),
+                (Motion::FirstLine, None) => 0,
+                _ => line_start(text, text.len()),
+            };
+            (first_non_blank(text, line), Kind::Linewise)
+        }
+        Motion::Find { ch, backward, till } => {
+            let (start, end) = (line_start(text, cursor), line_end(text, cursor));
+            let mut at = cursor;
+            for _ in 0..n {
+                let found = if backward {
+                    text[start..at].rfind(ch).map(|i| start + i)
+                } else {
+                    let from = next_char(text, at).min(end);
+                    text[from..end].find(ch).map(|i| from + i)
+                };
+                at = found?;
+            }
+            let at = match (till, backward) {
+                (false, _) => at,
+                (true, false) => prev_char(text, at),
+                (true, true) => next_char(text, at),
+            };
+            (at, if backward { Kind::Exclusive } else { Kind::Inclusive })
+        }
+    })
+}
+
+fn text_object(object: Object, inner: bool, text: &str, cursor: usize) -> Option<Range> {
+    match object {
+        Object::Word => {
+            let class = class(char_at(text, cursor)?, false);
+            let (start, end) = (line_start(text, cursor), line_end(text, cursor));
+
+            let mut a = cursor;
+            while a > start && class_before(text, a, false) == Some(class) {
+                a = prev_char(text, a);
+            }
+            let mut b = cursor;
+            while b < end && char_at(text, b).map(|c| self::class(c, false)) == Some(class) {
+                b = next_char(text, b);
+            }
+
+            if !inner && class != Class::Space {
+                // trailing space, or leading space when there is none after
+                let mut b2 = b;
+                while b2 < end && char_at(text, b2).is_some_and(|c| c == ' ' || c == '\t') {
+                    b2 = next_char(text, b2);
+                }
+                if b2 > b {
+                    b = b2;
+                } else {
+                    while a > start && class_before(text, a, false) == Some(Class::Space) {
+                        a = prev_char(text, a);
+                    }
+                }
+            }
+            Some(Range::chars(a, b))
+        }
+        Object::Block(open, close) => {
+            let (o, c) = enclosing(text, cursor, open, close)?;
+            if !inner {
+                return Some(Range::chars(o, c + close.len_utf8()));
+            }
+
+            let mut start = o + open.len_utf8();
+            let mut end = c;
+            // a block over several lines: its inner lines, not the breaks
+            // and indentation around them
+            if text[start..end].contains('\n') {
+                if text[start..].starts_with('\n') {
+                    start += 1;
+                }
+                let last_break = text[start..end]
+                    .rfind('\n')
+                    .map(|i| start + i)
+                    .filter(|&lb| text[lb + 1..end].trim().is_empty());
+                if let Some(lb) = last_break {
+                    end = lb;
+                    if end > start {
+                        return Some(Range {
+                            start,
+                            end,
+                            linewise: true,
+                        });
+                    }
+                }
+            }
+            Some(Range::chars(start, end.max(start)))
+        }
+    }
+}
+
+/// Innermost `open`..`close` pair around `cursor` (or starting/ending on it).
+fn enclosing(text: &str, cursor: usize, open: char, close: char) -> Option<(usize, usize)> {
+    let mut depth = 0;
+    let mut o = None;
+    let from = if char_at(text, cursor) == Some(close) {
+        cursor
+    } else {
+        next_char(text, cursor)
+    };
+    for (i, c) in text[..from].char_indices().rev() {
+        if c == close && i != cursor {
+            depth += 1;
+        } else if c == open {
+            if depth == 0 {
+                o = Some(i);
+                break;
+            }
+            depth -= 1;
+        }
+    }
+    let o = o?;
+
+    let mut depth = 0;
+    for (i, c) in text[o + open.len_utf8()..].char_indices() {
+        let i = o + open.len_utf8() + i;
+        if c == open {
+            depth += 1;
+        } else if c == close {
+            if depth == 0 {
+                return Some((o, i));
+            }
+            depth -= 1;
+        }
+    }
+    None
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Class {
+    Space,
+    Word,
+    Punct,
+}
+
+/// `big` = WORD semantics, where only whitespace separates.
+fn class(c: char, big: bool) -> Class {
+    if c.is_whitespace() {
+        Class::Space
+    } else if big || c.is_alphanumeric() || c == '_' {
+        Class::Word
+    } else {
+        Class::Punct
+    }
+}
+
+fn class_before(text: &str, at: usize, big: bool) -> Option<Class> {
+    text[..at].chars().next_back().map(|c| class(c, big))
+}
+
+fn class_at(text: &str, at: usize, big: bool) -> Option<Class> {
+    char_at(text, at).map(|c| class(c, big))
+}
+
+fn word_start(text: &str, at: usize, big: bool) -> usize {
+    let mut at = at;
+    if let Some(cls) = class_at(text, at, big).filter(|&c| c != Class::Space) {
+        while class_at(text, at, big) == Some(cls) {
+            at = next_char(text, at);
+        }
+    }
+    while class_at(text, at, big) == Some(Class::Space) {
+        at = next_char(text, at);
+    }
+    at
+}
+
+fn word_end(text: &str, at: usize, big: bool) -> usize {
+    if at >= text.len() {
+        return at;
+    }
+    let mut at = next_char(text, at);
+    while class_at(text, at, big) == Some(Class::Space) {
+        at = next_char(text, at);
+    }
+    let Some(cls) = class_at(text, at, big) else {
+        return prev_char(text, at);
+    };
+    while class_at(text, next_char(text, at), big) == Some(cls) {
+        at = next_char(text, at);
+    }
+    at
+}
+
+fn word_back(text: &str, at: usize, big: bool) -> usize {
+    let mut at = at;
+    while class_before(text, at, big) == Some(Class::Space) {
+        at = prev_char(text, at);
+    }
+    if let Some(cls) = class_before(text, at, big) {
+        while class_before(text, at, big) == Some(cls) {
+            at = prev_char(text, at);
+        }
+    }
+    at
+}
+
+/// `at` moved back onto a char boundary and into `text`; the anchor can
+/// be stale after the text changed under it (undo in visual mode).
+fn floor_char(text: &str, at: usize) -> usize {
+    let mut at = at.min(text.len());
+    while !text.is_char_boundary(at) {
+        at -= 1;
+    }
+    at
+}
+
+fn char_at(text: &str, at: usize) -> Option<char> {
+    text.get(at..)?.chars().next()
+}
+
+fn next_char(text: &str, at: usize) -> usize {
+    char_at(text, at).map_or(text.len(), |c| at + c.len_utf8())
+}
+
+fn prev_char(text: &str, at: usize) -> usize {
+    text[..at].chars().next_back().map_or(0, |c| at - c.len_utf8())
+}
+
+fn line_start(text: &str, at: usize) -> usize {
+    text[..at].rfind('\n').map_or(0, |i| i + 1)
+}
+
+fn line_end(text: &str, at: usize) -> usize {
+    text[at..].find('\n').map_or(text.len(), |i| at + i)
+}
+
+pub(crate) fn indentation(text: &str, at: usize) -> &str {
+    let start = line_start(text, at);
+    let line = &text[start..line_end(text, at)];
+    &line[..line.len() - line.trim_start().len()]
+}
+
+pub(crate) fn first_non_blank(text: &str, at: usize) -> usize {
+    let start = line_start(text, at);
+    start + indentation(text, at).len()
+}
+
+/// Start of 1-based line `n`, clamped to the last line.
+pub(crate) fn nth_line(text: &str, n: usize) -> usize {
+    text.match_indices('\n')
+        .nth(n.saturating_sub(2))
+        .filter(|_| n > 1)
+        .map_or(if n > 1 { line_start(text, text.len()) } else { 0 }, |(i, _)| i + 1)
+}
+
+/// Offset of character column `col` on the line starting at `start`,
+/// clamped to the line.
+fn column(text: &str, start: usize, col: usize) -> usize {
+    let end = line_end(text, start);
+    text[start..end]
+        .char_indices()
+        .nth(col)
+        .map_or(end, |(i, _)| start + i)
+}
+
+/// Normal mode sits *on* a character, so not past the end of a non-empty
+/// line.
+pub(crate) fn clamp_normal(text: &str, at: usize) -> usize {
+    let at = at.min(text.len());
+    if at == line_end(text, at) && at > line_start(text, at) {
+        prev_char(text, at)
+    } else {
+        at
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A buffer as the editor drives it; `|` in the text marks the cursor.
+    struct Ed {
+        vim: Vim,
+        text: String,
+        cursor: usize,
+    }
+
+    impl Ed {
+        fn new(marked: &str) -> Self {
+            let cursor = marked.find('|').expect("a `|` for the cursor");
+            Self { vim: Vim::new(), text: marked.replacen('|', "", 1), cursor }
+        }
+
+        /// Feed `keys` one by one; `<Esc>` and `<C-r>` are one key each.
+        fn keys(&mut self, keys: &str) -> Outcome {
+            let mut outcome = Outcome::Handled;
+            let mut rest = keys;
+            while let Some(c) = rest.chars().next() {
+                let len = match rest.find('>') {
+                    Some(end) if c == '<' && end > 1 => end + 1,
+                    _ => c.len_utf8(),
+                };
+                let key = match &rest[..len] {
+                    "<Esc>" => "Escape",
+                    key => key,
+                };
+                outcome = self.vim.key(key, &mut self.text, &mut self.cursor);
+                rest = &rest[len..];
+            }
+            outcome
+        }
+
+        /// What the textarea does with keys passed through in insert mode.
+        fn types(&mut self, typed: &str) -> &mut Self {
+            assert_eq!(self.vim.mode, Mode::Insert);
+            self.text.insert_str(self.cursor, typed);
+            self.cursor += typed.len();
+            self
+        }
+
+        fn shown(&self) -> String {
+            let mut shown = self.text.clone();
+            shown.insert(self.cursor, '|');
+            shown
+        }
+    }
+
+    /// `marked` after `keys`.
+    fn after(marked: &str, keys: &str) -> String {
+        let mut ed = Ed::new(marked);
+        ed.keys(keys);
+        ed.shown()
+    }
+
+    #[test]
+    fn motions() {
+        let line = "|let x = foo(bar);";
+        assert_eq!(after(line, "w"), "let |x = foo(bar);");
+        assert_eq!(after(line, "3w"), "let x = |foo(bar);");
+        assert_eq!(after(line, "3we"), "let x = fo|o(bar);");
+        assert_eq!(after(line, "W"), "let |x = foo(bar);");
+        assert_eq!(after(line, "$"), "let x = foo(bar)|;");
+        assert_eq!(after(line, "$b"), "let x = foo(bar|);");
+        assert_eq!(after(line, "$0"), "|let x = foo(bar);");
+        assert_eq!(after(line, "fb"), "let x = foo(|bar);");
+        assert_eq!(after(line, "tb"), "let x = foo|(bar);");
+        assert_eq!(after(line, "2fo"), "let x = fo|o(bar);");
+        assert_eq!(after(line, "$Fx"), "let |x = foo(bar);");
+        assert_eq!(after(line, "fz"), line);
+        assert_eq!(after(line, "5l3h"), "le|t x = foo(bar);");
+
+        let lines = "fn a() {\n    let |x = 1;\n}\n";
+        assert_eq!(after(lines, "^"), "fn a() {\n    |let x = 1;\n}\n");
+        // past the end of a shorter line, onto its last character
+        assert_eq!(after(lines, "k"), "fn a() |{\n    let x = 1;\n}\n");
+        assert_eq!(after(lines, "gg"), "|fn a() {\n    let x = 1;\n}\n");
+        assert_eq!(after(lines, "G"), "fn a() {\n    let x = 1;\n}\n|");
+        assert_eq!(after(lines, "ggjj"), "fn a() {\n    let x = 1;\n|}\n");
+        assert_eq!(after(lines, "2G"), "fn a() {\n    |let x = 1;\n}\n");
+    }
+
+    #[test]
+    fn operators() {
+        assert_eq!(after("|foo bar baz", "dw"), "|bar baz");
+        assert_eq!(after("foo |bar", "dw"), "foo| ");
+        assert_eq!(after("foo b|ar baz", "diw"), "foo | baz");
+        assert_eq!(after("foo b|ar baz", "daw"), "foo |baz");
+        assert_eq!(after("f(a, |b) + 1", "di("), "f(|) + 1");
+        assert_eq!(after("f(a, |b) + 1", "da("), "f| + 1");
+        assert_eq!(after("|let x = 1;", "d$"), "|");
+        assert_eq!(after("let |x = 1;", "D"), "let| ");
+        assert_eq!(after("let x = |1;", "dh"), "let x =|1;");
+        assert_eq!(after("let x = |foo(1);", "dt("), "let x = |(1);");
+        assert_eq!(after("let x = |foo(1);", "df("), "let x = |1);");
+
+        assert_eq!(after("a\n|b\nc", "dd"), "a\n|c");
+        assert_eq!(after("a\nb\n|c", "dd"), "a\n|b");
+        assert_eq!(after("|a\nb\nc", "dj"), "|c");
+        assert_eq!(after("|a\nb", "yyp"), "a\n|a\nb");
+        assert_eq!(after("a|b", "ylp"), "ab|b");
+        assert_eq!(after("|a\nb", "ddp"), "b\n|a");
+        assert_eq!(after("|x;", ">>"), "    |x;");
+        assert_eq!(after("        |x;", "<<"), "    |x;");
+
+        let mut ed = Ed::new("let |x = 1;");
+        ed.keys("cw");
+        ed.types("count").keys("<Esc>");
+        assert_eq!(ed.shown(), "let coun|t = 1;");
+
+        let mut ed = Ed::new("let |xy = 1;");
+        ed.keys("c2w");
+        ed.types("z").keys("<Esc>");
+        assert_eq!(ed.shown(), "let |z 1;");
+
+        let mut ed = Ed::new("    |a\nb");
+        ed.keys("cc");
+        ed.types("z").keys("<Esc>");
+        assert_eq!(ed.shown(), "    |z\nb");
+    }
+
+    #[test]
+    fn counts() {
+        assert_eq!(after("|abcdef", "3x"), "|def");
+        assert_eq!(after("|a b c d e f g", "2d3w"), "|g");
+        assert_eq!(after("|a b c d e f g", "3d2w"), "|g");
+        assert_eq!(after("|a\nb\nc\nd", "3dd"), "|d");
+        assert_eq!(after("|a\nb", "yy3p"), "a\n|a\na\na\nb");
+        assert_eq!(after("|x", "2>>"), "    |x");
+
+        let mut ed = Ed::new("|");
+        ed.keys("3i");
+        ed.types("ab").keys("<Esc>");
+        assert_eq!(ed.shown(), "ababa|b");
+
+        // a count that doesn't come to a command goes
+        let mut ed = Ed::new("|a b");
+        ed.keys("3");
+        assert_eq!(ed.vim.pending(), "3");
+        ed.keys("<Esc>w");
+        assert_eq!(ed.shown(), "a |b");
+    }
+
+    #[test]
+    fn dot_repeats_the_last_change() {
+        assert_eq!(after("|a b c d", "dw.."), "|d");
+        assert_eq!(after("|abcdef", "x2."), "|def");
+        // moving and yanking aren't changes
+        assert_eq!(after("|a b c d", "dwwyw."), "b |d");
+
+        let mut ed = Ed::new("|foo bar baz");
+        ed.keys("ciw");
+        ed.types("x").keys("<Esc>");
+        ed.keys("w.w.");
+        assert_eq!(ed.shown(), "x x |x");
+
+        let mut ed = Ed::new("|");
+        ed.keys("2A");
+        ed.types("ab").keys("<Esc>");
+        ed.keys(".");
+        assert_eq!(ed.shown(), "abababa|b");
+
+        let mut ed = Ed::new("|a\nb\nc\nd");
+        ed.keys("Vjd");
+        assert_eq!(ed.shown(), "|c\nd");
+        ed.keys(".");
+        assert_eq!(ed.shown(), "|");
+    }
+
+    #[test]
+    fn visual_modes() {
+        assert_eq!(after("|abcdef", "vlld"), "|def");
+        assert_eq!(after("abc|def", "vhhd"), "a|ef");
+        assert_eq!(after("ab|cdef", "vlohd"), "a|ef");
+        assert_eq!(after("f(a, |b)", "vi(d"), "f(|)");
+        assert_eq!(after("|a\nb\nc", "Vjd"), "|c");
+        assert_eq!(after("|a\nb\nc", "vjD"), "|c");
+        assert_eq!(after("|one two", "yiwwviwp"), "one on|e");
+        assert_eq!(after("|ab", "vvx"), "|b");
+
+        let mut ed = Ed::new("|a\nb\nc");
+        ed.keys("Vj");
+        assert_eq!(ed.vim.selection(&ed.text, ed.cursor), Some((0, 4)));
+        assert_eq!(ed.keys(":"), Outcome::CommandLine("1,2".to_owned()));
+        assert_eq!(ed.vim.mode, Mode::Normal);
+    }
+
+    #[test]
+    fn history_keys_come_back_out() {
+        let mut ed = Ed::new("|a");
+        assert_eq!(ed.keys("u"), Outcome::History(Step::Undo, 1));
+        assert_eq!(ed.keys("3<C-r>"), Outcome::History(Step::Redo, 3));
+        assert_eq!(ed.keys("g-"), Outcome::History(Step::Earlier, 1));
+        assert_eq!(ed.keys("2g+"), Outcome::History(Step::Later, 2));
+        assert_eq!(ed.keys(":"), Outcome::CommandLine(String::new()));
+        assert_eq!(ed.keys("i"), Outcome::Handled);
+        assert_eq!(ed.keys("u"), Outcome::PassThrough);
+        assert_eq!(ed.shown(), "|a");
+    }
+}
@@ -0,0 +1,350 @@
+//! Lossless WGSL tokenizer for highlighting: every byte of the source ends
+//! up in exactly one token, and broken input still tokenizes (it just gets
+//! coloured as punctuation), since the editor shows half-typed code.
+
+use std::{iter::Peekable, ops::Range, str::CharIndices};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum TokenKind {
+    Keyword,
+    Type,
+    /// `@group`, `@binding`, `@vertex`, ... (the `@` and the name).
+    Attribute,
+    /// Built-in functions (`sin`, `textureSample`, ...).
+    Builtin,
+    Number,
+    Comment,
+    Ident,
+    Punct,
+    Whitespace,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Token {
+    pub kind: TokenKind,
+    /// Byte range into the source.
+    pub span: Range<usize>,
+}
+
+const KEYWORDS: &[&str] = &[
+    "alias", "break", "case", "const", "const_assert", "continue", "continuing", "default",
+    "diagnostic", "discard", "else", "enable", "false", "fn", "for", "if", "let", "loop",
+    "override", "requires", "return", "struct", "switch", "true", "var", "while",
+    // address spaces and access modes, only meaningful inside `var<...>`
+    // and `ptr<...>` but nobody names things `uniform`
+    "function", "private", "workgroup", "uniform", "storage", "read", "write", "read_write",
+];
+
+const TYPES: &[&str] = &[
+    "bool", "f16", "f32", "i32", "u32", "array", "atomic", "ptr", "sampler",
+    "sampler_comparison",
+];
+
+pub(crate) const BUILTINS: &[&str] = &[
+    "abs", "acos", "acosh", "all", "any", "arrayLength", "asin", "asinh", "atan", "atan2",
+    "atanh", "bitcast", "ceil", "clamp", "cos", "cosh", "countLeadingZeros", "countOneBits",
+    "countTrailingZeros", "cross", "degrees", "determinant", "distance", "dot", "dpdx",
+    "dpdxCoarse", "dpdxFine", "dpdy", "dpdyCoarse", "dpdyFine", "exp", "exp2", "extractBits",
+    "faceForward", "firstLeadingBit", "firstTrailingBit", "floor", "fma", "fract", "frexp",
+    "fwidth", "insertBits", "inverseSqrt", "ldexp", "length", "log", "log2", "max", "min",
+    "mix", "modf", "normalize", "pack2x16float", "pack4x8snorm", "pack4x8unorm", "pow",
+    "quantizeToF16", "radians", "reflect", "refract", "reverseBits", "round", "saturate",
+    "select", "sign", "sin", "sinh", "smoothstep", "sqrt", "step", "storageBarrier", "tan",
+    "tanh", "textureDimensions", "textureGather", "textureGatherCompare", "textureLoad",
+    "textureNumLayers", "textureNumLevels", "textureNumSamples", "textureSample",
+    "textureSampleBias", "textureSampleCompare", "textureSampleCompareLevel",
+    "textureSampleGrad", "textureSampleLevel", "textureStore", "transpose", "trunc",
+    "unpack2x16float", "unpack4x8snorm", "unpack4x8unorm", "workgroupBarrier",
+];
+
+fn is_ident_start(c: char) -> bool {
+    c == '_' || c.is_alphabetic()
+}
+
+fn is_ident_continue(c: char) -> bool {
+    c == '_' || c.is_alphanumeric()
+}
+
+/// `vec3`, `vec3f`, `mat4x4`, `mat2x3h`, `texture_2d`, ...
+fn is_sized_type(word: &str) -> bool {
+    let suffix_ok = |rest: &str| matches!(rest, "" | "f" | "h" | "i" | "u");
+
+    if let Some(rest) = word.strip_prefix("vec") {
+        let mut chars = rest.chars();
+        return matches!(chars.next(), Some('2'..='4')) && suffix_ok(chars.as_str());
+    }
+    if let Some(rest) = word.strip_prefix("mat") {
+        let b = rest.as_bytes();
+        return b.len() >= 3
+            && (b'2'..=b'4').contains(&b[0])
+            && b[1] == b'x'
+            && (b'2'..=b'4').contains(&b[2])
+            && matches!(&rest[3..], "" | "f" | "h");
+    }
+    word.starts_with("texture_")
+}
+
+fn classify(word: &str) -> TokenKind {
+    if KEYWORDS.contains(&word) {
+        TokenKind::Keyword
+    } else if TYPES.contains(&word) || is_sized_type(word) {
+        TokenKind::Type
+    } else if BUILTINS.contains(&word) {
+        TokenKind::Builtin
+    } else {
+        TokenKind::Ident
+    }
+}
+
+type Chars<'a> = Peekable<CharIndices<'a>>;
+
+/// Byte offset of the next char.
+fn offset(src: &str, chars: &mut Chars) -> usize {
+    chars.peek().map_or(src.len(), |&(i, _)| i)
+}
+
+/// Advance while `pred` holds; returns the end offset.
+fn eat_while(src: &str, chars: &mut Chars, pred: impl Fn(char) -> bool) -> usize {
+    while chars.next_if(|&(_, c)| pred(c)).is_some() {}
+    offset(src, chars)
+}
+
+pub fn tokenize(src: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut chars = src.char_indices().peekable();
+
+    while let Some((start, c)) = chars.next() {
+        let next = chars.peek().map(|&(_, c)| c);
+
+        let (kind, end) = match c {
+            c if c.is_whitespace() => (TokenKind::Whitespace, eat_while(src, &mut chars, char::is_whitespace)),
+
+            '/' if next == Some('/') => (TokenKind::Comment, eat_while(src, &mut chars, |c| c != '\n')),
+
+            '/' if next == Some('*') => {
+                chars.next();
+                // block comments nest; an unterminated one runs to the end
+                let mut depth = 1;
+                let mut prev = '\0';
+                while depth > 0 {
+                    let Some((_, c)) = chars.next() else { break };
+                    match (prev, c) {
+                        ('/', '*') => {
+                            depth += 1;
+                            prev = '\0';
+                            continue;
+                        }
+                        ('*', '/') => {
+                            depth -= 1;
+                            prev = '\0';
+                            continue;
+                        }
+                        _ => {}
+                    }
+                    prev = c;
+                }
+                (TokenKind::Comment, offset(src, &mut chars))
+            }
+
+            '@' if next.is_some_and(is_ident_start) => {
+                (TokenKind::Attribute, eat_while(src, &mut chars, is_ident_continue))
+            }
+
+            c if c.is_ascii_digit() || (c == '.' && next.is_some_and(|n| n.is_ascii_digit())) => {
+                let end = if c == '0' && matches!(next, Some('x' | 'X')) {
+                    chars.next();
+                    eat_while(src, &mut chars, |c| {
+                        c.is_ascii_hexdigit() || matches!(c, '.' | 'p' | 'P' | 'i' | 'u' | 'h')
+                    })
+                } else {
+                    // digits, fraction, exponent and suffix; a sign only
+                    // directly after the exponent marker
+                    let mut prev = c;
+                    while let Some((_, n)) = chars.next_if(|&(_, n)| {
+                        n.is_ascii_digit()
+                            || matches!(n, '.' | 'e' | 'E' | 'i' | 'u' | 'f' | 'h')
+                            || (matches!(n, '+' | '-') && matches!(prev, 'e' | 'E'))
+                    }) {
+                        prev = n;
+                    }
+                    offset(src, &mut chars)
+                };
+                (TokenKind::Number, end)
+            }
+
+            c if is_ident_start(c) => {
+                let end = eat_while(src, &mut chars, is_ident_continue);
+                (classify(&src[start..end]), end)
+            }
+
+            _ => (TokenKind::Punct, start + c.len_utf8()),
+        };
+
+        tokens.push(Token {
+            kind,
+            span: start..end,
+        });
+    }
+
+    tokens
+}
+
+/// Tokens cut at line breaks: one list of `(byte range, kind)` per line,
+/// newlines themselves dropped. A block comment over three lines shows up
+/// as a comment segment on each of them.
+pub fn lines(src: &str, tokens: &[Token]) -> Vec<Vec<(Range<usize>, TokenKind)>> {
+    let mut lines = vec![Vec::new()];
+
+    for token in tokens {
+        let mut start = token.span.start;
+        for (i, _) in src[token.span.clone()].match_indices('\n') {
+            let nl = token.span.start + i;
+            if nl > start {
+                lines.last_mut().unwrap().push((start..nl, token.kind));
+            }
+            lines.push(Vec::new());
+            start = nl + 1;
+        }
+        if token.span.end > start {
+            lines.last_mut().unwrap().push((start..token.span.end, token.kind));
+        }
+    }
+
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use TokenKind::*;
+
+    /// Tokens other than whitespace, as text.
+    fn kinds(src: &str) -> Vec<(&str, TokenKind)> {
+        tokenize(src)
+            .into_iter()
+            .filter(|t| t.kind != Whitespace)
+            .map(|t| (&src[t.span], t.kind))
+            .collect()
+    }
+
+    #[test]
+    fn every_byte_lands_in_one_token() {
+        for src in [
+            "",
+            "@vertex fn vs_main(@builtin(vertex_index) i: u32) -> @builtin(position) vec4f {",
+            "let s = \"not a string\"; // ünïcode /* in */ a comment",
+            "/* unterminated /* nested */ comment",
+            "fn é() { return 0x1p-3f + .5e+2h; } @ 1.0.0 $#\\",
+        ] {
+            let tokens = tokenize(src);
+            let mut at = 0;
+            for token in &tokens {
+                assert_eq!(token.span.start, at, "{src}");
+                assert!(token.span.end > at, "{src}");
+                at = token.span.end;
+            }
+            assert_eq!(at, src.len(), "{src}");
+        }
+    }
+
+    #[test]
+    fn comments() {
+        assert_eq!(
+            kinds("a // b @c \"d\"\ne"),
+            [("a", Ident), ("// b @c \"d\"", Comment), ("e", Ident)],
+        );
+        assert_eq!(kinds("a /* b */ c"), [("a", Ident), ("/* b */", Comment), ("c", Ident)]);
+        assert_eq!(
+            kinds("/* a /* b */ c */ d"),
+            [("/* a /* b */ c */", Comment), ("d", Ident)],
+        );
+        assert_eq!(kinds("a /* b /* c */"), [("a", Ident), ("/* b /* c */", Comment)]);
+        // `*/` can't close on the `*` that opened it
+        assert_eq!(kinds("/*/ a */"), [("/*/ a */", Comment)]);
+        assert_eq!(kinds("a / b"), [("a", Ident), ("/", Punct), ("b", Ident)]);
+    }
+
+    #[test]
+    fn strings_are_not_wgsl() {
+        // no string literals: quotes are punctuation and what's between
+        // them tokenizes as code, so nothing swallows the rest of the line
+        assert_eq!(
+            kinds("\"fn x\""),
+            [("\"", Punct), ("fn", Keyword), ("x", Ident), ("\"", Punct)],
+        );
+        assert_eq!(kinds("'a'"), [("'", Punct), ("a", Ident), ("'", Punct)]);
+    }
+
+    #[test]
+    fn attributes() {
+        assert_eq!(
+            kinds("@group(0) @binding(1) var<uniform> u: Camera;"),
+            [
+                ("@group", Attribute),
+                ("(", Punct),
+                ("0", Number),
+                (")", Punct),
+                ("@binding", Attribute),
+                ("(", Punct),
+                ("1", Number),
+                (")", Punct),
+                ("var", Keyword),
+                ("<", Punct),
+                ("uniform", Keyword),
+                (">", Punct),
+                ("u", Ident),
+                (":", Punct),
+                ("Camera", Ident),
+                (";", Punct),
+            ],
+        );
+        assert_eq!(kinds("@builtin_x"), [("@builtin_x", Attribute)]);
+        assert_eq!(kinds("@ vertex"), [("@", Punct), ("vertex", Ident)]);
+        assert_eq!(kinds("@0"), [("@", Punct), ("0", Number)]);
+    }
+
+    #[test]
+    fn numbers() {
+        for n in ["1", "1.0", ".5", "1e-3", "1E+3f", "2.5h", "3i", "7u", "0x1Fu", "0x1.8p3"] {
+            assert_eq!(kinds(n), [(n, Number)], "{n}");
+        }
+        assert_eq!(kinds("1-2"), [("1", Number), ("-", Punct), ("2", Number)]);
+        assert_eq!(kinds("a.x"), [("a", Ident), (".", Punct), ("x", Ident)]);
+    }
+
+    #[test]
+    fn words() {
+        for (word, kind) in [
+            ("fn", Keyword),
+            ("read_write", Keyword),
+            ("f32", Type),
+            ("vec3f", Type),
+            ("vec4", Type),
+            ("mat4x4", Type),
+            ("mat2x3h", Type),
+            ("texture_2d", Type),
+            ("textureSample", Builtin),
+            ("sin", Builtin),
+            ("vec5", Ident),
+            ("mat4x5", Ident),
+            ("sine", Ident),
+            ("_tmp1", Ident),
+        ] {
+            assert_eq!(kinds(word), [(word, kind)], "{word}");
+        }
+    }
+
+    #[test]
+    fn lines_split_tokens_at_breaks() {
+        let src = "a /* b\nc */ d\n\ne";
+        let lines = lines(src, &tokenize(src));
+        let text = |line: &[(Range<usize>, TokenKind)]| -> Vec<(&str, TokenKind)> {
+            line.iter().map(|(span, kind)| (&src[span.clone()], *kind)).collect()
+        };
+        assert_eq!(lines.len(), 4);
+        assert_eq!(text(&lines[0]), [("a", Ident), (" ", Whitespace), ("/* b", Comment)]);
+        assert_eq!(text(&lines[1]), [("c */", Comment), (" ", Whitespace), ("d", Ident)]);
+        assert!(lines[2].is_empty());
+        assert_eq!(text(&lines[3]), [("e", Ident)]);
+    }
+}
@@ -0,0 +1,256 @@
+//! Shader Lab state in a link: demo, both sources (or the one module),
+//! edited modules, entry points, texture URLs and samplers, the built-in
+//! model and the pass toggles, as deflated JSON in the URL fragment.
+//! Fragments never reach the server, so a link is all there is to it.
+
+use std::collections::BTreeMap;
+
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use miniz_oxide::inflate::TINFLStatus;
+use serde::{Deserialize, Serialize};
+
+use crate::components::demo::Demo;
+use crate::render::renderer::gpu::samplers::SamplerDesc;
+
+/// Bumped when the payload changes shape; old links then fail to decode
+/// instead of restoring something half right.
+const VERSION: &str = "1";
+
+/// Most a link inflates to; well past a lab with every module edited, and
+/// small enough that a crafted link can't eat the tab's memory.
+const MAX_JSON: usize = 4 * 1024 * 1024;
+
+/// Route that restores a shared lab.
+pub const SHARE_PATH: &str = "/share";
+
+#[derive(Clone)]
+pub struct Shared {
+    pub demo: Demo,
+    pub vs: String,
+    pub fs: String,
+    /// Pass label to on/off; passes the demo doesn't have are ignored.
+    pub passes: BTreeMap<String, bool>,
+    /// Modules that differ from the shipped ones, by name.
+    pub files: BTreeMap<String, String>,
+    /// The one module both stages come from, when it's used.
+    pub module: Option<String>,
+    /// Entry points picked, by `pass/vs` or `pass/fs`.
+    pub entry_points: BTreeMap<String, String>,
+    /// Texture URLs by channel; `None` for the demo's own. Dropped files
+    /// don't travel.
+    pub textures: Option<BTreeMap<String, String>>,
+    /// Samplers picked, by channel.
+    pub samplers: BTreeMap<String, SamplerDesc>,
+    /// Built-in model drawn instead of the demo's mesh; uploaded ones don't
+    /// travel.
+    pub model: Option<String>,
+}
+
+impl Shared {
+    /// The lab `extras` describe, around `demo` and its sources.
+    pub(crate) fn with_extras(demo: Demo, vs: String, fs: String, extras: Extras) -> Self {
+        Self {
+            demo,
+            vs,
+            fs,
+            passes: extras.passes,
+            files: extras.files,
+            module: extras.module,
+            entry_points: extras.entry_points,
+            textures: extras.textures,
+            samplers: extras.samplers,
+            model: extras.model,
+        }
+    }
+
+    pub(crate) fn extras(&self) -> Extras {
+        Extras {
+            passes: self.passes.clone(),
+            files: self.files.clone(),
+            module: self.module.clone(),
+            entry_points: self.entry_points.clone(),
+            textures: self.textures.clone(),
+            samplers: self.samplers.clone(),
+            model: self.model.clone(),
+        }
+    }
+}
+
+/// Everything in a [`Shared`] past the demo and its sources, as links and
+/// saved shaders (see `snippets`) carry it; missing fields take defaults.
+#[derive(Serialize, Deserialize, Default)]
+#[serde(default)]
+pub(crate) struct Extras {
+    passes: BTreeMap<String, bool>,
+    files: BTreeMap<String, String>,
+    module: Option<String>,
+    entry_points: BTreeMap<String, String>,
+    textures: Option<BTreeMap<String, String>>,
+    samplers: BTreeMap<String, SamplerDesc>,
+    model: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Payload {
+    demo: String,
+    vs: String,
+    fs: String,
+    #[serde(flatten)]
+    extras: Extras,
+}
+
+pub fn encode(shared: &Shared) -> String {
+    let payload = Payload {
+        demo: shared.demo.slug().to_owned(),
+        vs: shared.vs.clone(),
+        fs: shared.fs.clone(),
+        extras: shared.extras(),
+    };
+    let json = serde_json::to_vec(&payload).expect("plain strings and bools");
+    let packed = miniz_oxide::deflate::compress_to_vec(&json, 9);
+    format!("{VERSION}.{}", URL_SAFE_NO_PAD.encode(packed))
+}
+
+pub fn decode(fragment: &str) -> Result<Shared, String> {
+    let fragment = fragment.strip_prefix('#').unwrap_or(fragment);
+    let Some((version, data)) = fragment.split_once('.') else {
+        return Err("not a shader link".to_owned());
+    };
+    if version != VERSION {
+        return Err(format!("link is from another version ({version})"));
+    }
+
+    let packed = URL_SAFE_NO_PAD
+        .decode(data)
+        .map_err(|e| format!("link is damaged: {e}"))?;
+    let json = miniz_oxide::inflate::decompress_to_vec_with_limit(&packed, MAX_JSON)
+        .map_err(|e| match e.status {
+            TINFLStatus::HasMoreOutput => "link is too large".to_owned(),
+            _ => format!("link is damaged: {e}"),
+        })?;
+    let payload: Payload =
+        serde_json::from_slice(&json).map_err(|e| format!("link is damaged: {e}"))?;
+
+    let demo = Demo::ALL
+        .into_iter()
+        .find(|d| d.slug() == payload.demo)
+        .ok_or_else(|| format!("unknown demo `{}`", payload.demo))?;
+    Ok(Shared::with_extras(demo, payload.vs, payload.fs, payload.extras))
+}
+
+/// Full link for `shared`, on this site.
+pub fn link(shared: &Shared) -> Option<String> {
+    let origin = web_sys::window()?.location().origin().ok()?;
+    Some(format!("{origin}{SHARE_PATH}#{}", encode(shared)))
+}
+
+/// What the current URL's fragment holds; `None` without one.
+pub fn from_location() -> Option<Result<Shared, String>> {
+    let hash = web_sys::window()?.location().hash().ok()?;
+    (hash.len() > 1).then(|| decode(&hash))
+}
+
+/// Put `text` on the clipboard. Where that isn't allowed (plain http,
+/// permissions), show it in a prompt to copy by hand. Resolves to whether
+/// the clipboard took it.
+pub async fn copy(text: &str) -> bool {
+    let Some(window) = web_sys::window() else { return false };
+    if window.is_secure_context() {
+        let promise = window.navigator().clipboard().write_text(text);
+        if wasm_bindgen_futures::JsFuture::from(promise).await.is_ok() {
+            return true;
+        }
+    }
+    window
+        .prompt_with_message_and_default("Copy this link:", text)
+        .ok();
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lab() -> Shared {
+        Shared {
+            demo: Demo::ALL[0],
+            vs: "// vs".to_owned(),
+            fs: "// fs ü".to_owned(),
+            passes: BTreeMap::from([("bloom".to_owned(), false)]),
+            files: BTreeMap::from([("time".to_owned(), "// mine".to_owned())]),
+            module: Some("// one module".to_owned()),
+            entry_points: BTreeMap::from([("main/fs".to_owned(), "fs_alt".to_owned())]),
+            textures: Some(BTreeMap::from([("0".to_owned(), "/img/a.png".to_owned())])),
+            samplers: BTreeMap::new(),
+            model: Some("torus.obj".to_owned()),
+        }
+    }
+
+    /// A link around `json` as it is, without going through [`Payload`].
+    fn link_to(json: &[u8]) -> String {
+        let packed = miniz_oxide::deflate::compress_to_vec(json, 9);
+        format!("{VERSION}.{}", URL_SAFE_NO_PAD.encode(packed))
+    }
+
+    #[test]
+    fn links_round_trip() {
+        let lab = lab();
+        let back = decode(&format!("#{}", encode(&lab))).unwrap();
+        assert!(back.demo == lab.demo);
+        assert_eq!((&back.vs, &back.fs, &back.module), (&lab.vs, &lab.fs, &lab.module));
+        assert_eq!(back.passes, lab.passes);
+        assert_eq!(back.files, lab.files);
+        assert_eq!(back.entry_points, lab.entry_points);
+        assert_eq!(back.textures, lab.textures);
+        assert_eq!(back.model, lab.model);
+    }
+
+    /// What a saved shader keeps beside its sources (see `snippets`).
+    #[test]
+    fn extras_round_trip_on_their_own() {
+        let lab = lab();
+        let json = serde_json::to_string(&lab.extras()).unwrap();
+        let extras: Extras = serde_json::from_str(&json).unwrap();
+        let back = Shared::with_extras(lab.demo, lab.vs.clone(), lab.fs.clone(), extras);
+        assert_eq!(encode(&back), encode(&lab));
+
+        let empty: Extras = serde_json::from_str("{}").unwrap();
+        let back = Shared::with_extras(lab.demo, String::new(), String::new(), empty);
+        assert_eq!((back.module, back.textures, back.model), (None, None, None));
+    }
+
+    #[test]
+    fn older_payloads_take_defaults() {
+        let shared = decode(&link_to(br#"{"demo":"animals","vs":"a","fs":"b"}"#)).unwrap();
+        assert!(shared.passes.is_empty() && shared.files.is_empty());
+        assert_eq!((shared.module, shared.textures, shared.model), (None, None, None));
+    }
+
+    #[test]
+    fn malformed_links_are_errors() {
+        let fine = encode(&lab());
+        let (_, data) = fine.split_once('.').unwrap();
+        let cases = [
+            ("", "not a shader link"),
+            ("no dot", "not a shader link"),
+            (&format!("0.{data}"), "another version"),
+            ("1.not*base64", "damaged"),
+            (&format!("1.{}", URL_SAFE_NO_PAD.encode(b"not deflate")), "damaged"),
+            (&format!("1.{}", &data[..data.len() / 2]), "damaged"),
+            (&link_to(b"[1, 2]"), "damaged"),
+            (&link_to(br#"{"demo":"nope","vs":"","fs":""}"#), "unknown demo"),
+        ];
+        for (link, expected) in cases {
+            let err = decode(link).err().unwrap_or_else(|| panic!("`{link}` decoded"));
+            assert!(err.contains(expected), "`{link}`: {err}");
+        }
+    }
+
+    #[test]
+    fn links_inflating_past_the_cap_are_refused() {
+        let huge = vec![b' '; MAX_JSON + 1];
+        let err = decode(&link_to(&huge)).err().unwrap();
+        assert!(err.contains("too large"), "{err}");
+    }
+}
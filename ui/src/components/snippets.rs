@@ -0,0 +1,84 @@
+//! Shaders saved on the server (`/api/shaders`): a lab as a share link
+//! carries it, under a short id, for links too long to share as a fragment.
+//! The server keeps the demo and both sources apart from the rest, which it
+//! stores as it gets it.
+
+use gloo_net::http::Request;
+use serde::{Deserialize, Serialize};
+
+use crate::components::demo::Demo;
+use crate::components::share::{Extras, Shared};
+
+/// Route that opens a saved shader, followed by its id.
+pub const SNIPPET_PATH: &str = "/shader";
+
+#[derive(Serialize)]
+struct NewSnippet<'a> {
+    demo: &'a str,
+    vs: &'a str,
+    fs: &'a str,
+    lab: Extras,
+}
+
+#[derive(Deserialize)]
+struct Snippet {
+    id: String,
+    demo: String,
+    vs: String,
+    fs: String,
+    /// Missing from shaders saved with their sources only.
+    #[serde(default)]
+    lab: Extras,
+}
+
+/// Store `shared`; resolves to the link that opens it. Saving the same lab
+/// again hands back the same link.
+pub async fn save(shared: &Shared) -> Result<String, String> {
+    let body = NewSnippet {
+        demo: shared.demo.slug(),
+        vs: &shared.vs,
+        fs: &shared.fs,
+        lab: shared.extras(),
+    };
+    let resp = Request::post("/api/shaders")
+        .json(&body)
+        .map_err(|e| e.to_string())?
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+    if !resp.ok() {
+        return Err(error(resp).await);
+    }
+    let snippet: Snippet = resp.json().await.map_err(|e| e.to_string())?;
+
+    let origin = web_sys::window()
+        .and_then(|w| w.location().origin().ok())
+        .unwrap_or_default();
+    Ok(format!("{origin}{SNIPPET_PATH}/{}", snippet.id))
+}
+
+/// The saved shader `id`, as a lab to open.
+pub async fn load(id: &str) -> Result<Shared, String> {
+    let resp = Request::get(&format!("/api/shaders/{id}"))
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+    if !resp.ok() {
+        return Err(error(resp).await);
+    }
+    let snippet: Snippet = resp.json().await.map_err(|e| e.to_string())?;
+
+    let demo = Demo::ALL
+        .into_iter()
+        .find(|d| d.slug() == snippet.demo)
+        .ok_or_else(|| format!("unknown demo `{}`", snippet.demo))?;
+    Ok(Shared::with_extras(demo, snippet.vs, snippet.fs, snippet.lab))
+}
+
+/// The server's reason, or the status when it gave none.
+async fn error(resp: gloo_net::http::Response) -> String {
+    match resp.text().await {
+        Ok(text) if !text.is_empty() => text,
+        _ => format!("{} {}", resp.status(), resp.status_text()),
+    }
+}
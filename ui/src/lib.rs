@@ -1,3 +1,5 @@
+#![recursion_limit = "256"]
+
 use leptos::*;
 use mount::mount_to_body;
 use wasm_bindgen::prelude::*;
@@ -40,7 +40,7 @@ pub const CUBE_VERTICES: &[Vertex] = &[
 ];
 
 // 6 faces × 2 triangles × 3 indices = 36
-pub const CUBE_INDICES: &[u16] = &[
+pub const CUBE_INDICES: &[u32] = &[
      0,  1,  2,  0,  2,  3,   // +X
      4,  5,  6,  4,  6,  7,   // -X
      8,  9, 10,  8, 10, 11,   // +Y
@@ -0,0 +1,372 @@
+//! Meshes from model files: Wavefront OBJ, and glTF 2.0 as `.gltf` (buffers
+//! embedded as data URIs) or `.glb`. Every object, group or primitive is
+//! merged into one [`CpuMesh`]; materials are ignored.
+//!
+//! Vertices keep the [`Vertex`] conventions the built-in meshes use: UV
+//! (0, 0) is the bottom-left of the image (glTF's V is flipped to match),
+//! normals are computed when the file has none, and UVs are 0 where it has
+//! none.
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use glam::{Mat3, Mat4, Vec3};
+
+use crate::render::renderer::{mesh::CpuMesh, vertex::Vertex};
+
+/// Models compiled in, by file name.
+pub const ASSETS: &[(&str, &[u8])] = &[
+    ("torus.obj", include_bytes!("models/torus.obj")),
+    ("pyramid.gltf", include_bytes!("models/pyramid.gltf")),
+];
+
+/// File extensions [`load`] reads, for file pickers.
+pub const EXTENSIONS: &[&str] = &["obj", "gltf", "glb"];
+
+pub fn asset(name: &str) -> Option<&'static [u8]> {
+    ASSETS.iter().find(|(n, _)| *n == name).map(|(_, bytes)| *bytes)
+}
+
+/// Parse `bytes` by `name`'s extension, then centre the mesh and scale it
+/// to fit the [-1, 1] cube the built-in meshes fill.
+pub fn load(name: &str, bytes: &[u8]) -> Result<CpuMesh, String> {
+    let ext = name.rsplit_once('.').map(|(_, ext)| ext.to_ascii_lowercase());
+    let mut mesh = match ext.as_deref() {
+        Some("obj") => obj(bytes)?,
+        Some("gltf" | "glb") => gltf(bytes)?,
+        _ => return Err(format!("`{name}`: expected one of .{}", EXTENSIONS.join(", ."))),
+    };
+    fit(&mut mesh);
+    Ok(mesh)
+}
+
+/// Every object and group in an OBJ file, triangulated. `mtllib`s are not
+/// looked up.
+pub fn obj(bytes: &[u8]) -> Result<CpuMesh, String> {
+    let (models, _) = tobj::load_obj_buf(
+        &mut std::io::Cursor::new(bytes),
+        &tobj::GPU_LOAD_OPTIONS,
+        |_| Ok(Default::default()),
+    )
+    .map_err(|e| e.to_string())?;
+
+    let mut out = Builder::default();
+    for model in &models {
+        let m = &model.mesh;
+        let positions: Vec<[f32; 3]> =
+            m.positions.chunks_exact(3).map(|p| [p[0], p[1], p[2]]).collect();
+        let normals = (m.normals.len() == m.positions.len())
+            .then(|| m.normals.chunks_exact(3).map(|n| [n[0], n[1], n[2]]).collect());
+        let uvs = (m.texcoords.len() / 2 == positions.len())
+            .then(|| m.texcoords.chunks_exact(2).map(|t| [t[0], t[1]]).collect());
+        out.push(positions, normals, uvs, m.indices.clone())?;
+    }
+    out.finish()
+}
+
+/// Every triangle primitive in the default scene (or the first), placed by
+/// its node's transform; without scenes, every mesh as it is.
+pub fn gltf(bytes: &[u8]) -> Result<CpuMesh, String> {
+    let gltf = ::gltf::Gltf::from_slice(bytes).map_err(|e| e.to_string())?;
+    let buffers = gltf
+        .buffers()
+        .map(|buffer| match buffer.source() {
+            ::gltf::buffer::Source::Bin => {
+                gltf.blob.clone().ok_or_else(|| "no binary chunk for buffer 0".to_owned())
+            }
+            ::gltf::buffer::Source::Uri(uri) => data_uri(uri),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut out = Builder::default();
+    match gltf.default_scene().or_else(|| gltf.scenes().next()) {
+        Some(scene) => {
+            for node in scene.nodes() {
+                gltf_node(&node, Mat4::IDENTITY, &buffers, &mut out)?;
+            }
+        }
+        None => {
+            for mesh in gltf.meshes() {
+                gltf_mesh(&mesh, Mat4::IDENTITY, &buffers, &mut out)?;
+            }
+        }
+    }
+    out.finish()
+}
+
+fn gltf_node(
+    node: &::gltf::Node,
+    parent: Mat4,
+    buffers: &[Vec<u8>],
+    out: &mut Builder,
+) -> Result<(), String> {
+    let world = parent * Mat4::from_cols_array_2d(&node.transform().matrix());
+    if let Some(mesh) = node.mesh() {
+        gltf_mesh(&mesh, world, buffers, out)?;
+    }
+    for child in node.children() {
+        gltf_node(&child, world, buffers, out)?;
+    }
+    Ok(())
+}
+
+fn gltf_mesh(
+    mesh: &::gltf::Mesh,
+    world: Mat4,
+    buffers: &[Vec<u8>],
+    out: &mut Builder,
+) -> Result<(), String> {
+    let normal_matrix = Mat3::from_mat4(world).inverse().transpose();
+
+    for primitive in mesh.primitives() {
+        // points and lines have nothing to shade
+        if primitive.mode() != ::gltf::mesh::Mode::Triangles {
+            continue;
+        }
+        let reader = primitive.reader(|b| buffers.get(b.index()).map(Vec::as_slice));
+        let Some(positions) = reader.read_positions() else {
+            continue;
+        };
+        let positions: Vec<[f32; 3]> = positions
+            .map(|p| world.transform_point3(Vec3::from(p)).to_array())
+            .collect();
+        let normals = reader.read_normals().map(|normals| {
+            normals
+                .map(|n| (normal_matrix * Vec3::from(n)).normalize_or_zero().to_array())
+                .collect()
+        });
+        let uvs = reader
+            .read_tex_coords(0)
+            .map(|uvs| uvs.into_f32().map(|[u, v]| [u, 1.0 - v]).collect());
+        let indices = match reader.read_indices() {
+            Some(indices) => indices.into_u32().collect(),
+            None => (0..positions.len() as u32).collect(),
+        };
+        out.push(positions, normals, uvs, indices)?;
+    }
+    Ok(())
+}
+
+/// The bytes of a `data:...;base64,` URI; glTF files here carry their
+/// buffers inside, as there's nowhere to fetch the others from.
+fn data_uri(uri: &str) -> Result<Vec<u8>, String> {
+    let Some(("data", rest)) = uri.split_once(':') else {
+        return Err(format!("external buffer `{uri}`: embed it or use .glb"));
+    };
+    let (_, data) = rest
+        .split_once(";base64,")
+        .ok_or_else(|| "only base64 data URIs are supported".to_owned())?;
+    STANDARD.decode(data).map_err(|e| e.to_string())
+}
+
+/// The merged mesh so far.
+#[derive(Default)]
+struct Builder {
+    vertices: Vec<Vertex>,
+    indices: Vec<u32>,
+}
+
+impl Builder {
+    fn push(
+        &mut self,
+        positions: Vec<[f32; 3]>,
+        normals: Option<Vec<[f32; 3]>>,
+        uvs: Option<Vec<[f32; 2]>>,
+        indices: Vec<u32>,
+    ) -> Result<(), String> {
+        if let Some(&i) = indices.iter().find(|&&i| i as usize >= positions.len()) {
+            return Err(format!("index {i} past the {} vertices", positions.len()));
+        }
+        let indices = &indices[..indices.len() / 3 * 3];
+        let normals = normals
+            .filter(|n| n.len() == positions.len())
+            .unwrap_or_else(|| smooth_normals(&positions, indices));
+        let uvs = uvs.filter(|t| t.len() == positions.len());
+
+        let base = self.vertices.len() as u32;
+        self.vertices.extend(positions.iter().enumerate().map(|(i, &position)| Vertex {
+            position,
+            normal: normals[i],
+            uv: uvs.as_ref().map_or([0.0; 2], |t| t[i]),
+        }));
+        self.indices.extend(indices.iter().map(|i| base + i));
+        Ok(())
+    }
+
+    fn finish(self) -> Result<CpuMesh, String> {
+        if self.indices.is_empty() {
+            return Err("no triangles".to_owned());
+        }
+        Ok(CpuMesh::new(self.vertices, self.indices))
+    }
+}
+
+/// Area-weighted face normals summed per vertex, so shared vertices shade
+/// smooth and split ones flat.
+fn smooth_normals(positions: &[[f32; 3]], indices: &[u32]) -> Vec<[f32; 3]> {
+    let mut sums = vec![Vec3::ZERO; positions.len()];
+    for tri in indices.chunks_exact(3) {
+        let [a, b, c] = [0, 1, 2].map(|k| Vec3::from(positions[tri[k] as usize]));
+        let face = (b - a).cross(c - a);
+        for &i in tri {
+            sums[i as usize] += face;
+        }
+    }
+    sums.into_iter().map(|n| n.normalize_or_zero().to_array()).collect()
+}
+
+/// Centre the bounding box on the origin and scale its longest side to 2.
+fn fit(mesh: &mut CpuMesh) {
+    let (min, max) = mesh.vertices.iter().fold(
+        (Vec3::splat(f32::MAX), Vec3::splat(f32::MIN)),
+        |(min, max), v| (min.min(v.position.into()), max.max(v.position.into())),
+    );
+    let centre = (min + max) * 0.5;
+    let size = (max - min).max_element();
+    let scale = if size > 0.0 { 2.0 / size } else { 1.0 };
+    for v in &mut mesh.vertices {
+        v.position = ((Vec3::from(v.position) - centre) * scale).to_array();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bounds(mesh: &CpuMesh) -> (Vec3, Vec3) {
+        mesh.vertices.iter().fold(
+            (Vec3::splat(f32::MAX), Vec3::splat(f32::MIN)),
+            |(min, max), v| (min.min(v.position.into()), max.max(v.position.into())),
+        )
+    }
+
+    /// `gltf` as a `.glb`: its one data-URI buffer moved to the binary chunk.
+    fn glb(gltf: &[u8]) -> Vec<u8> {
+        let mut json: serde_json::Value = serde_json::from_slice(gltf).unwrap();
+        let uri = json["buffers"][0]["uri"].take();
+        let mut bin = data_uri(uri.as_str().unwrap()).unwrap();
+        json["buffers"][0].as_object_mut().unwrap().remove("uri");
+        let mut json = serde_json::to_vec(&json).unwrap();
+
+        json.resize(json.len().next_multiple_of(4), b' ');
+        bin.resize(bin.len().next_multiple_of(4), 0);
+        let mut out = Vec::new();
+        let total = 12 + 8 + json.len() + 8 + bin.len();
+        for word in [0x4654_6c67, 2, total as u32] {
+            out.extend(u32::to_le_bytes(word));
+        }
+        for (kind, chunk) in [(0x4e4f_534a_u32, &json), (0x004e_4942, &bin)] {
+            out.extend(u32::to_le_bytes(chunk.len() as u32));
+            out.extend(u32::to_le_bytes(kind));
+            out.extend(chunk);
+        }
+        out
+    }
+
+    #[test]
+    fn obj_objects_merge_into_one_mesh() {
+        let torus = load("torus.obj", asset("torus.obj").unwrap()).unwrap();
+        assert_eq!((torus.vertices.len(), torus.triangle_count()), (33 * 17, 32 * 16 * 2));
+
+        // a triangle and a quad, neither with normals or UVs
+        let two = obj(b"\
+            o tri\nv 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 3\n\
+            o quad\nv 0 0 1\nv 1 0 1\nv 1 1 1\nv 0 1 1\nf 4 5 6 7\n")
+        .unwrap();
+        assert_eq!(two.vertices.len(), 7);
+        assert_eq!(two.indices, [0, 1, 2, 3, 4, 5, 3, 5, 6]);
+        for v in &two.vertices {
+            assert_eq!(v.normal, [0.0, 0.0, 1.0]);
+            assert_eq!(v.uv, [0.0, 0.0]);
+        }
+    }
+
+    /// Every primitive, placed by its node; the pyramid's sides have normals
+    /// and indices, its base neither.
+    #[test]
+    fn gltf_and_glb_read_the_same() {
+        let gltf = asset("pyramid.gltf").unwrap();
+        for (name, bytes) in [("pyramid.gltf", gltf.to_vec()), ("pyramid.glb", glb(gltf))] {
+            let pyramid = load(name, &bytes).unwrap();
+            assert_eq!((pyramid.vertices.len(), pyramid.triangle_count()), (18, 6), "{name}");
+
+            let (min, max) = bounds(&pyramid);
+            assert!(min.abs_diff_eq(Vec3::new(-1.0, -0.75, -1.0), 1e-5), "{min}");
+            assert!(max.abs_diff_eq(Vec3::new(1.0, 0.75, 1.0), 1e-5), "{max}");
+
+            // the node's 1.5 height steepens the sides, so their normals lean
+            // further out than the untransformed 45 degrees
+            let side = Vec3::from(pyramid.vertices[0].normal);
+            assert!((side.length() - 1.0).abs() < 1e-5 && side.y > 0.5 && side.y < 0.6, "{side}");
+            assert_eq!(pyramid.vertices[2].uv, [0.5, 1.0], "apex at the top of the image");
+            for base in &pyramid.vertices[12..] {
+                assert!(Vec3::from(base.normal).abs_diff_eq(Vec3::NEG_Y, 1e-5));
+            }
+        }
+    }
+
+    #[test]
+    fn unreadable_files_are_errors() {
+        let gltf = asset("pyramid.gltf").unwrap();
+        assert!(load("pyramid.fbx", gltf).is_err());
+        assert!(load("pyramid", gltf).is_err());
+        assert!(load("empty.obj", b"# nothing\n").is_err());
+        assert!(load("broken.obj", b"f 1 2 3\n").is_err());
+        assert!(load("broken.gltf", b"{").is_err());
+        assert!(data_uri("pyramid.bin").is_err());
+    }
+
+    #[test]
+    fn indices_past_the_vertices_are_errors() {
+        let triangle = || vec![[0.0; 3], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]];
+        let mut out = Builder::default();
+        assert_eq!(
+            out.push(triangle(), None, None, vec![0, 1, 3]),
+            Err("index 3 past the 3 vertices".to_owned())
+        );
+        assert!(out.vertices.is_empty() && out.indices.is_empty());
+
+        // indices count from each part's own first vertex
+        out.push(triangle(), None, None, vec![0, 1, 2]).unwrap();
+        out.push(triangle(), None, None, vec![2, 1, 0]).unwrap();
+        assert_eq!(out.indices, [0, 1, 2, 5, 4, 3]);
+        assert!(obj(b"v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 4\n").is_err());
+    }
+
+    #[test]
+    fn missing_or_mismatched_normals_and_uvs_are_filled_in() {
+        // a square folded along its diagonal: the shared vertices take both
+        // faces, weighted by area, the others keep their own
+        let positions = vec![[0.0; 3], [1.0, 0.0, 0.0], [1.0, 1.0, 0.0], [0.0, 1.0, 1.0]];
+        let mut out = Builder::default();
+        let short = Some(vec![[0.0, 1.0, 0.0]]);
+        let uvs = Some(vec![[0.5, 0.5]; 3]);
+        // the trailing index makes no triangle and is dropped
+        out.push(positions, short, uvs, vec![0, 1, 2, 0, 2, 3, 1]).unwrap();
+        let mesh = out.finish().unwrap();
+        assert_eq!(mesh.index_count, 6);
+
+        let normal = |i: usize| Vec3::from(mesh.vertices[i].normal);
+        assert!(normal(1).abs_diff_eq(Vec3::Z, 1e-5));
+        assert!(normal(3).abs_diff_eq(Vec3::new(1.0, -1.0, 1.0).normalize(), 1e-5));
+        let shared = (Vec3::Z + Vec3::new(1.0, -1.0, 1.0)).normalize();
+        assert!(normal(0).abs_diff_eq(shared, 1e-5) && normal(2).abs_diff_eq(shared, 1e-5));
+        assert!(mesh.vertices.iter().all(|v| v.uv == [0.0, 0.0]));
+    }
+
+    #[test]
+    fn fit_centres_and_scales_the_longest_side_to_two() {
+        let vertex = |position| Vertex { position, normal: [0.0; 3], uv: [0.0; 2] };
+        let mut mesh = CpuMesh::new(
+            vec![vertex([1.0, 2.0, 3.0]), vertex([3.0, 6.0, 4.0]), vertex([2.0, 4.0, 3.5])],
+            vec![0, 1, 2],
+        );
+        fit(&mut mesh);
+        let positions: Vec<_> = mesh.vertices.iter().map(|v| v.position).collect();
+        assert_eq!(positions, [[-0.5, -1.0, -0.25], [0.5, 1.0, 0.25], [0.0; 3]]);
+
+        // nothing to scale by, so only moved
+        let mut point = CpuMesh::new(vec![vertex([5.0, 5.0, 5.0]); 3], vec![0, 1, 2]);
+        fit(&mut point);
+        assert!(point.vertices.iter().all(|v| v.position == [0.0; 3]));
+    }
+}
@@ -3,3 +3,4 @@ pub mod quad;
 pub mod strip;
 pub mod utils;
 pub mod animals;
+pub mod import;
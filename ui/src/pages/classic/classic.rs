@@ -15,11 +15,20 @@ use leptos::prelude::OnAttribute;
 use leptos::prelude::RwSignal;
 use leptos::prelude::StyleAttribute;
 use leptos::prelude::Suspense;
+use leptos::prelude::GetUntracked;
+use leptos::prelude::Owner;
+use leptos::prelude::GetValue;
+use leptos::prelude::SetValue;
+use leptos::prelude::StoredValue;
+use leptos::prelude::Track;
 use leptos::prelude::Update;
+use leptos::prelude::With;
+use leptos::prelude::WithUntracked;
 use leptos::prelude::{Children, Effect, Get, Set};
 use leptos::server::LocalResource;
 use leptos::view;
-use std::collections::HashMap;
+use leptos_router::hooks::use_params_map;
+use std::collections::{BTreeMap, HashMap};
 use wasm_bindgen::JsCast;
 use wasm_bindgen::prelude::Closure;
 use web_sys::AddEventListenerOptions;
@@ -27,7 +36,17 @@ use web_sys::Element;
 use web_sys::Event;
 
 use crate::components::demo::{Demo, DemoTab};
-use crate::components::shader_editor::ShaderEditor;
+use crate::components::persist;
+use crate::components::share::{self, Shared};
+use crate::components::shader_editor::{Lab, ShaderEditor};
+use crate::components::snippets;
+use crate::render::renderer::gpu::diagnostics::{ShaderDiagnostics, Stage};
+use crate::render::renderer::gpu::params::ShaderParams;
+use crate::render::renderer::gpu::preprocess::ShaderFiles;
+use crate::render::renderer::gpu::setup::ShaderSetup;
+use crate::render::renderer::gpu::textures::TextureSlots;
+use crate::render::renderer::graph::LiveShaders;
+use crate::components::shader_editor::model::ModelSlot;
 
 #[component]
 pub fn AboutSection() -> impl IntoView {
@@ -345,7 +364,6 @@ pub fn CarouselDots(selected: RwSignal<usize>, total: usize) -> impl IntoView {
                 each=move || 0..total
                 key=|i| i + 1
                 children=move |i| {
-                    let selected = selected.clone();
                     view! {
                         <button
                             class=move || format!(
@@ -427,9 +445,8 @@ pub fn ProjectCards() -> impl IntoView {
                 .scroll_into_view_with_scroll_into_view_options(&opts);
 
             {
-                let autoscrolling_flag = autoscrolling.clone();
                 let cb = Closure::<dyn FnMut(Event)>::wrap(Box::new(move |_| {
-                    autoscrolling_flag.set(false);
+                    autoscrolling.set(false);
                 }));
 
                 let opts = AddEventListenerOptions::new();
@@ -504,47 +521,302 @@ pub fn ProjectCards() -> impl IntoView {
     }
 }
 
+#[derive(Copy, Clone)]
+struct Pass {
+    on: RwSignal<bool>,
+    default: bool,
+}
+
 #[derive(Clone)]
-pub struct PassFlags(RwSignal<HashMap<String, RwSignal<bool>>>);
+pub struct PassFlags {
+    // demos can name their passes alike; each keeps its own toggle
+    passes: RwSignal<HashMap<(Demo, String), Pass>>,
+    // flags outlive the canvas that declares them (it's rebuilt on every
+    // demo switch), so they're made under the lab's owner instead
+    owner: Owner,
+    /// States from a shared link, for its demo; these win over saved ones
+    /// and aren't saved themselves.
+    preset: Option<(Demo, BTreeMap<String, bool>)>,
+}
+
+impl Default for PassFlags {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
 impl PassFlags {
     pub fn new() -> Self {
-        Self(RwSignal::new(HashMap::new()))
+        Self {
+            passes: RwSignal::new(HashMap::new()),
+            owner: Owner::current().unwrap_or_default(),
+            preset: None,
+        }
     }
 
-    pub fn init_pass(&self, label: &str, state: bool) -> RwSignal<bool> {
-        let sig = RwSignal::new(state);
-        self.0.update(|m| {
-            m.insert(label.into(), sig);
+    pub fn with_preset(demo: Demo, preset: BTreeMap<String, bool>) -> Self {
+        Self {
+            passes: RwSignal::new(HashMap::new()),
+            owner: Owner::current().unwrap_or_default(),
+            preset: Some((demo, preset)),
+        }
+    }
+
+    /// The toggle for `demo`'s `label`, starting from the saved state if the
+    /// user changed it before. Declaring it again gives back the same signal.
+    pub fn init_pass(&self, demo: Demo, label: &str, default: bool) -> RwSignal<bool> {
+        let key = (demo, label.to_owned());
+        if let Some(pass) = self.passes.with_untracked(|m| m.get(&key).copied()) {
+            return pass.on;
+        }
+
+        let item = format!("pass/{label}");
+        let preset = match &self.preset {
+            Some((shared, states)) if *shared == demo => states.get(label).copied(),
+            _ => None,
+        };
+        let on = self.owner.with(|| {
+            if let Some(state) = preset {
+                return RwSignal::new(state);
+            }
+            let saved = persist::load(demo, &item).map(|v| v == "on");
+            let on = RwSignal::new(saved.unwrap_or(default));
+            Effect::new(move |_| {
+                let state = on.get();
+                let value = if state { "on" } else { "off" };
+                persist::store(demo, &item, (state != default).then_some(value));
+            });
+            on
         });
-        sig
+        self.passes.update(|m| {
+            m.insert(key, Pass { on, default });
+        });
+        on
     }
 
-    pub fn iter(&self) -> Vec<(String, RwSignal<bool>)> {
-        self.0
+    /// `demo`'s toggles, by label.
+    pub fn iter(&self, demo: Demo) -> Vec<(String, RwSignal<bool>)> {
+        self.passes
             .get() // reactive
             .iter()
-            .map(|(k, v)| (k.clone(), *v))
+            .filter(|((d, _), _)| *d == demo)
+            .map(|((_, label), p)| (label.clone(), p.on))
             .collect()
     }
+
+    /// Current state of `demo`'s toggles, for sharing.
+    pub fn states(&self, demo: Demo) -> BTreeMap<String, bool> {
+        self.passes.with_untracked(|m| {
+            m.iter()
+                .filter(|((d, _), _)| *d == demo)
+                .map(|((_, label), p)| (label.clone(), p.on.get_untracked()))
+                .collect()
+        })
+    }
+
+    /// Whether any of `demo`'s toggles is off its default.
+    pub fn modified(&self, demo: Demo) -> bool {
+        self.passes
+            .get()
+            .iter()
+            .any(|((d, _), p)| *d == demo && p.on.get() != p.default)
+    }
+
+    pub fn reset(&self, demo: Demo) {
+        let passes = self.passes.get_untracked();
+        for (_, pass) in passes.iter().filter(|((d, _), _)| *d == demo) {
+            pass.on.set(pass.default);
+        }
+    }
+}
+
+/// The lab as a link left it: `/share#...`.
+#[component]
+pub fn SharedLab() -> impl IntoView {
+    let (shared, error) = match share::from_location() {
+        Some(Ok(shared)) => (Some(shared), None),
+        Some(Err(e)) => (None, Some(format!("Couldn't open the shared shader: {e}"))),
+        None => (None, None),
+    };
+
+    view! {
+        <main class="max-w-6xl mx-auto px-6">
+            { error.map(|e| view! { <p class="mt-8 text-red-300">{ e }</p> }) }
+            <ShaderLab shared />
+        </main>
+    }
+}
+
+/// The lab as the server saved it: `/shader/{id}`.
+#[component]
+pub fn SavedLab() -> impl IntoView {
+    let id = use_params_map().with_untracked(|params| params.get("id").unwrap_or_default());
+    let saved = LocalResource::new(move || {
+        let id = id.clone();
+        async move { snippets::load(&id).await }
+    });
+
+    view! {
+        <main class="max-w-6xl mx-auto px-6">
+            <Suspense fallback=|| view! { <p class="mt-8 text-text-muted">"Loading shader…"</p> }>
+                { move || match saved.get() {
+                    Some(Ok(shared)) => view! { <ShaderLab shared=Some(shared) /> }.into_any(),
+                    Some(Err(e)) => view! {
+                        <p class="mt-8 text-red-300">"Couldn't open the saved shader: " { e }</p>
+                    }.into_any(),
+                    None => ().into_any(),
+                }}
+            </Suspense>
+        </main>
+    }
 }
 
 #[component]
-fn ShaderLab() -> impl IntoView {
-    let selected_demo = RwSignal::new(Demo::Animals);
-    let vs_src =
-        RwSignal::new(include_str!("../../render/renderer/shaders/fish.vert.wgsl").to_owned());
-    let fs_src =
-        RwSignal::new(include_str!("../../render/renderer/shaders/fish.frag.wgsl").to_owned());
-
-    // whenever demo changes, push its shader pair into the two text signals
+fn ShaderLab(
+    /// Start from a shared link instead of this browser's saved edits.
+    #[prop(optional_no_strip)]
+    shared: Option<Shared>,
+) -> impl IntoView {
+    let setup = ShaderSetup::new(String::new());
+    // the demo's module, whether it's used, and its entry point picks
+    let load_setup = move |demo: Demo| {
+        setup.module.set(persist::module(demo));
+        setup.single.set(persist::load(demo, "single").is_some());
+        setup.set_picks(persist::entry_points(demo));
+    };
+
+    let textures = TextureSlots::new();
+    let model = ModelSlot::new();
+
+    let (demo, vs, fs, modules, passes) = match shared {
+        Some(s) => {
+            setup.single.set(s.module.is_some());
+            setup.module.set(s.module.unwrap_or_else(|| s.demo.module().to_owned()));
+            setup.set_picks(s.entry_points);
+            textures.load_urls(&s.textures.unwrap_or_else(|| s.demo.textures()));
+            textures.set_samplers(&s.samplers);
+            model.set_asset(s.model.as_deref());
+            (s.demo, s.vs, s.fs, s.files, Some(s.passes))
+        }
+        None => {
+            let (vs, fs) = persist::shaders(Demo::Animals);
+            load_setup(Demo::Animals);
+            textures.load_urls(&persist::textures(Demo::Animals));
+            textures.set_samplers(&persist::samplers(Demo::Animals));
+            model.set_asset(persist::model(Demo::Animals).as_deref());
+            (Demo::Animals, vs, fs, persist::modules(), None)
+        }
+    };
+    // a shared link is only looked at; it doesn't overwrite the visitor's
+    // own edits unless they switch demos and come back
+    let keep_edits = StoredValue::new(passes.is_none());
+    let pass_flags = match passes {
+        Some(passes) => PassFlags::with_preset(demo, passes),
+        None => PassFlags::new(),
+    };
+
+    let selected_demo = RwSignal::new(demo);
+    let vs_src = RwSignal::new(vs);
+    let fs_src = RwSignal::new(fs);
+
+    let diagnostics = ShaderDiagnostics::new();
+    let params = ShaderParams::new();
+    let files = ShaderFiles::with_edits(modules);
+
+    // whenever demo changes, push its shader pair (as last edited) into the
+    // two text signals; the first pair is set up above
+    Effect::new(move |first: Option<()>| {
+        let demo = selected_demo.get();
+        if first.is_none() {
+            return;
+        }
+        let (vs, fs) = persist::shaders(demo);
+        vs_src.set(vs);
+        fs_src.set(fs);
+        load_setup(demo);
+        textures.load_urls(&persist::textures(demo));
+        textures.set_samplers(&persist::samplers(demo));
+        model.set_asset(persist::model(demo).as_deref());
+        keep_edits.set_value(true);
+        diagnostics.clear();
+    });
+
+    // and keep every edit; runs after the switch above has swapped the
+    // sources, so the demo is read untracked
+    Effect::new(move |_| {
+        let demo = selected_demo.get_untracked();
+        if !keep_edits.get_value() {
+            vs_src.track();
+            fs_src.track();
+            return;
+        }
+        let (vs, fs) = demo.shaders();
+        vs_src.with(|src| persist::store_shader(demo, "vs", src, vs));
+        fs_src.with(|src| persist::store_shader(demo, "fs", src, fs));
+    });
+
+    // and the same for the one module
+    Effect::new(move |_| {
+        let demo = selected_demo.get_untracked();
+        let single = setup.single.get();
+        let picks = setup.picks();
+        if !keep_edits.get_value() {
+            setup.module.track();
+            return;
+        }
+        setup.module.with(|src| persist::store_shader(demo, "module", src, demo.module()));
+        persist::store(demo, "single", single.then_some("on"));
+        persist::store_entry_points(demo, &picks);
+    });
+
+    // and which images the texture channels load, and how they're sampled
+    Effect::new(move |_| {
+        let demo = selected_demo.get_untracked();
+        let urls = textures.urls();
+        let samplers = textures.samplers();
+        if keep_edits.get_value() {
+            persist::store_textures(demo, &urls);
+            persist::store_samplers(demo, &samplers);
+        }
+    });
+
+    // and the built-in model, if one is picked
+    Effect::new(move |_| {
+        let demo = selected_demo.get_untracked();
+        let asset = model.asset();
+        if keep_edits.get_value() {
+            persist::store_model(demo, asset.as_deref());
+        }
+    });
+
+    // modules are shared by every demo, so they're kept apart
+    Effect::new(move |_| {
+        let edits = files.edits();
+        if keep_edits.get_value() {
+            persist::store_modules(&edits);
+        }
+    });
+
+    // controls for the uniforms the current sources declare, includes and
+    // all; left as they are while an include is broken
     Effect::new(move |_| {
-        let (vs, fs) = selected_demo.get().shaders();
-        vs_src.set(vs.to_owned());
-        fs_src.set(fs.to_owned());
+        let (vs_src, fs_src) = if setup.single.get() {
+            (setup.module, setup.module)
+        } else {
+            (vs_src, fs_src)
+        };
+        let vs = vs_src.with(|vs| files.expand(vs, Stage::Vertex));
+        let fs = fs_src.with(|fs| files.expand(fs, Stage::Fragment));
+        if let (Ok(vs), Ok(fs)) = (vs, fs) {
+            params.sync(&vs.code, &fs.code);
+        }
     });
 
-    let pass_flags = PassFlags::new();
+    let lab = Lab {
+        shaders: LiveShaders { vs: vs_src, fs: fs_src, diagnostics, params, files, setup },
+        textures,
+        model,
+    };
 
     view! {
         <section id="shader-lab" class="py-8">
@@ -572,11 +844,14 @@ fn ShaderLab() -> impl IntoView {
                 lg:gap-y-0
                 lg:gap-x-6
             ">
-                <ShaderEditor vs_src fs_src pass_flags=pass_flags.clone() selected_demo />
+                <ShaderEditor lab pass_flags=pass_flags.clone() selected_demo />
 
                 <div class="w-full h-[40rem] rounded-xl border overflow-hidden flex items-center justify-center">
                     {
-                        move || selected_demo.get().canvas(vs_src, fs_src, pass_flags.clone())
+                        move || {
+                            let flags = pass_flags.clone();
+                            selected_demo.get().canvas(lab, flags)
+                        }
                     }
                 </div>
             </div>
@@ -0,0 +1,282 @@
+use std::{cell::RefCell, rc::Rc};
+
+use super::gpu::utils::now_ms;
+
+/// Timing for one frame, as handed to `GpuState` by a [`Clock`].
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct FrameTime {
+    /// Milliseconds since the clock started (scaled/paused time, not wall time).
+    pub elapsed_ms: f64,
+
+    /// Milliseconds since the previous frame.
+    pub dt_ms: f64,
+}
+
+/// Source of time for the renderer. `tick` is called exactly once per frame.
+pub trait Clock {
+    fn tick(&mut self) -> FrameTime;
+}
+
+/// Lets a caller keep a handle to the clock (to pause it, scrub it, ...)
+/// while `GpuState` owns another.
+impl<C: Clock> Clock for Rc<RefCell<C>> {
+    fn tick(&mut self) -> FrameTime {
+        self.borrow_mut().tick()
+    }
+}
+
+/// Wall clock: `performance.now()` in the browser, `Instant` natively.
+pub struct RealClock {
+    start_ms: f64,
+    prev_ms: f64,
+}
+
+impl RealClock {
+    pub fn new() -> Self {
+        let t0 = now_ms();
+        Self {
+            start_ms: t0,
+            prev_ms: t0,
+        }
+    }
+}
+
+impl Default for RealClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for RealClock {
+    fn tick(&mut self) -> FrameTime {
+        let now = now_ms();
+        let dt_ms = now - self.prev_ms;
+        self.prev_ms = now;
+
+        FrameTime {
+            elapsed_ms: now - self.start_ms,
+            dt_ms,
+        }
+    }
+}
+
+/// Advances by the same step every frame, regardless of how long frames take.
+pub struct FixedStepClock {
+    pub step_ms: f64,
+    elapsed_ms: f64,
+}
+
+impl FixedStepClock {
+    pub fn new(step_ms: f64) -> Self {
+        Self::starting_at(0.0, step_ms)
+    }
+
+    /// First tick reports `start_ms`, each following one adds `step_ms`.
+    pub fn starting_at(start_ms: f64, step_ms: f64) -> Self {
+        Self {
+            step_ms,
+            elapsed_ms: start_ms - step_ms,
+        }
+    }
+}
+
+impl Clock for FixedStepClock {
+    fn tick(&mut self) -> FrameTime {
+        self.elapsed_ms += self.step_ms;
+
+        FrameTime {
+            elapsed_ms: self.elapsed_ms.max(0.0),
+            dt_ms: self.step_ms,
+        }
+    }
+}
+
+/// Real time that can be paused, stepped frame-by-frame, sped up / slowed
+/// down and scrubbed to an arbitrary point.
+pub struct PlaybackClock {
+    /// 1.0 = real time, 0.5 = half speed, ...
+    pub speed: f64,
+    /// Step used by [`PlaybackClock::step`].
+    pub step_ms: f64,
+
+    paused: bool,
+    elapsed_ms: f64,
+    prev_real_ms: f64,
+    pending_ms: f64, // queued by step()
+    seek_ms: Option<f64>,
+    /// Wall time; `now_ms` but in tests.
+    now: fn() -> f64,
+}
+
+impl PlaybackClock {
+    pub fn new() -> Self {
+        Self::with_source(now_ms)
+    }
+
+    fn with_source(now: fn() -> f64) -> Self {
+        Self {
+            speed: 1.0,
+            step_ms: 1000.0 / 60.0,
+            paused: false,
+            elapsed_ms: 0.0,
+            prev_real_ms: now(),
+            pending_ms: 0.0,
+            seek_ms: None,
+            now,
+        }
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    pub fn pause(&mut self) {
+        self.paused = true;
+    }
+
+    pub fn resume(&mut self) {
+        // don't count the time spent paused
+        self.prev_real_ms = (self.now)();
+        self.paused = false;
+    }
+
+    pub fn toggle(&mut self) {
+        if self.paused {
+            self.resume();
+        } else {
+            self.pause();
+        }
+    }
+
+    /// Advance `frames` steps on the next tick (meant for use while paused).
+    pub fn step(&mut self, frames: u32) {
+        self.pending_ms += self.step_ms * frames as f64;
+    }
+
+    /// Jump to `elapsed_ms`; the next tick reports exactly that time, and
+    /// playing (if it is) carries on from there.
+    pub fn seek(&mut self, elapsed_ms: f64) {
+        self.seek_ms = Some(elapsed_ms.max(0.0));
+        self.pending_ms = 0.0;
+    }
+
+    pub fn elapsed_ms(&self) -> f64 {
+        self.elapsed_ms
+    }
+}
+
+impl Default for PlaybackClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for PlaybackClock {
+    fn tick(&mut self) -> FrameTime {
+        let now = (self.now)();
+        let real_dt = now - self.prev_real_ms;
+        self.prev_real_ms = now;
+
+        let dt_ms = match self.seek_ms.take() {
+            // the time since the last tick went by before the jump
+            Some(target) => target - self.elapsed_ms,
+            None => {
+                let mut dt_ms = std::mem::take(&mut self.pending_ms);
+                if !self.paused {
+                    dt_ms += real_dt * self.speed;
+                }
+                dt_ms
+            }
+        };
+
+        // scrubbing backwards gives a negative dt; clamp time itself at 0
+        self.elapsed_ms = (self.elapsed_ms + dt_ms).max(0.0);
+
+        FrameTime {
+            elapsed_ms: self.elapsed_ms,
+            dt_ms,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    thread_local! {
+        static NOW: Cell<f64> = const { Cell::new(1000.0) };
+    }
+
+    fn fake_now() -> f64 {
+        NOW.get()
+    }
+
+    fn advance(ms: f64) {
+        NOW.set(NOW.get() + ms);
+    }
+
+    #[test]
+    fn fixed_step_starts_where_asked() {
+        let mut clock = FixedStepClock::starting_at(100.0, 10.0);
+        assert_eq!(clock.tick(), FrameTime { elapsed_ms: 100.0, dt_ms: 10.0 });
+        assert_eq!(clock.tick().elapsed_ms, 110.0);
+        assert_eq!(FixedStepClock::new(10.0).tick().elapsed_ms, 0.0);
+    }
+
+    #[test]
+    fn playback_follows_real_time_at_its_speed() {
+        let mut clock = PlaybackClock::with_source(fake_now);
+        advance(100.0);
+        assert_eq!(clock.tick(), FrameTime { elapsed_ms: 100.0, dt_ms: 100.0 });
+
+        clock.speed = 0.5;
+        advance(100.0);
+        assert_eq!(clock.tick(), FrameTime { elapsed_ms: 150.0, dt_ms: 50.0 });
+    }
+
+    #[test]
+    fn paused_time_stands_still_and_steps() {
+        let mut clock = PlaybackClock::with_source(fake_now);
+        clock.step_ms = 10.0;
+        clock.pause();
+        advance(500.0);
+        assert_eq!(clock.tick().elapsed_ms, 0.0);
+
+        clock.step(3);
+        advance(500.0);
+        assert_eq!(clock.tick(), FrameTime { elapsed_ms: 30.0, dt_ms: 30.0 });
+        assert_eq!(clock.tick().elapsed_ms, 30.0);
+
+        // the pause itself isn't made up for on resume
+        clock.toggle();
+        assert!(!clock.is_paused());
+        advance(20.0);
+        assert_eq!(clock.tick().elapsed_ms, 50.0);
+    }
+
+    #[test]
+    fn seek_lands_exactly_even_while_playing() {
+        let mut clock = PlaybackClock::with_source(fake_now);
+        advance(100.0);
+        clock.tick();
+
+        clock.seek(40.0);
+        advance(16.0);
+        assert_eq!(clock.tick(), FrameTime { elapsed_ms: 40.0, dt_ms: -60.0 });
+        advance(16.0);
+        assert_eq!(clock.tick().elapsed_ms, 56.0);
+
+        clock.seek(-5.0);
+        assert_eq!(clock.tick().elapsed_ms, 0.0);
+    }
+
+    #[test]
+    fn shared_handles_drive_the_same_clock() {
+        let shared = Rc::new(RefCell::new(PlaybackClock::with_source(fake_now)));
+        let mut owned = shared.clone();
+        shared.borrow_mut().seek(250.0);
+        assert_eq!(owned.tick().elapsed_ms, 250.0);
+        assert_eq!(shared.borrow().elapsed_ms(), 250.0);
+    }
+}
@@ -0,0 +1,394 @@
+//! Shader errors as data. WGSL is parsed and validated with naga before it
+//! reaches the device, so a typo in the editor turns into a list of located
+//! errors instead of uncaptured validation errors and a garbage frame. The
+//! editor runs the same checks on every edit, without a device at all.
+
+use std::{collections::BTreeMap, error::Error, fmt};
+
+use leptos::prelude::{RwSignal, Update, With, WithUntracked};
+
+use super::params;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        })
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Stage {
+    Vertex,
+    Fragment,
+
+    /// Both modules are fine, but they don't fit the pipeline (bind group
+    /// layouts, vertex buffers, targets, ...).
+    Pipeline,
+}
+
+impl fmt::Display for Stage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Stage::Vertex => "vertex shader",
+            Stage::Fragment => "fragment shader",
+            Stage::Pipeline => "pipeline",
+        })
+    }
+}
+
+/// Where in the stage's source an error points.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct SourceSpan {
+    /// 1-based.
+    pub line: u32,
+    /// 1-based, in characters (naga counts bytes).
+    pub column: u32,
+
+    /// Byte range into the source.
+    pub start: usize,
+    pub end: usize,
+}
+
+impl SourceSpan {
+    pub(crate) fn from_naga(src: &str, span: naga::Span) -> Option<Self> {
+        span.is_defined().then(|| Self::new(src, span.location(src)))
+    }
+
+    fn new(src: &str, loc: naga::SourceLocation) -> Self {
+        let start = loc.offset as usize;
+        let line_start = src[..start].rfind('\n').map_or(0, |i| i + 1);
+
+        Self {
+            line: loc.line_number,
+            column: src[line_start..start].chars().count() as u32 + 1,
+            start,
+            end: start + loc.length as usize,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub stage: Stage,
+    pub message: String,
+
+    /// `None` when the error isn't tied to a place in the source.
+    pub span: Option<SourceSpan>,
+    /// Module the span is in (see `preprocess`); `None` for the stage's own
+    /// source.
+    pub file: Option<String>,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(file) = &self.file {
+            write!(f, "{}, {file}", self.stage)?;
+        } else {
+            write!(f, "{}", self.stage)?;
+        }
+        match self.span {
+            Some(s) => write!(f, ":{}:{}: {}: {}", s.line, s.column, self.severity, self.message),
+            None => write!(f, ": {}: {}", self.severity, self.message),
+        }
+    }
+}
+
+impl Error for Diagnostic {}
+
+/// Entry points of a module that passed validation.
+pub(crate) type EntryPoints = Vec<(String, naga::ShaderStage)>;
+
+/// Parse and validate `src` the way wgpu would, without touching the device.
+pub(crate) fn validate_wgsl(src: &str, stage: Stage) -> Result<EntryPoints, Diagnostic> {
+    let (module, _) = parse_and_validate(src, stage)?;
+
+    Ok(module
+        .entry_points
+        .into_iter()
+        .map(|ep| (ep.name, ep.stage))
+        .collect())
+}
+
+/// The `@vertex` and `@fragment` functions `src` declares, in order; none
+/// if it doesn't parse. Validity doesn't matter here, so entry points can be
+/// picked while the source is still broken elsewhere.
+pub fn entry_points(src: &str) -> Vec<(String, Stage)> {
+    let Ok(module) = naga::front::wgsl::parse_str(src) else { return Vec::new() };
+    module
+        .entry_points
+        .into_iter()
+        .filter_map(|ep| match ep.stage {
+            naga::ShaderStage::Vertex => Some((ep.name, Stage::Vertex)),
+            naga::ShaderStage::Fragment => Some((ep.name, Stage::Fragment)),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Everything the editor shows for `src`: the error that stops it compiling,
+/// or warnings about a module that does compile.
+pub fn check_wgsl(src: &str, stage: Stage) -> Vec<Diagnostic> {
+    match parse_and_validate(src, stage) {
+        Ok((module, info)) => unused_globals(src, stage, &module, &info),
+        Err(e) => vec![e],
+    }
+}
+
+pub(crate) fn parse_and_validate(
+    src: &str,
+    stage: Stage,
+) -> Result<(naga::Module, naga::valid::ModuleInfo), Diagnostic> {
+    let module = naga::front::wgsl::parse_str(src).map_err(|e| Diagnostic {
+        severity: Severity::Error,
+        stage,
+        message: e.message().to_owned(),
+        span: e.location(src).map(|loc| SourceSpan::new(src, loc)),
+        file: None,
+    })?;
+
+    let info = naga::valid::Validator::new(
+        naga::valid::ValidationFlags::all(),
+        naga::valid::Capabilities::default(),
+    )
+    .validate(&module)
+    .map_err(|e| Diagnostic {
+        severity: Severity::Error,
+        stage,
+        message: error_chain(e.as_inner()),
+        span: e.location(src).map(|loc| SourceSpan::new(src, loc)),
+        file: None,
+    })?;
+    params::check_size(src, stage, &module)?;
+
+    Ok((module, info))
+}
+
+/// Globals no entry point touches; usually a leftover from an edit, or a
+/// typo in the name that's actually meant.
+fn unused_globals(
+    src: &str,
+    stage: Stage,
+    module: &naga::Module,
+    info: &naga::valid::ModuleInfo,
+) -> Vec<Diagnostic> {
+    module
+        .global_variables
+        .iter()
+        .filter(|&(handle, _)| {
+            (0..module.entry_points.len()).all(|i| info.get_entry_point(i)[handle].is_empty())
+        })
+        .map(|(handle, var)| Diagnostic {
+            severity: Severity::Warning,
+            stage,
+            message: format!(
+                "`{}` is declared but never used",
+                var.name.as_deref().unwrap_or("_")
+            ),
+            span: SourceSpan::from_naga(src, module.global_variables.get_span(handle)),
+            file: None,
+        })
+        .collect()
+}
+
+/// Error the device reported for a pipeline that naga was happy with.
+pub(crate) fn pipeline_error(err: &wgpu::Error) -> Diagnostic {
+    // wgpu-core formats these as an indented "Caused by:" tree; flatten it
+    // the same way as naga's chains
+    let message = err
+        .to_string()
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty() && *l != "Validation Error" && *l != "Caused by:")
+        .collect::<Vec<_>>()
+        .join(": ");
+
+    Diagnostic {
+        severity: Severity::Error,
+        stage: Stage::Pipeline,
+        message,
+        span: None,
+        file: None,
+    }
+}
+
+/// Validation errors nest ("function is invalid: expression is invalid:
+/// ..."); the useful part is usually at the bottom.
+fn error_chain(err: &dyn Error) -> String {
+    let mut msg = err.to_string();
+    let mut next = err.source();
+    while let Some(e) = next {
+        msg.push_str(": ");
+        msg.push_str(&e.to_string());
+        next = e.source();
+    }
+    msg
+}
+
+/// Current shader errors of every pass in a demo, keyed by pass label, for
+/// the editor to show. Passes sharing a source report the same error, so
+/// readers get them de-duplicated through [`ShaderDiagnostics::errors`].
+#[derive(Copy, Clone)]
+pub struct ShaderDiagnostics(RwSignal<BTreeMap<&'static str, Vec<Diagnostic>>>);
+
+impl Default for ShaderDiagnostics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ShaderDiagnostics {
+    pub fn new() -> Self {
+        Self(RwSignal::new(BTreeMap::new()))
+    }
+
+    /// Replace `pass`'s errors (empty = it compiles). Called every time a
+    /// pipeline settles, so it only notifies when something changed.
+    pub fn report(&self, pass: &'static str, errors: Vec<Diagnostic>) {
+        let unchanged = self
+            .0
+            .with_untracked(|m| m.get(pass).map_or(errors.is_empty(), |old| *old == errors));
+        if unchanged {
+            return;
+        }
+
+        self.0.update(|m| {
+            if errors.is_empty() {
+                m.remove(pass);
+            } else {
+                m.insert(pass, errors);
+            }
+        });
+    }
+
+    /// Forget everything, e.g. when switching demos.
+    pub fn clear(&self) {
+        if !self.0.with_untracked(BTreeMap::is_empty) {
+            self.0.update(BTreeMap::clear);
+        }
+    }
+
+    /// Every pass's errors, de-duplicated; tracked, so call it from a
+    /// reactive context (memo, effect, view).
+    pub fn errors(&self) -> Vec<Diagnostic> {
+        self.0.with(dedup)
+    }
+
+    /// [`Self::errors`] outside a reactive context: event handlers, tests.
+    pub fn errors_untracked(&self) -> Vec<Diagnostic> {
+        self.0.with_untracked(dedup)
+    }
+}
+
+fn dedup(by_pass: &BTreeMap<&'static str, Vec<Diagnostic>>) -> Vec<Diagnostic> {
+    let mut out: Vec<Diagnostic> = Vec::new();
+    for e in by_pass.values().flatten() {
+        if !out.contains(e) {
+            out.push(e.clone());
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use leptos::prelude::Owner;
+
+    use super::*;
+
+    #[test]
+    fn entry_points_are_found_in_order_even_in_invalid_modules() {
+        let src = "@vertex fn vs_main() -> @builtin(position) vec4<f32> { return vec4<f32>(); }
+            fn helper() {}
+            @fragment fn fs_main() -> @location(0) vec4<f32> { return vec4<f32>(1.0); }
+            @fragment fn fs_green() -> @location(0) vec4<f32> {}
+            @compute @workgroup_size(1) fn cs() {}";
+        let found = entry_points(src);
+        assert_eq!(
+            found,
+            [
+                ("vs_main".to_owned(), Stage::Vertex),
+                ("fs_main".to_owned(), Stage::Fragment),
+                ("fs_green".to_owned(), Stage::Fragment),
+            ]
+        );
+        assert!(!check_wgsl(src, Stage::Fragment).is_empty(), "the module doesn't validate");
+
+        assert!(entry_points("@fragment fn").is_empty());
+    }
+
+    #[test]
+    fn parse_errors_point_at_characters_not_bytes() {
+        let src = "// größe in ünits\nfn f() { let größe = ; }";
+        let [error] = &check_wgsl(src, Stage::Vertex)[..] else { panic!("one error") };
+        assert_eq!(error.severity, Severity::Error);
+        assert_eq!(error.stage, Stage::Vertex);
+
+        let span = error.span.expect("parse errors are located");
+        assert_eq!((span.line, span.column), (2, 22));
+        assert_eq!(&src[span.start..span.end], ";");
+        assert_eq!(error.to_string(), format!("vertex shader:2:22: error: {}", error.message));
+    }
+
+    #[test]
+    fn validation_errors_keep_their_whole_chain() {
+        let src = "fn f() -> i32 {\n    let a = vec2<f32>();\n    return a;\n}";
+        let [error] = &check_wgsl(src, Stage::Fragment)[..] else { panic!("one error") };
+        assert!(
+            error.message.starts_with("Function [0] 'f' is invalid: The `return` value"),
+            "{}",
+            error.message
+        );
+        assert_eq!(error.span.map(|s| s.line), Some(1));
+    }
+
+    #[test]
+    fn globals_no_entry_point_uses_are_warned_about() {
+        let src = "var<private> used: f32;\n\
+                   var<private> unused: f32;\n\
+                   @fragment fn fs_main() -> @location(0) vec4<f32> {\n\
+                   \x20   return vec4<f32>(used);\n\
+                   }";
+        let found = check_wgsl(src, Stage::Fragment);
+        let [warning] = &found[..] else { panic!("one warning, got {found:?}") };
+        assert_eq!(warning.severity, Severity::Warning);
+        assert_eq!(warning.message, "`unused` is declared but never used");
+        assert_eq!(warning.span.map(|s| s.line), Some(2));
+    }
+
+    #[test]
+    fn reports_replace_per_pass_and_read_back_deduplicated() {
+        let owner = Owner::new();
+        owner.set();
+
+        let error = |message: &str| Diagnostic {
+            severity: Severity::Error,
+            stage: Stage::Fragment,
+            message: message.to_owned(),
+            span: None,
+            file: None,
+        };
+        let diagnostics = ShaderDiagnostics::new();
+
+        // passes sharing a source fail the same way
+        diagnostics.report("scene", vec![error("a")]);
+        diagnostics.report("outline", vec![error("a"), error("b")]);
+        assert_eq!(diagnostics.errors_untracked(), [error("a"), error("b")]);
+
+        diagnostics.report("outline", Vec::new());
+        assert_eq!(diagnostics.errors_untracked(), [error("a")]);
+        diagnostics.report("scene", vec![error("c")]);
+        assert_eq!(diagnostics.errors_untracked(), [error("c")]);
+
+        diagnostics.report("scene", Vec::new());
+        assert!(diagnostics.0.with_untracked(BTreeMap::is_empty));
+    }
+}
@@ -1,39 +1,75 @@
 use std::{cell::RefCell, rc::Rc};
 
 use bytemuck::{Pod, Zeroable};
-use glam::{Mat4, Vec3};
-use leptos::prelude::{GetUntracked, RwSignal};
+use glam::Mat4;
 use wgpu::util::DeviceExt;
 use wgpu::StoreOp;
 
-use crate::components::demos::utils::InstanceCtx;
-use crate::render::web_gpu::default_pipeline;
-use crate::{
-    components::demos::utils::RenderPass,
-    render::renderer::{
-        camera_input::CameraInput, instance::InstanceRaw, mesh::CpuMesh, vertex::Vertex,
+use crate::render::renderer::{
+    camera_input::CameraInput,
+    clock::{Clock, FrameTime},
+    graph::{
+        ColorAttachment, Geometry, LiveShaders, MaterialGroup, MeshPass, MeshPassDesc, NodeRef,
+        Shaders, Target,
     },
+    instance::InstanceRaw,
+    mesh::{CpuMesh, MeshSlot},
+    vertex::Vertex,
 };
 
-use super::utils::{FragmentShader, VertexShader, create_shader_module};
+use super::textures::TextureSlots;
+use super::pipeline_cache::PipelineCache;
+use super::surface_context::RenderTarget;
+use super::utils::create_depth_view;
 use super::{resource_context::ResourceContext, surface_context::SurfaceContext};
 
 #[repr(C)]
-#[derive(Copy, Clone, Pod, Zeroable)]
-struct TimeUBO {
-    millis: u32,
-    secs: u32,
-    dt_ms: u32,
-    frame_id: u32,
+#[derive(Copy, Clone, Default, Pod, Zeroable)]
+/// The frame clock as shaders see it. Every field is the renderer's clock
+/// time, so pausing, stepping and speed apply to all of them: `secs` and
+/// `millis` split `elapsed_secs` (they used to be wall-clock time since the
+/// epoch, which no shader could use without overflowing an `f32`).
+pub struct TimeUBO {
+    /// 0-999, past `secs`.
+    pub millis: u32,
+    /// Whole seconds since the clock started.
+    pub secs: u32,
+    pub dt_ms: u32,
+    pub frame_id: u32,
+    pub elapsed_secs: f32, // since the clock started
+    pub _pad: [f32; 3],
+}
+
+impl TimeUBO {
+    pub fn new(t: FrameTime, frame_id: u32) -> Self {
+        let elapsed_ms = t.elapsed_ms.max(0.0);
+        Self {
+            millis: (elapsed_ms as u64 % 1000) as u32,
+            secs: (elapsed_ms / 1000.0) as u32,
+            dt_ms: t.dt_ms.max(0.0) as u32, // u32 fits 49 days
+            frame_id,
+            elapsed_secs: (elapsed_ms / 1000.0) as f32,
+            _pad: [0.0; 3],
+        }
+    }
 }
 
 pub struct GpuState {
     pub surface_context: SurfaceContext,
     pub resource_context: ResourceContext,
 
-    pub start_ms: f64,
-    pub prev_ms: f64, // since last frame
+    /// Sampled once per frame in `begin_frame`; swap it to pause/step/slow down.
+    pub clock: Box<dyn Clock>,
     pub frame_counter: u32,
+    /// This frame's time, written to the time UBO by `populate_common_buffers`.
+    pub time: TimeUBO,
+
+    /// Compiled pipelines/modules, keyed on (normalised) source and state.
+    pub pipeline_cache: PipelineCache,
+
+    /// Bumped each time the device is re-created; passes compare it to drop
+    /// pipelines/buffers that belong to the old device.
+    pub generation: u32,
 
     pub depth_view: wgpu::TextureView,
 }
@@ -47,7 +83,7 @@ pub fn create_vert_buff_init(sc: &SurfaceContext, vertices: &[Vertex]) -> wgpu::
         })
 }
 
-pub fn create_idx_buff_init(sc: &SurfaceContext, indices: &[u16]) -> wgpu::Buffer {
+pub fn create_idx_buff_init(sc: &SurfaceContext, indices: &[u32]) -> wgpu::Buffer {
     sc.device
         .create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: Some("Index Buffer"),
@@ -79,8 +115,30 @@ pub enum Projection {
     Custom(Mat4),
 }
 
+/// Why `begin_frame` produced no frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameError {
+    /// No surface texture this time (timeout, still outdated); try next frame.
+    Skipped,
+
+    /// The device is gone; the `GpuState` has to be rebuilt.
+    DeviceLost,
+}
+
+impl std::fmt::Display for FrameError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FrameError::Skipped => write!(f, "frame skipped"),
+            FrameError::DeviceLost => write!(f, "GPU device lost"),
+        }
+    }
+}
+
+impl std::error::Error for FrameError {}
+
 pub struct FrameCtx {
-    pub frame: wgpu::SurfaceTexture,
+    /// `None` when rendering offscreen; there is nothing to present.
+    pub frame: Option<wgpu::SurfaceTexture>,
     pub encoder: wgpu::CommandEncoder,
     pub color_view: wgpu::TextureView,
     pub depth_view: wgpu::TextureView,
@@ -138,15 +196,69 @@ impl GpuState {
         )
     }
 
+    /// Follow the canvas to a new size (physical pixels): reconfigures the
+    /// target, rebuilds the depth buffer and updates the resolution uniform.
+    pub fn resize(&mut self, width: u32, height: u32) {
+        if !self.surface_context.resize(width, height) {
+            return;
+        }
+
+        let sc = &self.surface_context;
+        self.depth_view = create_depth_view(&sc.device, &sc.config);
+        self.write_resolution();
+    }
+
+    fn write_resolution(&self) {
+        let (w, h) = self.resolution();
+        let res = [w, h, 0 as f32, 0 as f32];
+        self.surface_context.queue.write_buffer(
+            &self.resource_context.resolution_ubo,
+            0,
+            bytemuck::cast_slice(&res),
+        );
+    }
+
+    pub fn set_clock(&mut self, clock: impl Clock + 'static) {
+        self.clock = Box::new(clock);
+    }
+
     /// Borrow-checked “begin frame” – returns a FrameCtx the caller can mutate.
-    pub fn begin_frame(&mut self) -> FrameCtx {
-        // 1) acquire swap-chain tex
-        let frame = self
-            .surface_context
-            .surface
-            .get_current_texture()
-            .expect("swap-chain error");
-        let color_view = frame.texture.create_view(&Default::default());
+    ///
+    /// Outdated/lost surfaces are reconfigured in place; when no texture can
+    /// be had this frame it is `Skipped`. `DeviceLost` means the whole state
+    /// has to be rebuilt (see `web_gpu::reinit_wgpu`).
+    pub fn begin_frame(&mut self) -> Result<FrameCtx, FrameError> {
+        if self.surface_context.is_device_lost() {
+            return Err(FrameError::DeviceLost);
+        }
+
+        // 1) acquire swap-chain tex (or just view the offscreen one)
+        let sc = &self.surface_context;
+        let (frame, color_view) = match &sc.target {
+            RenderTarget::Canvas(surface) => {
+                let frame = match surface.get_current_texture() {
+                    Ok(frame) => frame,
+                    Err(wgpu::SurfaceError::Outdated | wgpu::SurfaceError::Lost) => {
+                        // e.g. resized or backgrounded: configure again, retry once
+                        surface.configure(&sc.device, &sc.config);
+                        surface
+                            .get_current_texture()
+                            .map_err(|_| FrameError::Skipped)?
+                    }
+                    Err(wgpu::SurfaceError::OutOfMemory) => return Err(FrameError::DeviceLost),
+                    Err(wgpu::SurfaceError::Timeout | wgpu::SurfaceError::Other) => {
+                        return Err(FrameError::Skipped);
+                    }
+                };
+                let view = frame.texture.create_view(&Default::default());
+                (Some(frame), view)
+            }
+            RenderTarget::Offscreen(texture) => (None, texture.create_view(&Default::default())),
+        };
+
+        // one clock sample per frame, shared by every pass
+        self.time = TimeUBO::new(self.clock.tick(), self.frame_counter);
+        self.frame_counter = self.frame_counter.wrapping_add(1);
 
         // 2) create an encoder for the caller
         let encoder = self
@@ -154,12 +266,12 @@ impl GpuState {
             .device
             .create_command_encoder(&Default::default());
 
-        FrameCtx {
+        Ok(FrameCtx {
             frame,
             encoder,
             color_view,
             depth_view: self.depth_view.clone(),
-        }
+        })
     }
 
     pub fn populate_common_buffers(&mut self, proj: &Projection, ci: &CameraInput) {
@@ -185,7 +297,7 @@ impl GpuState {
                 Mat4::orthographic_rh_gl(0.0, width, height, 0.0, -1.0, 1.0)
             }
             &Projection::Fulcrum => {
-                let aspect = self.resolution().0 as f32 / self.resolution().1 as f32;
+                let aspect = self.resolution().0 / self.resolution().1;
                 let proj = Mat4::perspective_rh_gl(45f32.to_radians(), aspect, 0.1, 100.0);
                 let view = Mat4::look_at_rh(ci.camera.eye(), ci.camera.target, ci.camera.up);
 
@@ -199,35 +311,28 @@ impl GpuState {
             bytemuck::cast_slice(&view_proj.to_cols_array_2d()),
         );
 
-        let (w, h) = self.resolution(); // canvas actual size
-        let res = [w as f32, h as f32, 0 as f32, 0 as f32];
-        self.surface_context.queue.write_buffer(
-            &self.resource_context.resolution_ubo,
-            0,
-            bytemuck::cast_slice(&res),
-        );
-
-        // ── time maths ────────────────────────────────
-        let now_ms = web_sys::window().unwrap().performance().unwrap().now();
-        let dt_ms = (now_ms - self.prev_ms) as u32; // u32 fits 49 days
-        let secs = (now_ms / 1000.0) as u32;
-        let millis = (now_ms as u32) % 1000;
-
-        let payload = TimeUBO {
-            millis,
-            secs,
-            dt_ms,
-            frame_id: self.frame_counter,
-        };
+        self.write_resolution(); // canvas actual size
 
         self.surface_context.queue.write_buffer(
             &self.resource_context.time_ubo,
             0,
-            bytemuck::bytes_of(&payload),
+            bytemuck::bytes_of(&self.time),
         );
+    }
+
+    /// Values for the params the shaders declare (packed by
+    /// [`ShaderParams::bytes`]); nothing to do without any.
+    pub fn populate_params(&self, bytes: &[u8]) {
+        if !bytes.is_empty() {
+            self.surface_context
+                .queue
+                .write_buffer(&self.resource_context.params_ubo, 0, bytes);
+        }
+    }
 
-        self.frame_counter += 1;
-        self.prev_ms = now_ms;
+    /// Upload the channel images that changed since the last frame.
+    pub fn sync_textures(&mut self, slots: &TextureSlots) {
+        self.resource_context.sync_textures(&self.surface_context, slots);
     }
 
     /// Finalise: submit & present.
@@ -240,7 +345,73 @@ impl GpuState {
         drop(frame_ctx.color_view); // no-op but clarifies intent
 
         // 4) submit + present
-        frame_ctx.frame.present();
+        if let Some(frame) = frame_ctx.frame {
+            frame.present();
+        }
+    }
+
+    /// Copy the offscreen target back to the CPU as tightly packed RGBA8 rows.
+    ///
+    /// Blocks until the GPU is done, so call it after `end_frame`.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn read_pixels(&self) -> anyhow::Result<Vec<u8>> {
+        let RenderTarget::Offscreen(texture) = &self.surface_context.target else {
+            anyhow::bail!("read_pixels needs an offscreen target");
+        };
+
+        let device = &self.surface_context.device;
+        let (width, height) = (texture.width(), texture.height());
+
+        let bytes_per_px = texture
+            .format()
+            .block_copy_size(None)
+            .filter(|&b| b == 4)
+            .ok_or_else(|| anyhow::anyhow!("read_pixels only supports 4-byte formats"))?;
+
+        // rows in a buffer copy must be 256-byte aligned
+        let unpadded = width * bytes_per_px;
+        let padded = unpadded.div_ceil(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT)
+            * wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+
+        let buf = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("readback buffer"),
+            size: (padded * height) as u64,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        let mut encoder = device.create_command_encoder(&Default::default());
+        encoder.copy_texture_to_buffer(
+            texture.as_image_copy(),
+            wgpu::TexelCopyBufferInfo {
+                buffer: &buf,
+                layout: wgpu::TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: Some(padded),
+                    rows_per_image: Some(height),
+                },
+            },
+            texture.size(),
+        );
+        self.surface_context.queue.submit(Some(encoder.finish()));
+
+        let slice = buf.slice(..);
+        let (tx, rx) = std::sync::mpsc::channel();
+        slice.map_async(wgpu::MapMode::Read, move |r| {
+            let _ = tx.send(r);
+        });
+        device.poll(wgpu::PollType::Wait)?;
+        rx.recv()??;
+
+        let mapped = slice.get_mapped_range();
+        let mut out = Vec::with_capacity((unpadded * height) as usize);
+        for row in mapped.chunks(padded as usize) {
+            out.extend_from_slice(&row[..unpadded as usize]);
+        }
+        drop(mapped);
+        buf.unmap();
+
+        Ok(out)
     }
 }
 
@@ -248,117 +419,49 @@ pub fn make_default_rpass(
     mesh: Rc<RefCell<CpuMesh>>,
     proj: Rc<RefCell<Projection>>,
 
-    vs_src: RwSignal<String>,
-    fs_src: RwSignal<String>,
-) -> (RenderPass, Rc<RefCell<Option<wgpu::RenderPipeline>>>) {
-    let pipeline = Rc::new(RefCell::new(None));
-
-    let pipe_handle = pipeline.clone();
-    let mesh_handle = mesh.clone();
-
-    let vbuf_handle:  Rc<RefCell<Option<wgpu::Buffer>>> = Rc::new(RefCell::new(None));
-    let ibuf_handle:  Rc<RefCell<Option<wgpu::Buffer>>> = Rc::new(RefCell::new(None));
-    let inst_handle: Rc<RefCell<Option<InstanceCtx>>> = Rc::new(RefCell::new(None));
-
-    let pass = Rc::new(RefCell::new(
-        move |st: &mut GpuState, cam: &CameraInput, ctx: &mut FrameCtx| {
-            if pipe_handle.borrow().is_none() {
-                let pipe = default_pipeline(
-                    &st.surface_context.device,
-                    &st.surface_context.config,
-                    &st.resource_context
-                        .pipeline_layout(&st.surface_context.device),
-                    &VertexShader(create_shader_module(
-                        "default live vs",
-                        &vs_src.get_untracked(),
-                        &st.surface_context.device,
-                    )),
-                    &FragmentShader(create_shader_module(
-                        "default live fs",
-                        &fs_src.get_untracked(),
-                        &st.surface_context.device,
-                    )),
-                );
-
-                *pipe_handle.borrow_mut() = Some(pipe);
-            }
-
-            if vbuf_handle.borrow().is_none() {
-                *vbuf_handle.borrow_mut() = Some(create_vert_buff_init(
-                    &st.surface_context,
-                    mesh_handle.borrow().vertices.as_slice(),
-                ));
-                *ibuf_handle.borrow_mut() = Some(create_idx_buff_init(
-                    &st.surface_context,
-                    mesh_handle.borrow().indices.as_slice(),
-                ));
-            }
-
-            if inst_handle.borrow().is_none() {
-                *inst_handle.borrow_mut() = Some(InstanceCtx::new(&st.surface_context, 256));
-            }
-
-            {
-                let mut binding = inst_handle.borrow_mut();
-                let inst = binding.as_mut().unwrap();
-
-                inst.sync_instances(&st.surface_context, || {
-                    let translations = [Vec3::ZERO];
-                    translations
-                        .iter()
-                        .map(|p| Mat4::from_translation(*p))
-                        .map(InstanceRaw::from_mat4)
-                        .collect()
-                });
-            }
-
-            st.populate_common_buffers(&proj.borrow(), cam);
-
-            let mut rp = ctx.encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                label: Some("Default Render Pass"),
-                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view: &ctx.color_view,
-                    resolve_target: None,
-                    ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(wgpu::Color::RED),
-                        store: wgpu::StoreOp::Store,
-                    },
-                })],
-                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
-                    view: &ctx.depth_view,
-                    depth_ops: Some(wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(1.0),
-                        store: StoreOp::Store,
-                    }),
-                    stencil_ops: None,
-                }),
-
-                occlusion_query_set: None,
-                timestamp_writes: None,
-            });
-
-            rp.set_pipeline(pipe_handle.borrow().as_ref().unwrap());
+    shaders: LiveShaders,
+    textures: TextureSlots,
+    // swapped in for `mesh` when loaded
+    model: Option<MeshSlot>,
+) -> NodeRef {
+    MeshPass::new(MeshPassDesc {
+        label: "Default Render Pass",
+        color: ColorAttachment {
+            target: Target::Surface,
+            load: wgpu::LoadOp::Clear(wgpu::Color::RED),
+        },
+        depth: Some(wgpu::LoadOp::Clear(1.0)),
 
-            let binding = vbuf_handle.borrow();
-            let vbuf = binding.as_ref().unwrap();
-            rp.set_vertex_buffer(0, vbuf.slice(..));
+        shaders: Shaders::Live(shaders),
+        entry_points: ("vs_main", "fs_main"),
+        topology: wgpu::PrimitiveTopology::TriangleList,
+        blend: None,
+        material: MaterialGroup::Texturing(textures),
 
-            let binding = inst_handle.borrow();
-            let inst = binding.as_ref().unwrap();
-            rp.set_vertex_buffer(1, inst.buff.slice(..));
+        geometry: match model {
+            Some(model) => Geometry::Model { mesh, model },
+            None => Geometry::Mesh(mesh),
+        },
+        instances: Some(Box::new(|| vec![InstanceRaw::from_mat4(Mat4::IDENTITY)])),
+        projection: proj,
 
-            let binding = ibuf_handle.borrow();
-            let ibuf = binding.as_ref().unwrap();
-            rp.set_index_buffer(ibuf.slice(..), wgpu::IndexFormat::Uint16);
+        enabled: None,
+    })
+    .into_node()
+}
 
-            rp.set_bind_group(0, &st.resource_context.common_bind_group.group, &[]);
-            rp.set_bind_group(1, &st.resource_context.spatial_bind_group.group, &[]);
-            rp.set_bind_group(2, &st.resource_context.texturing_bind_group.group, &[]);
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-            let inst_count = inst_handle.borrow().as_ref().unwrap().count;
-            rp.draw_indexed(0..mesh_handle.borrow().index_count, 0, 0..inst_count);
-        },
-    ));
+    #[test]
+    fn seconds_and_millis_split_the_clock_time() {
+        let t = TimeUBO::new(FrameTime { elapsed_ms: 61_250.5, dt_ms: 16.7 }, 7);
+        assert_eq!((t.secs, t.millis, t.dt_ms, t.frame_id), (61, 250, 16, 7));
+        assert_eq!(t.elapsed_secs, 61.2505);
 
-    (pass, pipeline)
+        // a clock seeked back past zero reads as its start
+        let t = TimeUBO::new(FrameTime { elapsed_ms: -5.0, dt_ms: -60.0 }, 0);
+        assert_eq!((t.secs, t.millis, t.dt_ms, t.elapsed_secs), (0, 0, 0, 0.0));
+    }
 }
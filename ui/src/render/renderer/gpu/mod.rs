@@ -1,6 +1,14 @@
 pub mod utils;
+pub mod diagnostics;
 pub mod gpu_state;
+pub mod mipmaps;
+pub mod params;
+pub mod pipeline_cache;
+pub mod preprocess;
+pub mod samplers;
+pub mod setup;
 pub mod surface_context;
+pub mod textures;
 pub mod resource_context;
 pub mod vertex_ctx;
 
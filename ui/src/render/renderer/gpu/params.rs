@@ -0,0 +1,579 @@
+//! Uniforms the user declares in their own shaders. Whatever sits at
+//! `@group(3) @binding(0)` (usually a struct) is reflected with naga: every
+//! field gets a control in the editor, and the values are packed into the
+//! params buffer each frame.
+//!
+//! The control follows the type, tuned by annotations in the field's
+//! trailing comment:
+//!
+//! ```wgsl
+//! struct Params {
+//!     speed: f32,       // @range(0, 10, 0.5) @default(2)
+//!     tint: vec3<f32>,  // @color @default(1, 0.5, 0)
+//!     wobble: u32,      // @toggle
+//! }
+//! @group(3) @binding(0) var<uniform> params: Params;
+//! ```
+//!
+//! `f32`, `i32`, `u32` and their vectors get controls; other fields (matrices,
+//! arrays, nested structs) are listed and left at zero.
+
+use std::collections::HashMap;
+
+use leptos::prelude::{Get, GetUntracked, RwSignal, Set, Update, With, WithUntracked};
+
+use super::diagnostics::{Diagnostic, Severity, SourceSpan, Stage, parse_and_validate};
+
+pub const PARAMS_GROUP: u32 = 3;
+pub const PARAMS_BINDING: u32 = 0;
+
+/// Size of the params buffer; a bigger declaration doesn't compile.
+pub const MAX_PARAMS_SIZE: u64 = 1024;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Scalar {
+    F32,
+    I32,
+    U32,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Control {
+    Slider { min: f32, max: f32, step: f32 },
+    /// `vec3<f32>` / `vec4<f32>` with `@color`; alpha gets a slider.
+    Color,
+    /// `i32` / `u32` with `@toggle`: 0 or 1.
+    Toggle,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ParamField {
+    pub name: String,
+    pub scalar: Scalar,
+    /// 1 for scalars, 2 to 4 for vectors.
+    pub components: usize,
+    /// Bytes into the buffer.
+    pub offset: u32,
+    pub control: Control,
+    /// Unused components are 0.
+    pub default: [f32; 4],
+}
+
+/// What a pair of shaders declares at the params binding.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ParamLayout {
+    pub fields: Vec<ParamField>,
+    /// Fields without a control, as `name: type`.
+    pub unsupported: Vec<String>,
+    /// Bytes the shaders read.
+    pub size: u32,
+}
+
+impl ParamLayout {
+    pub fn is_empty(&self) -> bool {
+        self.fields.is_empty() && self.unsupported.is_empty()
+    }
+
+    /// The buffer contents for `values` (by field name; missing ones take
+    /// their default).
+    pub fn pack(&self, values: &HashMap<String, [f32; 4]>) -> Vec<u8> {
+        let mut out = vec![0u8; self.size as usize];
+        for field in &self.fields {
+            let value = values.get(&field.name).unwrap_or(&field.default);
+            for (i, &v) in value.iter().take(field.components).enumerate() {
+                let bytes = match field.scalar {
+                    Scalar::F32 => v.to_le_bytes(),
+                    Scalar::I32 => (v.round() as i32).to_le_bytes(),
+                    Scalar::U32 => (v.round() as u32).to_le_bytes(),
+                };
+                let at = field.offset as usize + 4 * i;
+                out[at..at + 4].copy_from_slice(&bytes);
+            }
+        }
+        out
+    }
+}
+
+type Var<'a> = (naga::Handle<naga::GlobalVariable>, &'a naga::GlobalVariable);
+
+fn params_var(module: &naga::Module) -> Option<Var<'_>> {
+    module.global_variables.iter().find(|(_, var)| {
+        var.space == naga::AddressSpace::Uniform
+            && var.binding
+                == Some(naga::ResourceBinding {
+                    group: PARAMS_GROUP,
+                    binding: PARAMS_BINDING,
+                })
+    })
+}
+
+/// A declaration too big for the buffer, as a compile error on the
+/// variable.
+pub(crate) fn check_size(src: &str, stage: Stage, module: &naga::Module) -> Result<(), Diagnostic> {
+    let Some((handle, var)) = params_var(module) else { return Ok(()) };
+
+    let size = module.types[var.ty].inner.size(module.to_ctx());
+    if u64::from(size) <= MAX_PARAMS_SIZE {
+        return Ok(());
+    }
+    Err(Diagnostic {
+        severity: Severity::Error,
+        stage,
+        message: format!(
+            "params at @group({PARAMS_GROUP}) @binding({PARAMS_BINDING}) take {size} bytes, \
+             only {MAX_PARAMS_SIZE} fit"
+        ),
+        span: SourceSpan::from_naga(src, module.global_variables.get_span(handle)),
+        file: None,
+    })
+}
+
+/// The params `src` declares; `None` if it declares none.
+pub fn reflect(src: &str, module: &naga::Module) -> Option<ParamLayout> {
+    let (handle, var) = params_var(module)?;
+    let ty = &module.types[var.ty];
+    let mut layout = ParamLayout {
+        size: ty.inner.size(module.to_ctx()),
+        ..Default::default()
+    };
+
+    let mut add = |name: &str, offset: u32, ty: naga::Handle<naga::Type>, decl: Decl| {
+        match kind(&module.types[ty].inner) {
+            Some((scalar, components)) => layout.fields.push(field(
+                name.to_owned(),
+                scalar,
+                components,
+                offset,
+                &annotations(decl.comment),
+            )),
+            None => layout.unsupported.push(format!("{name}: {}", decl.ty)),
+        }
+    };
+
+    match (&ty.inner, &ty.name) {
+        (naga::TypeInner::Struct { members, .. }, Some(struct_name)) => {
+            let decls = member_decls(src, struct_name);
+            for m in members {
+                let name = m.name.as_deref().unwrap_or("_");
+                let decl = decls.get(name).copied().unwrap_or_default();
+                add(name, m.offset, m.ty, decl);
+            }
+        }
+        // a bare `var<uniform> gain: f32` is a single field
+        _ => {
+            let name = var.name.as_deref().unwrap_or("_");
+            let span = module.global_variables.get_span(handle);
+            let decl = SourceSpan::from_naga(src, span)
+                .map(|s| line_decl(line_at(src, s.start)).1)
+                .unwrap_or_default();
+            add(name, 0, var.ty, decl);
+        }
+    }
+    Some(layout)
+}
+
+/// What both shaders declare, fields merged by name; `None` while either
+/// doesn't compile.
+pub fn reflect_pair(vs: &str, fs: &str) -> Option<ParamLayout> {
+    let mut merged = ParamLayout::default();
+    for (src, stage) in [(fs, Stage::Fragment), (vs, Stage::Vertex)] {
+        let (module, _) = parse_and_validate(src, stage).ok()?;
+        let Some(layout) = reflect(src, &module) else { continue };
+
+        merged.size = merged.size.max(layout.size);
+        for f in layout.fields {
+            if !merged.fields.iter().any(|m| m.name == f.name) {
+                merged.fields.push(f);
+            }
+        }
+        for u in layout.unsupported {
+            if !merged.unsupported.contains(&u) {
+                merged.unsupported.push(u);
+            }
+        }
+    }
+    Some(merged)
+}
+
+fn kind(inner: &naga::TypeInner) -> Option<(Scalar, usize)> {
+    let scalar = |s: &naga::Scalar| match (s.kind, s.width) {
+        (naga::ScalarKind::Float, 4) => Some(Scalar::F32),
+        (naga::ScalarKind::Sint, 4) => Some(Scalar::I32),
+        (naga::ScalarKind::Uint, 4) => Some(Scalar::U32),
+        _ => None,
+    };
+    match inner {
+        naga::TypeInner::Scalar(s) => Some((scalar(s)?, 1)),
+        naga::TypeInner::Vector { size, scalar: s } => Some((scalar(s)?, *size as usize)),
+        _ => None,
+    }
+}
+
+#[derive(Default)]
+struct Annotations {
+    range: Option<(f32, f32, Option<f32>)>,
+    default: Vec<f32>,
+    color: bool,
+    toggle: bool,
+}
+
+/// `@name` or `@name(args)` anywhere in a comment; unknown names and
+/// arguments that aren't numbers are ignored, it's still just a comment.
+fn annotations(comment: &str) -> Annotations {
+    let mut found = Annotations::default();
+    for part in comment.split('@').skip(1) {
+        let name_len = part
+            .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+            .unwrap_or(part.len());
+        let (name, rest) = part.split_at(name_len);
+        let args: Vec<f32> = rest
+            .strip_prefix('(')
+            .and_then(|r| r.split_once(')'))
+            .map(|(args, _)| args.split(',').filter_map(|a| a.trim().parse().ok()).collect())
+            .unwrap_or_default();
+
+        match (name, args.as_slice()) {
+            ("range", &[min, max]) => found.range = Some((min, max, None)),
+            ("range", &[min, max, step]) => found.range = Some((min, max, Some(step))),
+            ("default", [_, ..]) => found.default = args.clone(),
+            ("color" | "colour", _) => found.color = true,
+            ("toggle", _) => found.toggle = true,
+            _ => {}
+        }
+    }
+    found
+}
+
+fn field(
+    name: String,
+    scalar: Scalar,
+    components: usize,
+    offset: u32,
+    a: &Annotations,
+) -> ParamField {
+    let control = match scalar {
+        Scalar::I32 | Scalar::U32 if a.toggle && components == 1 => Control::Toggle,
+        Scalar::F32 if a.color && components >= 3 => Control::Color,
+        _ => {
+            let (min, max, step) = match (a.range, scalar) {
+                (Some(r), _) => r,
+                (None, Scalar::F32) => (0.0, 1.0, None),
+                (None, _) => (0.0, 10.0, None),
+            };
+            let (min, max) = (min.min(max), min.max(max));
+            let step = match (step, scalar) {
+                (Some(s), _) if s > 0.0 => s,
+                (_, Scalar::F32) => (max - min) / 100.0,
+                _ => 1.0,
+            };
+            Control::Slider { min, max, step }
+        }
+    };
+
+    let mut default = [0.0; 4];
+    match a.default.as_slice() {
+        // one value for every component
+        &[v] => default[..components].fill(v),
+        vs => {
+            for (d, v) in default.iter_mut().zip(vs).take(components) {
+                *d = *v;
+            }
+        }
+    }
+    match control {
+        Control::Slider { min, max, .. } => {
+            for d in &mut default[..components] {
+                *d = d.clamp(min, max);
+            }
+        }
+        Control::Color if a.default.is_empty() => default[..components].fill(1.0),
+        Control::Color => {
+            for d in &mut default[..components] {
+                *d = d.clamp(0.0, 1.0);
+            }
+        }
+        Control::Toggle => default[0] = (default[0] != 0.0) as u32 as f32,
+    }
+
+    ParamField {
+        name,
+        scalar,
+        components,
+        offset,
+        control,
+        default,
+    }
+}
+
+/// A member's declared type and trailing comment, as written.
+#[derive(Copy, Clone, Default)]
+struct Decl<'a> {
+    ty: &'a str,
+    comment: &'a str,
+}
+
+/// Declarations in `struct name { ... }`, by member name. naga keeps no
+/// spans for members, so this reads the text, one member per line.
+fn member_decls<'a>(src: &'a str, name: &str) -> HashMap<&'a str, Decl<'a>> {
+    let mut decls = HashMap::new();
+    let Some(body) = struct_body(src, name) else { return decls };
+
+    for line in body.lines() {
+        let (code, decl) = line_decl(line);
+        let code = code.trim_start();
+        // `@align(16) @size(32) name: type`
+        let mut code = code;
+        while let Some(rest) = code.strip_prefix('@') {
+            let attr_end = match rest.find(|c: char| !c.is_ascii_alphanumeric() && c != '_') {
+                Some(i) if rest[i..].starts_with('(') => {
+                    rest[i..].find(')').map_or(rest.len(), |j| i + j + 1)
+                }
+                Some(i) => i,
+                None => rest.len(),
+            };
+            code = rest[attr_end..].trim_start();
+        }
+        if let Some((member, _)) = code.split_once(':') {
+            decls.insert(member.trim(), decl);
+        }
+    }
+    decls
+}
+
+/// Text between the braces of `struct name`, comments included.
+fn struct_body<'a>(src: &'a str, name: &str) -> Option<&'a str> {
+    let ident = |c: char| c.is_ascii_alphanumeric() || c == '_';
+    src.match_indices("struct").find_map(|(at, _)| {
+        if src[..at].ends_with(ident) {
+            return None;
+        }
+        let rest = src[at + "struct".len()..].trim_start();
+        let after = rest.strip_prefix(name)?;
+        if after.starts_with(ident) {
+            return None;
+        }
+        let open = after.trim_start().strip_prefix('{')?;
+        // a `}` inside a comment would end it early; not worth a tokenizer
+        open.find('}').map(|end| &open[..end])
+    })
+}
+
+/// Code and declaration of one line: `name: type, // comment`.
+fn line_decl(line: &str) -> (&str, Decl<'_>) {
+    let (code, comment) = line.split_once("//").unwrap_or((line, ""));
+    let ty = code
+        .split_once(':')
+        .map_or("", |(_, ty)| ty.trim().trim_end_matches([',', ';']).trim_end());
+    (code, Decl { ty, comment })
+}
+
+fn line_at(src: &str, at: usize) -> &str {
+    let start = src[..at].rfind('\n').map_or(0, |i| i + 1);
+    let end = src[at..].find('\n').map_or(src.len(), |i| at + i);
+    &src[start..end]
+}
+
+/// Values for the params the lab's shaders declare, kept by field name so
+/// they survive edits to the rest of the shader.
+#[derive(Copy, Clone)]
+pub struct ShaderParams {
+    layout: RwSignal<ParamLayout>,
+    values: RwSignal<HashMap<String, [f32; 4]>>,
+}
+
+impl Default for ShaderParams {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ShaderParams {
+    pub fn new() -> Self {
+        Self {
+            layout: RwSignal::new(ParamLayout::default()),
+            values: RwSignal::new(HashMap::new()),
+        }
+    }
+
+    /// Re-reflect after an edit. While the sources don't compile the old
+    /// layout stays, so the controls don't come and go with every keystroke.
+    pub fn sync(&self, vs: &str, fs: &str) {
+        let Some(layout) = reflect_pair(vs, fs) else { return };
+        if self.layout.with_untracked(|old| *old == layout) {
+            return;
+        }
+
+        // a field that changed shape (or default) starts over from its default
+        let old = self.layout.with_untracked(|l| l.fields.clone());
+        self.values.update(|values| {
+            for f in &layout.fields {
+                let same = old.iter().any(|o| {
+                    (&o.name, o.components, o.control, o.default)
+                        == (&f.name, f.components, f.control, f.default)
+                });
+                if !same {
+                    values.insert(f.name.clone(), f.default);
+                }
+            }
+        });
+        self.layout.set(layout);
+    }
+
+    pub fn layout(&self) -> ParamLayout {
+        self.layout.get()
+    }
+
+    pub fn get(&self, name: &str) -> [f32; 4] {
+        self.values.with(|v| v.get(name).copied().unwrap_or_default())
+    }
+
+    pub fn update(&self, name: &str, f: impl FnOnce(&mut [f32; 4])) {
+        self.values.update(|v| f(v.entry(name.to_owned()).or_default()));
+    }
+
+    /// Whether any field is off its default.
+    pub fn modified(&self) -> bool {
+        self.layout.with(|l| {
+            self.values
+                .with(|v| l.fields.iter().any(|f| v.get(&f.name).is_some_and(|x| *x != f.default)))
+        })
+    }
+
+    pub fn reset(&self) {
+        let layout = self.layout.get_untracked();
+        self.values.update(|v| {
+            for f in &layout.fields {
+                v.insert(f.name.clone(), f.default);
+            }
+        });
+    }
+
+    /// What goes in the params buffer this frame; empty without params.
+    pub fn bytes(&self) -> Vec<u8> {
+        self.layout
+            .with_untracked(|l| self.values.with_untracked(|v| l.pack(v)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use leptos::prelude::Owner;
+
+    use super::*;
+
+    const FS: &str = "struct Params {
+        tint: vec3<f32>, // @color
+        gain: f32,       // @range(0, 2) @default(1)
+    }
+    @group(3) @binding(0) var<uniform> params: Params;
+
+    @fragment
+    fn fs_main() -> @location(0) vec4<f32> {
+        return vec4<f32>(params.tint * params.gain, 1.0);
+    }";
+
+    const VS: &str = "@vertex
+    fn vs_main() -> @builtin(position) vec4<f32> {
+        return vec4<f32>(0.0);
+    }";
+
+    /// `fields` of a `Params` struct at the params binding, read by the
+    /// fragment stage.
+    fn params(fields: &str) -> String {
+        format!(
+            "struct Params {{\n{fields}\n}}\n\
+             @group(3) @binding(0) var<uniform> params: Params;\n\
+             @fragment fn fs_main() -> @location(0) vec4<f32> {{\n\
+                 let p = params;\n\
+                 return vec4<f32>(0.0);\n\
+             }}"
+        )
+    }
+
+    fn declared(fields: &str) -> ParamLayout {
+        reflect_pair(VS, &params(fields)).expect("compiles")
+    }
+
+    #[test]
+    fn controls_follow_the_type_and_annotations() {
+        let layout = reflect_pair(VS, FS).unwrap();
+        let fields: Vec<_> = layout.fields.iter().map(|f| (f.name.as_str(), f.control)).collect();
+        assert_eq!(
+            fields,
+            [
+                ("tint", Control::Color),
+                ("gain", Control::Slider { min: 0.0, max: 2.0, step: 0.02 }),
+            ]
+        );
+        assert_eq!(layout.fields[0].default, [1.0, 1.0, 1.0, 0.0], "colours start white");
+        assert_eq!(layout.fields[1].default, [1.0, 0.0, 0.0, 0.0]);
+        assert_eq!((layout.fields[1].offset, layout.size), (12, 16));
+
+        let layout = declared(
+            "on: u32,           // @toggle @default(5)
+             steps: i32,        // @range(10, -10, 2.5)
+             at: vec2<f32>,     // @default(2)
+             col: vec4<f32>,    // @colour @default(0.5, 2)",
+        );
+        let [on, steps, at, col] = &layout.fields[..] else { panic!("{layout:?}") };
+        assert_eq!((on.control, on.scalar, on.default[0]), (Control::Toggle, Scalar::U32, 1.0));
+        assert_eq!(steps.control, Control::Slider { min: -10.0, max: 10.0, step: 2.5 });
+        assert_eq!(at.default, [1.0, 1.0, 0.0, 0.0], "clamped to the slider");
+        assert_eq!((col.control, col.default), (Control::Color, [0.5, 1.0, 0.0, 0.0]));
+    }
+
+    #[test]
+    fn other_types_are_listed_without_controls() {
+        let layout = declared("m: mat2x2<f32>, // @range(0, 1)\n    x: f32,");
+        assert_eq!(layout.unsupported, ["m: mat2x2<f32>"]);
+        assert_eq!(layout.fields.len(), 1);
+
+        // a bare uniform is one field, its comment on its own line
+        let bare = "@group(3) @binding(0) var<uniform> gain: f32; // @range(0, 4)
+            @fragment fn fs_main() -> @location(0) vec4<f32> { return vec4<f32>(gain); }";
+        let layout = reflect_pair(VS, bare).unwrap();
+        assert_eq!(layout.fields[0].name, "gain");
+        assert_eq!(layout.fields[0].control, Control::Slider { min: 0.0, max: 4.0, step: 0.04 });
+
+        assert!(reflect_pair(VS, "not wgsl").is_none());
+        let plain = "@fragment fn fs_main() -> @location(0) vec4<f32> { return vec4<f32>(1.0); }";
+        assert!(reflect_pair(VS, plain).unwrap().is_empty());
+    }
+
+    #[test]
+    fn packing_writes_each_scalar_at_its_offset() {
+        let layout = declared("a: f32,\n    b: vec2<i32>,\n    c: u32,");
+        let values = HashMap::from([("b".to_owned(), [-1.4, 3.6, 9.0, 9.0])]);
+        let bytes = layout.pack(&values);
+        let word = |i: usize| <[u8; 4]>::try_from(&bytes[4 * i..4 * i + 4]).unwrap();
+        assert_eq!(bytes.len(), 24);
+        assert_eq!(f32::from_le_bytes(word(0)), 0.0);
+        assert_eq!((i32::from_le_bytes(word(2)), i32::from_le_bytes(word(3))), (-1, 4));
+        assert_eq!(u32::from_le_bytes(word(4)), 0);
+    }
+
+    #[test]
+    fn values_survive_edits_that_keep_the_field() {
+        let owner = Owner::new();
+        owner.set();
+
+        let params = ShaderParams::new();
+        params.sync(VS, FS);
+        params.update("gain", |v| v[0] = 0.5);
+        assert!(params.modified());
+
+        // the rest of the shader changing leaves the value be
+        params.sync(VS, &FS.replace("1.0);", "0.5);"));
+        assert_eq!(params.get("gain")[0], 0.5);
+        // and so does a shader that doesn't compile
+        params.sync(VS, "fn broken(");
+        assert_eq!(params.layout().fields.len(), 2);
+
+        // a new default starts the field over
+        params.sync(VS, &FS.replace("@default(1)", "@default(2)"));
+        assert_eq!(params.get("gain")[0], 2.0);
+        params.update("tint", |v| *v = [0.0; 4]);
+        params.reset();
+        assert!(!params.modified());
+    }
+}
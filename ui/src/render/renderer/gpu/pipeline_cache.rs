@@ -0,0 +1,576 @@
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    hash::{DefaultHasher, Hash, Hasher},
+    rc::Rc,
+};
+
+use super::diagnostics::{Diagnostic, EntryPoints, Severity, Stage, pipeline_error, validate_wgsl};
+
+/// How many pipelines (and, separately, shader modules) are kept around.
+pub const DEFAULT_CAPACITY: usize = 64;
+
+/// Everything that makes two render pipelines different.
+pub struct PipelineDesc<'a> {
+    pub label: &'a str,
+
+    pub vs_src: &'a str,
+    pub fs_src: &'a str,
+    pub vs_entry: &'a str,
+    pub fs_entry: &'a str,
+
+    /// Names the shape of `bind_group_layouts` (layouts themselves can't be
+    /// hashed); equal keys must mean equal layout descriptors.
+    pub layout_key: &'a str,
+    pub bind_group_layouts: &'a [&'a wgpu::BindGroupLayout],
+    pub buffers: &'a [wgpu::VertexBufferLayout<'a>],
+
+    pub format: wgpu::TextureFormat,
+    pub blend: Option<wgpu::BlendState>,
+    pub topology: wgpu::PrimitiveTopology,
+    pub depth: Option<wgpu::DepthStencilState>,
+}
+
+impl PipelineDesc<'_> {
+    /// Cheap enough to compute every frame: sources are hashed in a single
+    /// pass with comments and whitespace runs folded away, no allocation.
+    pub fn key(&self) -> u64 {
+        let mut h = DefaultHasher::new();
+
+        hash_wgsl(self.vs_src, &mut h);
+        hash_wgsl(self.fs_src, &mut h);
+        self.vs_entry.hash(&mut h);
+        self.fs_entry.hash(&mut h);
+        self.layout_key.hash(&mut h);
+
+        for b in self.buffers {
+            b.array_stride.hash(&mut h);
+            b.step_mode.hash(&mut h);
+            b.attributes.hash(&mut h);
+        }
+
+        self.format.hash(&mut h);
+        self.blend.hash(&mut h);
+        self.topology.hash(&mut h);
+
+        // DepthStencilState has f32s in it, so no derive
+        self.depth.is_some().hash(&mut h);
+        if let Some(d) = &self.depth {
+            d.format.hash(&mut h);
+            d.depth_write_enabled.hash(&mut h);
+            d.depth_compare.hash(&mut h);
+            d.stencil.hash(&mut h);
+            d.bias.constant.hash(&mut h);
+            d.bias.slope_scale.to_bits().hash(&mut h);
+            d.bias.clamp.to_bits().hash(&mut h);
+        }
+
+        h.finish()
+    }
+
+    /// [`key`](Self::key) (passed in, as it's at hand) plus the sources
+    /// exactly as written. Failures are kept under this one: their spans
+    /// point into the text, so a comment added above an error moves them.
+    pub fn failure_key(&self, key: u64) -> u64 {
+        let mut h = DefaultHasher::new();
+        key.hash(&mut h);
+        self.vs_src.hash(&mut h);
+        self.fs_src.hash(&mut h);
+        h.finish()
+    }
+}
+
+/// Hash WGSL so that edits which can't change the program (comments,
+/// indentation, blank lines) hash the same.
+fn hash_wgsl(src: &str, h: &mut impl Hasher) {
+    let mut chars = src.chars().peekable();
+    let mut pending_space = false;
+
+    while let Some(c) = chars.next() {
+        match c {
+            '/' if chars.peek() == Some(&'/') => {
+                for c in chars.by_ref() {
+                    if c == '\n' {
+                        break;
+                    }
+                }
+                pending_space = true;
+            }
+            '/' if chars.peek() == Some(&'*') => {
+                chars.next();
+                // block comments nest in WGSL
+                let mut depth = 1;
+                while depth > 0 {
+                    match chars.next() {
+                        Some('/') if chars.peek() == Some(&'*') => {
+                            chars.next();
+                            depth += 1;
+                        }
+                        Some('*') if chars.peek() == Some(&'/') => {
+                            chars.next();
+                            depth -= 1;
+                        }
+                        Some(_) => {}
+                        None => break,
+                    }
+                }
+                pending_space = true;
+            }
+            c if c.is_whitespace() => pending_space = true,
+            c => {
+                if pending_space {
+                    ' '.hash(h);
+                    pending_space = false;
+                }
+                c.hash(h);
+            }
+        }
+    }
+    // terminator so ("ab", "c") and ("a", "bc") differ
+    0xffu8.hash(h);
+}
+
+struct Entry<T> {
+    value: T,
+    last_used: u64,
+}
+
+/// Small LRU map; eviction is a linear scan, fine at this size.
+struct Lru<T> {
+    entries: HashMap<u64, Entry<T>>,
+    capacity: usize,
+}
+
+impl<T: Clone> Lru<T> {
+    fn new(capacity: usize) -> Self {
+        Self {
+            entries: HashMap::new(),
+            capacity: capacity.max(1),
+        }
+    }
+
+    fn get(&mut self, key: u64, now: u64) -> Option<T> {
+        let e = self.entries.get_mut(&key)?;
+        e.last_used = now;
+        Some(e.value.clone())
+    }
+
+    fn insert(&mut self, key: u64, value: T, now: u64) {
+        if self.entries.len() >= self.capacity && !self.entries.contains_key(&key) {
+            let oldest = self.entries.iter().min_by_key(|(_, e)| e.last_used).map(|(&k, _)| k);
+            if let Some(oldest) = oldest {
+                self.entries.remove(&oldest);
+            }
+        }
+
+        self.entries.insert(key, Entry { value, last_used: now });
+    }
+}
+
+/// A module that passed validation, with what it exports.
+#[derive(Clone)]
+struct CheckedModule {
+    module: wgpu::ShaderModule,
+    entry_points: Rc<EntryPoints>,
+}
+
+/// Why a module didn't validate, for the source exactly as written (by
+/// hash); the span is only good for that one.
+#[derive(Clone)]
+struct ModuleError {
+    source: u64,
+    error: Diagnostic,
+}
+
+/// What [`PipelineCache::get_or_create`] has for a descriptor.
+#[derive(Clone)]
+pub enum PipelineStatus {
+    Ready(wgpu::RenderPipeline),
+
+    /// Built, but the device hasn't reported on it yet (browsers validate
+    /// asynchronously); keep drawing with the previous pipeline meanwhile.
+    Pending,
+
+    /// Won't compile; the previous pipeline should stay in use.
+    Failed(Vec<Diagnostic>),
+}
+
+enum Slot {
+    Pending(wgpu::RenderPipeline),
+    Ready(wgpu::RenderPipeline),
+    /// With the [`failure_key`](PipelineDesc::failure_key) it's good for.
+    Failed(Vec<Diagnostic>, u64),
+}
+
+impl Slot {
+    fn status(&self) -> PipelineStatus {
+        match self {
+            Slot::Pending(_) => PipelineStatus::Pending,
+            Slot::Ready(pipe) => PipelineStatus::Ready(pipe.clone()),
+            Slot::Failed(errors, _) => PipelineStatus::Failed(errors.clone()),
+        }
+    }
+}
+
+/// Compiled shader modules and render pipelines, reused across edits and
+/// demo switches. Owned by `GpuState`, so it dies with the device.
+///
+/// Failures are cached too, so a broken edit is validated once rather than
+/// every frame until it is fixed; but by the exact source, so their spans
+/// stay where the error is.
+pub struct PipelineCache {
+    modules: Lru<Result<CheckedModule, ModuleError>>,
+    pipelines: Lru<Rc<RefCell<Slot>>>,
+    tick: u64,
+}
+
+impl Default for PipelineCache {
+    fn default() -> Self {
+        Self::new(DEFAULT_CAPACITY)
+    }
+}
+
+impl PipelineCache {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            modules: Lru::new(capacity),
+            pipelines: Lru::new(capacity),
+            tick: 0,
+        }
+    }
+
+    /// Cached pipeline for `desc`, built (reusing cached modules) on a miss.
+    /// Never raises device errors: sources go through naga first and the
+    /// build itself runs inside a validation error scope.
+    pub fn get_or_create(&mut self, device: &wgpu::Device, desc: &PipelineDesc) -> PipelineStatus {
+        self.tick += 1;
+        let key = desc.key();
+
+        if let Some(slot) = self.pipelines.get(key, self.tick) {
+            let slot = slot.borrow();
+            match &*slot {
+                // the same program, moved about; built again for its spans
+                Slot::Failed(_, at) if *at != desc.failure_key(key) => {}
+                slot => return slot.status(),
+            }
+        }
+
+        let slot = self.build(device, desc, desc.failure_key(key));
+        self.pipelines.insert(key, slot.clone(), self.tick);
+
+        slot.borrow().status()
+    }
+
+    fn build(
+        &mut self,
+        device: &wgpu::Device,
+        desc: &PipelineDesc,
+        failure_key: u64,
+    ) -> Rc<RefCell<Slot>> {
+        device.push_error_scope(wgpu::ErrorFilter::Validation);
+
+        let vs = self.module(device, desc.label, desc.vs_src, Stage::Vertex, desc.vs_entry);
+        let fs = self.module(device, desc.label, desc.fs_src, Stage::Fragment, desc.fs_entry);
+
+        let (vs, fs) = match (vs, fs) {
+            (Ok(vs), Ok(fs)) => (vs, fs),
+            (vs, fs) => {
+                // nothing reached the device, so the scope is empty
+                drop(device.pop_error_scope());
+                let errors = vs.err().into_iter().chain(fs.err()).collect();
+                return Rc::new(RefCell::new(Slot::Failed(errors, failure_key)));
+            }
+        };
+
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some(desc.label),
+            bind_group_layouts: desc.bind_group_layouts,
+            push_constant_ranges: &[],
+        });
+
+        let pipe = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some(desc.label),
+            layout: Some(&layout),
+            cache: None,
+            vertex: wgpu::VertexState {
+                module: &vs,
+                entry_point: Some(desc.vs_entry),
+                buffers: desc.buffers,
+                compilation_options: Default::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &fs,
+                entry_point: Some(desc.fs_entry),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: desc.format,
+                    blend: desc.blend,
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: Default::default(),
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: desc.topology,
+                strip_index_format: None,
+                ..Default::default()
+            },
+            depth_stencil: desc.depth.clone(),
+            multisample: Default::default(),
+            multiview: None,
+        });
+
+        let slot = Rc::new(RefCell::new(Slot::Pending(pipe)));
+
+        let pending = slot.clone();
+        on_scope_popped(device.pop_error_scope(), move |err| {
+            let mut slot = pending.borrow_mut();
+            let settled = std::mem::replace(&mut *slot, Slot::Failed(Vec::new(), failure_key));
+            *slot = match (settled, err) {
+                (Slot::Pending(pipe), None) => Slot::Ready(pipe),
+                (_, Some(err)) => Slot::Failed(vec![pipeline_error(&err)], failure_key),
+                (settled, None) => settled,
+            };
+        });
+
+        slot
+    }
+
+    /// Validated module for `src`, checked to export `entry` for `stage`.
+    fn module(
+        &mut self,
+        device: &wgpu::Device,
+        label: &str,
+        src: &str,
+        stage: Stage,
+        entry: &str,
+    ) -> Result<wgpu::ShaderModule, Diagnostic> {
+        let mut h = DefaultHasher::new();
+        hash_wgsl(src, &mut h);
+        let key = h.finish();
+        let mut h = DefaultHasher::new();
+        src.hash(&mut h);
+        let source = h.finish();
+
+        let checked = match self.modules.get(key, self.tick) {
+            Some(Err(e)) if e.source != source => None,
+            checked => checked,
+        };
+        let checked = match checked {
+            Some(checked) => checked,
+            None => {
+                let checked = validate_wgsl(src, stage)
+                    .map(|entry_points| CheckedModule {
+                        module: device.create_shader_module(wgpu::ShaderModuleDescriptor {
+                            label: Some(label),
+                            source: wgpu::ShaderSource::Wgsl(src.into()),
+                        }),
+                        entry_points: Rc::new(entry_points),
+                    })
+                    .map_err(|error| ModuleError { source, error });
+                self.modules.insert(key, checked.clone(), self.tick);
+                checked
+            }
+        };
+
+        // the same source may be cached from the other stage
+        let checked = checked.map_err(|e| Diagnostic { stage, ..e.error })?;
+
+        let naga_stage = match stage {
+            Stage::Fragment => naga::ShaderStage::Fragment,
+            _ => naga::ShaderStage::Vertex,
+        };
+        if !checked
+            .entry_points
+            .iter()
+            .any(|(name, s)| name == entry && *s == naga_stage)
+        {
+            let attr = if naga_stage == naga::ShaderStage::Fragment { "@fragment" } else { "@vertex" };
+            return Err(Diagnostic {
+                severity: Severity::Error,
+                stage,
+                message: format!("no {attr} entry point named `{entry}`"),
+                span: None,
+                file: None,
+            });
+        }
+
+        Ok(checked.module)
+    }
+}
+
+/// Hand the popped scope's result to `done`: right away natively (wgpu-core
+/// resolves scopes synchronously), once the browser answers on the web.
+fn on_scope_popped(
+    scope: impl Future<Output = Option<wgpu::Error>> + 'static,
+    done: impl FnOnce(Option<wgpu::Error>) + 'static,
+) {
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_futures::spawn_local(async move { done(scope.await) });
+
+    #[cfg(not(target_arch = "wasm32"))]
+    done(pollster::block_on(scope));
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use super::*;
+    use crate::render::web_gpu::test_state;
+
+    const VS: &str = "@vertex fn vs_main() -> @builtin(position) vec4<f32> {
+    return vec4<f32>(0.0, 0.0, 0.0, 1.0);
+}";
+
+    fn desc<'a>(vs_src: &'a str, fs_src: &'a str) -> PipelineDesc<'a> {
+        PipelineDesc {
+            label: "test",
+            vs_src,
+            fs_src,
+            vs_entry: "vs_main",
+            fs_entry: "fs_main",
+            layout_key: "none",
+            bind_group_layouts: &[],
+            buffers: &[],
+            format: wgpu::TextureFormat::Rgba8Unorm,
+            blend: None,
+            topology: wgpu::PrimitiveTopology::TriangleList,
+            depth: None,
+        }
+    }
+
+    fn hash(src: &str) -> u64 {
+        let mut h = DefaultHasher::new();
+        hash_wgsl(src, &mut h);
+        h.finish()
+    }
+
+    #[test]
+    fn comments_and_whitespace_fold_away() {
+        let src = "fn f() -> f32 {\n    return 1.0;\n}";
+        for same in [
+            "fn f() -> f32 { return 1.0; }",
+            "fn  f()\t->  f32 {\n\n  return 1.0; // one\n}",
+            "fn f() -> f32 { /* a /* nested */ comment */ return 1.0; }\n// trailing",
+        ] {
+            assert_eq!(hash(same), hash(src), "{same:?}");
+        }
+    }
+
+    /// Only whole comments and whitespace runs go; what's left of the tokens
+    /// and the space between them still tells sources apart.
+    #[test]
+    fn folding_keeps_tokens_apart() {
+        for (a, b) in [
+            ("let x = a b;", "let x = ab;"),
+            ("let x = a/*c*/b;", "let x = ab;"),
+            ("let x = a / /b;", "let x = a //b;"),
+            ("let x = a/b;", "let x = a//b;"),
+            ("let x = 1.0;", "let x = 1 .0;"),
+            ("let x = a;/*", "let x = a;/ *"),
+        ] {
+            assert_ne!(hash(a), hash(b), "{a:?} vs {b:?}");
+        }
+
+        let pair = |vs, fs| desc(vs, fs).key();
+        assert_ne!(pair("ab", "c"), pair("a", "bc"));
+        assert_ne!(pair("a", "b"), pair("b", "a"));
+    }
+
+    #[test]
+    fn the_least_recently_used_entry_goes_first() {
+        let mut lru = Lru::new(2);
+        lru.insert(1, "one", 1);
+        lru.insert(2, "two", 2);
+        assert_eq!(lru.get(1, 3), Some("one"));
+
+        lru.insert(3, "three", 4);
+        assert_eq!(lru.entries.len(), 2);
+        assert_eq!(lru.get(2, 5), None);
+        assert_eq!(lru.get(1, 5), Some("one"));
+
+        // replacing a key that's there makes no room
+        lru.insert(3, "drei", 6);
+        assert_eq!(lru.get(1, 7), Some("one"));
+        assert_eq!(lru.get(3, 7), Some("drei"));
+
+        let mut one = Lru::new(0);
+        one.insert(1, "one", 1);
+        one.insert(2, "two", 2);
+        assert_eq!((one.entries.len(), one.get(2, 3)), (1, Some("two")));
+    }
+
+    fn errors(status: PipelineStatus) -> Vec<Diagnostic> {
+        match status {
+            PipelineStatus::Failed(errors) => errors,
+            PipelineStatus::Ready(_) => panic!("built"),
+            PipelineStatus::Pending => panic!("pending"),
+        }
+    }
+
+    /// Edits that fold to the same key still get the failure where it is
+    /// in the text they make.
+    #[test]
+    fn failures_point_into_the_source_as_written() {
+        let Some(st) = test_state() else { return };
+        let device = &st.surface_context.device;
+        let mut cache = PipelineCache::default();
+
+        let broken = "@fragment fn fs_main() -> @location(0) vec4<f32> {
+    return vec4<f32>(1.0) +;
+}";
+        let moved = broken.replacen("{\n", "{\n    // a comment\n\n", 1);
+        assert_eq!(desc(VS, broken).key(), desc(VS, &moved).key());
+
+        let line = |src: &str, cache: &mut PipelineCache| {
+            let errors = errors(cache.get_or_create(device, &desc(VS, src)));
+            assert_eq!(errors.len(), 1);
+            let span = errors[0].span.expect("located");
+            assert_eq!(&src[span.start..span.end], ";");
+            span.line
+        };
+        assert_eq!(line(broken, &mut cache), 2);
+        assert_eq!(line(&moved, &mut cache), 4);
+        assert_eq!(line(broken, &mut cache), 2);
+    }
+
+    #[test]
+    fn failures_are_kept_until_the_source_changes() {
+        let Some(st) = test_state() else { return };
+        let device = &st.surface_context.device;
+        let mut cache = PipelineCache::default();
+
+        let fs = "@fragment fn fs_main() -> @location(0) vec4<f32> { return vec4<f32>(1.0); }";
+        let broken = fs.replace("return", "retrun");
+        let slot = |cache: &mut PipelineCache, src: &str| {
+            cache.pipelines.get(desc(VS, src).key(), 0).expect("cached")
+        };
+
+        assert_eq!(errors(cache.get_or_create(device, &desc(VS, &broken))).len(), 1);
+        let failed = slot(&mut cache, &broken);
+        errors(cache.get_or_create(device, &desc(VS, &broken)));
+        assert!(Rc::ptr_eq(&failed, &slot(&mut cache, &broken)), "not built again");
+
+        assert!(matches!(cache.get_or_create(device, &desc(VS, fs)), PipelineStatus::Ready(_)));
+
+        // naga checks the entry point before the device sees anything
+        let other = PipelineDesc { fs_entry: "fs_other", ..desc(VS, fs) };
+        let errors = errors(cache.get_or_create(device, &other));
+        assert_eq!(errors[0].stage, Stage::Fragment);
+        assert_eq!(errors[0].message, "no @fragment entry point named `fs_other`");
+    }
+
+    /// Sources naga is happy with that don't fit the pipeline fail on the
+    /// device, inside the error scope.
+    #[test]
+    fn pipeline_errors_come_back_from_the_device() {
+        let Some(st) = test_state() else { return };
+        let device = &st.surface_context.device;
+        let mut cache = PipelineCache::default();
+
+        let vs = "@vertex fn vs_main(@location(0) p: vec3<f32>) -> @builtin(position) vec4<f32> {
+    return vec4<f32>(p, 1.0);
+}";
+        let fs = "@fragment fn fs_main() -> @location(0) vec4<f32> { return vec4<f32>(1.0); }";
+        let errors = errors(cache.get_or_create(device, &desc(vs, fs)));
+        assert_eq!(errors.len(), 1);
+        assert_eq!((errors[0].stage, errors[0].span), (Stage::Pipeline, None));
+    }
+}
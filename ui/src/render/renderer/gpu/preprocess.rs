@@ -0,0 +1,651 @@
+//! `#include`, `#define` and `#ifdef` for WGSL, which has no way to share
+//! declarations between modules. Sources are expanded before naga or the
+//! device see them, and every line of the result remembers where it came
+//! from, so diagnostics point into the file that was actually edited.
+//!
+//! ```wgsl
+//! #include "camera.wgsl"   // `#import camera.wgsl` works too
+//! #define WOBBLE
+//! #define AMOUNT 0.1
+//!
+//! #ifdef WOBBLE
+//! let offset = sin(time_sec()) * AMOUNT;
+//! #else
+//! let offset = 0.0;
+//! #endif
+//! ```
+//!
+//! A module is pasted in once per shader however often it's included, so
+//! modules include what they need without guards. `#define NAME value`
+//! replaces `NAME` as a whole word in the lines after it; without a value
+//! it's only there for `#ifdef`. `VERTEX` or `FRAGMENT` is defined for the
+//! stage being expanded.
+
+use std::collections::BTreeMap;
+
+use leptos::prelude::{Get, GetUntracked, RwSignal, Update, With, WithUntracked};
+
+use super::diagnostics::{Diagnostic, Severity, SourceSpan, Stage};
+
+/// Modules every shader can include, whatever the demo.
+pub const SHIPPED: &[(&str, &str)] = &[
+    ("camera.wgsl", include_str!("../shaders/modules/camera.wgsl")),
+    ("channels.wgsl", include_str!("../shaders/modules/channels.wgsl")),
+    ("light.wgsl", include_str!("../shaders/modules/light.wgsl")),
+    ("screen.wgsl", include_str!("../shaders/modules/screen.wgsl")),
+    ("texturing.wgsl", include_str!("../shaders/modules/texturing.wgsl")),
+    ("time.wgsl", include_str!("../shaders/modules/time.wgsl")),
+];
+
+fn shipped(name: &str) -> Option<&'static str> {
+    SHIPPED.iter().find(|(n, _)| *n == name).map(|(_, src)| *src)
+}
+
+#[derive(Clone)]
+pub struct ShaderFile {
+    pub name: String,
+    pub src: RwSignal<String>,
+}
+
+/// The modules `#include` can name: the shipped ones, as edited, and the
+/// ones the user created. Files are only ever added, so an index names the
+/// same file for good.
+#[derive(Copy, Clone)]
+pub struct ShaderFiles(RwSignal<Vec<ShaderFile>>);
+
+impl Default for ShaderFiles {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ShaderFiles {
+    /// The shipped modules as they ship.
+    pub fn new() -> Self {
+        Self::with_edits(BTreeMap::new())
+    }
+
+    /// The shipped modules with `edits` (name to source) applied; names
+    /// that don't ship are modules of their own.
+    pub fn with_edits(mut edits: BTreeMap<String, String>) -> Self {
+        let mut files: Vec<ShaderFile> = SHIPPED
+            .iter()
+            .map(|(name, src)| ShaderFile {
+                name: name.to_string(),
+                src: RwSignal::new(edits.remove(*name).unwrap_or_else(|| src.to_string())),
+            })
+            .collect();
+        files.extend(edits.into_iter().map(|(name, src)| ShaderFile {
+            name,
+            src: RwSignal::new(src),
+        }));
+        Self(RwSignal::new(files))
+    }
+
+    pub fn names(&self) -> Vec<String> {
+        self.0.with(|files| files.iter().map(|f| f.name.clone()).collect())
+    }
+
+    pub fn name(&self, i: usize) -> Option<String> {
+        self.0.with(|files| files.get(i).map(|f| f.name.clone()))
+    }
+
+    pub fn src(&self, i: usize) -> Option<RwSignal<String>> {
+        self.0.with_untracked(|files| files.get(i).map(|f| f.src))
+    }
+
+    /// What file `i` ships as; `None` for one the user created.
+    pub fn shipped(&self, i: usize) -> Option<&'static str> {
+        self.0.with_untracked(|files| files.get(i).and_then(|f| shipped(&f.name)))
+    }
+
+    /// Whether file `i` differs from what ships; created ones always do.
+    pub fn modified(&self, i: usize) -> bool {
+        let Some(src) = self.src(i) else { return false };
+        src.with(|src| self.shipped(i) != Some(src.as_str()))
+    }
+
+    /// Every file that differs from what ships, by name; what's worth keeping.
+    pub fn edits(&self) -> BTreeMap<String, String> {
+        self.0.with(|files| {
+            files
+                .iter()
+                .filter(|f| f.src.with(|src| shipped(&f.name) != Some(src.as_str())))
+                .map(|f| (f.name.clone(), f.src.get()))
+                .collect()
+        })
+    }
+
+    /// Add an empty module named `name` (`.wgsl` is appended if missing);
+    /// returns its index.
+    pub fn create(&self, name: &str) -> Result<usize, String> {
+        let name = name.trim();
+        let name = match name.strip_suffix(".wgsl") {
+            Some(_) => name.to_owned(),
+            None => format!("{name}.wgsl"),
+        };
+        let stem = &name[..name.len() - ".wgsl".len()];
+        let allowed = |c: char| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.' | '/');
+        if stem.is_empty() || !stem.chars().all(allowed) {
+            return Err(format!(
+                "`{name}`: module names take letters, digits, `_`, `-`, `.` and `/`"
+            ));
+        }
+        if self.0.with_untracked(|files| files.iter().any(|f| f.name == name)) {
+            return Err(format!("there's already a module named `{name}`"));
+        }
+
+        let mut index = 0;
+        self.0.update(|files| {
+            index = files.len();
+            files.push(ShaderFile { name, src: RwSignal::new(String::new()) });
+        });
+        Ok(index)
+    }
+
+    /// `src` expanded for `stage`; the modules it includes are tracked.
+    pub fn expand(&self, src: &str, stage: Stage) -> Result<Expanded, Diagnostic> {
+        self.0.with(|files| {
+            expand(src, stage, |name| {
+                files.iter().find(|f| f.name == name).map(|f| f.src.get())
+            })
+        })
+    }
+
+    /// [`expand`](Self::expand) for the render loop, which tracks nothing.
+    pub fn expand_untracked(&self, src: &str, stage: Stage) -> Result<Expanded, Diagnostic> {
+        self.0.with_untracked(|files| {
+            expand(src, stage, |name| {
+                files.iter().find(|f| f.name == name).map(|f| f.src.get_untracked())
+            })
+        })
+    }
+}
+
+/// A shader with its modules pasted in and its directives resolved.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Expanded {
+    pub code: String,
+    /// Per line of `code`: the source it came from (index into `sources`)
+    /// and its 1-based line there.
+    origins: Vec<(usize, u32)>,
+    /// The stage's own source first (no name), then the modules in the
+    /// order they were included.
+    sources: Vec<(Option<String>, String)>,
+}
+
+impl Expanded {
+    /// `src` as it is, for shaders that don't go through the preprocessor.
+    pub fn plain(src: &str) -> Self {
+        Self {
+            code: src.to_owned(),
+            origins: (1..=src.split('\n').count() as u32).map(|line| (0, line)).collect(),
+            sources: vec![(None, src.to_owned())],
+        }
+    }
+
+    /// The sources of the modules pulled in, in the order they were.
+    pub fn included(&self) -> impl Iterator<Item = &str> {
+        self.sources[1..].iter().map(|(_, src)| src.as_str())
+    }
+
+    /// `d`, pointed at the line its code came from. Within the line the
+    /// place is kept, unless a `#define` changed the line's text.
+    pub fn locate(&self, mut d: Diagnostic) -> Diagnostic {
+        let Some(span) = d.span else { return d };
+        let Some(&(source, line)) = (span.line as usize)
+            .checked_sub(1)
+            .and_then(|i| self.origins.get(i))
+        else {
+            return d;
+        };
+        let (file, text) = &self.sources[source];
+
+        let start = line_start(text, line);
+        let original = text[start..].split('\n').next().unwrap_or_default();
+        let out_start = line_start(&self.code, span.line);
+        let expanded = self.code[out_start..].split('\n').next().unwrap_or_default();
+
+        let span = if original == expanded && span.start >= out_start {
+            let end = (span.end - out_start).min(original.len());
+            SourceSpan { line, start: start + span.start - out_start, end: start + end, ..span }
+        } else {
+            SourceSpan { line, column: 1, start, end: start + original.len() }
+        };
+        d.file = file.clone();
+        d.span = Some(span);
+        d
+    }
+}
+
+/// Byte offset of 1-based line `line`.
+fn line_start(text: &str, line: u32) -> usize {
+    text.split_inclusive('\n')
+        .take(line.saturating_sub(1) as usize)
+        .map(str::len)
+        .sum()
+}
+
+/// [`expand`] against the modules as they ship, for built-in shaders the
+/// editor never touches.
+pub fn expand_shipped(src: &str, stage: Stage) -> Result<Expanded, Diagnostic> {
+    expand(src, stage, |name| shipped(name).map(str::to_owned))
+}
+
+/// Expand `src` for `stage`; `module` looks up what an `#include` names.
+pub(crate) fn expand(
+    src: &str,
+    stage: Stage,
+    module: impl Fn(&str) -> Option<String>,
+) -> Result<Expanded, Diagnostic> {
+    let predefined = match stage {
+        Stage::Vertex => "VERTEX",
+        _ => "FRAGMENT",
+    };
+    let mut expander = Expander {
+        module,
+        stage,
+        defines: BTreeMap::from([(predefined.to_owned(), String::new())]),
+        out: Expanded {
+            code: String::new(),
+            origins: Vec::new(),
+            sources: vec![(None, src.to_owned())],
+        },
+    };
+    expander.run(0)?;
+
+    let mut out = expander.out;
+    out.code.pop(); // the last line's newline
+    Ok(out)
+}
+
+struct Expander<F> {
+    module: F,
+    stage: Stage,
+    /// Name to replacement; empty ones only count for `#ifdef`.
+    defines: BTreeMap<String, String>,
+    out: Expanded,
+}
+
+/// An `#ifdef` or `#ifndef` not yet closed.
+struct Open {
+    taking: bool,
+    seen_else: bool,
+    /// Of the `#ifdef`, for when it's never closed.
+    line: u32,
+    at: usize,
+}
+
+impl<F: Fn(&str) -> Option<String>> Expander<F> {
+    fn run(&mut self, source: usize) -> Result<(), Diagnostic> {
+        let text = self.out.sources[source].1.clone();
+        let mut open: Vec<Open> = Vec::new();
+        // how deep in `/* */` the next line starts (they nest in WGSL)
+        let mut depth = 0;
+
+        let mut at = 0;
+        for (i, line) in text.split('\n').enumerate() {
+            let number = i as u32 + 1;
+            let line_at = at;
+            at += line.len() + 1;
+            let active = open.iter().all(|o| o.taking);
+            let fail = |message: String| self.error(source, number, line_at, message);
+
+            // a `#` inside a block comment is only commented out text
+            let commented = depth > 0;
+            let code = strip_comments(line, &mut depth);
+            let directive = code.trim_start().strip_prefix('#').filter(|_| !commented);
+            let Some(directive) = directive else {
+                let code = if active { self.substitute(line) } else { String::new() };
+                self.emit(code, source, number);
+                continue;
+            };
+            let (name, arg) = directive.split_once(char::is_whitespace).unwrap_or((directive, ""));
+            let arg = arg.trim();
+
+            match name {
+                "ifdef" | "ifndef" => {
+                    let defined = self.defines.contains_key(word(arg).map_err(fail)?);
+                    open.push(Open {
+                        taking: defined == (name == "ifdef"),
+                        seen_else: false,
+                        line: number,
+                        at: line_at,
+                    });
+                }
+                "else" => {
+                    let Some(o) = open.last_mut() else {
+                        return Err(fail("`#else` without `#ifdef`".to_owned()));
+                    };
+                    if o.seen_else {
+                        return Err(fail("second `#else` for the same `#ifdef`".to_owned()));
+                    }
+                    o.seen_else = true;
+                    o.taking = !o.taking;
+                }
+                "endif" => {
+                    if open.pop().is_none() {
+                        return Err(fail("`#endif` without `#ifdef`".to_owned()));
+                    }
+                }
+                _ if !active => {}
+                "define" => {
+                    let (define, value) =
+                        arg.split_once(char::is_whitespace).unwrap_or((arg, ""));
+                    if define.contains('(') {
+                        return Err(fail("`#define` takes no parameters".to_owned()));
+                    }
+                    let define = word(define).map_err(fail)?;
+                    self.defines.insert(define.to_owned(), value.trim().to_owned());
+                }
+                "undef" => {
+                    let define = word(arg).map_err(fail)?;
+                    self.defines.remove(define);
+                }
+                "include" | "import" => {
+                    let file = arg
+                        .strip_prefix('"')
+                        .and_then(|a| a.strip_suffix('"'))
+                        .unwrap_or(arg);
+                    if file.is_empty() {
+                        return Err(fail(format!("`#{name}` needs a module name")));
+                    }
+                    let done = self.out.sources.iter().any(|(n, _)| n.as_deref() == Some(file));
+                    if !done {
+                        let Some(module) = (self.module)(file) else {
+                            return Err(fail(format!("no module named `{file}`")));
+                        };
+                        self.out.sources.push((Some(file.to_owned()), module));
+                        self.run(self.out.sources.len() - 1)?;
+                        // the module's lines stand in for this one
+                        continue;
+                    }
+                }
+                other => return Err(fail(format!("unknown directive `#{other}`"))),
+            }
+            self.emit(String::new(), source, number);
+        }
+
+        match open.last() {
+            Some(o) => Err(self.error(source, o.line, o.at, "`#ifdef` without `#endif`".to_owned())),
+            None => Ok(()),
+        }
+    }
+
+    fn emit(&mut self, code: String, source: usize, line: u32) {
+        self.out.code.push_str(&code);
+        self.out.code.push('\n');
+        self.out.origins.push((source, line));
+    }
+
+    /// `line` with the defines that have a value replaced, up to a `//`.
+    fn substitute(&self, line: &str) -> String {
+        if self.defines.values().all(String::is_empty) {
+            return line.to_owned();
+        }
+        let (code, comment) = line.split_at(line.find("//").unwrap_or(line.len()));
+        let word_char = |c: char| c == '_' || c.is_alphanumeric();
+
+        let mut out = String::with_capacity(line.len());
+        let mut rest = code;
+        while let Some(start) = rest.find(word_char) {
+            out.push_str(&rest[..start]);
+            rest = &rest[start..];
+            let end = rest.find(|c| !word_char(c)).unwrap_or(rest.len());
+            let word = &rest[..end];
+            // numbers (`1e5`, `2u`) aren't names
+            let replace = !word.starts_with(|c: char| c.is_ascii_digit());
+            match self.defines.get(word).filter(|v| replace && !v.is_empty()) {
+                Some(value) => out.push_str(value),
+                None => out.push_str(word),
+            }
+            rest = &rest[end..];
+        }
+        out.push_str(rest);
+        out.push_str(comment);
+        out
+    }
+
+    /// An error on the directive at byte `at` (line `line`) of `source`.
+    fn error(&self, source: usize, line: u32, at: usize, message: String) -> Diagnostic {
+        let (file, text) = &self.out.sources[source];
+        let directive = text[at..].split('\n').next().unwrap_or_default();
+        let indent = directive.len() - directive.trim_start().len();
+
+        Diagnostic {
+            severity: Severity::Error,
+            stage: self.stage,
+            message,
+            span: Some(SourceSpan {
+                line,
+                column: directive[..indent].chars().count() as u32 + 1,
+                start: at + indent,
+                end: at + directive.len(),
+            }),
+            file: file.clone(),
+        }
+    }
+}
+
+/// `line`'s code, with its comments blanked out; `depth` is how many block
+/// comments are open at its start, and at its end once this returns.
+fn strip_comments(line: &str, depth: &mut u32) -> String {
+    let mut out = String::with_capacity(line.len());
+    let mut rest = line;
+    while !rest.is_empty() {
+        if rest.starts_with("/*") {
+            *depth += 1;
+            rest = &rest[2..];
+        } else if *depth > 0 && rest.starts_with("*/") {
+            *depth -= 1;
+            rest = &rest[2..];
+            out.push(' ');
+        } else if *depth == 0 && rest.starts_with("//") {
+            break;
+        } else {
+            let c = rest.chars().next().unwrap_or_default();
+            if *depth == 0 {
+                out.push(c);
+            }
+            rest = &rest[c.len_utf8()..];
+        }
+    }
+    out
+}
+
+/// `arg` if it's a name a define can have.
+fn word(arg: &str) -> Result<&str, String> {
+    let mut chars = arg.chars();
+    let ok = chars.next().is_some_and(|c| c == '_' || c.is_alphabetic())
+        && chars.all(|c| c == '_' || c.is_alphanumeric());
+    if ok { Ok(arg) } else { Err(format!("`{arg}` isn't a name")) }
+}
+
+#[cfg(test)]
+mod tests {
+    use leptos::prelude::{Owner, Set};
+
+    use super::super::diagnostics::check_wgsl;
+    use super::*;
+
+    const A: &str = "// a\nfn a() -> f32 { return 1.0; }";
+    const B: &str = "#include \"a.wgsl\"\nfn b() -> f32 { return a(); }";
+
+    fn module(name: &str) -> Option<String> {
+        match name {
+            "a.wgsl" => Some(A.to_owned()),
+            "b.wgsl" => Some(B.to_owned()),
+            "broken.wgsl" => Some("fn ok() {}\n#bogus".to_owned()),
+            _ => None,
+        }
+    }
+
+    fn code(src: &str) -> String {
+        expand(src, Stage::Fragment, module).unwrap().code
+    }
+
+    fn error(src: &str) -> Diagnostic {
+        expand(src, Stage::Fragment, module).unwrap_err()
+    }
+
+    /// An error naga would report on 1-based `line` of the expanded code.
+    fn at_line(out: &Expanded, line: u32) -> Diagnostic {
+        let start = line_start(&out.code, line);
+        let len = out.code[start..].split('\n').next().unwrap().len();
+        Diagnostic {
+            severity: Severity::Error,
+            stage: Stage::Fragment,
+            message: "bad".to_owned(),
+            span: Some(SourceSpan { line, column: 1, start, end: start + len }),
+            file: None,
+        }
+    }
+
+    #[test]
+    fn includes_take_a_trailing_comment_and_paste_once() {
+        let out = code("#include \"b.wgsl\"   // b pulls in a\n#import a.wgsl\nfn main() {}");
+        assert_eq!(out.matches("fn a()").count(), 1);
+        assert!(out.contains("fn b()") && out.ends_with("fn main() {}"));
+        // one line out per line in, so naga's line numbers stay put
+        assert_eq!(out.lines().count(), A.lines().count() + B.lines().count() - 1 + 2);
+    }
+
+    #[test]
+    fn defines_stop_at_comments_and_replace_whole_words() {
+        let out = code(
+            "#define AMOUNT 0.1 // how much\n#define ON // flag only\n\
+             let x = AMOUNT * AMOUNTS + ON; // AMOUNT",
+        );
+        assert_eq!(out.lines().last(), Some("let x = 0.1 * AMOUNTS + ON; // AMOUNT"));
+    }
+
+    #[test]
+    fn conditionals_nest_and_take_comments() {
+        let src = "#define A\n\
+                   #ifdef A // outer\n\
+                   a\n\
+                   #ifndef FRAGMENT /* stage */\n\
+                   vertex\n\
+                   #else\n\
+                   fragment\n\
+                   #endif\n\
+                   #else\n\
+                   not_a\n\
+                   #endif // done\n\
+                   #undef A\n\
+                   #ifdef A\n\
+                   gone\n\
+                   #endif";
+        let out = code(src);
+        let kept: Vec<_> = out.lines().filter(|l| !l.is_empty()).collect();
+        assert_eq!(kept, ["a", "fragment"]);
+
+        let vertex = expand(src, Stage::Vertex, module).unwrap().code;
+        assert!(vertex.contains("vertex") && !vertex.contains("fragment"));
+    }
+
+    #[test]
+    fn directives_in_block_comments_are_text() {
+        let out = code("/* outer /* nested */\n#include \"missing.wgsl\"\n*/ fn f() {}");
+        assert!(out.contains("#include \"missing.wgsl\""));
+        assert!(out.contains("*/ fn f() {}"));
+        // and the comment is closed again afterwards
+        assert_eq!(code("/* a */ #define Y 2\nY").lines().last(), Some("2"));
+    }
+
+    #[test]
+    fn errors_point_at_the_directive() {
+        let e = error("fn f() {}\n  #else");
+        assert_eq!(e.message, "`#else` without `#ifdef`");
+        let span = e.span.unwrap();
+        assert_eq!((span.line, span.column, span.start), (2, 3, 12));
+
+        let e = error("#ifdef A\n#else\n#else\n#endif");
+        assert_eq!(e.span.unwrap().line, 3);
+
+        // never closed: the `#ifdef` itself
+        let e = error("x\n#ifdef A\ny");
+        assert_eq!((e.message.as_str(), e.span.unwrap().line), ("`#ifdef` without `#endif`", 2));
+
+        assert_eq!(error("#include \"nope.wgsl\" // c").message, "no module named `nope.wgsl`");
+        assert_eq!(error("#ifdef A B").message, "`A B` isn't a name");
+        assert_eq!(error("#define F(x) x").message, "`#define` takes no parameters");
+
+        let e = error("#include \"broken.wgsl\"");
+        assert_eq!(e.message, "unknown directive `#bogus`");
+        assert_eq!((e.file.as_deref(), e.span.unwrap().line), (Some("broken.wgsl"), 2));
+    }
+
+    #[test]
+    fn locate_maps_back_into_the_right_file() {
+        let src = "#include \"b.wgsl\"\n#define ONE 1.0\nfn main() { let x = ONE; }";
+        let out = expand(src, Stage::Fragment, module).unwrap();
+        let line_of =
+            |needle| out.code.lines().position(|l| l.contains(needle)).unwrap() as u32 + 1;
+
+        // a module's line, with the place in it kept
+        let d = out.locate(at_line(&out, line_of("fn a()")));
+        assert_eq!(d.file.as_deref(), Some("a.wgsl"));
+        let span = d.span.unwrap();
+        assert_eq!((span.line, &A[span.start..span.end]), (2, "fn a() -> f32 { return 1.0; }"));
+
+        // a line a define changed: the whole original line
+        let d = out.locate(at_line(&out, line_of("let x")));
+        assert_eq!(d.file, None);
+        let span = d.span.unwrap();
+        assert_eq!((span.line, &src[span.start..span.end]), (3, "fn main() { let x = ONE; }"));
+
+        // no span, or one past the end: left alone
+        let mut d = at_line(&out, 1);
+        d.span = None;
+        assert_eq!(out.locate(d.clone()), d);
+        let past = at_line(&out, 1);
+        let past = Diagnostic { span: past.span.map(|s| SourceSpan { line: 99, ..s }), ..past };
+        assert_eq!(out.locate(past.clone()), past);
+    }
+
+    #[test]
+    fn files_resolve_created_and_shipped_modules() {
+        let owner = Owner::new();
+        owner.set();
+
+        let files = ShaderFiles::new();
+        assert!(files.create("bad name").is_err());
+        let i = files.create("tint").unwrap();
+        assert_eq!(files.name(i).as_deref(), Some("tint.wgsl"));
+        assert!(files.create("tint.wgsl").is_err());
+
+        let tint = "#include \"time.wgsl\"\n\
+                    #define GREEN vec3<f32>(0.0, 1.0, 0.0)\n\
+                    const TINT = GREEN;\n\
+                    const OOPS = missing_thing;\n";
+        files.src(i).unwrap().set(tint.to_owned());
+        assert_eq!(files.edits().keys().collect::<Vec<_>>(), ["tint.wgsl"]);
+
+        let src = "#include \"tint.wgsl\"\n#include \"time.wgsl\"\nconst X = TINT;";
+        let out = files.expand(src, Stage::Fragment).unwrap();
+        let time = shipped("time.wgsl").unwrap();
+        assert_eq!(out.included().collect::<Vec<_>>(), [tint, time]);
+        assert!(out.code.contains("const TINT = vec3<f32>(0.0, 1.0, 0.0);"));
+
+        // naga's error, found in the pasted module, is at its own line there
+        let errors: Vec<_> = check_wgsl(&out.code, Stage::Fragment)
+            .into_iter()
+            .filter(|d| d.severity == Severity::Error)
+            .map(|d| out.locate(d))
+            .collect();
+        assert_eq!(errors.len(), 1, "{errors:#?}");
+        assert_eq!(errors[0].file.as_deref(), Some("tint.wgsl"));
+        let span = errors[0].span.unwrap();
+        assert_eq!((span.line, &tint[span.start..span.end]), (4, "missing_thing"));
+    }
+
+    #[test]
+    fn plain_sources_map_to_themselves() {
+        let src = "fn main() {}\nfn other() {}";
+        let out = Expanded::plain(src);
+        let d = out.locate(at_line(&out, 2));
+        assert_eq!(d.span.unwrap().start, 13);
+        assert_eq!(out.included().count(), 0);
+    }
+}
@@ -1,4 +1,12 @@
-use super::{surface_context::SurfaceContext, utils::{common_bind_group, create_ubos, load_texture, spatial_bind_group, texturing_bind_group}};
+use super::{
+    surface_context::SurfaceContext,
+    utils::{
+        common_bind_group, create_params_buffer, create_ubos, empty_bind_group,
+        params_bind_group, spatial_bind_group, texturing_bind_group,
+        texturing_bind_group_layout,
+    },
+    textures::{TextureManager, TextureSlots},
+};
 
 pub struct Group {
     pub group: wgpu::BindGroup,
@@ -12,13 +20,18 @@ pub struct ResourceContext {
     pub material_ubo: wgpu::Buffer,
     pub time_ubo: wgpu::Buffer,
     pub resolution_ubo: wgpu::Buffer,
+    /// Values for the uniforms a user shader declares (group 3).
+    pub params_ubo: wgpu::Buffer,
 
-    pub texture_view: wgpu::TextureView,
-    pub sampler: wgpu::Sampler,
+    /// What the texture channels hold on this device.
+    pub textures: TextureManager,
 
     pub common_bind_group: Group,
     pub spatial_bind_group: Group,
     pub texturing_bind_group: Group,
+    pub params_bind_group: Group,
+    /// Fills group 2 for passes without a material.
+    pub empty_bind_group: Group,
 }
 
 impl ResourceContext {
@@ -29,14 +42,15 @@ impl ResourceContext {
                 &self.common_bind_group.layout,
                 &self.spatial_bind_group.layout,
                 &self.texturing_bind_group.layout,
+                &self.params_bind_group.layout,
             ],
             push_constant_ranges: &[],
         })
     }
 
     pub async fn new_async(sc: &SurfaceContext) -> Self {
-        let (camera_ubo, model_ubo, light_ubo, material_ubo, time_ubo, resolution_ubo) = create_ubos(&sc);
-        let (texture_view, sampler) = load_texture(&sc);
+        let (camera_ubo, model_ubo, light_ubo, material_ubo, time_ubo, resolution_ubo) = create_ubos(sc);
+        let textures = TextureManager::new(sc);
 
         // let colours = [
         //     [1.0, 0.0, 0.0, 1.0],   // red cube
@@ -46,7 +60,13 @@ impl ResourceContext {
 
         let (common_layout, common_group) = common_bind_group(&sc.device, &time_ubo, &resolution_ubo);
         let (spatial_layout, spatial_group) = spatial_bind_group(&sc.device, &camera_ubo, &model_ubo, &light_ubo);
-        let (texturing_layout, texturing_group) = texturing_bind_group(&sc.device, &material_ubo, &texture_view, &sampler);
+        let texturing_layout = texturing_bind_group_layout(&sc.device);
+        let texturing_group =
+            texturing_bind_group(&sc.device, &texturing_layout, &material_ubo, &textures);
+
+        let params_ubo = create_params_buffer(sc);
+        let (params_layout, params_group) = params_bind_group(&sc.device, &params_ubo);
+        let (empty_layout, empty_group) = empty_bind_group(&sc.device);
 
         let common_bind_group = Group {
             group: common_group,
@@ -63,6 +83,16 @@ impl ResourceContext {
             layout: texturing_layout,
         };
 
+        let params_bind_group = Group {
+            group: params_group,
+            layout: params_layout,
+        };
+
+        let empty_bind_group = Group {
+            group: empty_group,
+            layout: empty_layout,
+        };
+
         ResourceContext {
             camera_ubo,
             model_ubo,
@@ -70,13 +100,27 @@ impl ResourceContext {
             material_ubo,
             time_ubo,
             resolution_ubo,
+            params_ubo,
 
             common_bind_group,
             spatial_bind_group,
             texturing_bind_group,
+            params_bind_group,
+            empty_bind_group,
 
-            sampler,
-            texture_view,
+            textures,
+        }
+    }
+
+    /// Upload channel images that changed, and rebind them.
+    pub fn sync_textures(&mut self, sc: &SurfaceContext, slots: &TextureSlots) {
+        if self.textures.sync(sc, slots) {
+            self.texturing_bind_group.group = texturing_bind_group(
+                &sc.device,
+                &self.texturing_bind_group.layout,
+                &self.material_ubo,
+                &self.textures,
+            );
         }
     }
 }
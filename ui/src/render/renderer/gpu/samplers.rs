@@ -0,0 +1,127 @@
+//! Samplers by what they do rather than by handle: a texture channel names
+//! a [`SamplerDesc`] and the [`SamplerRegistry`] hands out the one sampler
+//! per device made for it.
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Filter {
+    Nearest,
+    /// Trilinear: between texels and between mip levels.
+    #[default]
+    Linear,
+    /// Trilinear, sharper at grazing angles where the device supports it.
+    Anisotropic,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Wrap {
+    Clamp,
+    #[default]
+    Repeat,
+    Mirror,
+}
+
+impl Filter {
+    pub const ALL: [Filter; 3] = [Filter::Nearest, Filter::Linear, Filter::Anisotropic];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Filter::Nearest => "nearest",
+            Filter::Linear => "linear",
+            Filter::Anisotropic => "anisotropic",
+        }
+    }
+}
+
+impl Wrap {
+    pub const ALL: [Wrap; 3] = [Wrap::Clamp, Wrap::Repeat, Wrap::Mirror];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Wrap::Clamp => "clamp",
+            Wrap::Repeat => "repeat",
+            Wrap::Mirror => "mirror",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct SamplerDesc {
+    #[serde(default)]
+    pub filter: Filter,
+    #[serde(default)]
+    pub wrap: Wrap,
+}
+
+impl SamplerDesc {
+    fn descriptor(&self) -> wgpu::SamplerDescriptor<'static> {
+        let address_mode = match self.wrap {
+            Wrap::Clamp => wgpu::AddressMode::ClampToEdge,
+            Wrap::Repeat => wgpu::AddressMode::Repeat,
+            Wrap::Mirror => wgpu::AddressMode::MirrorRepeat,
+        };
+        let (filter, anisotropy_clamp) = match self.filter {
+            Filter::Nearest => (wgpu::FilterMode::Nearest, 1),
+            Filter::Linear => (wgpu::FilterMode::Linear, 1),
+            // ignored where there's no anisotropic filtering (some WebGL2)
+            Filter::Anisotropic => (wgpu::FilterMode::Linear, 16),
+        };
+
+        wgpu::SamplerDescriptor {
+            label: Some("Channel Sampler"),
+            address_mode_u: address_mode,
+            address_mode_v: address_mode,
+            address_mode_w: address_mode,
+            mag_filter: filter,
+            min_filter: filter,
+            mipmap_filter: filter,
+            anisotropy_clamp,
+            ..Default::default()
+        }
+    }
+}
+
+/// One sampler per distinct [`SamplerDesc`], made on first use. There are
+/// only nine, so nothing is ever dropped.
+#[derive(Default)]
+pub struct SamplerRegistry {
+    samplers: HashMap<SamplerDesc, wgpu::Sampler>,
+}
+
+impl SamplerRegistry {
+    pub fn get(&mut self, device: &wgpu::Device, desc: SamplerDesc) -> &wgpu::Sampler {
+        self.samplers
+            .entry(desc)
+            .or_insert_with(|| device.create_sampler(&desc.descriptor()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn descriptors_follow_filter_and_wrap() {
+        let d = SamplerDesc { filter: Filter::Nearest, wrap: Wrap::Mirror }.descriptor();
+        assert_eq!(d.address_mode_u, wgpu::AddressMode::MirrorRepeat);
+        assert_eq!(d.min_filter, wgpu::FilterMode::Nearest);
+        assert_eq!(d.mipmap_filter, wgpu::FilterMode::Nearest);
+
+        let d = SamplerDesc { filter: Filter::Anisotropic, wrap: Wrap::Clamp }.descriptor();
+        assert_eq!(d.address_mode_v, wgpu::AddressMode::ClampToEdge);
+        assert_eq!((d.mag_filter, d.anisotropy_clamp), (wgpu::FilterMode::Linear, 16));
+    }
+
+    #[test]
+    fn saved_descriptions_fill_in_defaults() {
+        let desc: SamplerDesc = serde_json::from_str(r#"{"wrap":"clamp"}"#).unwrap();
+        assert_eq!(desc, SamplerDesc { filter: Filter::Linear, wrap: Wrap::Clamp });
+        let json = serde_json::to_string(&SamplerDesc::default()).unwrap();
+        assert_eq!(json, r#"{"filter":"linear","wrap":"repeat"}"#);
+    }
+}
@@ -0,0 +1,99 @@
+//! How the editor's sources feed the passes: a source per stage, or one
+//! module holding both stages' entry points. Either way a pass runs the
+//! entry points it was built with unless others are picked for it; see
+//! [`entry_points`](super::diagnostics::entry_points) for what can be.
+
+use std::collections::BTreeMap;
+
+use leptos::prelude::{Get, RwSignal, Set, Update, With, WithUntracked};
+
+use super::diagnostics::Stage;
+
+#[derive(Copy, Clone)]
+pub struct ShaderSetup {
+    /// Both stages come from `module` instead of the VS/FS pair.
+    pub single: RwSignal<bool>,
+    pub module: RwSignal<String>,
+    /// `pass/vs` or `pass/fs` to the entry point picked for it.
+    picked: RwSignal<BTreeMap<String, String>>,
+}
+
+fn key(pass: &str, stage: Stage) -> String {
+    let stage = if stage == Stage::Fragment { "fs" } else { "vs" };
+    format!("{pass}/{stage}")
+}
+
+impl ShaderSetup {
+    /// A VS/FS pair, with `module` ready for when that's switched.
+    pub fn new(module: String) -> Self {
+        Self {
+            single: RwSignal::new(false),
+            module: RwSignal::new(module),
+            picked: RwSignal::new(BTreeMap::new()),
+        }
+    }
+
+    /// What `pass` runs for `stage`: the picked entry point, or `default`.
+    pub fn entry(&self, pass: &str, stage: Stage, default: &str) -> String {
+        self.picked
+            .with(|p| p.get(&key(pass, stage)).cloned())
+            .unwrap_or_else(|| default.to_owned())
+    }
+
+    /// [`entry`](Self::entry) for the render loop, which tracks nothing.
+    pub fn entry_untracked(&self, pass: &str, stage: Stage, default: &str) -> String {
+        self.picked
+            .with_untracked(|p| p.get(&key(pass, stage)).cloned())
+            .unwrap_or_else(|| default.to_owned())
+    }
+
+    /// Run `entry` for `stage` in `pass`; picking `default` forgets the pick.
+    pub fn pick(&self, pass: &str, stage: Stage, entry: String, default: &str) {
+        self.picked.update(|p| {
+            if entry == default {
+                p.remove(&key(pass, stage));
+            } else {
+                p.insert(key(pass, stage), entry);
+            }
+        });
+    }
+
+    /// Every pick, by `pass/vs` or `pass/fs`; what's worth keeping.
+    pub fn picks(&self) -> BTreeMap<String, String> {
+        self.picked.get()
+    }
+
+    pub fn set_picks(&self, picks: BTreeMap<String, String>) {
+        self.picked.set(picks);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use leptos::prelude::Owner;
+
+    use super::*;
+
+    #[test]
+    fn picks_override_the_default_until_it_is_picked_again() {
+        let owner = Owner::new();
+        owner.set();
+
+        let setup = ShaderSetup::new(String::new());
+        let pass = "Default Render Pass";
+        assert_eq!(setup.entry(pass, Stage::Fragment, "fs_main"), "fs_main");
+
+        setup.pick(pass, Stage::Fragment, "fs_green".to_owned(), "fs_main");
+        assert_eq!(setup.entry(pass, Stage::Fragment, "fs_main"), "fs_green");
+        assert_eq!(setup.entry_untracked(pass, Stage::Vertex, "vs_main"), "vs_main");
+        assert_eq!(setup.entry("other pass", Stage::Fragment, "fs_main"), "fs_main");
+        let picks = setup.picks();
+        assert_eq!(picks.get("Default Render Pass/fs").map(String::as_str), Some("fs_green"));
+
+        setup.pick(pass, Stage::Fragment, "fs_main".to_owned(), "fs_main");
+        assert!(setup.picks().is_empty());
+
+        setup.set_picks(picks);
+        assert_eq!(setup.entry_untracked(pass, Stage::Fragment, "fs_main"), "fs_green");
+    }
+}
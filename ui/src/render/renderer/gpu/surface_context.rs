@@ -1,14 +1,31 @@
 use anyhow::Result;
 use web_sys::HtmlCanvasElement;
 
-use super::utils::{create_surface_static, request_adapter, request_device};
+use std::sync::{
+    Arc,
+    atomic::{AtomicBool, Ordering},
+};
+
+use super::utils::{create_surface_static, request_adapter, request_device, watch_device_lost};
+
+/// Where frames end up.
+pub enum RenderTarget {
+    /// Swap-chain backed by a `<canvas>`; frames are presented.
+    Canvas(wgpu::Surface<'static>),
+
+    /// Plain texture; frames stay on the GPU until read back.
+    Offscreen(wgpu::Texture),
+}
 
 pub struct SurfaceContext {
-    pub surface: wgpu::Surface<'static>,
+    pub target: RenderTarget,
     pub adapter: wgpu::Adapter,
     pub device: wgpu::Device,
     pub queue: wgpu::Queue,
     pub config: wgpu::SurfaceConfiguration,
+
+    /// Set by wgpu's device-lost callback.
+    pub device_lost: Arc<AtomicBool>,
 }
 
 impl SurfaceContext {
@@ -24,6 +41,7 @@ impl SurfaceContext {
 
         let adapter = request_adapter(&instance, &surface).await?;
         let (device, queue) = request_device(&adapter).await?;
+        let device_lost = watch_device_lost(&device);
 
         device.on_uncaptured_error(Box::new(|e| {
             use wasm_bindgen::JsValue;
@@ -56,11 +74,102 @@ impl SurfaceContext {
         surface.configure(&device, &config);
 
         Ok(SurfaceContext {
-            surface,
+            target: RenderTarget::Canvas(surface),
             adapter,
             device,
             queue,
             config,
+            device_lost,
         })
     }
+
+    /// Canvas-less context that renders into a `width`×`height` texture.
+    ///
+    /// `config` is still filled in (with the texture's size and format) so
+    /// passes that read it work the same as on the web.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub async fn new_headless(width: u32, height: u32) -> Result<Self> {
+        use super::utils::request_headless_adapter;
+
+        let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor {
+            backends: wgpu::Backends::from_env().unwrap_or(wgpu::Backends::all()),
+            ..Default::default()
+        });
+
+        let adapter = request_headless_adapter(&instance).await?;
+        let (device, queue) = request_device(&adapter).await?;
+        let device_lost = watch_device_lost(&device);
+
+        // same reasoning as the canvas path: linear, so output matches the web
+        let config = wgpu::SurfaceConfiguration {
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            format: wgpu::TextureFormat::Rgba8Unorm,
+            width,
+            height,
+            present_mode: wgpu::PresentMode::Fifo,
+            desired_maximum_frame_latency: 2,
+            alpha_mode: wgpu::CompositeAlphaMode::Opaque,
+            view_formats: vec![],
+        };
+
+        let texture = create_offscreen_texture(&device, &config);
+
+        Ok(SurfaceContext {
+            target: RenderTarget::Offscreen(texture),
+            adapter,
+            device,
+            queue,
+            config,
+            device_lost,
+        })
+    }
+
+    pub fn is_device_lost(&self) -> bool {
+        self.device_lost.load(Ordering::Relaxed)
+    }
+
+    /// Reconfigure the target for a new size (in physical pixels).
+    ///
+    /// The size is clamped to `1..=max_texture_dimension_2d`; returns `false`
+    /// when nothing changed.
+    pub fn resize(&mut self, width: u32, height: u32) -> bool {
+        let max = self.device.limits().max_texture_dimension_2d;
+        let (width, height) = (width.clamp(1, max), height.clamp(1, max));
+
+        if (width, height) == (self.config.width, self.config.height) {
+            return false;
+        }
+
+        self.config.width = width;
+        self.config.height = height;
+
+        match &mut self.target {
+            RenderTarget::Canvas(surface) => surface.configure(&self.device, &self.config),
+            RenderTarget::Offscreen(texture) => {
+                *texture = create_offscreen_texture(&self.device, &self.config)
+            }
+        }
+
+        true
+    }
+}
+
+pub fn create_offscreen_texture(
+    device: &wgpu::Device,
+    config: &wgpu::SurfaceConfiguration,
+) -> wgpu::Texture {
+    device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("offscreen target"),
+        size: wgpu::Extent3d {
+            width: config.width,
+            height: config.height,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: config.format,
+        usage: config.usage,
+        view_formats: &[],
+    })
 }
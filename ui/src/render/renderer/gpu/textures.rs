@@ -0,0 +1,447 @@
+//! Images the shaders sample, ShaderToy style: [`CHANNELS`] slots,
+//! `iChannel0` to `iChannel3`, each filled from a URL or from a file the
+//! user drops or picks, and sampled as picked for it (see
+//! [`super::samplers`]). [`TextureSlots`] is what the UI fills and the
+//! passes read; [`TextureManager`] (in the resource context) keeps the
+//! textures on the device, uploading whatever changed since the last frame
+//! and building its mips (see [`super::mipmaps`]).
+//!
+//! Slot 0 is the demo's own texture, `texture_data` at binding 0, so shaders
+//! written before the slots keep working. The rest follow the material
+//! uniform at binding 2; `#include "channels.wgsl"` declares them all:
+//!
+//! ```wgsl
+//! @group(2) @binding(0) var iChannel0 : texture_2d<f32>;
+//! @group(2) @binding(1) var iChannel0_sampler : sampler;
+//! @group(2) @binding(3) var iChannel1 : texture_2d<f32>;
+//! @group(2) @binding(4) var iChannel1_sampler : sampler;
+//! ```
+
+use std::{collections::BTreeMap, sync::Arc};
+
+use gloo_net::http::Request;
+use image::{RgbaImage, imageops::FilterType};
+use leptos::prelude::{RwSignal, Update, With, WithUntracked};
+use leptos::task::spawn_local;
+
+use super::mipmaps::{MipGenerator, level_count};
+use super::samplers::{SamplerDesc, SamplerRegistry};
+use super::surface_context::SurfaceContext;
+
+pub const CHANNELS: usize = 4;
+
+/// (texture, sampler) bindings of `channel` in the texturing group.
+pub fn channel_bindings(channel: usize) -> (u32, u32) {
+    match channel as u32 {
+        0 => (0, 1),
+        c => (1 + 2 * c, 2 + 2 * c),
+    }
+}
+
+pub fn channel_name(channel: usize) -> String {
+    format!("iChannel{channel}")
+}
+
+/// Where a slot's image came from.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Source {
+    Url(String),
+    /// A dropped or picked file, by name; gone after a reload.
+    File(String),
+}
+
+impl Source {
+    pub fn label(&self) -> &str {
+        match self {
+            Source::Url(url) => url,
+            Source::File(name) => name,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum SlotState {
+    #[default]
+    Empty,
+    Loading,
+    Ready { width: u32, height: u32 },
+    Failed(String),
+}
+
+#[derive(Clone, Default)]
+pub struct Slot {
+    pub source: Option<Source>,
+    pub state: SlotState,
+    /// How the shader's sampler for this channel filters and wraps.
+    pub sampler: SamplerDesc,
+    /// Bottom row first, so UV (0, 0) is the bottom-left corner.
+    image: Option<Arc<RgbaImage>>,
+    /// Bumped whenever `image` changes; the device side uploads on a bump.
+    version: u64,
+    /// Bumped per load, so a slow one can't land after a later one.
+    request: u64,
+}
+
+/// The slots' contents, shared by the editor and the passes that sample
+/// them. A slot keeps its old image until a new one has decoded.
+#[derive(Copy, Clone)]
+pub struct TextureSlots {
+    slots: RwSignal<[Slot; CHANNELS]>,
+}
+
+impl Default for TextureSlots {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TextureSlots {
+    pub fn new() -> Self {
+        Self {
+            slots: RwSignal::new(Default::default()),
+        }
+    }
+
+    pub fn slot(&self, channel: usize) -> Slot {
+        self.slots.with(|s| s[channel].clone())
+    }
+
+    /// Fetch and decode `url` into `channel`.
+    pub fn load_url(&self, channel: usize, url: String) {
+        let request = self.begin(channel, Source::Url(url.clone()));
+        let slots = *self;
+        spawn_local(async move {
+            let image = fetch(&url).await.and_then(|bytes| decode(&bytes));
+            slots.finish(channel, request, image);
+        });
+    }
+
+    /// Decode a file's contents, read through the object URL made for it
+    /// (revoked once read).
+    pub fn load_file(&self, channel: usize, name: String, object_url: String) {
+        let request = self.begin(channel, Source::File(name));
+        let slots = *self;
+        spawn_local(async move {
+            let image = fetch(&object_url).await.and_then(|bytes| decode(&bytes));
+            web_sys::Url::revoke_object_url(&object_url).ok();
+            slots.finish(channel, request, image);
+        });
+    }
+
+    /// Decode `bytes` (any format `image` reads) into `channel` right away.
+    pub fn load_bytes(&self, channel: usize, name: String, bytes: &[u8]) {
+        let request = self.begin(channel, Source::File(name));
+        self.finish(channel, request, decode(bytes));
+    }
+
+    pub fn clear(&self, channel: usize) {
+        self.slots.update(|s| {
+            let slot = &mut s[channel];
+            *slot = Slot {
+                sampler: slot.sampler,
+                version: slot.version + 1,
+                request: slot.request + 1,
+                ..Slot::default()
+            };
+        });
+    }
+
+    pub fn set_sampler(&self, channel: usize, sampler: SamplerDesc) {
+        self.slots.update(|s| s[channel].sampler = sampler);
+    }
+
+    /// Channel name to sampler, where it isn't the default one.
+    pub fn samplers(&self) -> BTreeMap<String, SamplerDesc> {
+        self.slots.with(|s| {
+            (0..CHANNELS)
+                .filter(|&c| s[c].sampler != SamplerDesc::default())
+                .map(|c| (channel_name(c), s[c].sampler))
+                .collect()
+        })
+    }
+
+    /// `samplers` by channel name; the default one for the others.
+    pub fn set_samplers(&self, samplers: &BTreeMap<String, SamplerDesc>) {
+        self.slots.update(|s| {
+            for (c, slot) in s.iter_mut().enumerate() {
+                slot.sampler = samplers.get(&channel_name(c)).copied().unwrap_or_default();
+            }
+        });
+    }
+
+    /// Channel name to URL, for slots filled from one; what's worth keeping
+    /// or sharing.
+    pub fn urls(&self) -> BTreeMap<String, String> {
+        self.slots.with(|s| {
+            (0..CHANNELS)
+                .filter_map(|c| match &s[c].source {
+                    Some(Source::Url(url)) => Some((channel_name(c), url.clone())),
+                    _ => None,
+                })
+                .collect()
+        })
+    }
+
+    /// Empty every slot, then load `urls` (by channel name) into theirs.
+    pub fn load_urls(&self, urls: &BTreeMap<String, String>) {
+        for channel in 0..CHANNELS {
+            match urls.get(&channel_name(channel)) {
+                Some(url) => self.load_url(channel, url.clone()),
+                None => self.clear(channel),
+            }
+        }
+    }
+
+    /// Slots whose image isn't the `uploaded` version, for the render loop.
+    fn pending(&self, uploaded: &[u64; CHANNELS]) -> Vec<(usize, u64, Option<Arc<RgbaImage>>)> {
+        self.slots.with_untracked(|s| {
+            (0..CHANNELS)
+                .filter(|&c| s[c].version != uploaded[c])
+                .map(|c| (c, s[c].version, s[c].image.clone()))
+                .collect()
+        })
+    }
+
+    fn sampler_descs(&self) -> [SamplerDesc; CHANNELS] {
+        self.slots.with_untracked(|s| std::array::from_fn(|c| s[c].sampler))
+    }
+
+    fn begin(&self, channel: usize, source: Source) -> u64 {
+        let mut request = 0;
+        self.slots.update(|s| {
+            let slot = &mut s[channel];
+            slot.source = Some(source);
+            slot.state = SlotState::Loading;
+            slot.request += 1;
+            request = slot.request;
+        });
+        request
+    }
+
+    fn finish(&self, channel: usize, request: u64, image: Result<RgbaImage, String>) {
+        self.slots.update(|s| {
+            let slot = &mut s[channel];
+            if slot.request != request {
+                return;
+            }
+            match image {
+                Ok(image) => {
+                    let (width, height) = image.dimensions();
+                    slot.state = SlotState::Ready { width, height };
+                    slot.image = Some(Arc::new(image));
+                    slot.version += 1;
+                }
+                Err(e) => slot.state = SlotState::Failed(e),
+            }
+        });
+    }
+}
+
+pub(crate) async fn fetch(url: &str) -> Result<Vec<u8>, String> {
+    let resp = Request::get(url).send().await.map_err(|e| e.to_string())?;
+    if !resp.ok() {
+        return Err(format!("{} {}", resp.status(), resp.status_text()));
+    }
+    resp.binary().await.map_err(|e| e.to_string())
+}
+
+fn decode(bytes: &[u8]) -> Result<RgbaImage, String> {
+    image::load_from_memory(bytes)
+        .map(|img| img.flipv().into_rgba8())
+        .map_err(|e| e.to_string())
+}
+
+/// The slots' textures on this device and the samplers picked for them.
+/// Empty slots sample opaque black.
+pub struct TextureManager {
+    views: [wgpu::TextureView; CHANNELS],
+    uploaded: [u64; CHANNELS],
+    samplers: [wgpu::Sampler; CHANNELS],
+    sampler_descs: [SamplerDesc; CHANNELS],
+    registry: SamplerRegistry,
+    mips: MipGenerator,
+}
+
+impl TextureManager {
+    pub fn new(sc: &SurfaceContext) -> Self {
+        let mut registry = SamplerRegistry::default();
+        let sampler = registry.get(&sc.device, SamplerDesc::default()).clone();
+        let mips = MipGenerator::new(&sc.device, FORMAT);
+
+        Self {
+            views: std::array::from_fn(|_| upload(sc, &mips, &black())),
+            uploaded: [0; CHANNELS],
+            samplers: std::array::from_fn(|_| sampler.clone()),
+            sampler_descs: [SamplerDesc::default(); CHANNELS],
+            registry,
+            mips,
+        }
+    }
+
+    pub fn view(&self, channel: usize) -> &wgpu::TextureView {
+        &self.views[channel]
+    }
+
+    pub fn sampler(&self, channel: usize) -> &wgpu::Sampler {
+        &self.samplers[channel]
+    }
+
+    /// Upload what changed in `slots` and swap samplers that were picked;
+    /// true when anything was replaced, so the bind group holding them
+    /// needs rebuilding.
+    pub fn sync(&mut self, sc: &SurfaceContext, slots: &TextureSlots) -> bool {
+        let pending = slots.pending(&self.uploaded);
+        for (channel, version, image) in &pending {
+            self.views[*channel] = match image {
+                Some(image) => upload(sc, &self.mips, image),
+                None => upload(sc, &self.mips, &black()),
+            };
+            self.uploaded[*channel] = *version;
+        }
+
+        let descs = slots.sampler_descs();
+        let resampled = descs != self.sampler_descs;
+        if resampled {
+            for (channel, desc) in descs.into_iter().enumerate() {
+                self.samplers[channel] = self.registry.get(&sc.device, desc).clone();
+            }
+            self.sampler_descs = descs;
+        }
+
+        !pending.is_empty() || resampled
+    }
+}
+
+const FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
+
+fn black() -> RgbaImage {
+    RgbaImage::from_pixel(1, 1, image::Rgba([0, 0, 0, 255]))
+}
+
+/// `image` with its mip chain, shrunk first if the device can't hold it.
+fn upload(sc: &SurfaceContext, mips: &MipGenerator, image: &RgbaImage) -> wgpu::TextureView {
+    let max = sc.device.limits().max_texture_dimension_2d;
+    let shrunk = fit(image, max);
+    let image = shrunk.as_ref().unwrap_or(image);
+    let (width, height) = image.dimensions();
+    let size = wgpu::Extent3d { width, height, depth_or_array_layers: 1 };
+
+    let texture = sc.device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("Channel Texture"),
+        size,
+        mip_level_count: level_count(width, height),
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: FORMAT,
+        usage: wgpu::TextureUsages::TEXTURE_BINDING
+            | wgpu::TextureUsages::COPY_DST
+            | wgpu::TextureUsages::RENDER_ATTACHMENT,
+        view_formats: &[],
+    });
+
+    sc.queue.write_texture(
+        wgpu::TexelCopyTextureInfo {
+            texture: &texture,
+            mip_level: 0,
+            origin: wgpu::Origin3d::ZERO,
+            aspect: wgpu::TextureAspect::All,
+        },
+        image,
+        wgpu::TexelCopyBufferLayout {
+            offset: 0,
+            bytes_per_row: Some(4 * width),
+            rows_per_image: Some(height),
+        },
+        size,
+    );
+    mips.generate(&sc.device, &sc.queue, &texture);
+
+    texture.create_view(&Default::default())
+}
+
+/// `image` scaled down to at most `max` on a side; `None` if it fits.
+fn fit(image: &RgbaImage, max: u32) -> Option<RgbaImage> {
+    let (width, height) = image.dimensions();
+    let scale = max as f32 / width.max(height) as f32;
+    (scale < 1.0).then(|| {
+        let w = ((width as f32 * scale) as u32).max(1);
+        let h = ((height as f32 * scale) as u32).max(1);
+        image::imageops::resize(image, w, h, FilterType::Triangle)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use image::Rgba;
+    use leptos::prelude::Owner;
+
+    use super::*;
+    use crate::render::renderer::gpu::samplers::{Filter, Wrap};
+
+    fn png(width: u32, height: u32, pixel: impl Fn(u32, u32) -> [u8; 4]) -> Vec<u8> {
+        let image = RgbaImage::from_fn(width, height, |x, y| Rgba(pixel(x, y)));
+        let mut bytes = Vec::new();
+        image
+            .write_to(&mut std::io::Cursor::new(&mut bytes), image::ImageFormat::Png)
+            .unwrap();
+        bytes
+    }
+
+    #[test]
+    fn channels_after_the_first_follow_the_material() {
+        assert_eq!(channel_bindings(0), (0, 1));
+        assert_eq!(channel_bindings(1), (3, 4));
+        assert_eq!(channel_bindings(3), (7, 8));
+        assert_eq!(channel_name(2), "iChannel2");
+    }
+
+    #[test]
+    fn images_decode_bottom_row_first() {
+        let image = decode(&png(1, 2, |_, y| [0, 0, 255 * y as u8, 255])).unwrap();
+        assert_eq!(image.get_pixel(0, 0).0, [0, 0, 255, 255]);
+        assert!(decode(b"not an image").is_err());
+    }
+
+    #[test]
+    fn slots_keep_the_last_image_until_a_new_one_decodes() {
+        let owner = Owner::new();
+        owner.set();
+
+        let slots = TextureSlots::new();
+        slots.load_bytes(2, "green.png".to_owned(), &png(6, 3, |_, _| [0, 255, 0, 255]));
+        let slot = slots.slot(2);
+        assert_eq!(slot.state, SlotState::Ready { width: 6, height: 3 });
+        assert_eq!(slot.source, Some(Source::File("green.png".to_owned())));
+        let mut uploaded = [0; CHANNELS];
+        uploaded[2] = slot.version;
+        assert!(slots.pending(&uploaded).is_empty());
+
+        slots.load_bytes(2, "broken.png".to_owned(), b"not an image");
+        assert!(matches!(slots.slot(2).state, SlotState::Failed(_)));
+        assert!(slots.pending(&uploaded).is_empty(), "nothing new to upload");
+
+        // a load that's overtaken by a later one is dropped
+        let slow = slots.begin(1, Source::Url("slow.png".to_owned()));
+        slots.load_bytes(1, "fast.png".to_owned(), &png(1, 1, |_, _| [255; 4]));
+        slots.finish(1, slow, Ok(RgbaImage::new(8, 8)));
+        assert_eq!(slots.slot(1).state, SlotState::Ready { width: 1, height: 1 });
+    }
+
+    #[test]
+    fn samplers_survive_clearing_and_only_changed_ones_are_kept() {
+        let owner = Owner::new();
+        owner.set();
+
+        let slots = TextureSlots::new();
+        let nearest = SamplerDesc { filter: Filter::Nearest, wrap: Wrap::Clamp };
+        slots.set_sampler(3, nearest);
+        slots.load_bytes(3, "white.png".to_owned(), &png(1, 1, |_, _| [255; 4]));
+        slots.clear(3);
+        assert_eq!((slots.slot(3).state, slots.slot(3).sampler), (SlotState::Empty, nearest));
+        assert_eq!(slots.samplers(), BTreeMap::from([("iChannel3".to_owned(), nearest)]));
+
+        slots.set_samplers(&BTreeMap::new());
+        assert!(slots.samplers().is_empty());
+        assert_eq!(slots.sampler_descs(), [SamplerDesc::default(); CHANNELS]);
+    }
+}
@@ -1,10 +1,13 @@
-use std::{fs, num::{NonZeroU32, NonZeroU64}, ops::Deref};
+use std::{fs, num::NonZeroU64, ops::Deref, sync::{Arc, atomic::{AtomicBool, Ordering}}};
 use anyhow::{Context, Result};
 use glam::{Mat4, Vec3};
-use wgpu::{util::DeviceExt, SurfaceTarget};
+use wgpu::util::DeviceExt;
 use crate::web_sys::HtmlCanvasElement;
 
+use super::gpu_state::TimeUBO;
+use super::params::{MAX_PARAMS_SIZE, PARAMS_BINDING};
 use super::surface_context::SurfaceContext;
+use super::textures::{CHANNELS, TextureManager, channel_bindings};
 
 
 #[repr(C)]
@@ -56,16 +59,26 @@ pub async fn request_device(adapter: &wgpu::Adapter) -> Result<(wgpu::Device, wg
     }).await.context("Failed to request device")
 }
 
+#[cfg(target_arch = "wasm32")]
 pub fn create_surface_static(
     instance: &wgpu::Instance,
     canvas: &HtmlCanvasElement,
 ) -> anyhow::Result<wgpu::Surface<'static>> {
-    let target = SurfaceTarget::Canvas(canvas.clone());
+    let target = wgpu::SurfaceTarget::Canvas(canvas.clone());
     instance
         .create_surface(target)
         .context("webgpu surface init failed")
 }
 
+// NOTE: canvas surfaces only exist on the web, natively use `SurfaceContext::new_headless`
+#[cfg(not(target_arch = "wasm32"))]
+pub fn create_surface_static(
+    _instance: &wgpu::Instance,
+    _canvas: &HtmlCanvasElement,
+) -> anyhow::Result<wgpu::Surface<'static>> {
+    Err(anyhow::anyhow!("canvas surfaces are only available on wasm32"))
+}
+
 pub async fn request_adapter(
     instance: &wgpu::Instance,
     surface: &wgpu::Surface<'static>,
@@ -73,7 +86,7 @@ pub async fn request_adapter(
     let adapter = instance
         .request_adapter(&wgpu::RequestAdapterOptions {
             power_preference: wgpu::PowerPreference::HighPerformance,
-            compatible_surface: Some(&surface),
+            compatible_surface: Some(surface),
             force_fallback_adapter: false,
         })
         .await
@@ -82,13 +95,62 @@ pub async fn request_adapter(
     Ok(adapter)
 }
 
+/// Prefers a software (CPU) adapter so headless rendering works on GPU-less
+/// machines, but takes whatever is available if there is none.
+#[cfg(not(target_arch = "wasm32"))]
+pub async fn request_headless_adapter(instance: &wgpu::Instance) -> Result<wgpu::Adapter> {
+    let opts = |force_fallback_adapter| wgpu::RequestAdapterOptions {
+        power_preference: wgpu::PowerPreference::LowPower,
+        compatible_surface: None,
+        force_fallback_adapter,
+    };
+
+    if let Ok(adapter) = instance.request_adapter(&opts(true)).await {
+        return Ok(adapter);
+    }
+
+    instance
+        .request_adapter(&opts(false))
+        .await
+        .context("Failed to request a headless adapter")
+}
+
+/// Flag that flips to `true` once the device is lost (GPU reset, driver
+/// crash, ...). Losses caused by us dropping the device are ignored.
+pub fn watch_device_lost(device: &wgpu::Device) -> Arc<AtomicBool> {
+    let lost = Arc::new(AtomicBool::new(false));
+    let flag = lost.clone();
+    device.set_device_lost_callback(move |reason, _msg| {
+        if reason != wgpu::DeviceLostReason::Destroyed {
+            flag.store(true, Ordering::Relaxed);
+        }
+    });
+    lost
+}
+
+/// Milliseconds since an arbitrary origin (page load in the browser,
+/// first call natively).
+pub fn now_ms() -> f64 {
+    #[cfg(target_arch = "wasm32")]
+    {
+        web_sys::window().unwrap().performance().unwrap().now()
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        use std::{sync::OnceLock, time::Instant};
+
+        static ORIGIN: OnceLock<Instant> = OnceLock::new();
+        ORIGIN.get_or_init(Instant::now).elapsed().as_secs_f64() * 1000.0
+    }
+}
+
 
 pub fn create_time_buffer(sc: &SurfaceContext) -> wgpu::Buffer {
-    // 16-byte aligned – easiest: two f32’s or a vec4
-    let init: [f32; 4] = [0.0, 0.0, 0.0, 0.0];
+    let init = TimeUBO::default();
     sc.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
         label:    Some("Time UBO"),
-        contents: bytemuck::cast_slice(&init),
+        contents: bytemuck::bytes_of(&init),
         usage:    wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
     })
 }
@@ -132,7 +194,7 @@ pub fn create_ubos(sc: &SurfaceContext) -> (wgpu::Buffer, wgpu::Buffer, wgpu::Bu
         usage:  wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
     });
 
-    let time_buffer = create_time_buffer(&sc);
+    let time_buffer = create_time_buffer(sc);
 
     let res = [sc.config.width as f32, sc.config.height as f32, 0 as f32, 0 as f32];
 
@@ -162,7 +224,7 @@ pub fn common_bind_group(
         label: Some("Common Bind Group Layout"),
 
         entries: &[
-            simple_ubo_layout_entry!(0, wgpu::ShaderStages::FRAGMENT, 16),
+            simple_ubo_layout_entry!(0, wgpu::ShaderStages::FRAGMENT, std::mem::size_of::<TimeUBO>() as u64),
             simple_ubo_layout_entry!(1, wgpu::ShaderStages::FRAGMENT, 16),
         ],
     });
@@ -224,34 +286,91 @@ pub fn spatial_bind_group(
     (layout, group)
 }
 
+/// The material uniform at 2, and a texture and sampler per channel; see
+/// [`super::textures`] for where each one sits.
+pub fn texturing_bind_group_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+    let mut entries = vec![simple_ubo_layout_entry!(2, wgpu::ShaderStages::FRAGMENT, 32)];
+    for channel in 0..CHANNELS {
+        let (texture, sampler) = channel_bindings(channel);
+        entries.push(wgpu::BindGroupLayoutEntry {
+            binding:    texture,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Texture {
+                sample_type:     wgpu::TextureSampleType::Float { filterable: true },
+                view_dimension:  wgpu::TextureViewDimension::D2,
+                multisampled:    false,
+            },
+            count: None,
+        });
+        entries.push(wgpu::BindGroupLayoutEntry {
+            binding:    sampler,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+            count: None,
+        });
+    }
+
+    device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        label: Some("Texturing Bind Group Layout"),
+        entries: &entries,
+    })
+}
+
+/// Rebuilt whenever a channel's texture is replaced.
 pub fn texturing_bind_group(
     device: &wgpu::Device,
+    layout: &wgpu::BindGroupLayout,
 
     material_buffer: &wgpu::Buffer,
-    texture_view: &wgpu::TextureView,
-    sampler: &wgpu::Sampler,
+    textures: &TextureManager,
+) -> wgpu::BindGroup {
+    let mut entries = vec![wgpu::BindGroupEntry {
+        binding: 2,
+        resource: material_buffer.as_entire_binding(),
+    }];
+    for channel in 0..CHANNELS {
+        let (texture, sampler) = channel_bindings(channel);
+        entries.push(wgpu::BindGroupEntry {
+            binding: texture,
+            resource: wgpu::BindingResource::TextureView(textures.view(channel)),
+        });
+        entries.push(wgpu::BindGroupEntry {
+            binding: sampler,
+            resource: wgpu::BindingResource::Sampler(textures.sampler(channel)),
+        });
+    }
+
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        layout,
+        entries: &entries,
+        label: Some("Texturing Bind Group"),
+    })
+}
+
+/// Zeroed until a shader declares params; see [`super::params`].
+pub fn create_params_buffer(sc: &SurfaceContext) -> wgpu::Buffer {
+    sc.device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("Params UBO"),
+        size: MAX_PARAMS_SIZE,
+        usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    })
+}
+
+pub fn params_bind_group(
+    device: &wgpu::Device,
+
+    params_buffer: &wgpu::Buffer,
 ) -> (wgpu::BindGroupLayout, wgpu::BindGroup) {
     let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-        label: Some("Texturing Bind Group Layout"),
+        label: Some("Params Bind Group Layout"),
 
         entries: &[
-            wgpu::BindGroupLayoutEntry {
-                binding:    0,
-                visibility: wgpu::ShaderStages::FRAGMENT,
-                ty: wgpu::BindingType::Texture {
-                    sample_type:     wgpu::TextureSampleType::Float { filterable: true },
-                    view_dimension:  wgpu::TextureViewDimension::D2,
-                    multisampled:    false,
-                },
-                count: None,
-            },
-            wgpu::BindGroupLayoutEntry {
-                binding:    1,
-                visibility: wgpu::ShaderStages::FRAGMENT,
-                ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
-                count: None,
-            },
-            simple_ubo_layout_entry!(2, wgpu::ShaderStages::FRAGMENT, 32),
+            simple_ubo_layout_entry!(
+                PARAMS_BINDING,
+                wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+                MAX_PARAMS_SIZE
+            ),
         ],
     });
 
@@ -259,79 +378,31 @@ pub fn texturing_bind_group(
         layout: &layout,
         entries: &[
             wgpu::BindGroupEntry {
-                binding: 0,
-                resource: wgpu::BindingResource::TextureView(&texture_view),
-            },
-            wgpu::BindGroupEntry {
-                binding: 1,
-                resource: wgpu::BindingResource::Sampler(&sampler),
-            },
-            wgpu::BindGroupEntry {
-                binding: 2,
-                resource: material_buffer.as_entire_binding(),
+                binding: PARAMS_BINDING,
+                resource: params_buffer.as_entire_binding(),
             },
         ],
-        label: Some("Texturing Bind Group"),
+        label: Some("Params Bind Group"),
     });
 
     (layout, group)
 }
 
-// TODO: change to not compile time baked in image
-static IMAGE_BYTES: &[u8] = include_bytes!("../../../../texture.png");
-
-pub fn load_texture(sc: &SurfaceContext) -> (wgpu::TextureView, wgpu::Sampler) {
-    // 1. Load and flip Y so UV [0,0] is bottom-left
-    let img = image::load_from_memory(IMAGE_BYTES)
-        .expect("texture.png not found")
-        .flipv()
-        .into_rgba8();
-
-    let (width, height) = img.dimensions();
-    let size = wgpu::Extent3d {
-        width, height, depth_or_array_layers: 1,
-    };
-
-    // 2. Create the GPU texture
-    let texture = sc.device.create_texture(&wgpu::TextureDescriptor {
-        label: Some("Cube Texture"),
-        size,
-        mip_level_count: 1,
-        sample_count: 1,
-        dimension: wgpu::TextureDimension::D2,
-        format: wgpu::TextureFormat::Rgba8UnormSrgb,
-        usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
-        view_formats: &[],
+/// Stands in for a pass without a material group, so the params group can
+/// still be group 3 (layouts can't skip one).
+pub fn empty_bind_group(device: &wgpu::Device) -> (wgpu::BindGroupLayout, wgpu::BindGroup) {
+    let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        label: Some("Empty Bind Group Layout"),
+        entries: &[],
     });
 
-    // 3. Upload pixel data
-    sc.queue.write_texture(
-        wgpu::TexelCopyTextureInfo {
-            texture: &texture,
-            mip_level: 0,
-            origin: wgpu::Origin3d::ZERO,
-            aspect: wgpu::TextureAspect::All,
-        },
-        &img,
-        wgpu::TexelCopyBufferLayout {
-            offset: 0,
-            bytes_per_row: Some(NonZeroU32::new(4 * width).unwrap().into()),
-            rows_per_image: Some(NonZeroU32::new(height).unwrap().into()),
-        },
-        size,
-    );
-
-    // 4. Create a view & sampler
-    let texture_view = texture.create_view(&Default::default());
-    let sampler = sc.device.create_sampler(&wgpu::SamplerDescriptor {
-        address_mode_u: wgpu::AddressMode::Repeat,
-        address_mode_v: wgpu::AddressMode::Repeat,
-        mag_filter: wgpu::FilterMode::Linear,
-        min_filter: wgpu::FilterMode::Linear,
-        ..Default::default()
+    let group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+        layout: &layout,
+        entries: &[],
+        label: Some("Empty Bind Group"),
     });
 
-    (texture_view, sampler)
+    (layout, group)
 }
 
 pub fn create_depth_view(
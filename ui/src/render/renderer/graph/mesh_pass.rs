@@ -0,0 +1,536 @@
+use std::{
+    cell::RefCell,
+    hash::{DefaultHasher, Hash, Hasher},
+    rc::Rc,
+};
+
+use leptos::prelude::{GetUntracked, RwSignal, WithUntracked};
+
+use crate::components::demos::utils::InstanceCtx;
+use crate::render::renderer::{
+    camera_input::CameraInput,
+    gpu::{
+        GpuState,
+        gpu_state::{Projection, create_idx_buff_init, create_vert_buff_init},
+        diagnostics::{Diagnostic, ShaderDiagnostics, Stage},
+        params::ShaderParams,
+        pipeline_cache::{PipelineDesc, PipelineStatus},
+        preprocess::{ShaderFiles, expand_shipped},
+        setup::ShaderSetup,
+        surface_context::SurfaceContext,
+        textures::TextureSlots,
+        resource_context::Group,
+        vertex_ctx::VertexCtx,
+    },
+    instance::InstanceRaw,
+    mesh::{CpuMesh, MeshSlot},
+    vertex::Vertex,
+};
+
+use super::{ColorAttachment, Node, NodeDesc, NodeRef};
+
+/// Fills the (cleared) vertex list for this frame.
+pub type VertexFn = Box<dyn Fn(&mut Vec<Vertex>)>;
+pub type InstanceFn = Box<dyn Fn() -> Vec<InstanceRaw>>;
+
+/// Sources edited live in the shader editor; compile errors go to
+/// `diagnostics` while the last good pipeline keeps drawing. `params` fills
+/// group 3 for the uniforms they declare; `files` are the modules they can
+/// `#include`. `setup` can swap both for one module, and the entry points
+/// for others.
+#[derive(Copy, Clone)]
+pub struct LiveShaders {
+    pub vs: RwSignal<String>,
+    pub fs: RwSignal<String>,
+    pub diagnostics: ShaderDiagnostics,
+    pub params: ShaderParams,
+    pub files: ShaderFiles,
+    pub setup: ShaderSetup,
+}
+
+pub enum Shaders {
+    /// See [`LiveShaders`].
+    Live(LiveShaders),
+
+    /// Built-in sources (debug overlays etc.), which can include the shipped
+    /// modules as they ship; errors are only logged.
+    Static { vs: &'static str, fs: &'static str },
+}
+
+pub enum Geometry {
+    /// Uploaded once per device and drawn indexed.
+    Mesh(Rc<RefCell<CpuMesh>>),
+
+    /// Like `Mesh`, but whatever model is loaded into the slot draws in
+    /// its place, re-uploaded each time that changes.
+    Model {
+        mesh: Rc<RefCell<CpuMesh>>,
+        model: MeshSlot,
+    },
+
+    /// Rebuilt and re-uploaded every frame, drawn non-indexed.
+    Dynamic(VertexFn),
+}
+
+/// What goes in bind group 2 (0 = common, 1 = spatial and 3 = params are
+/// always bound).
+pub enum MaterialGroup {
+    None,
+
+    /// The shared texture/sampler/material group, its channels filled from
+    /// `slots`.
+    Texturing(TextureSlots),
+
+    /// Pass-specific group, built once per device.
+    Custom(Box<dyn Fn(&wgpu::Device) -> Group>),
+}
+
+pub struct MeshPassDesc {
+    pub label: &'static str,
+    pub color: ColorAttachment,
+    pub depth: Option<wgpu::LoadOp<f32>>,
+
+    pub shaders: Shaders,
+    /// (vertex, fragment); live shaders can have others picked, keyed by
+    /// `label`.
+    pub entry_points: (&'static str, &'static str),
+    pub topology: wgpu::PrimitiveTopology,
+    pub blend: Option<wgpu::BlendState>,
+    pub material: MaterialGroup,
+
+    pub geometry: Geometry,
+    /// Per-instance model matrices, rebuilt every frame. `None` draws once
+    /// without an instance buffer.
+    pub instances: Option<InstanceFn>,
+    pub projection: Rc<RefCell<Projection>>,
+
+    pub enabled: Option<RwSignal<bool>>,
+}
+
+/// A [`CpuMesh`] on the device.
+struct MeshBuffers {
+    vbuf: wgpu::Buffer,
+    ibuf: wgpu::Buffer,
+    index_count: u32,
+}
+
+impl MeshBuffers {
+    fn new(sc: &SurfaceContext, mesh: &CpuMesh) -> Self {
+        Self {
+            vbuf: create_vert_buff_init(sc, &mesh.vertices),
+            ibuf: create_idx_buff_init(sc, &mesh.indices),
+            index_count: mesh.index_count,
+        }
+    }
+}
+
+/// Generic graph node: one pipeline, one piece of geometry, optional
+/// instancing. Everything GPU-side is created lazily and rebuilt after a
+/// device loss.
+pub struct MeshPass {
+    desc: MeshPassDesc,
+
+    pipeline: Option<wgpu::RenderPipeline>,
+    pipeline_key: Option<u64>,
+    // failure key (exact sources) of the last failure, so it's reported once
+    // rather than every frame, and again wherever an edit moves it
+    failed_key: Option<u64>,
+    generation: u32,
+
+    mesh: Option<MeshBuffers>,
+    // the slot's version in `mesh`, for `Geometry::Model`
+    model_version: Option<u64>,
+    dynamic: Option<VertexCtx<Vertex>>,
+    inst: Option<InstanceCtx>,
+    material: Option<Group>,
+}
+
+impl MeshPass {
+    pub fn new(desc: MeshPassDesc) -> Self {
+        Self {
+            desc,
+            pipeline: None,
+            pipeline_key: None,
+            failed_key: None,
+            generation: 0,
+            mesh: None,
+            model_version: None,
+            dynamic: None,
+            inst: None,
+            material: None,
+        }
+    }
+
+    pub fn into_node(self) -> NodeRef {
+        Rc::new(RefCell::new(self))
+    }
+
+
+    /// Fetch the pipeline from the cache when anything that feeds it
+    /// (sources, state) changed since last frame. A pipeline that fails to
+    /// compile never replaces the running one.
+    fn sync_pipeline(&mut self, st: &mut GpuState) {
+        let GpuState {
+            surface_context: sc,
+            resource_context: rc,
+            pipeline_cache,
+            ..
+        } = st;
+
+        let expanded = match &self.desc.shaders {
+            Shaders::Live(LiveShaders { vs, fs, files, setup, .. }) => {
+                let (vs, fs) = if setup.single.get_untracked() {
+                    (setup.module, setup.module)
+                } else {
+                    (*vs, *fs)
+                };
+                let vs = vs.with_untracked(|vs| files.expand_untracked(vs, Stage::Vertex));
+                let fs = fs.with_untracked(|fs| files.expand_untracked(fs, Stage::Fragment));
+                vs.and_then(|vs| Ok((vs, fs?)))
+            }
+            Shaders::Static { vs, fs } => expand_shipped(vs, Stage::Vertex)
+                .and_then(|vs| Ok((vs, expand_shipped(fs, Stage::Fragment)?))),
+        };
+        let (vs, fs) = match expanded {
+            Ok(expanded) => expanded,
+            // keyed by the error, so it's reported once like a failed compile
+            Err(error) => {
+                let mut h = DefaultHasher::new();
+                error.to_string().hash(&mut h);
+                let key = h.finish();
+                if self.failed_key != Some(key) {
+                    self.failed_key = Some(key);
+                    self.report(vec![error]);
+                }
+                return;
+            }
+        };
+
+        let (vs_entry, fs_entry) = self.desc.entry_points;
+        let (vs_entry, fs_entry) = match &self.desc.shaders {
+            Shaders::Live(LiveShaders { setup, .. }) => (
+                setup.entry_untracked(self.desc.label, Stage::Vertex, vs_entry),
+                setup.entry_untracked(self.desc.label, Stage::Fragment, fs_entry),
+            ),
+            Shaders::Static { .. } => (vs_entry.to_owned(), fs_entry.to_owned()),
+        };
+
+        let (material, layout_key) = match &self.desc.material {
+            MaterialGroup::None => (&rc.empty_bind_group.layout, "common+spatial+empty+params"),
+            MaterialGroup::Texturing(_) => {
+                (&rc.texturing_bind_group.layout, "common+spatial+texturing+params")
+            }
+            MaterialGroup::Custom(_) => (&self.material.as_ref().unwrap().layout, self.desc.label),
+        };
+        let groups = [
+            &rc.common_bind_group.layout,
+            &rc.spatial_bind_group.layout,
+            material,
+            &rc.params_bind_group.layout,
+        ];
+
+        let mut buffers = vec![Vertex::desc()];
+        if self.desc.instances.is_some() {
+            buffers.push(InstanceRaw::desc());
+        }
+
+        let mut check = |vs_src: &str, fs_src: &str| {
+            let desc = PipelineDesc {
+                label: self.desc.label,
+                vs_src,
+                fs_src,
+                vs_entry: &vs_entry,
+                fs_entry: &fs_entry,
+                layout_key,
+                bind_group_layouts: &groups,
+                buffers: &buffers,
+                format: sc.config.format,
+                blend: self.desc.blend,
+                topology: self.desc.topology,
+                depth: self.desc.depth.map(|_| wgpu::DepthStencilState {
+                    format: wgpu::TextureFormat::Depth32Float,
+                    depth_write_enabled: true,
+                    depth_compare: wgpu::CompareFunction::Less, // passes if new depth < old
+                    stencil: Default::default(),
+                    bias: Default::default(),
+                }),
+            };
+
+            let key = desc.key();
+            if self.pipeline_key == Some(key) {
+                return (key, None, None);
+            }
+            let failure_key = desc.failure_key(key);
+            let seen = self.failed_key == Some(failure_key);
+            let status = (!seen).then(|| pipeline_cache.get_or_create(&sc.device, &desc));
+            (key, Some(failure_key), status)
+        };
+
+        let (key, failure_key, status) = check(&vs.code, &fs.code);
+
+        match status {
+            // edited back to what's running
+            None if self.pipeline_key == Some(key) && self.failed_key.take().is_some() => {
+                self.report(Vec::new())
+            }
+            None | Some(PipelineStatus::Pending) => {}
+            Some(PipelineStatus::Ready(pipe)) => {
+                self.pipeline = Some(pipe);
+                self.pipeline_key = Some(key);
+                self.failed_key = None;
+                self.report(Vec::new());
+            }
+            Some(PipelineStatus::Failed(errors)) => {
+                self.failed_key = failure_key;
+                // back to the files the errors are in
+                let errors = errors
+                    .into_iter()
+                    .map(|e| match e.stage {
+                        Stage::Vertex => vs.locate(e),
+                        Stage::Fragment => fs.locate(e),
+                        Stage::Pipeline => e,
+                    })
+                    .collect();
+                self.report(errors);
+            }
+        }
+    }
+
+    fn report(&self, errors: Vec<Diagnostic>) {
+        match &self.desc.shaders {
+            Shaders::Live(live) => live.diagnostics.report(self.desc.label, errors),
+            Shaders::Static { .. } => {
+                for e in errors {
+                    leptos::logging::error!("{}: {e}", self.desc.label);
+                }
+            }
+        }
+    }
+}
+
+impl Node for MeshPass {
+    fn desc(&self) -> NodeDesc {
+        NodeDesc {
+            label: self.desc.label,
+            color: self.desc.color,
+            depth: self.desc.depth,
+            inputs: Vec::new(),
+        }
+    }
+
+    fn enabled(&self) -> bool {
+        self.desc.enabled.is_none_or(|on| on.get_untracked())
+    }
+
+    fn prepare(&mut self, st: &mut GpuState, cam: &CameraInput) {
+        // new device → everything cached belongs to the old one
+        if self.generation != st.generation {
+            self.generation = st.generation;
+            self.pipeline = None;
+            self.pipeline_key = None;
+            self.failed_key = None;
+            self.mesh = None;
+            self.model_version = None;
+            self.dynamic = None;
+            self.inst = None;
+            self.material = None;
+        }
+
+        if let MaterialGroup::Custom(make) = &self.desc.material {
+            if self.material.is_none() {
+                self.material = Some(make(&st.surface_context.device));
+            }
+        }
+
+        self.sync_pipeline(st);
+        if let MaterialGroup::Texturing(slots) = &self.desc.material {
+            st.sync_textures(slots);
+        }
+
+        let sc = &st.surface_context;
+
+        match &self.desc.geometry {
+            Geometry::Mesh(mesh) => {
+                if self.mesh.is_none() {
+                    self.mesh = Some(MeshBuffers::new(sc, &mesh.borrow()));
+                }
+            }
+            Geometry::Model { mesh, model } => {
+                if let Some((version, loaded)) = model.pending(self.model_version) {
+                    self.mesh = Some(match loaded {
+                        Some(loaded) => MeshBuffers::new(sc, &loaded),
+                        None => MeshBuffers::new(sc, &mesh.borrow()),
+                    });
+                    self.model_version = Some(version);
+                }
+            }
+            Geometry::Dynamic(rebuild) => {
+                self.dynamic
+                    .get_or_insert_with(|| VertexCtx::new(sc, 1024))
+                    .sync(sc, rebuild);
+            }
+        }
+
+        if let Some(instances) = &self.desc.instances {
+            self.inst
+                .get_or_insert_with(|| InstanceCtx::new(sc, 256))
+                .sync_instances(sc, instances);
+        }
+
+        st.populate_common_buffers(&self.desc.projection.borrow(), cam);
+        if let Shaders::Live(live) = &self.desc.shaders {
+            st.populate_params(&live.params.bytes());
+        }
+    }
+
+    fn record(&self, st: &GpuState, _inputs: &[wgpu::TextureView], rp: &mut wgpu::RenderPass<'_>) {
+        // nothing has compiled yet
+        let Some(pipeline) = &self.pipeline else {
+            return;
+        };
+        let rc = &st.resource_context;
+
+        rp.set_pipeline(pipeline);
+
+        rp.set_bind_group(0, &rc.common_bind_group.group, &[]);
+        rp.set_bind_group(1, &rc.spatial_bind_group.group, &[]);
+        let material = match &self.desc.material {
+            MaterialGroup::None => &rc.empty_bind_group.group,
+            MaterialGroup::Texturing(_) => &rc.texturing_bind_group.group,
+            MaterialGroup::Custom(_) => &self.material.as_ref().unwrap().group,
+        };
+        rp.set_bind_group(2, material, &[]);
+        rp.set_bind_group(3, &rc.params_bind_group.group, &[]);
+
+        let instances = match &self.inst {
+            Some(inst) => {
+                rp.set_vertex_buffer(1, inst.buff.slice(..));
+                0..inst.count
+            }
+            None => 0..1,
+        };
+
+        match &self.desc.geometry {
+            Geometry::Mesh(_) | Geometry::Model { .. } => {
+                let mesh = self.mesh.as_ref().unwrap();
+                rp.set_vertex_buffer(0, mesh.vbuf.slice(..));
+                rp.set_index_buffer(mesh.ibuf.slice(..), wgpu::IndexFormat::Uint32);
+                rp.draw_indexed(0..mesh.index_count, 0, instances);
+            }
+            Geometry::Dynamic(_) => {
+                let vc = self.dynamic.as_ref().unwrap();
+                rp.set_vertex_buffer(0, vc.buf.slice(..));
+                rp.draw(0..vc.count, instances);
+            }
+        }
+    }
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use leptos::prelude::{Owner, Set};
+
+    use super::*;
+    use crate::render::renderer::{
+        gpu::{gpu_state::Projection, params::ShaderParams},
+        graph::Target,
+    };
+    use crate::render::web_gpu::test_state;
+
+    const VS: &str = "@vertex fn vs_main(@location(0) p: vec3<f32>) -> @builtin(position) vec4<f32> {
+    return vec4<f32>(p, 1.0);
+}";
+    const FS: &str = "@fragment fn fs_main() -> @location(0) vec4<f32> {
+    return vec4<f32>(1.0);
+}";
+
+    fn live() -> LiveShaders {
+        LiveShaders {
+            vs: RwSignal::new(VS.to_owned()),
+            fs: RwSignal::new(FS.to_owned()),
+            diagnostics: ShaderDiagnostics::new(),
+            params: ShaderParams::new(),
+            files: ShaderFiles::new(),
+            setup: ShaderSetup::new(String::new()),
+        }
+    }
+
+    fn pass(shaders: LiveShaders) -> MeshPass {
+        MeshPass::new(MeshPassDesc {
+            label: "test",
+            color: ColorAttachment { target: Target::Surface, load: wgpu::LoadOp::Load },
+            depth: None,
+            shaders: Shaders::Live(shaders),
+            entry_points: ("vs_main", "fs_main"),
+            topology: wgpu::PrimitiveTopology::TriangleList,
+            blend: None,
+            material: MaterialGroup::None,
+            geometry: Geometry::Dynamic(Box::new(|_| {})),
+            instances: None,
+            projection: Rc::new(RefCell::new(Projection::Fulcrum)),
+            enabled: None,
+        })
+    }
+
+    #[test]
+    fn edits_rebuild_the_pipeline_only_when_the_tokens_change() {
+        let Some(mut st) = test_state() else { return };
+        let owner = Owner::new();
+        owner.set();
+
+        let shaders = live();
+        let mut pass = pass(shaders);
+        pass.sync_pipeline(&mut st);
+        let first = pass.pipeline.clone().expect("the shaders compile");
+        let key = pass.pipeline_key;
+        assert!(shaders.diagnostics.errors_untracked().is_empty());
+
+        shaders.fs.set(FS.replace("    return", "\n  // white\n  return"));
+        pass.sync_pipeline(&mut st);
+        assert_eq!(pass.pipeline_key, key);
+        assert_eq!(pass.pipeline.as_ref(), Some(&first));
+
+        shaders.fs.set(FS.replace("1.0", "0.5"));
+        pass.sync_pipeline(&mut st);
+        assert_ne!(pass.pipeline_key, key);
+        assert_ne!(pass.pipeline.as_ref(), Some(&first));
+    }
+
+    #[test]
+    fn failures_are_reported_once_and_again_where_an_edit_moves_them() {
+        let Some(mut st) = test_state() else { return };
+        let owner = Owner::new();
+        owner.set();
+
+        let shaders = live();
+        let mut pass = pass(shaders);
+        pass.sync_pipeline(&mut st);
+        let running = pass.pipeline.clone().expect("the shaders compile");
+        let key = pass.pipeline_key;
+
+        let line = |d: &ShaderDiagnostics| d.errors_untracked()[0].span.map(|s| s.line);
+        let broken = FS.replace("1.0", "oops");
+        shaders.fs.set(broken.clone());
+        pass.sync_pipeline(&mut st);
+        assert_eq!(line(&shaders.diagnostics), Some(2));
+        assert_eq!(pass.pipeline.as_ref(), Some(&running), "the last good pipeline keeps drawing");
+        assert_eq!(pass.pipeline_key, key);
+
+        // the same failure isn't reported again every frame...
+        shaders.diagnostics.clear();
+        pass.sync_pipeline(&mut st);
+        assert!(shaders.diagnostics.errors_untracked().is_empty());
+
+        // ...but is once an edit moves it, even if the tokens are the same
+        shaders.fs.set(format!("\n{broken}"));
+        pass.sync_pipeline(&mut st);
+        assert_eq!(line(&shaders.diagnostics), Some(3));
+
+        // editing back to what's running clears it without a rebuild
+        shaders.fs.set(FS.to_owned());
+        pass.sync_pipeline(&mut st);
+        assert!(shaders.diagnostics.errors_untracked().is_empty());
+        assert_eq!(pass.failed_key, None);
+        assert_eq!(pass.pipeline.as_ref(), Some(&running));
+    }
+}
@@ -0,0 +1,461 @@
+//! Declarative render graph.
+//!
+//! Nodes say *what* they draw into (colour target, depth, load/clear) and
+//! which offscreen targets they sample; the graph works out the order, owns
+//! the offscreen textures, opens each `wgpu::RenderPass` and hands it to the
+//! node to record its draws.
+
+pub mod mesh_pass;
+
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
+
+use anyhow::{Result, anyhow, bail};
+
+use super::camera_input::CameraInput;
+use super::gpu::GpuState;
+use super::gpu::gpu_state::FrameCtx;
+use super::gpu::utils::create_depth_view;
+
+pub use mesh_pass::{Geometry, LiveShaders, MaterialGroup, MeshPass, MeshPassDesc, Shaders};
+
+/// What a node renders into.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Target {
+    /// The frame's colour view (swap-chain or headless texture).
+    Surface,
+
+    /// An offscreen texture registered with [`RenderGraph::add_target`].
+    Texture(&'static str),
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct ColorAttachment {
+    pub target: Target,
+
+    /// `Load` on a target nobody wrote yet this frame is turned into a clear
+    /// (transparent), so nodes never see last frame's contents by accident.
+    pub load: wgpu::LoadOp<wgpu::Color>,
+}
+
+/// Static description of a node; read once when the graph is (re)ordered.
+#[derive(Clone, Debug)]
+pub struct NodeDesc {
+    pub label: &'static str,
+    pub color: ColorAttachment,
+
+    /// Depth buffer of the colour target, if the node uses one. Same
+    /// first-writer rule as colour: `Load` becomes a clear to 1.0.
+    pub depth: Option<wgpu::LoadOp<f32>>,
+
+    /// Offscreen targets the node samples, in the order they are handed to
+    /// [`Node::record`]. Their writers are scheduled first.
+    pub inputs: Vec<&'static str>,
+}
+
+pub trait Node {
+    fn desc(&self) -> NodeDesc;
+
+    /// Checked every frame; disabled nodes are skipped (and don't count as
+    /// writers for the clear rule).
+    fn enabled(&self) -> bool {
+        true
+    }
+
+    /// CPU → GPU work before the pass opens: lazy pipelines, uploads, UBOs.
+    fn prepare(&mut self, st: &mut GpuState, cam: &CameraInput);
+
+    /// Record draws into the pass the graph opened from `desc`.
+    fn record(&self, st: &GpuState, inputs: &[wgpu::TextureView], rp: &mut wgpu::RenderPass<'_>);
+}
+
+pub type NodeRef = Rc<RefCell<dyn Node>>;
+
+/// Offscreen colour target, sized relative to the surface.
+#[derive(Copy, Clone, Debug)]
+pub struct TargetDesc {
+    /// `None` = same format as the surface.
+    pub format: Option<wgpu::TextureFormat>,
+
+    /// 1.0 = surface resolution, 0.5 = half, ...
+    pub scale: f32,
+}
+
+impl Default for TargetDesc {
+    fn default() -> Self {
+        Self {
+            format: None,
+            scale: 1.0,
+        }
+    }
+}
+
+struct Offscreen {
+    desc: TargetDesc,
+    // (size, generation) the textures below were made for
+    made_for: Option<((u32, u32), u32)>,
+    color: Option<wgpu::TextureView>,
+    depth: Option<wgpu::TextureView>,
+}
+
+impl Offscreen {
+    fn ensure(&mut self, st: &GpuState) {
+        let config = &st.surface_context.config;
+        let size = (
+            ((config.width as f32 * self.desc.scale) as u32).max(1),
+            ((config.height as f32 * self.desc.scale) as u32).max(1),
+        );
+
+        if self.made_for == Some((size, st.generation)) {
+            return;
+        }
+
+        let device = &st.surface_context.device;
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("graph target"),
+            size: wgpu::Extent3d {
+                width: size.0,
+                height: size.1,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: self.desc.format.unwrap_or(config.format),
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+
+        let depth_config = wgpu::SurfaceConfiguration {
+            width: size.0,
+            height: size.1,
+            ..config.clone()
+        };
+
+        self.color = Some(texture.create_view(&Default::default()));
+        self.depth = Some(create_depth_view(device, &depth_config));
+        self.made_for = Some((size, st.generation));
+    }
+}
+
+/// Targets drawn to so far this frame, for the first-writer rule: a `Load`
+/// of one nobody wrote yet is turned into a clear.
+#[derive(Default)]
+struct Written {
+    color: Vec<Target>,
+    depth: Vec<Target>,
+}
+
+impl Written {
+    /// The load ops `desc` runs with, now that it's writing.
+    fn loads(&mut self, desc: &NodeDesc) -> (wgpu::LoadOp<wgpu::Color>, Option<wgpu::LoadOp<f32>>) {
+        let target = desc.color.target;
+        let color = match desc.color.load {
+            wgpu::LoadOp::Load if !self.color.contains(&target) => {
+                wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT)
+            }
+            op => op,
+        };
+        self.color.push(target);
+
+        let depth = desc.depth.map(|op| {
+            let load = match op {
+                wgpu::LoadOp::Load if !self.depth.contains(&target) => wgpu::LoadOp::Clear(1.0),
+                op => op,
+            };
+            self.depth.push(target);
+            load
+        });
+
+        (color, depth)
+    }
+}
+
+#[derive(Default)]
+pub struct RenderGraph {
+    nodes: Vec<NodeRef>,
+    targets: HashMap<&'static str, Offscreen>,
+
+    // node indices in execution order, `None` after the graph changed
+    order: Option<Vec<usize>>,
+}
+
+impl RenderGraph {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Graph drawing straight to the surface, in the given order.
+    pub fn with_nodes(nodes: impl IntoIterator<Item = NodeRef>) -> Self {
+        let mut graph = Self::new();
+        for node in nodes {
+            graph.add_node(node);
+        }
+        graph
+    }
+
+    pub fn add_node(&mut self, node: NodeRef) -> &mut Self {
+        self.nodes.push(node);
+        self.order = None;
+        self
+    }
+
+    pub fn add_target(&mut self, name: &'static str, desc: TargetDesc) -> &mut Self {
+        self.targets.insert(
+            name,
+            Offscreen {
+                desc,
+                made_for: None,
+                color: None,
+                depth: None,
+            },
+        );
+        self.order = None;
+        self
+    }
+
+    /// Writers of a texture run before its readers; otherwise nodes keep the
+    /// order they were added in (so later nodes draw on top).
+    fn sort(&self) -> Result<Vec<usize>> {
+        let descs: Vec<NodeDesc> = self.nodes.iter().map(|n| n.borrow().desc()).collect();
+
+        for d in &descs {
+            let names = d.inputs.iter().copied().chain(match d.color.target {
+                Target::Texture(name) => Some(name),
+                Target::Surface => None,
+            });
+            for name in names {
+                if !self.targets.contains_key(name) {
+                    bail!("node '{}' uses unknown target '{name}'", d.label);
+                }
+            }
+        }
+
+        // edge i -> j when i writes a texture j reads
+        let mut indegree = vec![0usize; descs.len()];
+        let mut edges = vec![Vec::new(); descs.len()];
+        for (i, writer) in descs.iter().enumerate() {
+            let Target::Texture(out) = writer.color.target else {
+                continue;
+            };
+            for (j, reader) in descs.iter().enumerate() {
+                if i != j && reader.inputs.contains(&out) {
+                    edges[i].push(j);
+                    indegree[j] += 1;
+                }
+            }
+        }
+
+        // Kahn's, always taking the lowest ready index to stay stable
+        let mut order = Vec::with_capacity(descs.len());
+        let mut done = vec![false; descs.len()];
+        while order.len() < descs.len() {
+            let Some(next) = (0..descs.len()).find(|&i| !done[i] && indegree[i] == 0) else {
+                let stuck: Vec<_> = (0..descs.len())
+                    .filter(|&i| !done[i])
+                    .map(|i| descs[i].label)
+                    .collect();
+                bail!("render graph has a cycle between {stuck:?}");
+            };
+
+            done[next] = true;
+            order.push(next);
+            for &j in &edges[next] {
+                indegree[j] -= 1;
+            }
+        }
+
+        Ok(order)
+    }
+
+    /// Run every enabled node, in dependency order, into `ctx`.
+    pub fn execute(&mut self, st: &mut GpuState, cam: &CameraInput, ctx: &mut FrameCtx) -> Result<()> {
+        if self.order.is_none() {
+            self.order = Some(self.sort()?);
+        }
+        let order = self.order.clone().unwrap_or_default();
+
+        let mut written = Written::default();
+
+        for i in order {
+            let node = self.nodes[i].clone();
+            let mut node = node.borrow_mut();
+            if !node.enabled() {
+                continue;
+            }
+
+            let desc = node.desc();
+            node.prepare(st, cam);
+
+            let (color_view, depth_view) = match desc.color.target {
+                Target::Surface => (ctx.color_view.clone(), ctx.depth_view.clone()),
+                Target::Texture(name) => {
+                    let t = self
+                        .targets
+                        .get_mut(name)
+                        .ok_or_else(|| anyhow!("unknown target '{name}'"))?;
+                    t.ensure(st);
+                    (t.color.clone().unwrap(), t.depth.clone().unwrap())
+                }
+            };
+
+            let inputs = desc
+                .inputs
+                .iter()
+                .map(|name| {
+                    let t = self
+                        .targets
+                        .get_mut(name)
+                        .ok_or_else(|| anyhow!("unknown target '{name}'"))?;
+                    t.ensure(st);
+                    Ok(t.color.clone().unwrap())
+                })
+                .collect::<Result<Vec<_>>>()?;
+
+            let (load, depth_load) = written.loads(&desc);
+            let depth_ops = depth_load.map(|load| wgpu::Operations {
+                load,
+                store: wgpu::StoreOp::Store,
+            });
+
+            let mut rp = ctx.encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some(desc.label),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &color_view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load,
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: depth_ops.map(|ops| {
+                    wgpu::RenderPassDepthStencilAttachment {
+                        view: &depth_view,
+                        depth_ops: Some(ops),
+                        stencil_ops: None,
+                    }
+                }),
+                ..Default::default()
+            });
+
+            node.record(st, &inputs, &mut rp);
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Fake(NodeDesc);
+
+    impl Node for Fake {
+        fn desc(&self) -> NodeDesc {
+            self.0.clone()
+        }
+
+        fn prepare(&mut self, _: &mut GpuState, _: &CameraInput) {}
+
+        fn record(&self, _: &GpuState, _: &[wgpu::TextureView], _: &mut wgpu::RenderPass<'_>) {}
+    }
+
+    fn node(label: &'static str, target: Target, inputs: &[&'static str]) -> NodeDesc {
+        NodeDesc {
+            label,
+            color: ColorAttachment { target, load: wgpu::LoadOp::Load },
+            depth: None,
+            inputs: inputs.to_vec(),
+        }
+    }
+
+    fn graph(nodes: Vec<NodeDesc>) -> RenderGraph {
+        let mut graph = RenderGraph::with_nodes(
+            nodes.into_iter().map(|d| Rc::new(RefCell::new(Fake(d))) as NodeRef),
+        );
+        for name in ["a", "b", "c"] {
+            graph.add_target(name, TargetDesc::default());
+        }
+        graph
+    }
+
+    fn labels(graph: &RenderGraph) -> Vec<&'static str> {
+        let order = graph.sort().unwrap();
+        order.into_iter().map(|i| graph.nodes[i].borrow().desc().label).collect()
+    }
+
+    #[test]
+    fn writers_run_before_their_readers() {
+        let g = graph(vec![
+            node("composite", Target::Surface, &["b", "c"]),
+            node("blur", Target::Texture("b"), &["a"]),
+            node("scene", Target::Texture("a"), &[]),
+            node("mask", Target::Texture("c"), &["a"]),
+        ]);
+        assert_eq!(labels(&g), ["scene", "blur", "mask", "composite"]);
+    }
+
+    /// Nodes nothing orders keep the order they were added in, so later
+    /// ones still draw on top.
+    #[test]
+    fn independent_nodes_keep_their_order() {
+        let g = graph(vec![
+            node("sky", Target::Surface, &[]),
+            node("terrain", Target::Surface, &[]),
+            node("overlay", Target::Surface, &["a"]),
+            node("hud", Target::Surface, &[]),
+            node("minimap", Target::Texture("a"), &[]),
+        ]);
+        assert_eq!(labels(&g), ["sky", "terrain", "hud", "minimap", "overlay"]);
+
+        // reading the target it draws into doesn't order a node after itself
+        let g = graph(vec![node("feedback", Target::Texture("a"), &["a"])]);
+        assert_eq!(labels(&g), ["feedback"]);
+    }
+
+    #[test]
+    fn cycles_and_unknown_targets_are_errors() {
+        let g = graph(vec![
+            node("first", Target::Surface, &[]),
+            node("ping", Target::Texture("a"), &["b"]),
+            node("pong", Target::Texture("b"), &["a"]),
+        ]);
+        let err = g.sort().unwrap_err().to_string();
+        assert_eq!(err, r#"render graph has a cycle between ["ping", "pong"]"#);
+
+        let g = graph(vec![node("lost", Target::Surface, &["nowhere"])]);
+        let err = g.sort().unwrap_err().to_string();
+        assert_eq!(err, "node 'lost' uses unknown target 'nowhere'");
+        let g = graph(vec![node("lost", Target::Texture("nowhere"), &[])]);
+        assert!(g.sort().is_err());
+    }
+
+    #[test]
+    fn the_first_writer_of_a_target_clears_it() {
+        let clear = wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT);
+        let red = wgpu::LoadOp::Clear(wgpu::Color::RED);
+        let with_depth = |target, load, depth| NodeDesc {
+            color: ColorAttachment { target, load },
+            depth,
+            ..node("n", target, &[])
+        };
+
+        let mut written = Written::default();
+        let surface = |load, depth| with_depth(Target::Surface, load, depth);
+        let a = |load, depth| with_depth(Target::Texture("a"), load, depth);
+
+        // colour and depth are counted apart
+        let first = written.loads(&surface(wgpu::LoadOp::Load, None));
+        assert_eq!(first, (clear, None));
+        let second = written.loads(&surface(wgpu::LoadOp::Load, Some(wgpu::LoadOp::Load)));
+        assert_eq!(second, (wgpu::LoadOp::Load, Some(wgpu::LoadOp::Clear(1.0))));
+        let third = written.loads(&surface(wgpu::LoadOp::Load, Some(wgpu::LoadOp::Load)));
+        assert_eq!(third, (wgpu::LoadOp::Load, Some(wgpu::LoadOp::Load)));
+
+        // per target, and explicit clears are left alone
+        assert_eq!(written.loads(&a(red, Some(wgpu::LoadOp::Clear(0.5)))).0, red);
+        assert_eq!(written.loads(&a(wgpu::LoadOp::Load, None)).0, wgpu::LoadOp::Load);
+        let b = with_depth(Target::Texture("b"), wgpu::LoadOp::Load, None);
+        assert_eq!(written.loads(&b).0, clear);
+    }
+}
@@ -1,15 +1,19 @@
+use std::sync::Arc;
+
+use leptos::prelude::{RwSignal, Update, WithUntracked};
+
 use super::vertex::Vertex;
 
 pub struct CpuMesh {
     pub vertices: Vec<Vertex>,
-    pub indices: Vec<u16>,
+    pub indices: Vec<u32>,
     pub index_count: u32,
 }
 
 impl CpuMesh {
     pub fn new(
         vertices: Vec<Vertex>,
-        indices:  Vec<u16>,
+        indices:  Vec<u32>,
     ) -> Self {
 
         let c = indices.len() as u32;
@@ -19,4 +23,46 @@ impl CpuMesh {
             index_count: c,
         }
     }
+
+    pub fn triangle_count(&self) -> usize {
+        self.indices.len() / 3
+    }
+}
+
+/// A mesh drawn in place of a pass's own (see
+/// [`Geometry::Model`](super::graph::mesh_pass::Geometry::Model)); `None`
+/// puts the pass's own back.
+#[derive(Copy, Clone)]
+pub struct MeshSlot {
+    // bumped on every swap; the pass re-uploads on a bump
+    mesh: RwSignal<(u64, Option<Arc<CpuMesh>>)>,
+}
+
+impl Default for MeshSlot {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MeshSlot {
+    pub fn new() -> Self {
+        Self {
+            mesh: RwSignal::new((0, None)),
+        }
+    }
+
+    pub fn set(&self, mesh: Option<Arc<CpuMesh>>) {
+        self.mesh.update(|(version, m)| {
+            *version += 1;
+            *m = mesh;
+        });
+    }
+
+    /// The mesh and its version when it isn't the `uploaded` one, for the
+    /// render loop; `None` inside means the pass's own.
+    pub(crate) fn pending(&self, uploaded: Option<u64>) -> Option<(u64, Option<Arc<CpuMesh>>)> {
+        self.mesh.with_untracked(|(version, mesh)| {
+            (uploaded != Some(*version)).then(|| (*version, mesh.clone()))
+        })
+    }
 }
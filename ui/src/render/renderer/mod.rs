@@ -1,6 +1,8 @@
 pub mod camera;
 pub mod camera_input;
+pub mod clock;
 pub mod instance;
 pub mod vertex;
 pub mod gpu;
+pub mod graph;
 pub mod mesh;
@@ -1,23 +1,6 @@
-struct Light { dir : vec3<f32>, color : vec3<f32> };
-
-struct TimeUBO {
-    millis     : u32,   // 0-999
-    secs       : u32,   // whole seconds
-    dt_millis  : u32,   // last-frame Δ in ms
-    frame_id   : u32,   // ++ every render()
-};
-@group(0) @binding(0)
-var<uniform> g_time : TimeUBO;
-
-// helper if you want float seconds
-fn time_sec() -> f32 {
-    return f32(g_time.secs) + f32(g_time.millis) * 0.001;
-}
-
-@group(1) @binding(2) var<uniform> light  : Light;
-
-@group(2) @binding(0) var texture_data    : texture_2d<f32>;
-@group(2) @binding(1) var texture_sampler : sampler;
+#include "light.wgsl"
+#include "time.wgsl"
+#include "texturing.wgsl"
 
 struct FSIn {
     @location(0) frag_pos : vec3<f32>,
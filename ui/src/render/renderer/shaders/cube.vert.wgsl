@@ -1,9 +1,4 @@
-struct Camera {
-    view_proj: mat4x4<f32>,
-};
-
-@group(1) @binding(0)
-var<uniform> camera: Camera;
+#include "camera.wgsl"
 
 struct VSIn {
     @location(0) position: vec3<f32>,  // <- from Vertex::ATTRIBS[0]
@@ -15,9 +15,7 @@ struct VSIn {
     @location(6) m3: vec4<f32>,
 };
 
-struct Camera { view_proj : mat4x4<f32>, };
-@group(1) @binding(0)
-var<uniform> camera : Camera;
+#include "camera.wgsl"
 
 @vertex
 fn vs_main(v: VSIn) -> VSOut {
@@ -1,29 +1,16 @@
-struct VSOut {
+struct FSIn {
     @builtin(position) Position: vec4<f32>,
     @location(0)      uv: vec2<f32>,
 };
 
-struct TimeUBO {
-    millis: u32,   // 0-999
-    secs: u32,   // whole seconds
-    dt_millis: u32,   // last-frame Δ in ms
-    frame_id: u32,   // ++ every render()
-};
-
-@group(0) @binding(0)
-var<uniform> g_time : TimeUBO;
-
-// helper if you want float seconds
-fn time_sec() -> f32 {
-    return f32(g_time.secs) + f32(g_time.millis) * 0.001;
-}
+#include "time.wgsl"
 
 fn triangle_wave(x: f32) -> f32 {
     return abs(x * 2 - 1);
 }
 
 @fragment
-fn joints_fs(i: VSOut) -> @location(0) vec4<f32> {
+fn joints_fs(i: FSIn) -> @location(0) vec4<f32> {
     let p = i.uv * 2.0 - 1.0;
     let d = length(p);
 
@@ -34,7 +21,7 @@ fn joints_fs(i: VSOut) -> @location(0) vec4<f32> {
 }
 
 @fragment
-fn bones_fs(i: VSOut) -> @location(0) vec4<f32> {
+fn bones_fs(i: FSIn) -> @location(0) vec4<f32> {
     let t = time_sec();
     let speed = 0.2;
     let phase = triangle_wave(fract(i.uv.x - t * speed));
@@ -20,9 +20,7 @@ struct BoneIn {
     @location(2) uv: vec2<f32>,
 };
 
-struct Camera { view_proj: mat4x4<f32>, };
-@group(1) @binding(0)
-var<uniform> camera: Camera;
+#include "camera.wgsl"
 
 @vertex
 fn joints_vs(v: JointIn) -> VSOut {
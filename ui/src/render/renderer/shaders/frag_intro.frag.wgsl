@@ -1,24 +1,5 @@
-struct TimeUBO {
-    millis     : u32,   // 0-999
-    secs       : u32,   // whole seconds
-    dt_millis  : u32,   // last-frame Δ in ms
-    frame_id   : u32,   // ++ every render()
-};
-
-@group(0) @binding(0)
-var<uniform> g_time : TimeUBO;
-
-struct Screen {
-    resolution : vec2<f32>, // (width, height)
-    _pad       : vec2<f32>, // alignment padding
-};
-@group(0) @binding(1)
-var<uniform> screen : Screen;
-
-// helper if you want float seconds
-fn time_sec() -> f32 {
-    return f32(g_time.secs) + f32(g_time.millis) * 0.001;
-}
+#include "time.wgsl"
+#include "screen.wgsl"
 
 struct FSIn {
     @location(0) frag_pos : vec3<f32>,
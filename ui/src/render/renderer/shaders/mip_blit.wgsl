@@ -0,0 +1,25 @@
+// One mip level from the one above: a fullscreen triangle sampling the
+// larger level with a linear filter, so each texel averages the 2x2 under it.
+// Through sRGB views the average is taken in linear space.
+struct VSOut {
+    @builtin(position) pos : vec4<f32>,
+    @location(0)       uv  : vec2<f32>,
+};
+
+@vertex
+fn vs_main(@builtin(vertex_index) i : u32) -> VSOut {
+    let corner = vec2<f32>(f32((i << 1u) & 2u), f32(i & 2u));
+    var out : VSOut;
+    out.pos = vec4<f32>(corner * 2.0 - 1.0, 0.0, 1.0);
+    // clip space is y-up, texture rows go down
+    out.uv  = vec2<f32>(corner.x, 1.0 - corner.y);
+    return out;
+}
+
+@group(0) @binding(0) var src         : texture_2d<f32>;
+@group(0) @binding(1) var src_sampler : sampler;
+
+@fragment
+fn fs_main(in : VSOut) -> @location(0) vec4<f32> {
+    return textureSample(src, src_sampler, in.uv);
+}
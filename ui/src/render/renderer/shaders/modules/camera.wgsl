@@ -0,0 +1,6 @@
+// Spatial group, vertex stage only.
+struct Camera {
+    view_proj: mat4x4<f32>,
+};
+@group(1) @binding(0)
+var<uniform> camera: Camera;
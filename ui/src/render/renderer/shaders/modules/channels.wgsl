@@ -0,0 +1,11 @@
+// Texture channels, texturing group, filled from the UI tab. iChannel0 is
+// texture_data under its ShaderToy name, so include this or texturing.wgsl,
+// not both. Fragment stage only.
+@group(2) @binding(0) var iChannel0         : texture_2d<f32>;
+@group(2) @binding(1) var iChannel0_sampler : sampler;
+@group(2) @binding(3) var iChannel1         : texture_2d<f32>;
+@group(2) @binding(4) var iChannel1_sampler : sampler;
+@group(2) @binding(5) var iChannel2         : texture_2d<f32>;
+@group(2) @binding(6) var iChannel2_sampler : sampler;
+@group(2) @binding(7) var iChannel3         : texture_2d<f32>;
+@group(2) @binding(8) var iChannel3_sampler : sampler;
@@ -0,0 +1,4 @@
+// Spatial group, fragment stage only.
+struct Light { dir : vec3<f32>, color : vec3<f32> };
+@group(1) @binding(2)
+var<uniform> light : Light;
@@ -0,0 +1,7 @@
+// Canvas size in pixels, common group. Fragment stage only.
+struct Screen {
+    resolution : vec2<f32>, // (width, height)
+    _pad       : vec2<f32>, // alignment padding
+};
+@group(0) @binding(1)
+var<uniform> screen : Screen;
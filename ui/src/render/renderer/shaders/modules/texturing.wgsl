@@ -0,0 +1,3 @@
+// Texturing group: only in demos with a material. Fragment stage only.
+@group(2) @binding(0) var texture_data    : texture_2d<f32>;
+@group(2) @binding(1) var texture_sampler : sampler;
@@ -0,0 +1,14 @@
+// Frame clock, common group. Fragment stage only.
+struct TimeUBO {
+    millis     : u32,   // 0-999, past `secs`
+    secs       : u32,   // whole seconds since start
+    dt_millis  : u32,   // last-frame Δ in ms
+    frame_id   : u32,   // ++ every render()
+    elapsed    : f32,   // float seconds since start
+};
+@group(0) @binding(0)
+var<uniform> g_time : TimeUBO;
+
+fn time_sec() -> f32 {
+    return g_time.elapsed;
+}
@@ -1,149 +1,70 @@
-use std::cell::RefCell;
-use std::rc::Rc;
-
-use anyhow::anyhow;
 use anyhow::Result;
 
 use web_sys::HtmlCanvasElement;
 use crate::render::renderer::gpu::utils::*;
-use crate::render::renderer::instance::InstanceRaw;
-use crate::render::renderer::vertex::Vertex;
-use crate::render::renderer::vertex;
-use web_sys;
 
-use super::renderer::gpu::gpu_state::GpuState;
+use super::renderer::clock::RealClock;
+use super::renderer::gpu::gpu_state::{GpuState, TimeUBO};
+use super::renderer::gpu::pipeline_cache::PipelineCache;
 use super::renderer::gpu::resource_context::ResourceContext;
 use super::renderer::gpu::surface_context::SurfaceContext;
 
-pub async fn reload_pipeline(
-    state_rc: &Rc<RefCell<Option<GpuState>>>,
-    vs_src: &str,
-    fs_src: &str,
-) -> anyhow::Result<wgpu::RenderPipeline> {
-    use wgpu::ErrorFilter as F;
-
-    let (layout, config, device) = {
-        let guard = state_rc.borrow();
-        let st = guard.as_ref()
-            .ok_or_else(|| anyhow!("GpuState is None"))?;
-        (
-            st.resource_context.pipeline_layout(&st.surface_context.device),
-            st.surface_context.config.clone(),
-            st.surface_context.device.clone(),
-        )
-    };
-
-    device.push_error_scope(F::Validation);
+pub async fn init_wgpu(canvas: &HtmlCanvasElement, ) -> Result<GpuState> {
+    let sc = SurfaceContext::new_async(canvas).await?;
+    Ok(init_state(sc).await)
+}
 
-    let vs = device.create_shader_module(wgpu::ShaderModuleDescriptor {
-        label: Some("live-reload VS"),
-        source: wgpu::ShaderSource::Wgsl(vs_src.into()),
-    });
-    let fs = device.create_shader_module(wgpu::ShaderModuleDescriptor {
-        label: Some("live-reload FS"),
-        source: wgpu::ShaderSource::Wgsl(fs_src.into()),
-    });
+/// Rebuild `old` from scratch on `canvas` after its device was lost.
+///
+/// The clock and frame counter carry over so time doesn't jump; `generation`
+/// is bumped so passes drop pipelines/buffers made on the dead device.
+pub async fn reinit_wgpu(canvas: &HtmlCanvasElement, old: GpuState) -> Result<GpuState> {
+    let mut state = init_wgpu(canvas).await?;
 
-    let promise = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-        label: Some("live-reload pipeline"),
-        layout: Some(&layout),
-        cache: None,
-        vertex: wgpu::VertexState {
-            module: &vs,
-            entry_point: Some("vs_main"),
-            buffers: &[Vertex::desc()],
-            compilation_options: Default::default(),
-        },
-        fragment: Some(wgpu::FragmentState {
-            module: &fs,
-            entry_point: Some("fs_main"),
-            targets: &[Some(wgpu::ColorTargetState {
-                format: config.format,
-                blend: None,
-                write_mask: wgpu::ColorWrites::ALL,
-            })],
-            compilation_options: Default::default(),
-        }),
-        primitive: Default::default(),
-        depth_stencil: Some(wgpu::DepthStencilState{
-            format: wgpu::TextureFormat::Depth32Float,
-            depth_write_enabled: false,   // or true – doesn’t matter for flat-quad
-            depth_compare: wgpu::CompareFunction::Always,
-            stencil: Default::default(),
-            bias: Default::default(),
-        }),
+    state.clock = old.clock;
+    state.frame_counter = old.frame_counter;
+    state.generation = old.generation.wrapping_add(1);
 
-        multisample: Default::default(),
-        multiview: None,
-    });
+    Ok(state)
+}
 
-    let new_pipe = promise;
-    match device.pop_error_scope().await {
-        None => Ok(new_pipe),
-        Some(err) => Err(anyhow!(err.to_string())),
-    }
+/// Canvas-less `GpuState` rendering into a `width`×`height` offscreen texture,
+/// read it back with `GpuState::read_pixels`.
+#[cfg(not(target_arch = "wasm32"))]
+pub async fn init_headless(width: u32, height: u32) -> Result<GpuState> {
+    let sc = SurfaceContext::new_headless(width, height).await?;
+    Ok(init_state(sc).await)
 }
 
-pub fn default_pipeline(
-    device: &wgpu::Device,
-    config: &wgpu::SurfaceConfiguration,
-    pipeline_layout: &wgpu::PipelineLayout,
-    vs_shader: &VertexShader,
-    fs_shader: &FragmentShader,
-) -> wgpu::RenderPipeline {
-    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-        cache: None,
-        label: Some("Default Render Pipeline"),
-        layout: Some(pipeline_layout),
-        vertex: wgpu::VertexState {
-            compilation_options: Default::default(),
-            module: &vs_shader,
-            entry_point: Some("vs_main"),
-            buffers: &[vertex::Vertex::desc(), InstanceRaw::desc()],
-        },
-        fragment: Some(wgpu::FragmentState {
-            compilation_options: Default::default(),
-            module: &fs_shader,
-            entry_point: Some("fs_main"),
-            targets: &[Some(wgpu::ColorTargetState {
-                format: config.format,
-                blend: None,
-                write_mask: wgpu::ColorWrites::ALL,
-            })],
-        }),
-        primitive: wgpu::PrimitiveState {
-            topology: wgpu::PrimitiveTopology::TriangleList,
-            strip_index_format: None,
-            ..Default::default()
-        },
-        depth_stencil: Some(wgpu::DepthStencilState {
-            format: wgpu::TextureFormat::Depth32Float,
-            depth_write_enabled: true,
-            depth_compare: wgpu::CompareFunction::Less, // passes if new depth < old
-            stencil: Default::default(),
-            bias: Default::default(),
-        }),
-        multisample: Default::default(),
-        multiview: None,
-    })
+/// A small [`init_headless`] state for unit tests that need a device. As in
+/// the golden tests, no adapter is a failure unless `SKIP_GOLDEN` is set.
+#[cfg(all(test, not(target_arch = "wasm32")))]
+pub(crate) fn test_state() -> Option<GpuState> {
+    match pollster::block_on(init_headless(4, 4)) {
+        Ok(st) => Some(st),
+        Err(err) if std::env::var_os("SKIP_GOLDEN").is_some() => {
+            eprintln!("skipping, no usable adapter: {err:?}");
+            None
+        }
+        Err(err) => panic!("no usable adapter (set SKIP_GOLDEN=1 to skip): {err:?}"),
+    }
 }
 
-pub async fn init_wgpu(canvas: &HtmlCanvasElement, ) -> Result<GpuState> {
-    let sc = SurfaceContext::new_async(&canvas).await?;
+async fn init_state(sc: SurfaceContext) -> GpuState {
     let rc = ResourceContext::new_async(&sc).await;
 
     let depth_view = create_depth_view(&sc.device, &sc.config);
 
-    let t0 = web_sys::window().unwrap().performance().unwrap().now();
-
-    Ok(GpuState {
+    GpuState {
         surface_context: sc,
         resource_context: rc,
 
-        start_ms: t0,
-        prev_ms: t0,
+        clock: Box::new(RealClock::new()),
         frame_counter: 0,
+        time: TimeUBO::default(),
+        pipeline_cache: PipelineCache::default(),
+        generation: 0,
 
         depth_view,
-    })
+    }
 }
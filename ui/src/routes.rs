@@ -14,7 +14,8 @@ use crate::components::{
 
 use crate::pages::{
     classic::classic::AboutSection, classic::classic::ClassicMain,
-    classic::classic::ContactSection, home::Home,
+    classic::classic::ContactSection, classic::classic::SavedLab, classic::classic::SharedLab,
+    home::Home,
 };
 
 #[component]
@@ -26,6 +27,8 @@ pub fn RoutesMenu() -> impl IntoView {
         // <Route path=path!("/demos/mandelbrot") view=Mandelbrot/>
         // <Route path=path!("/demos/cube")     view=CubeDemo        />
         <Route path=path!("/classic")     view=ClassicMain        />
+        <Route path=path!("/share")       view=SharedLab          />
+        <Route path=path!("/shader/:id")  view=SavedLab           />
         <Route path=path!("/contact")     view=ContactSection     />
         <Route path=path!("/about")     view=AboutSection     />
       </Routes>
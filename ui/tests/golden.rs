@@ -0,0 +1,581 @@
+//! Golden-image tests: render each demo offscreen (software adapter) on a
+//! fixed-step clock, then compare against the PNGs in `tests/golden/`.
+//!
+//! Regenerate after an intentional visual change with:
+//! `UPDATE_GOLDEN=1 cargo test -p ui --test golden`
+//!
+//! Without a usable adapter (not even a software one) the tests fail; set
+//! `SKIP_GOLDEN=1` to skip them on such a machine instead.
+
+#![cfg(not(target_arch = "wasm32"))]
+
+use std::{cell::RefCell, path::PathBuf, rc::Rc};
+
+use image::{Rgba, RgbaImage};
+use leptos::prelude::{GetUntracked, Owner, RwSignal, Set, Update};
+
+use ui::components::demo::Demo;
+use ui::components::demos::animals::main::fish;
+use ui::components::demos::animals::utils::{make_skin_rpass, make_spine_rpass};
+use ui::components::demos::utils::make_points_rpass;
+use ui::components::shader_editor::Lab;
+use ui::components::shader_editor::model::{ModelSlot, ModelState};
+use ui::meshes;
+use ui::render::renderer::camera_input::CameraInput;
+use ui::render::renderer::gpu::GpuState;
+use ui::render::renderer::gpu::diagnostics::{Diagnostic, ShaderDiagnostics, Stage, check_wgsl};
+use ui::render::renderer::clock::FixedStepClock;
+use ui::render::renderer::gpu::gpu_state::{Projection, make_default_rpass};
+use ui::render::renderer::gpu::params::ShaderParams;
+use ui::render::renderer::gpu::preprocess::ShaderFiles;
+use ui::render::renderer::gpu::samplers::{Filter, SamplerDesc, Wrap};
+use ui::render::renderer::gpu::setup::ShaderSetup;
+use ui::render::renderer::gpu::textures::TextureSlots;
+use ui::render::renderer::graph::{LiveShaders, NodeRef, RenderGraph};
+use ui::render::renderer::mesh::CpuMesh;
+use ui::render::web_gpu::init_headless;
+
+// same aspect as the 864x1024 demo canvases
+const WIDTH: u32 = 216;
+const HEIGHT: u32 = 256;
+
+/// Time of every rendered frame.
+const START_MS: f64 = 3250.0;
+const STEP_MS: f64 = 16.0;
+
+/// Per-pixel YIQ distance (0..1, pixelmatch scale) above which a pixel differs.
+const PIXEL_THRESHOLD: f32 = 0.1;
+
+/// Share of differing pixels tolerated before the image counts as changed.
+const MAX_DIFF_RATIO: f32 = 0.005;
+
+/// Signals need an owner when leptos is built with `sandboxed-arenas`
+/// (which the workspace enables through `site`); keep it alive for the test.
+fn reactive_owner() -> Owner {
+    let owner = Owner::new();
+    owner.set();
+    owner
+}
+
+/// The offscreen target; a test without one fails unless `SKIP_GOLDEN` is
+/// set, so a machine with no adapter can't pass by testing nothing.
+fn headless() -> Option<GpuState> {
+    match pollster::block_on(init_headless(WIDTH, HEIGHT)) {
+        Ok(st) => Some(st),
+        Err(err) if std::env::var_os("SKIP_GOLDEN").is_some() => {
+            eprintln!("skipping golden test (SKIP_GOLDEN), no usable adapter: {err:?}");
+            None
+        }
+        Err(err) => panic!("no usable adapter (set SKIP_GOLDEN=1 to skip): {err:?}"),
+    }
+}
+
+/// One frame of `nodes`, always at `START_MS`, so only what a test changes
+/// can change the image.
+fn render(st: &mut GpuState, cam: &CameraInput, nodes: Vec<NodeRef>) -> RgbaImage {
+    st.set_clock(FixedStepClock::starting_at(START_MS, STEP_MS));
+    let mut graph = RenderGraph::with_nodes(nodes);
+
+    let mut ctx = st.begin_frame().expect("offscreen frame");
+    graph.execute(st, cam, &mut ctx).expect("render graph");
+    st.end_frame(ctx);
+
+    let px = st.read_pixels().expect("read back offscreen target");
+    RgbaImage::from_raw(WIDTH, HEIGHT, px).expect("image size matches target")
+}
+
+fn shared<T>(value: T) -> Rc<RefCell<T>> {
+    Rc::new(RefCell::new(value))
+}
+
+/// A demo's passes as its canvas builds them, drawing a fresh [`Lab`] that
+/// starts on the demo's shipped shaders; tests change what's drawn through
+/// `lab`.
+struct Fixture {
+    lab: Lab,
+    nodes: Vec<NodeRef>,
+    cam: CameraInput,
+}
+
+impl Fixture {
+    fn new(demo: Demo) -> Self {
+        let (vs, fs) = demo.shaders();
+        let lab = Lab {
+            shaders: LiveShaders {
+                vs: RwSignal::new(vs.to_owned()),
+                fs: RwSignal::new(fs.to_owned()),
+                diagnostics: ShaderDiagnostics::new(),
+                params: ShaderParams::new(),
+                files: ShaderFiles::new(),
+                setup: ShaderSetup::new(demo.module().to_owned()),
+            },
+            textures: TextureSlots::new(),
+            model: ModelSlot::new(),
+        };
+        let mut cam = CameraInput::default();
+
+        let nodes = match demo {
+            Demo::CubePlanet => {
+                // what the page fetches from /assets/images
+                let planet = std::fs::read(golden_dir().join("../../assets/images/texture.png"));
+                lab.textures.load_bytes(0, "texture.png".to_owned(), &planet.unwrap());
+                // tilted so three faces are visible
+                cam.camera.yaw = 0.6;
+                cam.camera.pitch = 0.4;
+
+                let mesh = CpuMesh::new(
+                    meshes::cube::CUBE_VERTICES.to_vec(),
+                    meshes::cube::CUBE_INDICES.to_vec(),
+                );
+                vec![make_default_rpass(
+                    shared(mesh),
+                    shared(Projection::Fulcrum),
+                    lab.shaders,
+                    lab.textures,
+                    Some(lab.model.mesh()),
+                )]
+            }
+            Demo::FragIntro => {
+                let mesh = CpuMesh::new(
+                    meshes::quad::QUAD_VERTS.to_vec(),
+                    meshes::quad::QUAD_INDICES.to_vec(),
+                );
+                vec![make_default_rpass(
+                    shared(mesh),
+                    shared(Projection::FlatQuad),
+                    lab.shaders,
+                    lab.textures,
+                    None,
+                )]
+            }
+            Demo::Animals => {
+                let points = shared(meshes::animals::FISH_SPINE.to_vec());
+                let snake = shared(fish(&points.borrow()));
+                let skin = snake.borrow().skin.clone();
+                let on = || RwSignal::new(true);
+                vec![
+                    make_skin_rpass(snake.clone(), 0.015, lab.shaders, on()),
+                    make_spine_rpass(snake, lab.shaders, on()),
+                    make_points_rpass(points, [1., 0., 0., 0.], on()),
+                    make_points_rpass(skin, [0., 1., 0., 0.], on()),
+                ]
+            }
+        };
+        Self { lab, nodes, cam }
+    }
+
+    fn render(&self, st: &mut GpuState) -> RgbaImage {
+        render(st, &self.cam, self.nodes.clone())
+    }
+
+    /// The colour a frame has at the centre of the target.
+    fn centre(&self, st: &mut GpuState) -> [u8; 3] {
+        let img = self.render(st);
+        let px = img.get_pixel(WIDTH / 2, HEIGHT / 2);
+        [px[0], px[1], px[2]]
+    }
+
+    fn errors(&self) -> Vec<Diagnostic> {
+        self.lab.shaders.diagnostics.errors_untracked()
+    }
+
+    fn assert_compiles(&self, name: &str) {
+        let errors = self.errors();
+        assert!(errors.is_empty(), "{name}: shaders don't compile: {errors:#?}");
+    }
+}
+
+fn png(image: RgbaImage) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    image
+        .write_to(&mut std::io::Cursor::new(&mut bytes), image::ImageFormat::Png)
+        .unwrap();
+    bytes
+}
+
+fn golden_dir() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/golden")
+}
+
+/// Squared YIQ distance between two opaque colours, as used by pixelmatch.
+fn yiq_delta(a: &Rgba<u8>, b: &Rgba<u8>) -> f32 {
+    let yiq = |p: &Rgba<u8>| {
+        let [r, g, b] = [p[0] as f32, p[1] as f32, p[2] as f32];
+        (
+            r * 0.298_895_3 + g * 0.586_622_5 + b * 0.114_482_23,
+            r * 0.595_978 - g * 0.274_176_1 - b * 0.321_801_9,
+            r * 0.211_470_17 - g * 0.522_617_1 + b * 0.311_146_94,
+        )
+    };
+
+    let (y1, i1, q1) = yiq(a);
+    let (y2, i2, q2) = yiq(b);
+    let (y, i, q) = (y1 - y2, i1 - i2, q1 - q2);
+
+    0.5053 * y * y + 0.299 * i * i + 0.1957 * q * q
+}
+
+fn assert_matches_golden(name: &str, actual: &RgbaImage) {
+    let path = golden_dir().join(format!("{name}.png"));
+
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        std::fs::create_dir_all(golden_dir()).unwrap();
+        actual.save(&path).unwrap();
+        eprintln!("updated {}", path.display());
+        return;
+    }
+
+    let expected = image::open(&path)
+        .unwrap_or_else(|e| panic!("missing golden {} ({e}), run with UPDATE_GOLDEN=1", path.display()))
+        .into_rgba8();
+
+    assert_eq!(
+        expected.dimensions(),
+        actual.dimensions(),
+        "{name}: golden size differs"
+    );
+
+    // 35215 is the largest possible YIQ delta
+    let max_delta = 35215.0 * PIXEL_THRESHOLD * PIXEL_THRESHOLD;
+
+    let mut diff = RgbaImage::new(WIDTH, HEIGHT);
+    let mut bad = 0usize;
+    for (x, y, px) in actual.enumerate_pixels() {
+        if yiq_delta(px, expected.get_pixel(x, y)) > max_delta {
+            bad += 1;
+            diff.put_pixel(x, y, Rgba([255, 0, 0, 255]));
+        } else {
+            diff.put_pixel(x, y, Rgba([px[0] / 4, px[1] / 4, px[2] / 4, 255]));
+        }
+    }
+
+    let ratio = bad as f32 / (WIDTH * HEIGHT) as f32;
+    if ratio > MAX_DIFF_RATIO {
+        let out = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("golden");
+        std::fs::create_dir_all(&out).unwrap();
+        actual.save(out.join(format!("{name}.actual.png"))).unwrap();
+        diff.save(out.join(format!("{name}.diff.png"))).unwrap();
+
+        panic!(
+            "{name}: {bad} pixels ({:.2}%) differ from the golden, see {}",
+            ratio * 100.0,
+            out.display()
+        );
+    }
+}
+
+#[test]
+fn cube_planet() {
+    let _owner = reactive_owner();
+    let Some(mut st) = headless() else { return };
+
+    let cube = Fixture::new(Demo::CubePlanet);
+    let img = cube.render(&mut st);
+    cube.assert_compiles("cube_planet");
+    assert_matches_golden("cube_planet", &img);
+}
+
+#[test]
+fn frag_intro() {
+    let _owner = reactive_owner();
+    let Some(mut st) = headless() else { return };
+
+    let quad = Fixture::new(Demo::FragIntro);
+    let img = quad.render(&mut st);
+    quad.assert_compiles("frag_intro");
+    assert_matches_golden("frag_intro", &img);
+}
+
+/// A broken edit is reported with its location and the previous pipeline
+/// keeps drawing; fixing it clears the report.
+#[test]
+fn broken_edit_keeps_last_good_pipeline() {
+    let _owner = reactive_owner();
+    let Some(mut st) = headless() else { return };
+
+    let quad = Fixture::new(Demo::FragIntro);
+    quad.render(&mut st);
+    quad.assert_compiles("frag_intro");
+
+    let good = Demo::FragIntro.shaders().1;
+    let broken = format!("{good}\nfn broken() -> f32 {{ return undefined_thing; }}\n");
+    let (line, text) = broken
+        .lines()
+        .enumerate()
+        .find(|(_, l)| l.contains("undefined_thing"))
+        .unwrap();
+    quad.lab.shaders.fs.set(broken.clone());
+
+    assert_matches_golden("frag_intro", &quad.render(&mut st));
+
+    let errors = quad.errors();
+    assert_eq!(errors.len(), 1, "{errors:#?}");
+    assert_eq!(errors[0].stage, Stage::Fragment);
+    let span = errors[0].span.expect("parse errors are located");
+    assert_eq!(span.line as usize, line + 1);
+    assert_eq!(span.column as usize, text.find("undefined_thing").unwrap() + 1);
+    assert_eq!(&broken[span.start..span.end], "undefined_thing");
+
+    // the editor checks without a device, includes pasted in, and must agree
+    let expanded = ShaderFiles::new().expand(&broken, Stage::Fragment).unwrap();
+    let checked: Vec<_> = check_wgsl(&expanded.code, Stage::Fragment)
+        .into_iter()
+        .map(|d| expanded.locate(d))
+        .collect();
+    assert_eq!(checked, errors);
+
+    quad.lab.shaders.fs.set(good.to_owned());
+    quad.render(&mut st);
+    quad.assert_compiles("frag_intro (fixed)");
+}
+
+#[test]
+fn animals() {
+    let _owner = reactive_owner();
+    let Some(mut st) = headless() else { return };
+
+    let fish = Fixture::new(Demo::Animals);
+    let img = fish.render(&mut st);
+    fish.assert_compiles("animals");
+    assert_matches_golden("animals", &img);
+}
+
+/// What the controls for a shader's params hold is what the shader reads.
+#[test]
+fn declared_params_reach_the_shader() {
+    let _owner = reactive_owner();
+    let Some(mut st) = headless() else { return };
+
+    let quad = Fixture::new(Demo::FragIntro);
+    let LiveShaders { vs, fs, params, .. } = quad.lab.shaders;
+    fs.set(
+        "struct Params {
+            tint: vec3<f32>, // @color
+            gain: f32,       // @range(0, 2) @default(1)
+        }
+        @group(3) @binding(0) var<uniform> params: Params;
+
+        @fragment
+        fn fs_main() -> @location(0) vec4<f32> {
+            return vec4<f32>(params.tint * params.gain, 1.0);
+        }"
+        .to_owned(),
+    );
+    // as the page does after every edit
+    params.sync(&vs.get_untracked(), &fs.get_untracked());
+
+    // defaults: white at full gain
+    assert_eq!(quad.centre(&mut st), [255, 255, 255]);
+    quad.assert_compiles("params");
+
+    params.update("tint", |v| *v = [0.0, 1.0, 0.0, 0.0]);
+    assert_eq!(quad.centre(&mut st), [0, 255, 0]);
+
+    params.update("gain", |v| v[0] = 0.0);
+    assert_eq!(quad.centre(&mut st), [0, 0, 0]);
+}
+
+/// Included modules are pasted in before compiling, and an error in one is
+/// reported in that module.
+#[test]
+fn includes_resolve_and_errors_point_into_modules() {
+    let _owner = reactive_owner();
+    let Some(mut st) = headless() else { return };
+
+    let quad = Fixture::new(Demo::FragIntro);
+    let files = quad.lab.shaders.files;
+    let tint = files.src(files.create("tint").unwrap()).unwrap();
+    let good = "#include \"time.wgsl\"
+#define GREEN vec3<f32>(0.0, 1.0, 0.0)
+const TINT = GREEN;
+";
+    tint.set(good.to_owned());
+
+    quad.lab.shaders.fs.set(
+        "#include \"tint.wgsl\"
+        #include \"time.wgsl\"
+
+        @fragment
+        fn fs_main() -> @location(0) vec4<f32> {
+            return vec4<f32>(TINT, 1.0);
+        }"
+        .to_owned(),
+    );
+
+    assert_eq!(quad.centre(&mut st), [0, 255, 0]);
+    quad.assert_compiles("includes");
+
+    tint.set(format!("{good}const OOPS = missing_thing;\n"));
+    assert_eq!(quad.centre(&mut st), [0, 255, 0], "the last good pipeline keeps drawing");
+
+    let errors = quad.errors();
+    assert_eq!(errors.len(), 1, "{errors:#?}");
+    assert_eq!(errors[0].file.as_deref(), Some("tint.wgsl"));
+
+    tint.set(good.to_owned());
+    quad.centre(&mut st);
+    quad.assert_compiles("includes (fixed)");
+}
+
+/// The one-module version of a demo draws what its VS/FS pair does, and a
+/// pass runs whichever entry point is picked for it.
+#[test]
+fn single_module_runs_picked_entry_points() {
+    let _owner = reactive_owner();
+    let Some(mut st) = headless() else { return };
+
+    let quad = Fixture::new(Demo::FragIntro);
+    let LiveShaders { vs, fs, setup, .. } = quad.lab.shaders;
+    setup.single.set(true);
+    // the pair is ignored while the module is in use
+    vs.set("not wgsl".to_owned());
+    fs.set("not wgsl".to_owned());
+
+    let img = quad.render(&mut st);
+    quad.assert_compiles("frag_intro (one module)");
+    assert_matches_golden("frag_intro", &img);
+
+    setup.module.update(|src| {
+        src.push_str(
+            "
+            @fragment
+            fn fs_green(in: VSOut) -> @location(0) vec4<f32> {
+                return vec4<f32>(0.0, 1.0, 0.0, 1.0);
+            }",
+        )
+    });
+    let pass_label = "Default Render Pass";
+    setup.pick(pass_label, Stage::Fragment, "fs_green".to_owned(), "fs_main");
+    assert_eq!(quad.centre(&mut st), [0, 255, 0]);
+    quad.assert_compiles("fs_green");
+
+    setup.pick(pass_label, Stage::Fragment, "fs_missing".to_owned(), "fs_main");
+    assert_eq!(quad.centre(&mut st), [0, 255, 0], "the last good pipeline keeps drawing");
+    let errors = quad.errors();
+    assert_eq!(errors.len(), 1, "{errors:#?}");
+    assert!(errors[0].message.contains("fs_missing"), "{errors:#?}");
+
+    // picking the default again forgets the pick
+    setup.pick(pass_label, Stage::Fragment, "fs_main".to_owned(), "fs_main");
+    quad.centre(&mut st);
+    quad.assert_compiles("fs_main");
+}
+
+/// An image loaded into a channel is what the shader samples there, mips
+/// and all; clearing the channel leaves black.
+#[test]
+fn texture_channels_bind_loaded_images() {
+    let _owner = reactive_owner();
+    let Some(mut st) = headless() else { return };
+
+    let quad = Fixture::new(Demo::FragIntro);
+    quad.lab.shaders.fs.set(
+        "#include \"channels.wgsl\"
+
+        @fragment
+        fn fs_main() -> @location(0) vec4<f32> {
+            let uv = vec2<f32>(0.5, 0.5);
+            return textureSampleLevel(iChannel2, iChannel2_sampler, uv, 0.0);
+        }"
+        .to_owned(),
+    );
+    let textures = quad.lab.textures;
+
+    assert_eq!(quad.centre(&mut st), [0, 0, 0], "empty channels sample black");
+    quad.assert_compiles("channels");
+
+    // odd sizes, so the mip chain has non-square levels
+    let green = png(RgbaImage::from_pixel(6, 3, Rgba([0, 255, 0, 255])));
+    textures.load_bytes(2, "green.png".to_owned(), &green);
+    assert_eq!(quad.centre(&mut st), [0, 255, 0]);
+
+    textures.load_bytes(2, "broken.png".to_owned(), b"not an image");
+    assert_eq!(quad.centre(&mut st), [0, 255, 0], "a failed load keeps the last image");
+
+    textures.clear(2);
+    assert_eq!(quad.centre(&mut st), [0, 0, 0]);
+}
+
+/// Mip levels are averaged in linear space on the GPU, and each channel
+/// filters and wraps as the sampler picked for it says.
+#[test]
+fn mips_and_samplers_follow_the_channel() {
+    let _owner = reactive_owner();
+    let Some(mut st) = headless() else { return };
+
+    let quad = Fixture::new(Demo::FragIntro);
+    // left half of the target: iChannel1's 1x1 level; right half, from the
+    // top: iChannel2 at u 0.4, 1.3 and 1.7
+    quad.lab.shaders.fs.set(
+        "#include \"channels.wgsl\"
+        #include \"screen.wgsl\"
+
+        @fragment
+        fn fs_main(@builtin(position) p: vec4<f32>) -> @location(0) vec4<f32> {
+            let at = p.xy / screen.resolution;
+            if at.x < 0.5 {
+                return textureSampleLevel(iChannel1, iChannel1_sampler, vec2(0.5), 1.0);
+            }
+            let u = select(select(1.7, 1.3, at.y < 0.66), 0.4, at.y < 0.33);
+            return textureSampleLevel(iChannel2, iChannel2_sampler, vec2(u, 0.5), 0.0);
+        }"
+        .to_owned(),
+    );
+    let textures = quad.lab.textures;
+    // red of the left half, then the three right-hand samples
+    let sample = |st: &mut GpuState| {
+        let img = quad.render(st);
+        let red = |x, y| img.get_pixel(x, y)[0];
+        let right = |sixth| red(3 * WIDTH / 4, HEIGHT * sixth / 6);
+        (red(WIDTH / 4, HEIGHT / 2), [right(1), right(3), right(5)])
+    };
+
+    let (black, white) = (Rgba([0, 0, 0, 255]), Rgba([255, 255, 255, 255]));
+    let checker = RgbaImage::from_fn(2, 2, |x, y| if (x + y) % 2 == 0 { black } else { white });
+    textures.load_bytes(1, "checker.png".to_owned(), &png(checker));
+    let halves = RgbaImage::from_fn(2, 1, |x, _| if x == 0 { black } else { white });
+    textures.load_bytes(2, "halves.png".to_owned(), &png(halves));
+
+    // half white in linear light, not in sRGB (which would come out at 55)
+    let (mip, _) = sample(&mut st);
+    assert!(mip.abs_diff(128) <= 2, "top mip level is {mip}");
+    quad.assert_compiles("samplers");
+
+    let nearest = |wrap| SamplerDesc { filter: Filter::Nearest, wrap };
+    textures.set_sampler(2, nearest(Wrap::Clamp));
+    assert_eq!(sample(&mut st).1, [0, 255, 255]);
+    textures.set_sampler(2, nearest(Wrap::Repeat));
+    assert_eq!(sample(&mut st).1, [0, 0, 255]);
+    textures.set_sampler(2, nearest(Wrap::Mirror));
+    assert_eq!(sample(&mut st).1, [0, 255, 0]);
+
+    // u 0.4 is 30% of the way from the black texel's centre to the white one's
+    textures.set_sampler(2, SamplerDesc { filter: Filter::Linear, wrap: Wrap::Clamp });
+    let [linear, ..] = sample(&mut st).1;
+    assert!(linear.abs_diff(77) <= 2, "linear filtering gave {linear}");
+}
+
+/// Models draw in place of the demo's mesh until cleared; see
+/// `meshes::import` for how they're read.
+#[test]
+fn models_draw_in_place_of_the_demo_mesh() {
+    let _owner = reactive_owner();
+
+    let Some(mut st) = headless() else { return };
+    let cube = Fixture::new(Demo::CubePlanet);
+    let model = cube.lab.model;
+
+    model.load_asset("torus.obj");
+    assert!(matches!(model.state(), ModelState::Ready { .. }));
+    let img = cube.render(&mut st);
+    cube.assert_compiles("model");
+    assert_matches_golden("model_torus", &img);
+
+    // a file that doesn't parse leaves the torus drawing
+    model.load_bytes("broken.obj".to_owned(), b"f 1 2 3\n");
+    assert!(matches!(model.state(), ModelState::Failed(_)));
+    assert_matches_golden("model_torus", &cube.render(&mut st));
+
+    model.clear();
+    assert_matches_golden("cube_planet", &cube.render(&mut st));
+}